@@ -0,0 +1,494 @@
+/* Copyright (c) 2018 Garrett Berg, vitiral@gmail.com
+ *
+ * Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+ * http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+ * http://opensource.org/licenses/MIT>, at your option. This file may not be
+ * copied, modified, or distributed except according to those terms.
+ */
+//! A lightweight declarative command line argument parser.
+use super::*;
+use std::collections::HashMap;
+use std::env;
+use std::error;
+use std::ffi::{OsStr, OsString};
+use std::fmt;
+use std::io;
+
+/// A declarative command line parser for small tools.
+///
+/// Arguments are declared with [`Arg`] and the parsed values are accessed by name from the
+/// [`Matches`]. What makes it different from other parsers is path handling:
+///
+/// - Path values (`.path()`, `.file()`, `.dir()`) are shell expanded, so `~/data` and
+///   `$HOME/data` work even when the shell didn't expand them (i.e. `--out=~/data`).
+/// - `.file()` and `.dir()` values are validated while parsing and are available as a
+///   `PathFile` or `PathDir`, so a missing input is reported before any work is done.
+///
+/// Arguments are read with `env::args_os`, so path values which are not unicode are kept exactly
+/// as given (they are not shell expanded). Other values are converted lossily.
+///
+/// Supported syntax is `--name value`, `--name=value`, `-n value` and `--` to end options.
+/// `--help` and `-h` return [`ArgsError::Help`] with the usage message.
+///
+/// [`Arg`]: struct.Arg.html
+/// [`Matches`]: struct.Matches.html
+/// [`ArgsError::Help`]: enum.ArgsError.html#variant.Help
+///
+/// # Examples
+/// ```rust
+/// # extern crate ergo;
+/// use ergo::*;
+///
+/// # fn try_main() -> ::std::io::Result<()> {
+/// let tmp = PathTmp::create("args")?;
+/// let input = PathFile::create(tmp.join("input.txt"))?;
+///
+/// let args = Args::new("mytool")
+///     .arg(Arg::flag("verbose").short('v').help("print more"))
+///     .arg(Arg::option("out").short('o').path().help("where to write"))
+///     .arg(Arg::positional("input").file().help("the file to read"));
+///
+/// let matches = args
+///     .parse_from(vec!["-v", "--out=~/out", input.to_str().unwrap()])
+///     .unwrap();
+/// assert!(matches.flag("verbose"));
+/// assert_eq!(home_dir().unwrap().join("out").as_path(), matches.path("out").unwrap());
+/// assert_eq!(&input, matches.file("input").unwrap());
+///
+/// // Paths are validated
+/// let err = args.parse_from(vec![tmp.join("missing.txt").to_path_buf()]).unwrap_err();
+/// assert!(err.to_string().starts_with("invalid value for <input>: "));
+/// # Ok(()) } fn main() { try_main().unwrap() }
+/// ```
+#[derive(Debug, Clone)]
+pub struct Args {
+    name: String,
+    about: Option<String>,
+    args: Vec<Arg>,
+}
+
+/// The declaration of a single argument, see [`Args`](struct.Args.html).
+#[derive(Debug, Clone)]
+pub struct Arg {
+    name: String,
+    short: Option<char>,
+    help: String,
+    style: Style,
+    kind: Kind,
+    required: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Style {
+    Flag,
+    Option,
+    Positional,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Kind {
+    Str,
+    Path,
+    File,
+    Dir,
+}
+
+/// The values parsed by [`Args`](struct.Args.html).
+#[derive(Debug, Clone, Default)]
+pub struct Matches {
+    flags: HashMap<String, bool>,
+    values: HashMap<String, Value>,
+}
+
+#[derive(Debug, Clone)]
+enum Value {
+    Str(String),
+    Path(PathBuf),
+    File(PathFile),
+    Dir(PathDir),
+}
+
+/// An error from parsing command line arguments.
+#[derive(Debug)]
+pub enum ArgsError {
+    /// `--help` or `-h` was passed. Contains the usage message.
+    Help(String),
+    /// An option which was not declared.
+    Unknown(String),
+    /// An option was given without its value.
+    MissingValue(String),
+    /// A required argument was not given.
+    Missing(String),
+    /// More positional arguments were given than declared.
+    Unexpected(String),
+    /// A path value could not be shell expanded.
+    Expand {
+        /// The argument name.
+        arg: String,
+        /// The expansion error.
+        error: ExpandError,
+    },
+    /// A `.file()` or `.dir()` value does not exist or is the wrong type.
+    Invalid {
+        /// The argument name.
+        arg: String,
+        /// The filesystem error.
+        error: ergo_fs::Error,
+    },
+}
+
+impl Args {
+    /// Declare the arguments of the program `name`.
+    pub fn new<S: Into<String>>(name: S) -> Args {
+        Args {
+            name: name.into(),
+            about: None,
+            args: Vec::new(),
+        }
+    }
+
+    /// A description of the program, shown in the usage message.
+    pub fn about<S: Into<String>>(mut self, about: S) -> Args {
+        self.about = Some(about.into());
+        self
+    }
+
+    /// Declare an argument. Positional arguments are matched in the order they are declared.
+    pub fn arg(mut self, arg: Arg) -> Args {
+        self.args.push(arg);
+        self
+    }
+
+    /// Parse the arguments of the current process.
+    pub fn parse(&self) -> Result<Matches, ArgsError> {
+        self.parse_from(env::args_os().skip(1))
+    }
+
+    /// Parse `args`, which should not include the program name.
+    pub fn parse_from<I, S>(&self, args: I) -> Result<Matches, ArgsError>
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<OsStr>,
+    {
+        let mut matches = Matches::default();
+        let mut positionals = self.args.iter().filter(|a| a.style == Style::Positional);
+        let mut args = args.into_iter().map(|a| a.as_ref().to_os_string());
+        let mut only_positional = false;
+
+        while let Some(raw) = args.next() {
+            // only used to match options, values are taken from `raw`
+            let text = raw.to_string_lossy().into_owned();
+            if !only_positional && text == "--" {
+                only_positional = true;
+                continue;
+            }
+            if !only_positional && (text == "--help" || text == "-h") {
+                return Err(ArgsError::Help(self.usage()));
+            }
+            if only_positional || !text.starts_with('-') || text == "-" {
+                let arg = positionals
+                    .next()
+                    .ok_or_else(|| ArgsError::Unexpected(text.clone()))?;
+                matches.insert(arg, &raw)?;
+                continue;
+            }
+
+            // the inline value starts after the option and a `=` (long) or directly after it
+            // (short)
+            let (arg, start) = if let Some(long) = text.strip_prefix("--") {
+                let name = long.split('=').next().unwrap_or("");
+                let arg = self
+                    .args
+                    .iter()
+                    .find(|a| a.style != Style::Positional && a.name == name);
+                (arg, 2 + name.len() + 1)
+            } else {
+                let short = text[1..].chars().next();
+                let arg = self
+                    .args
+                    .iter()
+                    .find(|a| a.style != Style::Positional && a.short == short);
+                (arg, 1 + short.map_or(0, char::len_utf8))
+            };
+            let arg = arg.ok_or_else(|| ArgsError::Unknown(text.clone()))?;
+            let inline = if text.starts_with("--") {
+                inline_value(&raw, start)
+            } else {
+                inline_value(&raw, start).filter(|v| !v.is_empty())
+            };
+
+            match arg.style {
+                Style::Flag => {
+                    if inline.is_some() {
+                        return Err(ArgsError::Unknown(text));
+                    }
+                    matches.flags.insert(arg.name.clone(), true);
+                }
+                _ => {
+                    let value = match inline {
+                        Some(v) => v,
+                        None => args
+                            .next()
+                            .ok_or_else(|| ArgsError::MissingValue(arg.display()))?,
+                    };
+                    matches.insert(arg, &value)?;
+                }
+            }
+        }
+
+        for arg in &self.args {
+            if arg.required && !matches.values.contains_key(&arg.name) {
+                return Err(ArgsError::Missing(arg.display()));
+            }
+        }
+        Ok(matches)
+    }
+
+    /// The usage message, listing every argument with its help.
+    pub fn usage(&self) -> String {
+        let mut out = String::new();
+        if let Some(ref about) = self.about {
+            out.push_str(about);
+            out.push_str("\n\n");
+        }
+        out.push_str("USAGE:\n    ");
+        out.push_str(&self.name);
+        if self.args.iter().any(|a| a.style != Style::Positional) {
+            out.push_str(" [OPTIONS]");
+        }
+        for arg in self.args.iter().filter(|a| a.style == Style::Positional) {
+            out.push(' ');
+            if arg.required {
+                out.push_str(&arg.display());
+            } else {
+                out.push_str(&format!("[{}]", arg.display()));
+            }
+        }
+        out.push_str("\n\nARGS:\n");
+        let rows: Vec<(String, &str)> = self
+            .args
+            .iter()
+            .map(|a| {
+                let mut spec = match a.short {
+                    Some(c) if a.style != Style::Positional => format!("-{}, ", c),
+                    _ => String::new(),
+                };
+                spec.push_str(&a.display());
+                if a.style == Style::Option {
+                    spec.push_str(&format!(" <{}>", a.kind.placeholder()));
+                }
+                (spec, a.help.as_str())
+            })
+            .collect();
+        let width = rows.iter().map(|r| r.0.len()).max().unwrap_or(0);
+        for (spec, help) in rows {
+            out.push_str(&format!("    {:width$}    {}\n", spec, help, width = width));
+        }
+        out
+    }
+}
+
+impl Arg {
+    fn new<S: Into<String>>(name: S, style: Style) -> Arg {
+        Arg {
+            name: name.into(),
+            short: None,
+            help: String::new(),
+            style,
+            kind: Kind::Str,
+            required: style == Style::Positional,
+        }
+    }
+
+    /// A flag (`--name`), which takes no value.
+    pub fn flag<S: Into<String>>(name: S) -> Arg {
+        Arg::new(name, Style::Flag)
+    }
+
+    /// An option (`--name value` or `--name=value`). Options are optional unless `required`.
+    pub fn option<S: Into<String>>(name: S) -> Arg {
+        Arg::new(name, Style::Option)
+    }
+
+    /// A positional argument. Positional arguments are required unless `optional`.
+    pub fn positional<S: Into<String>>(name: S) -> Arg {
+        Arg::new(name, Style::Positional)
+    }
+
+    /// The short name, i.e. `-v`. Ignored for positional arguments.
+    pub fn short(mut self, short: char) -> Arg {
+        self.short = Some(short);
+        self
+    }
+
+    /// The help shown in the usage message.
+    pub fn help<S: Into<String>>(mut self, help: S) -> Arg {
+        self.help = help.into();
+        self
+    }
+
+    /// Require the argument to be given.
+    pub fn required(mut self) -> Arg {
+        self.required = true;
+        self
+    }
+
+    /// Allow the argument to be omitted.
+    pub fn optional(mut self) -> Arg {
+        self.required = false;
+        self
+    }
+
+    /// The value is a path, which is shell expanded but not validated.
+    pub fn path(mut self) -> Arg {
+        self.kind = Kind::Path;
+        self
+    }
+
+    /// The value is a path to an existing file, which is shell expanded and validated.
+    pub fn file(mut self) -> Arg {
+        self.kind = Kind::File;
+        self
+    }
+
+    /// The value is a path to an existing directory, which is shell expanded and validated.
+    pub fn dir(mut self) -> Arg {
+        self.kind = Kind::Dir;
+        self
+    }
+
+    fn display(&self) -> String {
+        match self.style {
+            Style::Positional => format!("<{}>", self.name),
+            _ => format!("--{}", self.name),
+        }
+    }
+
+    fn parse(&self, raw: &OsStr) -> Result<Value, ArgsError> {
+        if self.kind == Kind::Str {
+            return Ok(Value::Str(raw.to_string_lossy().into_owned()));
+        }
+        let path = match raw.to_str() {
+            Some(raw) => {
+                let expanded = expand(raw).map_err(|error| ArgsError::Expand {
+                    arg: self.display(),
+                    error,
+                })?;
+                PathBuf::from(expanded.as_ref())
+            }
+            // only unicode can be expanded
+            None => PathBuf::from(raw),
+        };
+        let invalid = |err: path_abs::Error| ArgsError::Invalid {
+            arg: self.display(),
+            error: err.into(),
+        };
+        Ok(match self.kind {
+            Kind::File => Value::File(PathFile::new(path).map_err(invalid)?),
+            Kind::Dir => Value::Dir(PathDir::new(path).map_err(invalid)?),
+            _ => Value::Path(path),
+        })
+    }
+}
+
+/// The value in `raw` after the first `start` bytes, which must be those of a matched option.
+fn inline_value(raw: &OsStr, start: usize) -> Option<OsString> {
+    let bytes = raw.as_encoded_bytes();
+    if bytes.len() < start {
+        return None;
+    }
+    // SAFETY: the option name is a `String`, so `start` directly follows valid UTF-8
+    Some(unsafe { OsStr::from_encoded_bytes_unchecked(&bytes[start..]) }.to_os_string())
+}
+
+impl Kind {
+    fn placeholder(&self) -> &'static str {
+        match *self {
+            Kind::Str => "value",
+            Kind::Path => "path",
+            Kind::File => "file",
+            Kind::Dir => "dir",
+        }
+    }
+}
+
+impl Matches {
+    fn insert(&mut self, arg: &Arg, raw: &OsStr) -> Result<(), ArgsError> {
+        let value = arg.parse(raw)?;
+        self.values.insert(arg.name.clone(), value);
+        Ok(())
+    }
+
+    /// Whether the flag was given.
+    pub fn flag(&self, name: &str) -> bool {
+        self.flags.get(name).cloned().unwrap_or(false)
+    }
+
+    /// The value of the argument as it was given, or `None` if it was not given or is a path.
+    pub fn value(&self, name: &str) -> Option<&str> {
+        match *self.values.get(name)? {
+            Value::Str(ref s) => Some(s),
+            _ => None,
+        }
+    }
+
+    /// The expanded path of a `.path()`, `.file()` or `.dir()` argument.
+    pub fn path(&self, name: &str) -> Option<&Path> {
+        match *self.values.get(name)? {
+            Value::Path(ref p) => Some(p),
+            Value::File(ref f) => Some(f.as_ref()),
+            Value::Dir(ref d) => Some(d.as_ref()),
+            _ => None,
+        }
+    }
+
+    /// The file of a `.file()` argument.
+    pub fn file(&self, name: &str) -> Option<&PathFile> {
+        match *self.values.get(name)? {
+            Value::File(ref f) => Some(f),
+            _ => None,
+        }
+    }
+
+    /// The directory of a `.dir()` argument.
+    pub fn dir(&self, name: &str) -> Option<&PathDir> {
+        match *self.values.get(name)? {
+            Value::Dir(ref d) => Some(d),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for ArgsError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            ArgsError::Help(ref usage) => write!(f, "{}", usage),
+            ArgsError::Unknown(ref arg) => write!(f, "unknown argument {}", arg),
+            ArgsError::MissingValue(ref arg) => write!(f, "{} requires a value", arg),
+            ArgsError::Missing(ref arg) => write!(f, "missing required argument {}", arg),
+            ArgsError::Unexpected(ref arg) => write!(f, "unexpected argument {}", arg),
+            ArgsError::Expand { ref arg, ref error } => {
+                write!(f, "invalid value for {}: {}", arg, error)
+            }
+            ArgsError::Invalid { ref arg, ref error } => {
+                write!(f, "invalid value for {}: {}", arg, error)
+            }
+        }
+    }
+}
+
+impl error::Error for ArgsError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match *self {
+            ArgsError::Expand { ref error, .. } => Some(error),
+            ArgsError::Invalid { ref error, .. } => Some(error),
+            _ => None,
+        }
+    }
+}
+
+impl From<ArgsError> for io::Error {
+    fn from(err: ArgsError) -> io::Error {
+        io::Error::new(io::ErrorKind::InvalidInput, err)
+    }
+}
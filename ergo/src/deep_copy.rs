@@ -7,94 +7,845 @@
  */
 //! Define the deepcopy function
 use super::*;
+use ergo_fs::faults::{self, FsOp};
+use std::collections::{BTreeSet, HashSet};
+use std::env;
+use std::fmt;
 use std::fs;
 use std::io;
+use std::path::Component;
+use std::sync::OnceLock;
+use std::time::Instant;
 
 /// Do a deep copy of a directory from one location to another.
 ///
-/// This will follow symlinks and copy the _contents_. Recursive paths will cause an
-/// error to be raised.
+/// This will follow symlinks and copy the _contents_. A symlink which points to one of its own
+/// ancestors is not followed, a [`SymlinkLoop`] error is sent for it instead. Copying a directory
+/// into itself is also an error.
 ///
 /// Errors are sent over the `send_err` channel.
+///
+/// This is the same as `DeepCopy::new(from, to).run(send_err)`, see [`DeepCopy`] for more options.
+///
+/// [`DeepCopy`]: struct.DeepCopy.html
+/// [`SymlinkLoop`]: struct.SymlinkLoop.html
 pub fn deep_copy<P: AsRef<Path>>(send_err: Sender<io::Error>, from: PathDir, to: P) {
-    let to = ch_try!(
-        send_err,
-        create_dir_maybe(to).map_err(|err| err.into()),
-        return
-    );
-
-    let (send_file, recv_file) = ch::bounded(128);
-
-    // First thread walks and creates directories, and sends files to copy
-    take!(=send_err as errs, =to as to_walk);
-    spawn(move || {
-        walk_and_create_dirs(from, to_walk, errs, send_file);
-    });
-
-    // Threadpool copy files into directories that are pre-created.
-    for _ in 0..num_cpus::get() {
-        take!(=send_err, =recv_file, =to);
-        spawn(move || {
-            for (from, to_postfix) in recv_file {
-                ch_try!(
-                    send_err,
-                    from.copy(to.join(to_postfix)).map_err(|err| err.into()),
-                    continue
-                );
+    DeepCopy::new(from, to).run(send_err)
+}
+
+/// Builder for a deep copy of a directory with additional options.
+///
+/// See [`deep_copy`](fn.deep_copy.html).
+///
+/// # Examples
+/// ```rust
+/// # extern crate ergo;
+/// use ergo::*;
+///
+/// # fn try_main() -> ::std::io::Result<()> {
+/// let tmp = PathTmp::create("deep_copy")?;
+/// let from = PathDir::create(tmp.join("from"))?;
+/// PathFile::create(from.join("example.txt"))?.write_str("example")?;
+///
+/// let (send_err, recv_err) = ch::unbounded();
+/// DeepCopy::new(from, tmp.join("to"))
+///     .retry(RetryPolicy::new().attempts(3))
+///     .copy_options(CopyOptions::new().reflink(true).fsync(FsyncPolicy::DirAtEnd))
+///     .same_file_system(true)
+///     .run(send_err);
+///
+/// let errs: Vec<_> = recv_err.iter().collect();
+/// assert!(errs.is_empty(), "{:?}", errs);
+/// let copied = PathFile::new(tmp.join("to").join("example.txt"))?;
+/// assert_eq!("example", copied.read_string()?);
+///
+/// // copying a directory into itself is an error
+/// let (send_err, recv_err) = ch::unbounded();
+/// let from = PathDir::new(tmp.join("from"))?;
+/// DeepCopy::new(from.clone(), from.join("inner")).run(send_err);
+/// assert_eq!(1, recv_err.iter().count());
+/// assert!(!from.join("inner").exists());
+/// # Ok(()) } fn main() { try_main().unwrap() }
+/// ```
+#[derive(Debug, Clone)]
+pub struct DeepCopy {
+    from: PathDir,
+    to: PathArc,
+    retry: RetryPolicy,
+    options: CopyOptions,
+    journal: Option<PathArc>,
+    resume: bool,
+    walk: WalkOptions,
+    pool: Option<Arc<Pool>>,
+    concurrency: usize,
+    policy: ErrorPolicy,
+    observer: Option<Arc<dyn FsObserver>>,
+}
+
+impl DeepCopy {
+    /// Create a new deep copy from the directory `from` to the path `to`.
+    pub fn new<P: AsRef<Path>>(from: PathDir, to: P) -> DeepCopy {
+        DeepCopy {
+            from,
+            to: PathArc::new(to),
+            retry: RetryPolicy::never(),
+            options: CopyOptions::new(),
+            journal: None,
+            resume: false,
+            walk: WalkOptions::new().follow_symlinks(true),
+            pool: None,
+            concurrency: num_cpus::get(),
+            policy: ErrorPolicy::Continue,
+            observer: None,
+        }
+    }
+
+    /// Record the operations of the copy in a [`Journal`](struct.Journal.html) at `path`, so
+    /// that it can be [`resume`](#method.resume)d if it is interrupted.
+    ///
+    /// Entries are appended if the journal already exists. Every file is synced to disk before
+    /// it is recorded as copied, so that a crash can't lose a copy the journal vouches for.
+    pub fn journal<P: AsRef<Path>>(mut self, path: P) -> DeepCopy {
+        self.journal = Some(PathArc::new(path));
+        self
+    }
+
+    /// Resume an interrupted copy which was recorded in the journal at `path`.
+    ///
+    /// The destination is allowed to exist already and every file which the journal records as
+    /// completely copied is skipped. Everything else is copied again (overwriting partial
+    /// copies) and recorded in the same journal.
+    ///
+    /// # Examples
+    /// ```rust
+    /// # extern crate ergo;
+    /// use ergo::*;
+    ///
+    /// # fn try_main() -> ::std::io::Result<()> {
+    /// let tmp = PathTmp::create("resume")?;
+    /// let from = PathDir::create(tmp.join("from"))?;
+    /// PathFile::create(from.join("a.txt"))?.write_str("a")?;
+    /// PathFile::create(from.join("b.txt"))?.write_str("b")?;
+    /// let journal = tmp.join("copy.journal");
+    ///
+    /// let (send_err, recv_err) = ch::unbounded();
+    /// DeepCopy::new(from.clone(), tmp.join("to"))
+    ///     .journal(&journal)
+    ///     .run(send_err);
+    /// assert_eq!(0, recv_err.iter().count());
+    ///
+    /// // Pretend the copy of b.txt never happened
+    /// PathFile::new(tmp.join("to").join("b.txt"))?.remove()?;
+    /// let entries: Vec<_> = Journal::read(&journal)?
+    ///     .into_iter()
+    ///     .filter(|e| match *e {
+    ///         JournalEntry::Copied { ref to, .. } => !to.ends_with("b.txt"),
+    ///         _ => true,
+    ///     })
+    ///     .collect();
+    /// PathFile::new(&journal)?.remove()?;
+    /// let rewritten = Journal::open(&journal)?;
+    /// for entry in &entries {
+    ///     rewritten.record(entry)?;
+    /// }
+    ///
+    /// let (send_err, recv_err) = ch::unbounded();
+    /// DeepCopy::new(from, tmp.join("to")).resume(&journal).run(send_err);
+    /// assert_eq!(0, recv_err.iter().count());
+    /// assert_eq!("b", PathFile::new(tmp.join("to").join("b.txt"))?.read_string()?);
+    /// # Ok(()) } fn main() { try_main().unwrap() }
+    /// ```
+    pub fn resume<P: AsRef<Path>>(mut self, path: P) -> DeepCopy {
+        self.journal = Some(PathArc::new(path));
+        self.resume = true;
+        self
+    }
+
+    /// Retry creating directories and copying files which fail with transient errors.
+    ///
+    /// By default operations are not retried.
+    pub fn retry(mut self, policy: RetryPolicy) -> DeepCopy {
+        self.retry = policy;
+        self
+    }
+
+    /// Set the options used to copy each file.
+    ///
+    /// By default the holes of sparse files are preserved. Enable
+    /// [`CopyOptions::reflink`](struct.CopyOptions.html#method.reflink) to clone files
+    /// copy-on-write on filesystems which support it (i.e. Btrfs, XFS and APFS), which makes
+    /// copying large trees nearly instant.
+    ///
+    /// With [`FsyncPolicy::DirAtEnd`](enum.FsyncPolicy.html) every copied file and every
+    /// directory containing them is synced once all files have been copied, before the error
+    /// channel is closed.
+    pub fn copy_options(mut self, options: CopyOptions) -> DeepCopy {
+        self.options = options;
+        self
+    }
+
+    /// Set the options used to walk `from`, i.e. to exclude files from the copy.
+    ///
+    /// By default symlinks are followed and everything is copied. Options which would make the
+    /// copy incomplete in surprising ways are ignored: the walk always starts at `from` and is
+    /// never contents-first.
+    ///
+    /// # Examples
+    /// ```rust
+    /// # extern crate ergo;
+    /// use ergo::*;
+    ///
+    /// # fn try_main() -> ::std::io::Result<()> {
+    /// let tmp = PathTmp::create("walk_options")?;
+    /// let from = PathDir::create(tmp.join("from"))?;
+    /// PathFile::create(from.join("lib.rs"))?;
+    /// PathDir::create(from.join("target"))?;
+    /// PathFile::create(from.join("target").join("lib.o"))?;
+    ///
+    /// let (send_err, recv_err) = ch::unbounded();
+    /// DeepCopy::new(from, tmp.join("to"))
+    ///     .walk_options(WalkOptions::new().exclude(GlobPattern::new("target").unwrap()))
+    ///     .run(send_err);
+    /// assert_eq!(0, recv_err.iter().count());
+    /// assert!(tmp.join("to").join("lib.rs").exists());
+    /// assert!(!tmp.join("to").join("target").exists());
+    /// # Ok(()) } fn main() { try_main().unwrap() }
+    /// ```
+    pub fn walk_options(mut self, options: WalkOptions) -> DeepCopy {
+        self.walk = options.min_depth(0).contents_first(false);
+        self
+    }
+
+    /// Don't descend into directories on a different filesystem than `from` (default `false`),
+    /// so that i.e. a backup of `/` does not copy `/proc` or network mounts.
+    ///
+    /// This is the same as setting
+    /// [`WalkOptions::same_file_system`](struct.WalkOptions.html#method.same_file_system) with
+    /// [`walk_options`](#method.walk_options), which replaces this setting.
+    pub fn same_file_system(mut self, same: bool) -> DeepCopy {
+        self.walk = self.walk.same_file_system(same);
+        self
+    }
+
+    /// Copy the files on `pool` instead of the pool which is shared by every deep copy.
+    ///
+    /// By default files are copied on a single pool with a thread per cpu, which is created by
+    /// the first copy. Copying several trees at once then doesn't start more threads than the
+    /// machine has cpus.
+    pub fn pool(mut self, pool: Arc<Pool>) -> DeepCopy {
+        self.pool = Some(pool);
+        self
+    }
+
+    /// Copy at most `limit` files of this copy at once (default: the number of cpus).
+    ///
+    /// The walk waits for a copy to complete before queueing more, so a copy sharing the
+    /// [`pool`](#method.pool) with others can be kept from taking all of its threads.
+    ///
+    /// # Examples
+    /// ```rust
+    /// # extern crate ergo;
+    /// use ergo::*;
+    ///
+    /// # fn try_main() -> ::std::io::Result<()> {
+    /// let tmp = PathTmp::create("concurrency")?;
+    /// let pool = Arc::new(Pool::new(4));
+    /// let (send_err, recv_err) = ch::unbounded();
+    /// for name in &["a", "b"] {
+    ///     let from = PathDir::create(tmp.join(name))?;
+    ///     for i in 0..10 {
+    ///         PathFile::create(from.join(format!("{}.txt", i)))?.write_str(name)?;
+    ///     }
+    ///     DeepCopy::new(from, tmp.join(format!("{}_copy", name)))
+    ///         .pool(pool.clone())
+    ///         .concurrency(2)
+    ///         .run(send_err.clone());
+    /// }
+    /// drop(send_err);
+    /// assert_eq!(0, recv_err.iter().count());
+    /// assert_eq!("b", PathFile::new(tmp.join("b_copy").join("9.txt"))?.read_string()?);
+    /// # Ok(()) } fn main() { try_main().unwrap() }
+    /// ```
+    pub fn concurrency(mut self, limit: usize) -> DeepCopy {
+        self.concurrency = limit.max(1);
+        self
+    }
+
+    /// Set what the copy does when an error occurs (default `ErrorPolicy::Continue`).
+    ///
+    /// With `ErrorPolicy::FailFast` the first error stops the walk and the files which are
+    /// queued to be copied are skipped. The files which are being copied at that moment are not
+    /// cancelled: they are completed and stay in the destination, and their errors are not
+    /// sent. Only the first error is sent, use [`run_wait`](#method.run_wait) to get it
+    /// directly.
+    pub fn error_policy(mut self, policy: ErrorPolicy) -> DeepCopy {
+        self.policy = policy;
+        self
+    }
+
+    /// Call `observer` with an event for every directory created, every file copied, every
+    /// file skipped because a resumed copy already copied it and every error, see
+    /// [`FsObserver`](trait.FsObserver.html).
+    ///
+    /// # Examples
+    /// ```rust
+    /// # extern crate ergo;
+    /// use ergo::*;
+    ///
+    /// # fn try_main() -> ::std::io::Result<()> {
+    /// let tmp = PathTmp::create("observer")?;
+    /// let from = PathDir::create(tmp.join("from"))?;
+    /// PathFile::create(from.join("a.txt"))?.write_str("hello")?;
+    /// PathFile::create(from.join("b.txt"))?.write_str("world!")?;
+    ///
+    /// let copied = Arc::new(AtomicUsize::new(0));
+    /// take!(=copied as counter);
+    /// let observer = Arc::new(move |event: &FsEvent| {
+    ///     if let FsEvent::CopiedFile { bytes, .. } = *event {
+    ///         counter.fetch_add(bytes as usize, AtomicOrdering::SeqCst);
+    ///     }
+    /// });
+    /// DeepCopy::new(from, tmp.join("to")).observer(observer).run_wait()?;
+    /// assert_eq!(11, copied.load(AtomicOrdering::SeqCst));
+    /// # Ok(()) } fn main() { try_main().unwrap() }
+    /// ```
+    pub fn observer(mut self, observer: Arc<dyn FsObserver>) -> DeepCopy {
+        self.observer = Some(observer);
+        self
+    }
+
+    /// Plan the deep copy without touching the destination (a "dry run").
+    ///
+    /// This walks `from` exactly as [`run`](#method.run) would and returns the operations it
+    /// would perform, in order. The first error which `run` would hit while walking (i.e. the
+    /// destination already existing or a symlink loop) is returned instead.
+    ///
+    /// # Examples
+    /// ```rust
+    /// # extern crate ergo;
+    /// use ergo::*;
+    ///
+    /// # fn try_main() -> ::std::io::Result<()> {
+    /// let tmp = PathTmp::create("plan")?;
+    /// let from = PathDir::create(tmp.join("from"))?;
+    /// PathFile::create(from.join("example.txt"))?;
+    ///
+    /// let to = tmp.join("to");
+    /// let plan = DeepCopy::new(from.clone(), &to).plan()?;
+    /// assert_eq!(
+    ///     vec![
+    ///         PlannedOp::CreateDir(to.to_path_buf()),
+    ///         PlannedOp::CopyFile {
+    ///             from: from.join("example.txt").to_path_buf(),
+    ///             to: to.join("example.txt").to_path_buf(),
+    ///         },
+    ///     ],
+    ///     plan
+    /// );
+    /// for op in &plan {
+    ///     println!("would {}", op);
+    /// }
+    /// assert!(!to.exists());
+    /// # Ok(()) } fn main() { try_main().unwrap() }
+    /// ```
+    pub fn plan(&self) -> io::Result<Vec<PlannedOp>> {
+        check_not_inside(&self.from, &self.to)?;
+        if self.to.exists() {
+            return Err(path_abs::Error::new(
+                io::Error::new(io::ErrorKind::AlreadyExists, "destination already exists"),
+                "creating dir",
+                self.to.clone(),
+            )
+            .into());
+        }
+        let mut ops = vec![PlannedOp::CreateDir(self.to.to_path_buf())];
+        for entry in self.from.walk_with(&self.walk) {
+            let entry = entry?;
+            if entry.depth() == 0 {
+                continue;
+            }
+            let to = {
+                let to_postfix = entry.strip_prefix(&self.from).map_err(io::Error::other)?;
+                self.to.join(to_postfix).to_path_buf()
+            };
+            ops.push(match entry.into_path_type() {
+                PathType::Dir(_) => PlannedOp::CreateDir(to),
+                PathType::File(file) => PlannedOp::CopyFile {
+                    from: file.to_path_buf(),
+                    to,
+                },
+            });
+        }
+        Ok(ops)
+    }
+
+    /// Start the deep copy in the background, returning its errors.
+    ///
+    /// This is [`run`](#method.run) with an error channel owned by the copy: it never blocks
+    /// when the errors are not received, at most [`ERROR_STREAM_LEN`] of them are kept and the
+    /// rest are counted in [`ErrorStream::overflowed`].
+    ///
+    /// [`ERROR_STREAM_LEN`]: constant.ERROR_STREAM_LEN.html
+    /// [`ErrorStream::overflowed`]: struct.ErrorStream.html#method.overflowed
+    ///
+    /// # Examples
+    /// ```rust
+    /// # extern crate ergo;
+    /// use ergo::*;
+    ///
+    /// # fn try_main() -> ::std::io::Result<()> {
+    /// let tmp = PathTmp::create("start")?;
+    /// let from = PathDir::create(tmp.join("from"))?;
+    /// PathFile::create(from.join("example.txt"))?;
+    ///
+    /// let mut errors = DeepCopy::new(from, tmp.join("to")).start();
+    /// assert_eq!(0, errors.by_ref().count());
+    /// assert_eq!(0, errors.overflowed());
+    /// assert!(tmp.join("to").join("example.txt").exists());
+    /// # Ok(()) } fn main() { try_main().unwrap() }
+    /// ```
+    pub fn start(self) -> ErrorStream {
+        ErrorStream::new(ERROR_STREAM_LEN, |send_err| self.run(send_err))
+    }
+
+    /// Run the deep copy and wait for it to complete, returning the first error.
+    ///
+    /// This is mostly useful with [`ErrorPolicy::FailFast`](#method.error_policy), which stops
+    /// the copy at the first error. It is not all-or-nothing: the directories and files which
+    /// were copied before the copy stopped are left in the destination. Use a
+    /// [`journal`](#method.journal) to resume the copy later.
+    ///
+    /// # Examples
+    /// ```rust
+    /// # extern crate ergo;
+    /// use ergo::*;
+    ///
+    /// # fn try_main() -> ::std::io::Result<()> {
+    /// let tmp = PathTmp::create("run_wait")?;
+    /// let from = PathDir::create(tmp.join("from"))?;
+    /// PathFile::create(from.join("example.txt"))?;
+    ///
+    /// let copy = DeepCopy::new(from, tmp.join("to")).error_policy(ErrorPolicy::FailFast);
+    /// copy.clone().run_wait()?;
+    /// assert!(tmp.join("to").join("example.txt").exists());
+    ///
+    /// // the destination exists now
+    /// let err = copy.run_wait().unwrap_err();
+    /// assert_eq!(::std::io::ErrorKind::AlreadyExists, err.kind());
+    /// # Ok(()) } fn main() { try_main().unwrap() }
+    /// ```
+    pub fn run_wait(self) -> io::Result<()> {
+        let (send_err, recv_err) = ch::unbounded();
+        self.run(send_err);
+        let mut errs = recv_err.iter();
+        match errs.next() {
+            Some(err) => {
+                // let the copy complete before returning
+                errs.for_each(drop);
+                Err(err)
+            }
+            None => Ok(()),
+        }
+    }
+
+    /// Run the deep copy in the background: a thread walks `from` and creates the directories,
+    /// the files are copied on the [`pool`](#method.pool).
+    ///
+    /// Errors are sent over the `send_err` channel, which is closed once the copy is complete.
+    pub fn run(self, send_err: Sender<io::Error>) {
+        let DeepCopy {
+            from,
+            to,
+            retry,
+            options,
+            journal,
+            resume,
+            walk,
+            pool,
+            concurrency,
+            policy,
+            observer,
+        } = self;
+        ch_try!(send_err, check_not_inside(&from, &to), return);
+        let completed = match journal {
+            Some(ref path) if resume && path.exists() => {
+                ch_try!(send_err, Journal::completed(path), return)
             }
+            _ => HashSet::new(),
+        };
+        let journal = match journal {
+            Some(path) => Some(Arc::new(ch_try!(send_err, Journal::open(path), return))),
+            None => None,
+        };
+        let to = ch_try!(
+            send_err,
+            retry
+                .run(|| if resume {
+                    create_dir(&to)
+                } else {
+                    create_dir_maybe(&to)
+                })
+                .map_err(|err| err.into()),
+            return
+        );
+
+        // With DirAtEnd the files are synced by a final thread once every copy is complete.
+        let (options, send_created) = if options.fsync_policy() == FsyncPolicy::DirAtEnd {
+            let (send_created, recv_created) = ch::unbounded();
+            take!(=send_err as errs, =to as to_sync);
+            spawn(move || sync_at_end(&to_sync, recv_created, errs));
+            (options.fsync(FsyncPolicy::None), Some(send_created))
+        } else {
+            (options, None)
+        };
+
+        let copier = Arc::new(Copier {
+            to,
+            retry,
+            options,
+            send_err,
+            send_created,
+            journal,
+            policy,
+            failed: AtomicBool::new(false),
+            observer,
         });
+        let (send_permit, recv_permit) = ch::bounded(concurrency);
+        let walker = Walker {
+            from,
+            copier,
+            pool: pool.unwrap_or_else(shared_pool),
+            send_permit,
+            recv_permit,
+            completed,
+            walk,
+        };
+        spawn(move || walker.run());
     }
 }
 
-/// Do a contents-first yeild and follow any symlinks -- we are doing an _actual_ copy
-fn walk_and_create_dirs(
-    from: PathDir,
+/// The pool which deep copies use unless they are given one.
+fn shared_pool() -> Arc<Pool> {
+    static POOL: OnceLock<Arc<Pool>> = OnceLock::new();
+    POOL.get_or_init(|| Arc::new(Pool::new(num_cpus::get())))
+        .clone()
+}
+
+/// What is needed to create and copy into the destination, shared by the walker and the copies
+/// running on the pool. The error channel is closed once all of them are dropped.
+struct Copier {
     to: PathDir,
+    retry: RetryPolicy,
+    options: CopyOptions,
     send_err: Sender<io::Error>,
-    send_file: Sender<(PathFile, PathBuf)>,
-) {
-    let mut it = from.walk().follow_links(true).into_iter();
-    loop {
-        let entry = match it.next() {
-            Some(entry) => entry,
-            None => break,
-        };
-        macro_rules! handle_err {
-            ($entry:expr) => {
-                match $entry {
-                    Ok(e) => e,
-                    Err(err) => {
-                        ch!(send_err <- err.into());
-                        continue;
-                    }
+    send_created: Option<Sender<Created>>,
+    journal: Option<Arc<Journal>>,
+    policy: ErrorPolicy,
+    /// Whether an error occurred, which stops the copy with `ErrorPolicy::FailFast`.
+    failed: AtomicBool,
+    observer: Option<Arc<dyn FsObserver>>,
+}
+
+impl Copier {
+    /// Copy the file `from` into the pre-created directories of the destination.
+    fn copy_file(&self, from: &PathFile, to_postfix: &Path, bytes: u64) {
+        if self.stopped() {
+            return;
+        }
+        let to_file = self.to.join(to_postfix);
+        let start = Instant::now();
+        if let Err(err) = self.retry.run(|| from.copy_with(&to_file, &self.options)) {
+            return self.report(err.into());
+        }
+        self.notify(&FsEvent::CopiedFile {
+            from,
+            to: &to_file,
+            bytes,
+            duration: start.elapsed(),
+        });
+        if let Some(ref journal) = self.journal {
+            if self.options.fsync_policy() != FsyncPolicy::PerFile {
+                if let Err(err) = sync_file(&to_file) {
+                    return self.report(err.into());
                 }
+            }
+            let entry = JournalEntry::Copied {
+                from: from.to_path_buf(),
+                to: to_file.to_path_buf(),
             };
+            if let Err(err) = journal.record(&entry) {
+                return self.report(err);
+            }
+        }
+        if let Some(ref created) = self.send_created {
+            ch!(created <- Created::File(to_file.to_path_buf()));
+        }
+    }
+
+    /// Send the error, unless the copy already stopped after another one.
+    fn report(&self, err: io::Error) {
+        self.notify(&FsEvent::Error(&err));
+        let first = !self.failed.swap(true, AtomicOrdering::SeqCst);
+        if first || self.policy == ErrorPolicy::Continue {
+            let send_err = &self.send_err;
+            ch!(send_err <- err);
         }
-        let entry = handle_err!(entry);
-        let to_postfix = entry
-            .path()
-            .strip_prefix(&from)
-            .map_err(|e| io::Error::new(io::ErrorKind::Other, e));
-        let to_postfix = handle_err!(to_postfix);
-
-        match handle_err!(PathType::new(entry.path())) {
-            PathType::Dir(_) => {
-                // Create it immediately
-                if let Err(err) = PathDir::create(to.join(to_postfix)) {
-                    ch!(send_err <- err.into());
-                    // We couldn't create the directory so it needs to be skipped.
-                    it.skip_current_dir();
+    }
+
+    fn notify(&self, event: &FsEvent) {
+        if let Some(ref observer) = self.observer {
+            observer.on_event(event);
+        }
+    }
+
+    /// Whether no more work should be started.
+    fn stopped(&self) -> bool {
+        self.policy == ErrorPolicy::FailFast && self.failed.load(AtomicOrdering::SeqCst)
+    }
+}
+
+/// A file copy which is queued or running, see [`DeepCopy::concurrency`]. Dropping it lets the
+/// walker queue another.
+///
+/// [`DeepCopy::concurrency`]: struct.DeepCopy.html#method.concurrency
+struct Permit(Receiver<()>);
+
+impl Drop for Permit {
+    fn drop(&mut self) {
+        let _ = self.0.try_recv();
+    }
+}
+
+/// The state of the thread which walks `from`, creating directories and queueing files to copy.
+struct Walker {
+    from: PathDir,
+    copier: Arc<Copier>,
+    pool: Arc<Pool>,
+    send_permit: Sender<()>,
+    recv_permit: Receiver<()>,
+    /// Destination files which are already copied (when resuming).
+    completed: HashSet<PathBuf>,
+    walk: WalkOptions,
+}
+
+impl Walker {
+    /// Do a contents-first yeild and follow any symlinks -- we are doing an _actual_ copy
+    fn run(self) {
+        let Walker {
+            from,
+            copier,
+            pool,
+            send_permit,
+            recv_permit,
+            completed,
+            walk,
+        } = self;
+        let mut it = from.walk_with(&walk);
+        loop {
+            if copier.stopped() {
+                break;
+            }
+            let entry = match it.next() {
+                Some(entry) => entry,
+                None => break,
+            };
+            macro_rules! handle_err {
+                ($entry:expr) => {
+                    match $entry {
+                        Ok(e) => e,
+                        Err(err) => {
+                            copier.report(err.into());
+                            continue;
+                        }
+                    }
+                };
+            }
+            let entry = handle_err!(entry);
+            let to_postfix = entry
+                .strip_prefix(&from)
+                .map(Path::to_path_buf)
+                .map_err(io::Error::other);
+            let to_postfix = handle_err!(to_postfix);
+            let bytes = entry.metadata().len();
+
+            // The walk already read the metadata, so don't stat the path again.
+            match entry.into_path_type() {
+                PathType::Dir(_) => {
+                    // Create it immediately
+                    let to_dir = copier.to.join(&to_postfix);
+                    match copier.retry.run(|| create_dir(&to_dir)) {
+                        Ok(_) => {
+                            copier.notify(&FsEvent::EnteredDir(&to_dir));
+                            if let Some(ref journal) = copier.journal {
+                                let entry = JournalEntry::CreateDir(to_dir.to_path_buf());
+                                handle_err!(journal.record(&entry));
+                            }
+                            if let Some(ref created) = copier.send_created {
+                                ch!(created <- Created::Dir(to_dir.to_path_buf()));
+                            }
+                        }
+                        Err(err) => {
+                            copier.report(err.into());
+                            // We couldn't create the directory so it needs to be skipped.
+                            it.skip_current_dir();
+                        }
+                    }
+                }
+                PathType::File(from_file) => {
+                    let to_file = copier.to.join(&to_postfix).to_path_buf();
+                    if completed.contains(&to_file) {
+                        copier.notify(&FsEvent::Skipped(&from_file));
+                        continue;
+                    }
+                    if let Some(ref journal) = copier.journal {
+                        let entry = JournalEntry::Planned {
+                            from: from_file.to_path_buf(),
+                            to: to_file,
+                        };
+                        handle_err!(journal.record(&entry));
+                    }
+                    // Prefetch the file while it waits to be copied, this is only a hint.
+                    let _ = readahead(&from_file);
+                    // Blocks while `concurrency` files are queued or copying.
+                    ch!(send_permit <- ());
+                    let permit = Permit(recv_permit.clone());
+                    let copier = copier.clone();
+                    pool.execute(move || {
+                        let _permit = permit;
+                        copier.copy_file(&from_file, &to_postfix, bytes);
+                    });
                 }
             }
-            PathType::File(from_file) => {
-                ch!(send_file <- (from_file, to_postfix.to_path_buf()));
+        }
+    }
+}
+
+/// An operation which [`DeepCopy::plan`](struct.DeepCopy.html#method.plan) would perform, see
+/// also [`preflight`](fn.preflight.html).
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum PlannedOp {
+    /// Create the directory.
+    CreateDir(PathBuf),
+    /// Copy the file `from` to `to`.
+    CopyFile {
+        /// The source file.
+        from: PathBuf,
+        /// The destination file.
+        to: PathBuf,
+    },
+    /// Remove the file or directory. Only used by [`plan_remove`](fn.plan_remove.html).
+    Remove(PathBuf),
+}
+
+impl fmt::Display for PlannedOp {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            PlannedOp::CreateDir(ref dir) => write!(f, "create dir {}", dir.display()),
+            PlannedOp::CopyFile { ref from, ref to } => {
+                write!(f, "copy {} to {}", from.display(), to.display())
             }
+            PlannedOp::Remove(ref path) => write!(f, "remove {}", path.display()),
         }
     }
 }
 
+/// A path created by the copy, which must be synced with `FsyncPolicy::DirAtEnd`.
+enum Created {
+    File(PathBuf),
+    Dir(PathBuf),
+}
+
+/// Once everything has been copied (the channel is closed) sync every file, followed by every
+/// directory which had entries created in it.
+fn sync_at_end(to: &PathDir, recv_created: Receiver<Created>, send_err: Sender<io::Error>) {
+    let mut files = Vec::new();
+    let mut dirs = BTreeSet::new();
+    if let Some(parent) = to.parent() {
+        dirs.insert(parent.to_path_buf());
+    }
+    for created in recv_created {
+        let path = match created {
+            Created::File(path) => {
+                files.push(path.clone());
+                path
+            }
+            Created::Dir(path) => path,
+        };
+        if let Some(parent) = path.parent() {
+            dirs.insert(parent.to_path_buf());
+        }
+    }
+    for file in files {
+        ch_try!(
+            send_err,
+            sync_file(&file).map_err(|err| err.into()),
+            continue
+        );
+    }
+    for dir in dirs {
+        ch_try!(send_err, sync_dir(&dir).map_err(|err| err.into()), continue);
+    }
+}
+
+/// Refuse to copy `from` into itself, which would never terminate.
+///
+/// The paths are compared by file identity rather than by string so that symlinks and
+/// case-insensitive filesystems are handled.
+fn check_not_inside(from: &PathDir, to: &PathArc) -> io::Result<()> {
+    let to_abs = if to.is_absolute() {
+        to.to_path_buf()
+    } else {
+        env::current_dir()?.join(to)
+    };
+    // `to` (or some of its parents) may not exist yet, find the closest one that does.
+    let (existing, missing) = match to_abs
+        .ancestors()
+        .filter_map(|p| fs::canonicalize(p).ok().map(|c| (c, p)))
+        .next()
+    {
+        Some((canonical, p)) => (canonical, to_abs.strip_prefix(p).expect("an ancestor")),
+        None => return Ok(()),
+    };
+    // The missing components can't be symlinks, so `..` in them can be resolved lexically.
+    let mut resolved = existing;
+    for component in missing.components() {
+        match component {
+            Component::ParentDir => {
+                resolved.pop();
+            }
+            Component::CurDir => {}
+            c => resolved.push(c),
+        }
+    }
+    for ancestor in resolved.ancestors().filter(|p| p.exists()) {
+        if from.same_file(ancestor)? {
+            return Err(ergo_fs::Error::with_paths(
+                io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    "cannot copy a directory into itself",
+                ),
+                "copying",
+                PathArc::new(from),
+                to.clone(),
+            )
+            .into());
+        }
+    }
+    Ok(())
+}
+
+/// Create the directory `path`, or use it if it already exists.
+fn create_dir<P: AsRef<Path>>(path: P) -> path_abs::Result<PathDir> {
+    let arc = PathArc::new(path);
+    faults::check(FsOp::CreateDir, &arc)
+        .map_err(|err| path_abs::Error::new(err, "creating dir", arc.clone()))?;
+    PathDir::create(arc)
+}
+
 fn create_dir_maybe<P: AsRef<Path>>(path: P) -> path_abs::Result<PathDir> {
     let arc = PathArc::new(path);
-    fs::create_dir(&arc).map_err(|err| path_abs::Error::new(err, "creating dir", arc.clone()))?;
+    faults::check(FsOp::CreateDir, &arc)
+        .and_then(|()| fs::create_dir(&arc))
+        .map_err(|err| path_abs::Error::new(err, "creating dir", arc.clone()))?;
     PathDir::new(arc)
 }
@@ -0,0 +1,321 @@
+/* Copyright (c) 2018 Garrett Berg, vitiral@gmail.com
+ *
+ * Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+ * http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+ * http://opensource.org/licenses/MIT>, at your option. This file may not be
+ * copied, modified, or distributed except according to those terms.
+ */
+//! The unified error type of `ergo`.
+use super::*;
+use std::error;
+use std::fmt;
+use std::io;
+use std::path::Path;
+
+/// A `Result` which defaults to the [`ergo::Error`](enum.Error.html) type.
+///
+/// The error type can still be given explicitly, so `Result<T, E>` means the same as
+/// `std::result::Result<T, E>`.
+pub type Result<T, E = Error> = ::std::result::Result<T, E>;
+
+/// An error from any of the `ergo` crates.
+///
+/// Every error type from the `ergo_*` crates converts into this one with `?`, keeping the
+/// context (path, pattern or variable) which makes its message useful. This means one function
+/// can use the filesystem, globs and configuration together without resorting to
+/// `Box<dyn Error>`. The variants of a sub-crate only exist when its feature is enabled.
+///
+/// It can also be converted into an `io::Error`, so `?` works in functions which return
+/// `io::Result`. Errors wrapped with [`ResultExt::context`] convert too, keeping the category
+/// and path of the wrapped error.
+///
+/// [`ResultExt::context`]: trait.ResultExt.html#tymethod.context
+///
+/// # Examples
+/// ```rust
+/// # extern crate ergo;
+/// use ergo::*;
+///
+/// fn load_port(path: &Path) -> ergo::Result<u16> {
+///     let file = PathFile::new(path).context("loading config")?;
+///     let config = Config::from_toml_str(&file.read_string()?)?;
+///     Ok(config.get("server.port")?)
+/// }
+///
+/// # fn try_main() -> ::std::io::Result<()> {
+/// let tmp = PathTmp::create("error")?;
+/// let missing = tmp.join("missing.toml");
+/// let err = load_port(&missing).unwrap_err();
+/// assert_eq!("fs", err.category());
+/// assert_eq!(Some(missing.as_path()), err.path());
+/// assert!(err.to_string().starts_with("loading config: "));
+///
+/// let config = PathFile::create(tmp.join("ergo.toml"))?;
+/// config.write_str("[server]\nhost = 'localhost'\n")?;
+/// let err = load_port(config.as_path()).unwrap_err();
+/// assert_eq!("config", err.category());
+/// assert_eq!("server.port: not found", err.to_string());
+/// # Ok(()) } fn main() { try_main().unwrap() }
+/// ```
+#[derive(Debug)]
+pub enum Error {
+    /// A filesystem error, with the action and path(s) involved.
+    #[cfg(feature = "fs")]
+    Fs(ergo_fs::Error),
+    /// An io error without a known path.
+    Io(io::Error),
+    /// An invalid glob pattern.
+    #[cfg(feature = "fs")]
+    Glob(GlobPatternError),
+    /// A variable which could not be expanded.
+    #[cfg(feature = "fs")]
+    Expand(ExpandError),
+    /// An error loading or accessing configuration.
+    #[cfg(feature = "config")]
+    Config(ConfigError),
+    /// A channel was disconnected or timed out.
+    #[cfg(feature = "sync")]
+    Channel(String),
+    /// Invalid command line arguments.
+    #[cfg(feature = "fs")]
+    Args(ArgsError),
+    /// Another error wrapped with context by [`ResultExt`](trait.ResultExt.html).
+    #[cfg(feature = "std")]
+    Context(Context<Box<Error>>),
+}
+
+impl Error {
+    /// A short, stable name for the kind of error: `"fs"`, `"io"`, `"glob"`, `"expand"`,
+    /// `"config"`, `"channel"` or `"args"`.
+    ///
+    /// Context does not change the category, it is the category of the wrapped error.
+    pub fn category(&self) -> &'static str {
+        match *self {
+            #[cfg(feature = "fs")]
+            Error::Fs(_) => "fs",
+            Error::Io(_) => "io",
+            #[cfg(feature = "fs")]
+            Error::Glob(_) => "glob",
+            #[cfg(feature = "fs")]
+            Error::Expand(_) => "expand",
+            #[cfg(feature = "config")]
+            Error::Config(_) => "config",
+            #[cfg(feature = "sync")]
+            Error::Channel(_) => "channel",
+            #[cfg(feature = "fs")]
+            Error::Args(_) => "args",
+            #[cfg(feature = "std")]
+            Error::Context(ref err) => err.get_ref().category(),
+        }
+    }
+
+    /// The filesystem path associated with the error, if there is one.
+    pub fn path(&self) -> Option<&Path> {
+        match *self {
+            #[cfg(feature = "fs")]
+            Error::Fs(ref err) => Some(err.path()),
+            #[cfg(feature = "std")]
+            Error::Context(ref err) => err.get_ref().path(),
+            _ => None,
+        }
+    }
+
+    /// The name of the variable which could not be expanded, if that is the cause of the error.
+    pub fn variable(&self) -> Option<&str> {
+        match *self {
+            #[cfg(feature = "fs")]
+            Error::Expand(ref err) => Some(&err.var_name),
+            #[cfg(feature = "std")]
+            Error::Context(ref err) => err.get_ref().variable(),
+            _ => None,
+        }
+    }
+
+    /// The `io::ErrorKind` which best describes the error.
+    pub fn kind(&self) -> io::ErrorKind {
+        match *self {
+            #[cfg(feature = "fs")]
+            Error::Fs(ref err) => err.kind(),
+            Error::Io(ref err) => err.kind(),
+            #[cfg(feature = "fs")]
+            Error::Glob(_) => io::ErrorKind::InvalidInput,
+            #[cfg(feature = "fs")]
+            Error::Expand(_) => io::ErrorKind::NotFound,
+            #[cfg(feature = "config")]
+            Error::Config(ConfigError::Io(ref err)) => err.kind(),
+            #[cfg(feature = "config")]
+            Error::Config(ConfigError::NotFound { .. }) => io::ErrorKind::NotFound,
+            #[cfg(feature = "config")]
+            Error::Config(_) => io::ErrorKind::InvalidData,
+            #[cfg(feature = "sync")]
+            Error::Channel(_) => io::ErrorKind::BrokenPipe,
+            #[cfg(feature = "fs")]
+            Error::Args(_) => io::ErrorKind::InvalidInput,
+            #[cfg(feature = "std")]
+            Error::Context(ref err) => err.get_ref().kind(),
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            #[cfg(feature = "fs")]
+            Error::Fs(ref err) => write!(f, "{}", err),
+            Error::Io(ref err) => write!(f, "{}", err),
+            #[cfg(feature = "fs")]
+            Error::Glob(ref err) => write!(f, "{}", err),
+            #[cfg(feature = "fs")]
+            Error::Expand(ref err) => write!(f, "{}", err),
+            #[cfg(feature = "config")]
+            Error::Config(ref err) => write!(f, "{}", err),
+            #[cfg(feature = "sync")]
+            Error::Channel(ref msg) => f.write_str(msg),
+            #[cfg(feature = "fs")]
+            Error::Args(ref err) => write!(f, "{}", err),
+            #[cfg(feature = "std")]
+            Error::Context(ref err) => write!(f, "{}", err),
+        }
+    }
+}
+
+impl error::Error for Error {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match *self {
+            #[cfg(feature = "fs")]
+            Error::Fs(ref err) => Some(err),
+            Error::Io(ref err) => Some(err),
+            #[cfg(feature = "fs")]
+            Error::Glob(ref err) => Some(err),
+            #[cfg(feature = "fs")]
+            Error::Expand(ref err) => Some(err),
+            #[cfg(feature = "config")]
+            Error::Config(ref err) => Some(err),
+            #[cfg(feature = "sync")]
+            Error::Channel(_) => None,
+            #[cfg(feature = "fs")]
+            Error::Args(ref err) => Some(err),
+            #[cfg(feature = "std")]
+            Error::Context(ref err) => Some(err.get_ref().as_ref()),
+        }
+    }
+}
+
+impl From<Error> for io::Error {
+    fn from(err: Error) -> io::Error {
+        match err {
+            #[cfg(feature = "fs")]
+            Error::Fs(err) => err.into(),
+            Error::Io(err) => err,
+            #[cfg(feature = "config")]
+            Error::Config(err) => err.into(),
+            // without any sub-crate every error is `Io`
+            #[allow(unreachable_patterns)]
+            err => io::Error::new(err.kind(), err),
+        }
+    }
+}
+
+#[cfg(feature = "fs")]
+impl From<ergo_fs::Error> for Error {
+    fn from(err: ergo_fs::Error) -> Error {
+        Error::Fs(err)
+    }
+}
+
+#[cfg(feature = "fs")]
+impl From<path_abs::Error> for Error {
+    fn from(err: path_abs::Error) -> Error {
+        Error::Fs(err.into())
+    }
+}
+
+#[cfg(feature = "fs")]
+impl From<WalkError> for Error {
+    fn from(err: WalkError) -> Error {
+        Error::Fs(err.into())
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(err: io::Error) -> Error {
+        Error::Io(err)
+    }
+}
+
+#[cfg(feature = "fs")]
+impl From<GlobPatternError> for Error {
+    fn from(err: GlobPatternError) -> Error {
+        Error::Glob(err)
+    }
+}
+
+#[cfg(feature = "fs")]
+impl From<ExpandError> for Error {
+    fn from(err: ExpandError) -> Error {
+        Error::Expand(err)
+    }
+}
+
+#[cfg(feature = "config")]
+impl From<ConfigError> for Error {
+    fn from(err: ConfigError) -> Error {
+        Error::Config(err)
+    }
+}
+
+#[cfg(feature = "fs")]
+impl From<ArgsError> for Error {
+    fn from(err: ArgsError) -> Error {
+        Error::Args(err)
+    }
+}
+
+#[cfg(feature = "std")]
+impl<E: Into<Error>> From<Context<E>> for Error {
+    fn from(err: Context<E>) -> Error {
+        Error::Context(err.map(|e| Box::new(e.into())))
+    }
+}
+
+#[cfg(feature = "sync")]
+impl<T> From<SendError<T>> for Error {
+    fn from(err: SendError<T>) -> Error {
+        Error::Channel(err.to_string())
+    }
+}
+
+#[cfg(feature = "sync")]
+impl<T> From<TrySendError<T>> for Error {
+    fn from(err: TrySendError<T>) -> Error {
+        Error::Channel(err.to_string())
+    }
+}
+
+#[cfg(feature = "sync")]
+impl<T> From<SendTimeoutError<T>> for Error {
+    fn from(err: SendTimeoutError<T>) -> Error {
+        Error::Channel(err.to_string())
+    }
+}
+
+#[cfg(feature = "sync")]
+impl From<RecvError> for Error {
+    fn from(err: RecvError) -> Error {
+        Error::Channel(err.to_string())
+    }
+}
+
+#[cfg(feature = "sync")]
+impl From<TryRecvError> for Error {
+    fn from(err: TryRecvError) -> Error {
+        Error::Channel(err.to_string())
+    }
+}
+
+#[cfg(feature = "sync")]
+impl From<RecvTimeoutError> for Error {
+    fn from(err: RecvTimeoutError) -> Error {
+        Error::Channel(err.to_string())
+    }
+}
@@ -0,0 +1,89 @@
+/* Copyright (c) 2018 Garrett Berg, vitiral@gmail.com
+ *
+ * Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+ * http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+ * http://opensource.org/licenses/MIT>, at your option. This file may not be
+ * copied, modified, or distributed except according to those terms.
+ */
+//! Bounded streams of errors from background operations.
+use super::*;
+use std::io;
+
+/// The number of errors an [`ErrorStream`](struct.ErrorStream.html) buffers by default.
+pub const ERROR_STREAM_LEN: usize = 1024;
+
+/// The errors of an operation running in the background, i.e. from [`DeepCopy::start`].
+///
+/// Unlike passing in a `Sender`, the operation can neither block on a full channel nor buffer
+/// an unbounded number of errors when they are not received: at most `capacity` errors are kept
+/// and any further ones are dropped and counted in [`overflowed`].
+///
+/// Iterating blocks until the next error or until the operation is complete.
+///
+/// [`DeepCopy::start`]: struct.DeepCopy.html#method.start
+/// [`overflowed`]: #method.overflowed
+///
+/// # Examples
+/// ```rust
+/// # extern crate ergo;
+/// use ergo::*;
+/// use std::io;
+/// use std::thread;
+///
+/// // the operation is complete once this returns
+/// let mut errors = ErrorStream::new(2, |send_err| {
+///     for i in 0..5 {
+///         ch!(send_err <- io::Error::other(format!("error {}", i)));
+///     }
+/// });
+/// // nothing receives the errors, so all but 2 of them overflow once they are forwarded
+/// while errors.overflowed() < 3 {
+///     thread::yield_now();
+/// }
+/// assert_eq!(2, errors.by_ref().count());
+/// assert_eq!(3, errors.overflowed());
+/// ```
+pub struct ErrorStream {
+    recv: Receiver<io::Error>,
+    overflowed: Arc<AtomicUsize>,
+}
+
+impl ErrorStream {
+    /// Start the operation `op` with a sender of its errors, keeping at most `capacity` of them
+    /// until they are received.
+    ///
+    /// The stream ends once `op` and everything it gave a clone of the sender have dropped it.
+    pub fn new<F>(capacity: usize, op: F) -> ErrorStream
+    where
+        F: FnOnce(Sender<io::Error>),
+    {
+        let (send_err, recv_err) = ch::unbounded();
+        let (send, recv) = ch::bounded(capacity);
+        let overflowed = Arc::new(AtomicUsize::new(0));
+        take!(=overflowed as counter);
+        // Forward the errors as they arrive so that the operation never waits on the receiver.
+        spawn(move || {
+            for err in recv_err {
+                if send.try_send(err).is_err() {
+                    counter.fetch_add(1, AtomicOrdering::SeqCst);
+                }
+            }
+        });
+        op(send_err);
+        ErrorStream { recv, overflowed }
+    }
+
+    /// The number of errors which were dropped because the buffer was full. Only final once
+    /// the stream has ended.
+    pub fn overflowed(&self) -> usize {
+        self.overflowed.load(AtomicOrdering::SeqCst)
+    }
+}
+
+impl Iterator for ErrorStream {
+    type Item = io::Error;
+
+    fn next(&mut self) -> Option<io::Error> {
+        self.recv.recv().ok()
+    }
+}
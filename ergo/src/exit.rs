@@ -0,0 +1,104 @@
+/* Copyright (c) 2018 Garrett Berg, vitiral@gmail.com
+ *
+ * Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+ * http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+ * http://opensource.org/licenses/MIT>, at your option. This file may not be
+ * copied, modified, or distributed except according to those terms.
+ */
+//! Helpers for the `main` function of binaries.
+use super::*;
+use std::error;
+use std::io::{self, Write};
+use std::process::ExitCode;
+
+/// Define `fn main()` which calls `$main` with [`run_main`](fn.run_main.html), printing its
+/// error (if any) and exiting with the matching code.
+///
+/// # Examples
+/// ```rust,no_run
+/// #[macro_use] extern crate ergo;
+/// use ergo::*;
+///
+/// fn try_main() -> ergo::Result<()> {
+///     let config = PathFile::new("ergo.toml").context("loading config")?;
+///     println!("{}", config.read_string()?);
+///     Ok(())
+/// }
+///
+/// main_result!(try_main);
+/// ```
+#[macro_export]
+macro_rules! main_result {
+    ($main:expr) => {
+        fn main() -> ::std::process::ExitCode {
+            $crate::run_main($main)
+        }
+    };
+}
+
+/// Run `main`, and if it fails print the full error chain to stderr and return a failing exit
+/// code. Return the result of this from `fn main() -> ExitCode`, or use the
+/// [`main_result!`](macro.main_result.html) macro.
+///
+/// The error is printed as `error: {error}` followed by a `caused by: {source}` line for every
+/// source which is not already part of the message. See [`render_error`]. When stderr is a
+/// terminal `error:` is colored, see [`term`].
+///
+/// [`render_error`]: fn.render_error.html
+/// [`term`]: term/index.html
+pub fn run_main<F>(main: F) -> ExitCode
+where
+    F: FnOnce() -> Result<()>,
+{
+    match main() {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(err) => {
+            let stderr = io::stderr();
+            let prefix = term::style("error:").red().bold().stderr().to_string();
+            let _ = stderr
+                .lock()
+                .write_all(render_error_with(&err, &prefix).as_bytes());
+            ExitCode::FAILURE
+        }
+    }
+}
+
+/// Render an error and its chain of sources as it is printed by [`run_main`].
+///
+/// Sources whose message is already included in the message of the error that wraps them
+/// (like an error with [`context`]) are skipped, so nothing is printed twice.
+///
+/// [`run_main`]: fn.run_main.html
+/// [`context`]: trait.ResultExt.html#tymethod.context
+///
+/// # Examples
+/// ```rust
+/// # extern crate ergo;
+/// use ergo::*;
+///
+/// # fn main() {
+/// let err = ergo::Error::from(PathFile::new("/this/does/not/exist").unwrap_err());
+/// let err: ergo::Error = Err::<(), _>(err).context("loading config").unwrap_err().into();
+///
+/// let rendered = render_error(&err);
+/// assert!(rendered.starts_with("error: loading config: "), "{}", rendered);
+/// assert_eq!(1, rendered.lines().count());
+/// # }
+/// ```
+pub fn render_error(err: &dyn error::Error) -> String {
+    render_error_with(err, "error:")
+}
+
+fn render_error_with(err: &dyn error::Error, prefix: &str) -> String {
+    let message = err.to_string();
+    let mut out = format!("{} {}\n", prefix, message);
+    let mut source = err.source();
+    while let Some(err) = source {
+        let cause = err.to_string();
+        if !message.contains(&cause) {
+            out.push_str(&format!("  caused by: {}\n", cause));
+        }
+        source = err.source();
+    }
+    out
+}
@@ -0,0 +1,82 @@
+/* Copyright (c) 2018 Garrett Berg, vitiral@gmail.com
+ *
+ * Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+ * http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+ * http://opensource.org/licenses/MIT>, at your option. This file may not be
+ * copied, modified, or distributed except according to those terms.
+ */
+//! Drop-in replacements for common `std::fs` functions.
+//!
+//! Every function here has the same signature as its `std::fs` counterpart except that it returns
+//! a [`path_abs::Result`], so errors include the action and path that caused them. Large
+//! codebases can migrate by changing a single import and then adopt the typed `Path*` APIs
+//! incrementally.
+//!
+//! Since `path_abs::Error` converts into `std::io::Error`, existing `io::Result` code continues
+//! to compile with `?`.
+//!
+//! [`path_abs::Result`]: ../../path_abs/type.Result.html
+//!
+//! # Examples
+//! ```rust
+//! # extern crate ergo;
+//! // use std::fs;
+//! use ergo::fs_compat as fs;
+//! # use ergo::*;
+//!
+//! # fn try_main() -> ::std::io::Result<()> {
+//! # let tmp = PathTmp::create("fs_compat")?;
+//! # let example = tmp.join("example.txt");
+//! fs::write(&example, "hello")?;
+//! assert_eq!(b"hello".to_vec(), fs::read(&example)?);
+//!
+//! let err = fs::read(tmp.join("missing.txt")).unwrap_err();
+//! assert!(err.to_string().contains("missing.txt"));
+//! # Ok(()) } fn main() { try_main().unwrap() }
+//! ```
+use super::*;
+use std::fs;
+
+/// Read the entire contents of a file into a bytes vector.
+///
+/// See [`std::fs::read`](https://doc.rust-lang.org/std/fs/fn.read.html).
+pub fn read<P: AsRef<Path>>(path: P) -> path_abs::Result<Vec<u8>> {
+    fs::read(&path).map_err(|err| path_abs::Error::new(err, "reading", PathArc::new(&path)))
+}
+
+/// Write a slice as the entire contents of a file, creating it if it does not exist.
+///
+/// See [`std::fs::write`](https://doc.rust-lang.org/std/fs/fn.write.html).
+pub fn write<P: AsRef<Path>, C: AsRef<[u8]>>(path: P, contents: C) -> path_abs::Result<()> {
+    fs::write(&path, contents)
+        .map_err(|err| path_abs::Error::new(err, "writing", PathArc::new(&path)))
+}
+
+/// Copy the contents of one file to another, returning the number of bytes copied.
+///
+/// See [`std::fs::copy`](https://doc.rust-lang.org/std/fs/fn.copy.html).
+pub fn copy<P: AsRef<Path>, Q: AsRef<Path>>(from: P, to: Q) -> path_abs::Result<u64> {
+    fs::copy(&from, &to).map_err(|err| {
+        path_abs::Error::new(
+            err,
+            &format!("copying {} from", to.as_ref().display()),
+            PathArc::new(&from),
+        )
+    })
+}
+
+/// Recursively create a directory and all of its parent components if they are missing.
+///
+/// See [`std::fs::create_dir_all`](https://doc.rust-lang.org/std/fs/fn.create_dir_all.html).
+pub fn create_dir_all<P: AsRef<Path>>(path: P) -> path_abs::Result<()> {
+    fs::create_dir_all(&path)
+        .map_err(|err| path_abs::Error::new(err, "creating-all", PathArc::new(&path)))
+}
+
+/// Remove a directory after removing all its contents. Use carefully!
+///
+/// See [`std::fs::remove_dir_all`](https://doc.rust-lang.org/std/fs/fn.remove_dir_all.html).
+pub fn remove_dir_all<P: AsRef<Path>>(path: P) -> path_abs::Result<()> {
+    fs::remove_dir_all(&path)
+        .map_err(|err| path_abs::Error::new(err, "removing-all", PathArc::new(&path)))
+}
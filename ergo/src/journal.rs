@@ -0,0 +1,175 @@
+/* Copyright (c) 2018 Garrett Berg, vitiral@gmail.com
+ *
+ * Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+ * http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+ * http://opensource.org/licenses/MIT>, at your option. This file may not be
+ * copied, modified, or distributed except according to those terms.
+ */
+//! A journal of filesystem operations for recovering from crashes.
+use super::*;
+use ergo_fs::faults::{self, FsOp};
+use std::collections::HashSet;
+use std::fmt;
+use std::io;
+use std::sync::Mutex;
+
+/// An append-only journal of the operations performed by a long running filesystem operation
+/// (i.e. [`DeepCopy`]), so that it can be resumed after being interrupted.
+///
+/// Each entry is written as one line of JSON ([ndjson]) and flushed immediately, so the journal
+/// is complete up to the moment of a crash (apart from possibly a partial last line, which is
+/// ignored when reading). Paths which are not valid unicode are recorded lossily.
+///
+/// ```text
+/// {"op":"create_dir","to":"/backup/src"}
+/// {"from":"/home/user/src/lib.rs","op":"planned","to":"/backup/src/lib.rs"}
+/// {"from":"/home/user/src/lib.rs","op":"copied","to":"/backup/src/lib.rs"}
+/// ```
+///
+/// [`DeepCopy`]: struct.DeepCopy.html
+/// [ndjson]: http://ndjson.org/
+///
+/// # Examples
+/// ```rust
+/// # extern crate ergo;
+/// use ergo::*;
+///
+/// # fn try_main() -> ::std::io::Result<()> {
+/// let tmp = PathTmp::create("journal")?;
+/// let path = tmp.join("copy.journal");
+/// {
+///     let journal = Journal::open(&path)?;
+///     journal.record(&JournalEntry::Copied {
+///         from: "/from/a.txt".into(),
+///         to: "/to/a.txt".into(),
+///     })?;
+///     journal.record(&JournalEntry::Planned {
+///         from: "/from/b.txt".into(),
+///         to: "/to/b.txt".into(),
+///     })?;
+/// }
+///
+/// let completed = Journal::completed(&path)?;
+/// assert!(completed.contains(Path::new("/to/a.txt")));
+/// assert!(!completed.contains(Path::new("/to/b.txt")));
+/// # Ok(()) } fn main() { try_main().unwrap() }
+/// ```
+pub struct Journal {
+    path: PathArc,
+    file: Mutex<FileWrite>,
+}
+
+/// An entry in a [`Journal`](struct.Journal.html).
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum JournalEntry {
+    /// The directory was created.
+    CreateDir(PathBuf),
+    /// The file `from` is about to be copied to `to`.
+    Planned {
+        /// The source file.
+        from: PathBuf,
+        /// The destination file.
+        to: PathBuf,
+    },
+    /// The file `from` was completely copied to `to`.
+    Copied {
+        /// The source file.
+        from: PathBuf,
+        /// The destination file.
+        to: PathBuf,
+    },
+}
+
+impl Journal {
+    /// Open the journal at `path` for appending, creating it if it does not exist.
+    pub fn open<P: AsRef<Path>>(path: P) -> io::Result<Journal> {
+        let file = FileWrite::append(path)?;
+        Ok(Journal {
+            path: PathArc::new(file.path()),
+            file: Mutex::new(file),
+        })
+    }
+
+    /// The path of the journal.
+    pub fn path(&self) -> &Path {
+        self.path.as_ref()
+    }
+
+    /// Append the entry to the journal and flush it.
+    pub fn record(&self, entry: &JournalEntry) -> io::Result<()> {
+        let mut line = entry.to_json().to_string();
+        line.push('\n');
+        let mut file = self.file.lock().expect("journal poisoned");
+        faults::check(FsOp::Write, &self.path)?;
+        file.write_str(&line)?;
+        file.flush()?;
+        Ok(())
+    }
+
+    /// Read all of the entries of the journal at `path`, skipping any lines which cannot be
+    /// parsed (i.e. a line which was being written during a crash).
+    pub fn read<P: AsRef<Path>>(path: P) -> io::Result<Vec<JournalEntry>> {
+        let text = PathFile::new(path)?.read_string()?;
+        Ok(text.lines().filter_map(JournalEntry::from_json).collect())
+    }
+
+    /// Read the destinations of every file which the journal at `path` records as completely
+    /// copied.
+    pub fn completed<P: AsRef<Path>>(path: P) -> io::Result<HashSet<PathBuf>> {
+        Ok(Journal::read(path)?
+            .into_iter()
+            .filter_map(|entry| match entry {
+                JournalEntry::Copied { to, .. } => Some(to),
+                _ => None,
+            })
+            .collect())
+    }
+}
+
+impl fmt::Debug for Journal {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Journal({})", self.path.display())
+    }
+}
+
+impl JournalEntry {
+    fn to_json(&self) -> json::Value {
+        let path = |p: &PathBuf| json::Value::String(p.to_string_lossy().into_owned());
+        let mut map = json::Map::new();
+        let op = match *self {
+            JournalEntry::CreateDir(ref to) => {
+                map.insert("to".into(), path(to));
+                "create_dir"
+            }
+            JournalEntry::Planned { ref from, ref to } => {
+                map.insert("from".into(), path(from));
+                map.insert("to".into(), path(to));
+                "planned"
+            }
+            JournalEntry::Copied { ref from, ref to } => {
+                map.insert("from".into(), path(from));
+                map.insert("to".into(), path(to));
+                "copied"
+            }
+        };
+        map.insert("op".into(), json::Value::String(op.into()));
+        json::Value::Object(map)
+    }
+
+    fn from_json(line: &str) -> Option<JournalEntry> {
+        let value: json::Value = json::from_str(line).ok()?;
+        let path = |key| value.get(key).and_then(|v| v.as_str()).map(PathBuf::from);
+        match value.get("op")?.as_str()? {
+            "create_dir" => Some(JournalEntry::CreateDir(path("to")?)),
+            "planned" => Some(JournalEntry::Planned {
+                from: path("from")?,
+                to: path("to")?,
+            }),
+            "copied" => Some(JournalEntry::Copied {
+                from: path("from")?,
+                to: path("to")?,
+            }),
+            _ => None,
+        }
+    }
+}
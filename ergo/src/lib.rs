@@ -11,24 +11,94 @@
 //! more details_
 //!
 //! https://github.com/rust-crates/ergo
+//!
+//! # Exports
+//!
+//! Everything from the sub-crates (`ergo_config`, `ergo_fs`, `ergo_std`, `ergo_sync` and
+//! `ergo_sys`) is exported here, so `use ergo::*` is all a script needs. Each sub-crate is
+//! behind a feature (`config`, `fs`, `std`, `sync` and `sys`, all enabled by default), so the
+//! ones which are not needed are neither compiled nor exported. The items of this crate which
+//! build on a sub-crate (i.e. [`DeepCopy`](struct.DeepCopy.html) needs `config`, `fs` and
+//! `sync`) are only available when its feature is enabled.
+//!
+//! In larger projects, prefer [`ergo::prelude`](prelude/index.html): a curated set of the
+//! most used items which doesn't conflict with other imports.
 #![allow(unused_imports)]
 
+#[cfg(feature = "config")]
 #[macro_use]
 pub extern crate ergo_config;
+#[cfg(feature = "fs")]
 #[macro_use]
 pub extern crate ergo_fs;
+#[cfg(feature = "std")]
 #[macro_use]
 pub extern crate ergo_std;
+#[cfg(feature = "sync")]
 #[macro_use]
 pub extern crate ergo_sync;
+#[cfg(feature = "sys")]
 #[macro_use]
 pub extern crate ergo_sys;
 
+#[cfg(feature = "config")]
 pub use ergo_config::*;
+#[cfg(feature = "fs")]
 pub use ergo_fs::*;
+#[cfg(feature = "std")]
 pub use ergo_std::*;
+#[cfg(feature = "sync")]
 pub use ergo_sync::*;
+#[cfg(feature = "sys")]
 pub use ergo_sys::*;
 
+pub mod prelude;
+
+/// The test helpers of the sub-crates, which both have a `testing` module.
+pub mod testing {
+    #[cfg(feature = "fs")]
+    pub use ergo_fs::testing::*;
+    #[cfg(feature = "sync")]
+    pub use ergo_sync::testing::*;
+}
+
+mod error;
+pub use error::{Error, Result};
+
+#[cfg(feature = "fs")]
+mod args;
+#[cfg(feature = "fs")]
+pub use args::{Arg, Args, ArgsError, Matches};
+
+#[cfg(feature = "std")]
+mod exit;
+#[cfg(feature = "std")]
+pub use exit::{render_error, run_main};
+
+#[cfg(feature = "sync")]
+mod error_stream;
+#[cfg(feature = "sync")]
+pub use error_stream::{ErrorStream, ERROR_STREAM_LEN};
+
+#[cfg(all(feature = "config", feature = "fs", feature = "sync"))]
 mod deep_copy;
-pub use deep_copy::deep_copy;
+#[cfg(all(feature = "config", feature = "fs", feature = "sync"))]
+pub use deep_copy::{deep_copy, DeepCopy, PlannedOp};
+
+#[cfg(all(feature = "config", feature = "fs", feature = "sync"))]
+mod preflight;
+#[cfg(all(feature = "config", feature = "fs", feature = "sync"))]
+pub use preflight::{plan_remove, preflight};
+
+#[cfg(all(feature = "config", feature = "fs"))]
+mod journal;
+#[cfg(all(feature = "config", feature = "fs"))]
+pub use journal::{Journal, JournalEntry};
+
+#[cfg(all(feature = "fs", feature = "sync"))]
+mod tar_stream;
+#[cfg(all(feature = "fs", feature = "sync"))]
+pub use tar_stream::{tar_receive, tar_stream, TAR_CHUNK_SIZE};
+
+#[cfg(feature = "fs")]
+pub mod fs_compat;
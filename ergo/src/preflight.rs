@@ -0,0 +1,93 @@
+/* Copyright (c) 2018 Garrett Berg, vitiral@gmail.com
+ *
+ * Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+ * http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+ * http://opensource.org/licenses/MIT>, at your option. This file may not be
+ * copied, modified, or distributed except according to those terms.
+ */
+//! Checking the permissions of planned operations up front.
+use super::*;
+use std::collections::HashSet;
+use std::io;
+
+/// Check that every operation of a `plan` is permitted before performing any of them.
+///
+/// Destructive operations which fail midway leave a half copied or half removed tree behind.
+/// Instead, get the plan from [`DeepCopy::plan`] or [`plan_remove`] and call this first: every
+/// permission problem is sent over `send_err` (not just the first), and the channel is closed
+/// once the whole plan was checked.
+///
+/// For each operation this checks:
+///
+/// - creating a directory or copying a file: write access to the nearest existing ancestor of
+///   the destination, which is where the first missing directory is created.
+/// - copying a file: read access to the source.
+/// - removing a path: write access to its parent.
+///
+/// [`DeepCopy::plan`]: struct.DeepCopy.html#method.plan
+/// [`plan_remove`]: fn.plan_remove.html
+///
+/// # Examples
+/// ```rust
+/// # extern crate ergo;
+/// use ergo::*;
+///
+/// # fn try_main() -> ::std::io::Result<()> {
+/// let tmp = PathTmp::create("preflight")?;
+/// let from = PathDir::create(tmp.join("from"))?;
+/// PathFile::create(from.join("example.txt"))?;
+///
+/// let copy = DeepCopy::new(from.clone(), tmp.join("to"));
+/// let (send_err, recv_err) = ch::unbounded();
+/// preflight(&copy.plan()?, send_err);
+/// let problems: Vec<_> = recv_err.iter().collect();
+/// assert!(problems.is_empty(), "{:?}", problems);
+///
+/// let (send_err, recv_err) = ch::unbounded();
+/// copy.run(send_err);
+/// assert_eq!(0, recv_err.iter().count());
+///
+/// let (send_err, recv_err) = ch::unbounded();
+/// preflight(&plan_remove(&from)?, send_err);
+/// assert_eq!(0, recv_err.iter().count());
+/// # Ok(()) } fn main() { try_main().unwrap() }
+/// ```
+pub fn preflight(plan: &[PlannedOp], send_err: Sender<io::Error>) {
+    let mut checked = HashSet::new();
+    for op in plan {
+        let (dest, source) = match *op {
+            PlannedOp::CreateDir(ref dir) => (dir, None),
+            PlannedOp::CopyFile { ref from, ref to } => (to, Some(from)),
+            PlannedOp::Remove(ref path) => (path, None),
+        };
+        let mut checks = Vec::with_capacity(2);
+        if let Some(parent) = dest.ancestors().skip(1).find(|p| p.exists()) {
+            checks.push((parent, Access::Write));
+        }
+        if let Some(from) = source {
+            checks.push((from.as_path(), Access::Read));
+        }
+        for (path, access) in checks {
+            if !checked.insert((path.to_path_buf(), access)) {
+                continue;
+            }
+            if let Err(err) = check_access(path, access) {
+                ch!(send_err <- err.into());
+            }
+        }
+    }
+}
+
+/// Plan the removal of the directory `dir` and everything in it, contents first.
+///
+/// This is the order [`PathDirExt::deep_remove`] removes them in. Pass the plan to
+/// [`preflight`](fn.preflight.html) to check the permissions before removing anything.
+///
+/// [`PathDirExt::deep_remove`]: trait.PathDirExt.html#method.deep_remove
+pub fn plan_remove(dir: &PathDir) -> io::Result<Vec<PlannedOp>> {
+    let mut ops = Vec::new();
+    for entry in dir.walk_post_order() {
+        ops.push(PlannedOp::Remove(entry?.to_path_buf()));
+    }
+    Ok(ops)
+}
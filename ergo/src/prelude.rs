@@ -0,0 +1,76 @@
+/* Copyright (c) 2018 Garrett Berg, vitiral@gmail.com
+ *
+ * Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+ * http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+ * http://opensource.org/licenses/MIT>, at your option. This file may not be
+ * copied, modified, or distributed except according to those terms.
+ */
+//! A curated set of the most used items, which can be glob imported without conflicts.
+//!
+//! `use ergo::*` imports everything from every sub-crate, which is convenient for scripts but
+//! in larger projects it shadows (or is ambiguous with) names like `Error`, `Result`, `env`
+//! and `time`. `use ergo::prelude::*` only imports the types and traits which are used
+//! everywhere and whose names don't collide:
+//!
+//! - `Error` and `Result` are always [`ergo::Error`](../struct.Error.html), which every error
+//!   of the sub-crates converts into. The errors of the sub-crates are renamed, i.e.
+//!   `ergo_fs::Error` is `FsError`.
+//! - The modules of the sub-crates (`ch`, `env`, `rand`, `time`, ...) are not imported, use
+//!   them through `ergo::`.
+//!
+//! Like the glob exports of `ergo`, the items of each sub-crate are only included when its
+//! feature (`config`, `fs`, `std`, `sync` or `sys`, all enabled by default) is enabled.
+//!
+//! # Examples
+//! ```rust
+//! # extern crate ergo;
+//! use ergo::prelude::*;
+//!
+//! fn save(dir: &PathDir, name: &str, lines: &[&str]) -> Result<PathFile> {
+//!     let file = PathFile::create(dir.join(name)).context("saving lines")?;
+//!     file.write_str(&lines.iter().join("\n"))?;
+//!     Ok(file)
+//! }
+//!
+//! # fn main() {
+//! let tmp = PathTmp::create("prelude").unwrap();
+//! let file = save(tmp.as_ref(), "out.txt", &["a", "b"]).unwrap();
+//! assert_eq!("a\nb", file.read_string().unwrap());
+//! # }
+//! ```
+
+#[cfg(feature = "fs")]
+pub use args::{Arg, Args, ArgsError, Matches};
+#[cfg(all(feature = "config", feature = "fs", feature = "sync"))]
+pub use deep_copy::{deep_copy, DeepCopy};
+pub use error::{Error, Result};
+#[cfg(feature = "std")]
+pub use exit::run_main;
+
+#[cfg(feature = "config")]
+pub use ergo_config::{Config, ConfigError, Secret, TomlDoc};
+
+#[cfg(feature = "fs")]
+pub use ergo_fs::Error as FsError;
+#[cfg(feature = "fs")]
+pub use ergo_fs::{
+    expand, glob, home_dir, ErgoMetadata, FileEdit, FileRead, FileReadExt, FileWrite, FileWriteExt,
+    PathAbs, PathArc, PathDir, PathDirExt, PathFile, PathFileExt, PathInfo, PathTmp, PathType,
+    PathTypeExt, WalkOptions,
+};
+
+#[cfg(feature = "std")]
+pub use ergo_std::serde::{Deserialize, Serialize};
+#[cfg(feature = "std")]
+#[doc(hidden)]
+pub use ergo_std::serde_derive::*;
+#[cfg(feature = "std")]
+pub use ergo_std::std_prelude::*;
+#[cfg(feature = "std")]
+pub use ergo_std::{Context, Histogram, IndexMap, IndexSet, Itertools, Regex, ResultExt};
+
+#[cfg(feature = "sync")]
+pub use ergo_sync::{par_map_ordered, FinishHandle, Pool, Receiver, Sender};
+
+#[cfg(feature = "sys")]
+pub use ergo_sys::{invocation, Invocation};
@@ -0,0 +1,186 @@
+/* Copyright (c) 2018 Garrett Berg, vitiral@gmail.com
+ *
+ * Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+ * http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+ * http://opensource.org/licenses/MIT>, at your option. This file may not be
+ * copied, modified, or distributed except according to those terms.
+ */
+//! Streaming tar archives over channels.
+use super::*;
+use std::cmp;
+use std::io;
+
+/// The size of the chunks sent by [`tar_stream`](fn.tar_stream.html).
+pub const TAR_CHUNK_SIZE: usize = 64 * 1024;
+
+/// Archive the contents of `dir` as a tar, sending it over `send` in chunks of
+/// [`TAR_CHUNK_SIZE`] bytes (the last chunk may be smaller).
+///
+/// This lets an archive be piped into a socket, a compressor or [`tar_receive`] on another
+/// thread without writing an intermediate file. Paths in the archive are relative to `dir`.
+///
+/// Returns an error with kind `BrokenPipe` if the receiver is dropped before the archive is
+/// complete. If this returns an error the receiver has an incomplete archive, so the error must
+/// be checked by whoever owns the sending side.
+///
+/// [`TAR_CHUNK_SIZE`]: constant.TAR_CHUNK_SIZE.html
+/// [`tar_receive`]: fn.tar_receive.html
+///
+/// # Examples
+/// ```rust
+/// # extern crate ergo;
+/// use ergo::*;
+///
+/// # fn try_main() -> ::std::io::Result<()> {
+/// let tmp = PathTmp::create("tar_stream")?;
+/// let src = PathDir::create(tmp.join("src"))?;
+/// PathDir::create(src.join("sub"))?;
+/// PathFile::create(src.join("sub").join("a.txt"))?.write_str("hello")?;
+///
+/// let (send, recv) = ch::bounded(4);
+/// let sender = spawn(move || tar_stream(src, send));
+///
+/// let dest = tmp.join("dest");
+/// tar_receive(recv, &dest)?;
+/// sender.join().unwrap()?;
+/// assert_eq!("hello", PathFile::new(dest.join("sub").join("a.txt"))?.read_string()?);
+/// # Ok(()) } fn main() { try_main().unwrap() }
+/// ```
+pub fn tar_stream<P: AsRef<Path>>(dir: P, send: Sender<Vec<u8>>) -> io::Result<()> {
+    let dir = PathDir::new(dir)?;
+    let mut builder = tar::Builder::new(ChannelWrite {
+        send,
+        buf: Vec::with_capacity(TAR_CHUNK_SIZE),
+    });
+    builder.append_dir_all(".", &dir)?;
+    builder.finish()?;
+    builder.into_inner()?.finish()
+}
+
+/// Receive a tar archive in chunks from `recv` and unpack it into `dest`, creating `dest` if it
+/// does not exist.
+///
+/// The sender marks the end of the archive by sending an empty chunk, which
+/// [`tar_stream`](fn.tar_stream.html) does once the whole archive was sent. If every sender is
+/// dropped before that the archive is incomplete and this returns an error with kind
+/// `UnexpectedEof`, even if the archive ended between two entries.
+///
+/// # Examples
+/// ```rust
+/// # extern crate ergo;
+/// use ergo::*;
+/// use std::io;
+///
+/// # fn try_main() -> ::std::io::Result<()> {
+/// let tmp = PathTmp::create("tar_receive")?;
+/// let src = PathDir::create(tmp.join("src"))?;
+/// PathFile::create(src.join("a.txt"))?.write_str("hello")?;
+/// PathFile::create(src.join("b.txt"))?.write_str("world")?;
+///
+/// let (send, recv) = ch::unbounded();
+/// tar_stream(src, send)?;
+/// let archive = recv.iter().collect::<Vec<_>>().concat();
+///
+/// // The sender dies before the last entry (a header and one block of contents) and the two
+/// // blocks marking the end of the archive.
+/// let (send, recv) = ch::unbounded();
+/// send.send(archive[..archive.len() - 2048].to_vec()).unwrap();
+/// drop(send);
+/// let err = tar_receive(recv, tmp.join("dest")).unwrap_err();
+/// assert_eq!(io::ErrorKind::UnexpectedEof, err.kind());
+/// # Ok(()) } fn main() { try_main().unwrap() }
+/// ```
+pub fn tar_receive<P: AsRef<Path>>(recv: Receiver<Vec<u8>>, dest: P) -> io::Result<()> {
+    let dest = PathDir::create_all(dest)?;
+    let mut archive = tar::Archive::new(ChannelRead {
+        recv,
+        chunk: Vec::new(),
+        pos: 0,
+        done: false,
+    });
+    archive.unpack(&dest)?;
+    // tar stops reading at the end-of-archive marker, but the sender may still flush the
+    // zero padding after it, which must not fail with `BrokenPipe`.
+    let mut reader = archive.into_inner();
+    io::copy(&mut reader, &mut io::sink())?;
+    Ok(())
+}
+
+/// Writes to a channel in chunks of `TAR_CHUNK_SIZE`.
+struct ChannelWrite {
+    send: Sender<Vec<u8>>,
+    buf: Vec<u8>,
+}
+
+impl ChannelWrite {
+    fn send(&self, chunk: Vec<u8>) -> io::Result<()> {
+        self.send
+            .send(chunk)
+            .map_err(|_| io::Error::new(io::ErrorKind::BrokenPipe, "tar receiver was dropped"))
+    }
+
+    fn send_buf(&mut self) -> io::Result<()> {
+        if self.buf.is_empty() {
+            return Ok(());
+        }
+        let chunk = ::std::mem::replace(&mut self.buf, Vec::with_capacity(TAR_CHUNK_SIZE));
+        self.send(chunk)
+    }
+
+    /// Send the last chunk followed by the empty chunk which marks the end of the archive.
+    fn finish(mut self) -> io::Result<()> {
+        self.send_buf()?;
+        self.send(Vec::new())
+    }
+}
+
+impl io::Write for ChannelWrite {
+    fn write(&mut self, data: &[u8]) -> io::Result<usize> {
+        let len = cmp::min(data.len(), TAR_CHUNK_SIZE - self.buf.len());
+        self.buf.extend_from_slice(&data[..len]);
+        if self.buf.len() == TAR_CHUNK_SIZE {
+            self.send_buf()?;
+        }
+        Ok(len)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.send_buf()
+    }
+}
+
+/// Reads the chunks received from a channel until the empty chunk marking the end.
+struct ChannelRead {
+    recv: Receiver<Vec<u8>>,
+    chunk: Vec<u8>,
+    pos: usize,
+    /// Whether the empty chunk was received.
+    done: bool,
+}
+
+impl io::Read for ChannelRead {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        while self.pos == self.chunk.len() {
+            if self.done {
+                return Ok(0);
+            }
+            match self.recv.recv() {
+                Ok(chunk) => {
+                    self.done = chunk.is_empty();
+                    self.chunk = chunk;
+                    self.pos = 0;
+                }
+                Err(_) => {
+                    return Err(io::Error::new(
+                        io::ErrorKind::UnexpectedEof,
+                        "tar sender was dropped before the end of the archive",
+                    ))
+                }
+            }
+        }
+        let len = cmp::min(buf.len(), self.chunk.len() - self.pos);
+        buf[..len].copy_from_slice(&self.chunk[self.pos..self.pos + len]);
+        self.pos += len;
+        Ok(len)
+    }
+}
@@ -0,0 +1,230 @@
+//! Dynamic access to loaded configuration values.
+
+use std::error;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use json::{self, Value};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use toml;
+use yaml;
+
+use merge::merge_values;
+use secret::redact_paths;
+
+/// A configuration tree loaded from TOML, YAML or JSON, with typed access by dotted path.
+///
+/// This is for when the keys are not known at compile time (i.e. plugin systems). When they
+/// _are_ known, prefer deserializing directly into your own type with `#[derive(Deserialize)]`.
+///
+/// Paths are keys separated by `.`, where integer keys index into arrays. The empty path `""`
+/// refers to the whole tree.
+///
+/// # Examples
+/// ```rust
+/// # extern crate ergo_config;
+/// use ergo_config::*;
+///
+/// # fn try_main() -> Result<(), ConfigError> {
+/// let config = Config::from_toml_str(r#"
+/// [server]
+/// port = 8080
+/// hosts = ["a.example.com", "b.example.com"]
+/// "#)?;
+///
+/// assert_eq!(8080, config.get::<u16>("server.port")?);
+/// assert_eq!("b.example.com", config.get::<String>("server.hosts.1")?);
+/// assert_eq!(None, config.get_opt::<u16>("server.timeout")?);
+///
+/// let err = config.get::<String>("server.port").unwrap_err();
+/// assert_eq!(
+///     "server.port: invalid type: integer `8080`, expected a string",
+///     err.to_string()
+/// );
+/// # Ok(()) } fn main() { try_main().unwrap() }
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct Config {
+    value: Value,
+}
+
+/// An error from loading or accessing a [`Config`](struct.Config.html).
+#[derive(Debug)]
+pub enum ConfigError {
+    /// The configuration could not be read.
+    Io(io::Error),
+    /// The configuration could not be parsed.
+    Parse(String),
+    /// There is no value at the path.
+    NotFound {
+        /// The path which was requested.
+        path: String,
+    },
+    /// The value at the path is not of the requested type.
+    Type {
+        /// The path which was requested.
+        path: String,
+        /// A description of the mismatch.
+        msg: String,
+    },
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            ConfigError::Io(ref err) => write!(f, "{}", err),
+            ConfigError::Parse(ref msg) => write!(f, "{}", msg),
+            ConfigError::NotFound { ref path } => write!(f, "{}: not found", path),
+            ConfigError::Type { ref path, ref msg } => write!(f, "{}: {}", path, msg),
+        }
+    }
+}
+
+impl error::Error for ConfigError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match *self {
+            ConfigError::Io(ref err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl From<ConfigError> for io::Error {
+    fn from(err: ConfigError) -> io::Error {
+        match err {
+            ConfigError::Io(err) => err,
+            ConfigError::NotFound { .. } => io::Error::new(io::ErrorKind::NotFound, err),
+            _ => io::Error::new(io::ErrorKind::InvalidData, err),
+        }
+    }
+}
+
+impl Config {
+    /// Create a configuration from any serializable value.
+    pub fn from_value<T: Serialize>(value: &T) -> Result<Config, ConfigError> {
+        json::to_value(value)
+            .map(Config::from)
+            .map_err(|err| ConfigError::Parse(err.to_string()))
+    }
+
+    /// Parse a configuration from a TOML string.
+    pub fn from_toml_str(s: &str) -> Result<Config, ConfigError> {
+        toml::from_str::<Value>(s)
+            .map(Config::from)
+            .map_err(|err| ConfigError::Parse(format!("invalid toml: {}", err)))
+    }
+
+    /// Parse a configuration from a YAML string.
+    pub fn from_yaml_str(s: &str) -> Result<Config, ConfigError> {
+        yaml::from_str::<Value>(s)
+            .map(Config::from)
+            .map_err(|err| ConfigError::Parse(format!("invalid yaml: {}", err)))
+    }
+
+    /// Parse a configuration from a JSON string.
+    pub fn from_json_str(s: &str) -> Result<Config, ConfigError> {
+        json::from_str::<Value>(s)
+            .map(Config::from)
+            .map_err(|err| ConfigError::Parse(format!("invalid json: {}", err)))
+    }
+
+    /// Load a configuration file, using its extension (`toml`, `yaml`, `yml` or `json`) to
+    /// determine the format.
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Config, ConfigError> {
+        let path = path.as_ref();
+        let text = fs::read_to_string(path).map_err(|err| io_error(err, "reading", path))?;
+        let ext = path.extension().and_then(|e| e.to_str()).unwrap_or("");
+        let config = match ext {
+            "toml" => Config::from_toml_str(&text),
+            "yaml" | "yml" => Config::from_yaml_str(&text),
+            "json" => Config::from_json_str(&text),
+            _ => {
+                return Err(ConfigError::Parse(format!(
+                    "unknown config format for {}",
+                    path.display()
+                )))
+            }
+        };
+        config.map_err(|err| match err {
+            ConfigError::Parse(msg) => ConfigError::Parse(format!("{} in {}", msg, path.display())),
+            err => err,
+        })
+    }
+
+    /// Get the value at the dotted `path` and deserialize it into `T`.
+    pub fn get<T: DeserializeOwned>(&self, path: &str) -> Result<T, ConfigError> {
+        match self.get_opt(path)? {
+            Some(v) => Ok(v),
+            None => Err(ConfigError::NotFound { path: path.into() }),
+        }
+    }
+
+    /// Get the value at the dotted `path` and deserialize it into `T`, returning `None` if there
+    /// is no value at the path.
+    pub fn get_opt<T: DeserializeOwned>(&self, path: &str) -> Result<Option<T>, ConfigError> {
+        let value = match self.value_at(path) {
+            Some(v) => v.clone(),
+            None => return Ok(None),
+        };
+        json::from_value(value)
+            .map(Some)
+            .map_err(|err| ConfigError::Type {
+                path: path.into(),
+                msg: err.to_string(),
+            })
+    }
+
+    /// Get a reference to the raw value at the dotted `path`.
+    pub fn value_at(&self, path: &str) -> Option<&Value> {
+        if path.is_empty() {
+            return Some(&self.value);
+        }
+        path.split('.')
+            .try_fold(&self.value, |value, key| match *value {
+                Value::Object(ref map) => map.get(key),
+                Value::Array(ref arr) => key.parse::<usize>().ok().and_then(|i| arr.get(i)),
+                _ => None,
+            })
+    }
+
+    /// Deep merge `overlay` on top of this configuration, see
+    /// [`merge_values`](fn.merge_values.html).
+    pub fn merge(self, overlay: Config) -> Config {
+        Config::from(merge_values(self.value, overlay.value))
+    }
+
+    /// Get a copy of the configuration with the values at the dotted `paths` replaced with
+    /// `"***"`, for logging it. See [`redact_paths`](fn.redact_paths.html).
+    pub fn redacted(&self, paths: &[&str]) -> Config {
+        let mut value = self.value.clone();
+        redact_paths(&mut value, paths);
+        Config::from(value)
+    }
+
+    /// Get a reference to the whole configuration tree.
+    pub fn value(&self) -> &Value {
+        &self.value
+    }
+
+    /// Consume the configuration, returning the whole tree.
+    pub fn into_value(self) -> Value {
+        self.value
+    }
+}
+
+impl From<Value> for Config {
+    fn from(value: Value) -> Config {
+        Config { value }
+    }
+}
+
+/// Add the action and path to an io error.
+pub(crate) fn io_error(err: io::Error, action: &str, path: &Path) -> ConfigError {
+    ConfigError::Io(io::Error::new(
+        err.kind(),
+        format!("{} when {} {}", err, action, path.display()),
+    ))
+}
@@ -0,0 +1,93 @@
+//! Reading and writing CSV files, requires the `csv` feature.
+
+use std::fs;
+use std::path::Path;
+
+use csv;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+
+use config::{io_error, ConfigError};
+
+/// Read every row of a CSV file with a header row, deserializing each into `T`.
+///
+/// Fields are matched by the header names for structs and by position for tuples. If a row is
+/// invalid the error includes the path and the row number, counting from 1 after the header.
+///
+/// Requires the `csv` feature.
+///
+/// # Examples
+/// ```rust
+/// # extern crate ergo_config;
+/// #[macro_use] extern crate serde_derive;
+/// use ergo_config::*;
+///
+/// #[derive(Debug, PartialEq, Serialize, Deserialize)]
+/// struct Score {
+///     name: String,
+///     points: u32,
+/// }
+///
+/// # fn try_main() -> Result<(), ConfigError> {
+/// let path = std::env::temp_dir().join("ergo_config_read_csv_example.csv");
+/// let scores = vec![
+///     Score { name: "alice".into(), points: 10 },
+///     Score { name: "bob".into(), points: 7 },
+/// ];
+/// write_csv(&path, &scores)?;
+/// assert_eq!(scores, read_csv::<Score, _>(&path)?);
+///
+/// std::fs::write(&path, "name,points\nalice,10\nbob,seven\n").unwrap();
+/// let err = read_csv::<Score, _>(&path).unwrap_err();
+/// assert!(err.to_string().starts_with("invalid csv at row 2 of "));
+/// # std::fs::remove_file(&path).unwrap();
+/// # Ok(()) } fn main() { try_main().unwrap() }
+/// ```
+pub fn read_csv<T, P>(path: P) -> Result<Vec<T>, ConfigError>
+where
+    T: DeserializeOwned,
+    P: AsRef<Path>,
+{
+    let path = path.as_ref();
+    let file = fs::File::open(path).map_err(|err| io_error(err, "opening", path))?;
+    csv::Reader::from_reader(file)
+        .deserialize()
+        .enumerate()
+        .map(|(i, row)| row.map_err(|err| csv_error(err, i + 1, "reading", path)))
+        .collect()
+}
+
+/// Write the rows to a CSV file, with a header row of the field names if `T` is a struct.
+///
+/// See [`read_csv`](fn.read_csv.html). Requires the `csv` feature.
+pub fn write_csv<T, P>(path: P, rows: &[T]) -> Result<(), ConfigError>
+where
+    T: Serialize,
+    P: AsRef<Path>,
+{
+    let path = path.as_ref();
+    let file = fs::File::create(path).map_err(|err| io_error(err, "creating", path))?;
+    let mut writer = csv::Writer::from_writer(file);
+    for (i, row) in rows.iter().enumerate() {
+        writer
+            .serialize(row)
+            .map_err(|err| csv_error(err, i + 1, "writing", path))?;
+    }
+    writer.flush().map_err(|err| io_error(err, "writing", path))
+}
+
+fn csv_error(err: csv::Error, row: usize, action: &str, path: &Path) -> ConfigError {
+    let msg = match *err.kind() {
+        csv::ErrorKind::Deserialize { ref err, .. } => err.to_string(),
+        _ => err.to_string(),
+    };
+    match err.into_kind() {
+        csv::ErrorKind::Io(err) => io_error(err, action, path),
+        _ => ConfigError::Parse(format!(
+            "invalid csv at row {} of {}: {}",
+            row,
+            path.display(),
+            msg
+        )),
+    }
+}
@@ -6,12 +6,29 @@
 //! > for how to use its exported features. For one thing it depends on `serde` to actually
 //! > be used in most cases.
 //!
+//! # Types
+//!
+//! - [`Config`](struct.Config.html): a configuration tree loaded from TOML, YAML or JSON with
+//!   typed access by dotted path, i.e. `config.get::<u16>("server.port")`.
+//! - [`merge_values`](fn.merge_values.html): deep merge configuration trees, i.e. defaults with
+//!   user configuration and command line overrides. Use [`FromMerged`](trait.FromMerged.html)
+//!   to deserialize your own type from the layers.
+//! - [`read_csv`](fn.read_csv.html) and [`write_csv`](fn.write_csv.html): read and write CSV
+//!   files of serde types, requires the `csv` feature.
+//! - [`Secret`](struct.Secret.html): a password or token which prints as `***`. Use
+//!   [`redact_paths`](fn.redact_paths.html) to hide secrets in an untyped configuration before
+//!   logging it.
+//! - [`TomlDoc`](struct.TomlDoc.html): edit a TOML file (i.e. `Cargo.toml`) while preserving its
+//!   comments and ordering.
+//!
 //! ### Special thanks
 //!
 //! The crates that are exported are:
 //!
 //! - [**configure**](https://github.com/withoutboats/configure): pull in configuration from the
 //!   environment.
+//! - [**csv**](https://github.com/BurntSushi/rust-csv): A CSV parser for Rust (with the `csv`
+//!   feature).
 //! - [**ron**](https://github.com/ron-rs/ron): Rusty Object Notation.
 //! - [**serde_json**](https://github.com/serde-rs/json): Strongly typed JSON library for Rust.
 //! - [**serde_yaml**](https://github.com/dtolnay/serde-yaml): Strongly typed YAML library for
@@ -26,8 +43,26 @@
 
 #[macro_use]
 pub extern crate configure;
+#[cfg(feature = "csv")]
+pub extern crate csv;
+extern crate serde;
 pub extern crate serde_json as json;
 pub extern crate serde_yaml as yaml;
 pub extern crate toml;
+extern crate toml_edit;
 
 pub use configure::*;
+
+mod config;
+#[cfg(feature = "csv")]
+mod csv_io;
+mod merge;
+mod secret;
+mod toml_doc;
+
+pub use config::{Config, ConfigError};
+#[cfg(feature = "csv")]
+pub use csv_io::{read_csv, write_csv};
+pub use merge::{merge_values, merge_values_with, ArrayMerge, FromMerged};
+pub use secret::{redact_paths, Secret};
+pub use toml_doc::TomlDoc;
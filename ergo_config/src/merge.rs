@@ -0,0 +1,128 @@
+//! Merging configuration trees.
+
+use json::{self, Value};
+use serde::de::DeserializeOwned;
+
+use config::ConfigError;
+
+/// How [`merge_values_with`](fn.merge_values_with.html) merges two arrays.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ArrayMerge {
+    /// The overlay's array replaces the base's array.
+    #[default]
+    Replace,
+    /// The overlay's items are appended to the base's array.
+    Append,
+}
+
+/// Deep merge `overlay` on top of `base`, replacing arrays.
+///
+/// Objects are merged key by key, any other value in `overlay` replaces the value in `base`.
+/// This is for layering configuration, i.e. "defaults + user config + command line overrides",
+/// without re-reading any files.
+///
+/// Values are `serde_json` values, which are used as the common representation of every format.
+/// Values of other formats (i.e. `toml::Value`) can be converted with
+/// [`Config::from_value`](struct.Config.html#method.from_value) or `json::to_value`.
+///
+/// # Examples
+/// ```rust
+/// # extern crate ergo_config;
+/// use ergo_config::*;
+///
+/// # fn try_main() -> Result<(), ConfigError> {
+/// let defaults = Config::from_toml_str(r#"
+/// [server]
+/// port = 80
+/// hosts = ["localhost"]
+/// "#)?;
+/// let user = Config::from_yaml_str("server: { port: 8080, hosts: [a.example.com] }")?;
+///
+/// let merged = merge_values(defaults.into_value(), user.into_value());
+/// assert_eq!(
+///     json::json!({"server": {"port": 8080, "hosts": ["a.example.com"]}}),
+///     merged
+/// );
+/// # Ok(()) } fn main() { try_main().unwrap() }
+/// ```
+pub fn merge_values(base: Value, overlay: Value) -> Value {
+    merge_values_with(base, overlay, ArrayMerge::Replace)
+}
+
+/// Deep merge `overlay` on top of `base`, merging arrays according to `arrays`.
+///
+/// See [`merge_values`](fn.merge_values.html).
+///
+/// # Examples
+/// ```rust
+/// # extern crate ergo_config;
+/// use ergo_config::*;
+/// use ergo_config::json::json;
+///
+/// # fn main() {
+/// let base = json!({"plugins": ["a"], "debug": false});
+/// let overlay = json!({"plugins": ["b"], "debug": true});
+/// assert_eq!(
+///     json!({"plugins": ["a", "b"], "debug": true}),
+///     merge_values_with(base, overlay, ArrayMerge::Append)
+/// );
+/// # }
+/// ```
+pub fn merge_values_with(base: Value, overlay: Value, arrays: ArrayMerge) -> Value {
+    match (base, overlay) {
+        (Value::Object(mut base), Value::Object(overlay)) => {
+            for (key, value) in overlay {
+                let merged = match base.remove(&key) {
+                    Some(existing) => merge_values_with(existing, value, arrays),
+                    None => value,
+                };
+                base.insert(key, merged);
+            }
+            Value::Object(base)
+        }
+        (Value::Array(mut base), Value::Array(overlay)) => match arrays {
+            ArrayMerge::Replace => Value::Array(overlay),
+            ArrayMerge::Append => {
+                base.extend(overlay);
+                Value::Array(base)
+            }
+        },
+        (_, overlay) => overlay,
+    }
+}
+
+/// Deserialize a type from layers of configuration which are merged in order.
+///
+/// This is implemented for every type which implements `DeserializeOwned`.
+///
+/// # Examples
+/// ```rust
+/// # extern crate ergo_config;
+/// use std::collections::BTreeMap;
+/// use ergo_config::*;
+/// use ergo_config::json::json;
+///
+/// # fn try_main() -> Result<(), ConfigError> {
+/// let defaults = json!({"port": 80, "host": "localhost"});
+/// let cli = json!({"port": 8080});
+///
+/// let settings = BTreeMap::<String, json::Value>::from_merged(&[defaults, cli])?;
+/// assert_eq!(json!(8080), settings["port"]);
+/// assert_eq!(json!("localhost"), settings["host"]);
+/// # Ok(()) } fn main() { try_main().unwrap() }
+/// ```
+pub trait FromMerged: Sized {
+    /// Merge `layers` with [`merge_values`](fn.merge_values.html), later layers taking
+    /// precedence, and deserialize the result.
+    fn from_merged(layers: &[Value]) -> Result<Self, ConfigError>;
+}
+
+impl<T: DeserializeOwned> FromMerged for T {
+    fn from_merged(layers: &[Value]) -> Result<T, ConfigError> {
+        let merged = layers.iter().cloned().fold(Value::Null, merge_values);
+        json::from_value(merged).map_err(|err| ConfigError::Type {
+            path: String::new(),
+            msg: err.to_string(),
+        })
+    }
+}
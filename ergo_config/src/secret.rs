@@ -0,0 +1,188 @@
+//! Keeping secrets out of logs.
+
+use std::fmt;
+
+use json::Value;
+use serde::de::{Deserialize, Deserializer};
+use serde::ser::Serializer;
+
+const REDACTED: &str = "***";
+
+/// A value (i.e. a password or token) which is never printed.
+///
+/// `Debug` and `Display` print `***`, so a config containing a `Secret` can be logged without
+/// leaking it. Deserializing is transparent, so use it as the type of a field in your
+/// configuration struct. Use [`expose`](#method.expose) to get the actual value where it is
+/// needed.
+///
+/// `Secret` does not implement `Serialize`, since saving a config with the secret replaced by
+/// `***` would lose it. Use [`serialize_redacted`](#method.serialize_redacted) for the fields
+/// of a type which is only serialized for showing it.
+///
+/// # Examples
+/// ```rust
+/// # extern crate ergo_config;
+/// use ergo_config::*;
+///
+/// # fn try_main() -> Result<(), ConfigError> {
+/// let config = Config::from_toml_str(r#"
+/// [db]
+/// user = "admin"
+/// password = "hunter2"
+/// "#)?;
+///
+/// let password: Secret<String> = config.get("db.password")?;
+/// assert_eq!("hunter2", password.expose());
+/// assert_eq!("***", password.to_string());
+/// assert_eq!("Secret(***)", format!("{:?}", password));
+/// # Ok(()) } fn main() { try_main().unwrap() }
+/// ```
+#[derive(Clone, Default, PartialEq, Eq, Hash)]
+pub struct Secret<T = String>(T);
+
+impl<T> Secret<T> {
+    /// Wrap a value.
+    pub fn new(value: T) -> Secret<T> {
+        Secret(value)
+    }
+
+    /// Get a reference to the actual value.
+    pub fn expose(&self) -> &T {
+        &self.0
+    }
+
+    /// Consume the secret, returning the actual value.
+    pub fn into_inner(self) -> T {
+        self.0
+    }
+
+    /// Serialize the secret as `***`, for use with `#[serde(serialize_with = "...")]`.
+    ///
+    /// # Examples
+    /// ```rust
+    /// # extern crate ergo_config;
+    /// #[macro_use] extern crate serde_derive;
+    /// use ergo_config::*;
+    ///
+    /// #[derive(Serialize)]
+    /// struct Db {
+    ///     user: String,
+    ///     #[serde(serialize_with = "Secret::serialize_redacted")]
+    ///     password: Secret,
+    /// }
+    ///
+    /// # fn main() {
+    /// let db = Db { user: "admin".into(), password: Secret::new("hunter2".into()) };
+    /// assert_eq!(
+    ///     r#"{"user":"admin","password":"***"}"#,
+    ///     json::to_string(&db).unwrap()
+    /// );
+    /// # }
+    /// ```
+    pub fn serialize_redacted<S: Serializer>(
+        _: &Secret<T>,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(REDACTED)
+    }
+}
+
+impl<T> From<T> for Secret<T> {
+    fn from(value: T) -> Secret<T> {
+        Secret(value)
+    }
+}
+
+impl<T> fmt::Debug for Secret<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Secret({})", REDACTED)
+    }
+}
+
+impl<T> fmt::Display for Secret<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(REDACTED)
+    }
+}
+
+impl<'de, T: Deserialize<'de>> Deserialize<'de> for Secret<T> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Secret<T>, D::Error> {
+        T::deserialize(deserializer).map(Secret)
+    }
+}
+
+/// Replace the values at the dotted `paths` with `"***"`, i.e. before logging a configuration.
+///
+/// A `*` key matches every key of an object or item of an array. Paths which do not exist are
+/// ignored.
+///
+/// This is for configuration which is not deserialized into a type with [`Secret`] fields, see
+/// also [`Config::redacted`].
+///
+/// [`Secret`]: struct.Secret.html
+/// [`Config::redacted`]: struct.Config.html#method.redacted
+///
+/// # Examples
+/// ```rust
+/// # extern crate ergo_config;
+/// use ergo_config::*;
+/// use ergo_config::json::json;
+///
+/// # fn main() {
+/// let mut value = json!({
+///     "token": "abc",
+///     "servers": [
+///         {"host": "a.example.com", "password": "hunter2"},
+///         {"host": "b.example.com"},
+///     ],
+/// });
+/// redact_paths(&mut value, &["token", "servers.*.password"]);
+/// assert_eq!(
+///     json!({
+///         "token": "***",
+///         "servers": [
+///             {"host": "a.example.com", "password": "***"},
+///             {"host": "b.example.com"},
+///         ],
+///     }),
+///     value
+/// );
+/// # }
+/// ```
+pub fn redact_paths(value: &mut Value, paths: &[&str]) {
+    for path in paths {
+        let keys: Vec<&str> = path.split('.').collect();
+        redact(value, &keys);
+    }
+}
+
+fn redact(value: &mut Value, keys: &[&str]) {
+    let (key, rest) = match keys.split_first() {
+        Some(split) => split,
+        None => {
+            *value = Value::String(REDACTED.into());
+            return;
+        }
+    };
+    match *value {
+        Value::Object(ref mut map) => {
+            if *key == "*" {
+                for child in map.values_mut() {
+                    redact(child, rest);
+                }
+            } else if let Some(child) = map.get_mut(*key) {
+                redact(child, rest);
+            }
+        }
+        Value::Array(ref mut arr) => {
+            if *key == "*" {
+                for child in arr.iter_mut() {
+                    redact(child, rest);
+                }
+            } else if let Some(child) = key.parse::<usize>().ok().and_then(|i| arr.get_mut(i)) {
+                redact(child, rest);
+            }
+        }
+        _ => {}
+    }
+}
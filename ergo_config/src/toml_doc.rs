@@ -0,0 +1,253 @@
+//! Editing TOML documents while preserving their formatting.
+
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+use json::{self, Value};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use toml_edit::{self, DocumentMut, InlineTable, Item, Table, TableLike};
+
+use config::{io_error, Config, ConfigError};
+
+/// A TOML document which can be edited without losing its comments, ordering or formatting.
+///
+/// Round-tripping a user edited file through `serde` rewrites the whole file, which is not
+/// acceptable for tools which update `Cargo.toml`-style files. `TomlDoc` only changes the values
+/// which are [`set`](#method.set) or [`remove`](#method.remove)d, leaving everything else
+/// (including comments on the changed lines) as it was.
+///
+/// Paths are keys separated by `.`, the same as for [`Config`](struct.Config.html).
+///
+/// # Examples
+/// ```rust
+/// # extern crate ergo_config;
+/// use ergo_config::*;
+///
+/// # fn try_main() -> Result<(), ConfigError> {
+/// let mut doc: TomlDoc = r#"
+/// ## the server settings
+/// [server]
+/// port = 80 # the default
+/// "#.parse()?;
+///
+/// doc.set("server.port", &8080)?;
+/// doc.set("server.hosts", &["a.example.com"])?;
+/// doc.set("client.retries", &3)?;
+///
+/// let expected = r#"
+/// ## the server settings
+/// [server]
+/// port = 8080 # the default
+/// hosts = ["a.example.com"]
+///
+/// [client]
+/// retries = 3
+/// "#;
+/// assert_eq!(expected, doc.to_string());
+/// assert_eq!(Some(8080), doc.get::<u16>("server.port")?);
+///
+/// assert!(doc.remove("client"));
+/// assert_eq!(None, doc.get::<u32>("client.retries")?);
+/// # Ok(()) } fn main() { try_main().unwrap() }
+/// ```
+#[derive(Debug, Clone)]
+pub struct TomlDoc {
+    path: Option<PathBuf>,
+    doc: DocumentMut,
+}
+
+impl TomlDoc {
+    /// Open a TOML file for editing.
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<TomlDoc, ConfigError> {
+        let path = path.as_ref();
+        let text = fs::read_to_string(path).map_err(|err| io_error(err, "reading", path))?;
+        let mut doc = text.parse::<TomlDoc>().map_err(|err| match err {
+            ConfigError::Parse(msg) => ConfigError::Parse(format!("{} in {}", msg, path.display())),
+            err => err,
+        })?;
+        doc.path = Some(path.to_path_buf());
+        Ok(doc)
+    }
+
+    /// The path the document was opened from.
+    pub fn path(&self) -> Option<&Path> {
+        self.path.as_deref()
+    }
+
+    /// Get the value at the dotted `path` and deserialize it into `T`, returning `None` if there
+    /// is no value at the path.
+    pub fn get<T: DeserializeOwned>(&self, path: &str) -> Result<Option<T>, ConfigError> {
+        self.to_config()?.get_opt(path)
+    }
+
+    /// Get the current contents of the document as a [`Config`](struct.Config.html).
+    pub fn to_config(&self) -> Result<Config, ConfigError> {
+        Config::from_toml_str(&self.doc.to_string())
+    }
+
+    /// Set the value at the dotted `path`, creating any tables which do not exist.
+    ///
+    /// If there is already a value at the path it is replaced in place, keeping its comments.
+    /// Objects become tables (or inline tables inside of arrays).
+    pub fn set<T: Serialize + ?Sized>(&mut self, path: &str, value: &T) -> Result<(), ConfigError> {
+        let value = json::to_value(value).map_err(|err| ConfigError::Type {
+            path: path.into(),
+            msg: err.to_string(),
+        })?;
+        let mut item = to_item(value, path)?;
+
+        let mut keys: Vec<&str> = path.split('.').collect();
+        let last = keys.pop().expect("split returns at least one item");
+        if last.is_empty() {
+            return Err(ConfigError::NotFound { path: path.into() });
+        }
+        let mut table: &mut dyn TableLike = self.doc.as_table_mut();
+        for (i, key) in keys.iter().enumerate() {
+            if !table.contains_key(key) {
+                let mut new = Table::new();
+                new.set_implicit(true);
+                table.insert(key, Item::Table(new));
+            }
+            table = table
+                .get_mut(key)
+                .and_then(|item| item.as_table_like_mut())
+                .ok_or_else(|| ConfigError::Type {
+                    path: keys[..i + 1].join("."),
+                    msg: "expected a table".into(),
+                })?;
+        }
+
+        match table.get_mut(last) {
+            Some(existing) => {
+                if let Some(decor) = existing.as_value().map(|v| v.decor().clone()) {
+                    item = Item::Value(
+                        item.into_value()
+                            .expect("only tables and values are created, tables are inlined"),
+                    );
+                    *item
+                        .as_value_mut()
+                        .expect("item was converted to a value")
+                        .decor_mut() = decor;
+                } else if let (Some(old), Some(new)) = (existing.as_table(), item.as_table_mut()) {
+                    *new.decor_mut() = old.decor().clone();
+                    if let Some(position) = old.position() {
+                        new.set_position(position);
+                    }
+                }
+                *existing = item;
+            }
+            None => {
+                table.insert(last, item);
+            }
+        }
+        Ok(())
+    }
+
+    /// Remove the value (or table) at the dotted `path`, returning whether it existed.
+    pub fn remove(&mut self, path: &str) -> bool {
+        let mut keys: Vec<&str> = path.split('.').collect();
+        let last = keys.pop().expect("split returns at least one item");
+        let mut table: &mut dyn TableLike = self.doc.as_table_mut();
+        for key in keys {
+            table = match table.get_mut(key).and_then(|item| item.as_table_like_mut()) {
+                Some(t) => t,
+                None => return false,
+            };
+        }
+        table.remove(last).is_some()
+    }
+
+    /// Write the document back to the file it was [`open`](#method.open)ed from.
+    pub fn save(&self) -> Result<(), ConfigError> {
+        match self.path {
+            Some(ref path) => self.save_as(path),
+            None => Err(ConfigError::Io(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "the TomlDoc was not opened from a file, use save_as",
+            ))),
+        }
+    }
+
+    /// Write the document to `path`.
+    pub fn save_as<P: AsRef<Path>>(&self, path: P) -> Result<(), ConfigError> {
+        let path = path.as_ref();
+        fs::write(path, self.doc.to_string()).map_err(|err| io_error(err, "writing", path))
+    }
+}
+
+impl FromStr for TomlDoc {
+    type Err = ConfigError;
+
+    fn from_str(s: &str) -> Result<TomlDoc, ConfigError> {
+        let doc = s
+            .parse::<DocumentMut>()
+            .map_err(|err| ConfigError::Parse(format!("invalid toml: {}", err)))?;
+        Ok(TomlDoc { path: None, doc })
+    }
+}
+
+impl fmt::Display for TomlDoc {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.doc)
+    }
+}
+
+/// Convert a value to a TOML item, using tables for objects.
+fn to_item(value: Value, path: &str) -> Result<Item, ConfigError> {
+    match value {
+        Value::Object(map) => {
+            let mut table = Table::new();
+            table.set_implicit(true);
+            for (key, value) in map {
+                let item = to_item(value, &format!("{}.{}", path, key))?;
+                table.insert(&key, item);
+            }
+            Ok(Item::Table(table))
+        }
+        value => to_value(value, path).map(Item::Value),
+    }
+}
+
+/// Convert a value to an inline TOML value.
+fn to_value(value: Value, path: &str) -> Result<toml_edit::Value, ConfigError> {
+    let value = match value {
+        Value::Null => {
+            return Err(ConfigError::Type {
+                path: path.into(),
+                msg: "toml cannot represent null".into(),
+            })
+        }
+        Value::Bool(b) => b.into(),
+        Value::Number(n) => match (n.as_i64(), n.as_f64()) {
+            (Some(i), _) => i.into(),
+            (None, Some(f)) if !n.is_u64() => f.into(),
+            _ => {
+                return Err(ConfigError::Type {
+                    path: path.into(),
+                    msg: format!("{} is out of range for toml", n),
+                })
+            }
+        },
+        Value::String(s) => s.into(),
+        Value::Array(arr) => {
+            let mut out = toml_edit::Array::new();
+            for (i, value) in arr.into_iter().enumerate() {
+                out.push(to_value(value, &format!("{}.{}", path, i))?);
+            }
+            out.into()
+        }
+        Value::Object(map) => {
+            let mut out = InlineTable::new();
+            for (key, value) in map {
+                let value = to_value(value, &format!("{}.{}", path, key))?;
+                out.insert(&key, value);
+            }
+            out.into()
+        }
+    };
+    Ok(value)
+}
@@ -0,0 +1,106 @@
+/* Copyright (c) 2018 Garrett Berg, vitiral@gmail.com
+ *
+ * Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+ * http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+ * http://opensource.org/licenses/MIT>, at your option. This file may not be
+ * copied, modified, or distributed except according to those terms.
+ */
+//! Checking access to a path before using it.
+
+use error::{Error, Result};
+use path_abs::PathArc;
+use std::fmt;
+use std::io;
+use std_prelude::*;
+
+/// A kind of access to a file or directory, see
+/// [`PathDirExt::check_access`](trait.PathDirExt.html#method.check_access).
+///
+/// For a directory, `Read` is listing it, `Write` is creating, renaming and removing entries in
+/// it and `Execute` is accessing the entries in it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Access {
+    /// Reading a file or listing a directory.
+    Read,
+    /// Writing a file or changing the entries of a directory.
+    Write,
+    /// Executing a file or accessing the entries of a directory.
+    Execute,
+}
+
+impl fmt::Display for Access {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match *self {
+            Access::Read => "read",
+            Access::Write => "write",
+            Access::Execute => "execute",
+        })
+    }
+}
+
+/// Check that the current process has `access` to the file or directory at `path`, failing
+/// with `PermissionDenied` if it does not.
+///
+/// See [`PathDirExt::check_access`](trait.PathDirExt.html#method.check_access).
+pub fn check_access<P: AsRef<Path>>(path: P, access: Access) -> Result<()> {
+    let path = path.as_ref();
+    imp::check(path, access).map_err(|err| {
+        let err = if err.kind() == io::ErrorKind::PermissionDenied {
+            io::Error::new(
+                io::ErrorKind::PermissionDenied,
+                format!("no {} access", access),
+            )
+        } else {
+            err
+        };
+        Error::new(err, "checking access to", PathArc::new(path))
+    })
+}
+
+#[cfg(unix)]
+mod imp {
+    use super::Access;
+    use libc;
+    use std::ffi::CString;
+    use std::io;
+    use std::os::unix::ffi::OsStrExt;
+    use std_prelude::*;
+
+    pub(super) fn check(path: &Path, access: Access) -> io::Result<()> {
+        let path = CString::new(path.as_os_str().as_bytes())
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+        let mode = match access {
+            Access::Read => libc::R_OK,
+            Access::Write => libc::W_OK,
+            Access::Execute => libc::X_OK,
+        };
+        // check as the effective user, which is the one that will perform the operations
+        let res = unsafe { libc::faccessat(libc::AT_FDCWD, path.as_ptr(), mode, libc::AT_EACCESS) };
+        match res {
+            0 => Ok(()),
+            _ => Err(io::Error::last_os_error()),
+        }
+    }
+}
+
+#[cfg(not(unix))]
+mod imp {
+    use super::Access;
+    use std::fs;
+    use std::io;
+    use std_prelude::*;
+
+    /// Without `access(2)` try the operation where it has no side effects, and otherwise go by
+    /// the read-only attribute.
+    pub(super) fn check(path: &Path, access: Access) -> io::Result<()> {
+        let meta = fs::metadata(path)?;
+        match access {
+            Access::Read if meta.is_dir() => fs::read_dir(path).map(|_| ()),
+            Access::Read => fs::File::open(path).map(|_| ()),
+            Access::Write if meta.permissions().readonly() => {
+                Err(io::Error::new(io::ErrorKind::PermissionDenied, "read-only"))
+            }
+            Access::Write | Access::Execute => Ok(()),
+        }
+    }
+}
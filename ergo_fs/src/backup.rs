@@ -0,0 +1,93 @@
+/* Copyright (c) 2018 Garrett Berg, vitiral@gmail.com
+ *
+ * Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+ * http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+ * http://opensource.org/licenses/MIT>, at your option. This file may not be
+ * copied, modified, or distributed except according to those terms.
+ */
+//! Backing up files before modifying them.
+
+use error::{Error, Result};
+use faults::{self, FsOp};
+use path_abs::{PathArc, PathFile};
+use std::fs::{self, OpenOptions};
+use std::io;
+use std_prelude::*;
+
+/// How the backup of a file is named, see
+/// [`PathFileExt::backup`](trait.PathFileExt.html#method.backup).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum BackupStyle {
+    /// Keep every backup, numbered like `cp --backup=numbered`: `notes.txt.~1~`,
+    /// `notes.txt.~2~`, ...
+    Numbered,
+    /// Keep a single backup, named with a suffix (i.e. `".bak"` or `"~"`) appended to the file
+    /// name. An existing backup is overwritten.
+    Suffix(String),
+}
+
+/// Copy `file` next to itself, returning the backup.
+pub(crate) fn backup(file: &Path, style: &BackupStyle) -> Result<PathFile> {
+    let name = file
+        .file_name()
+        .expect("files have a name")
+        .to_string_lossy()
+        .into_owned();
+    let dir = file.parent().expect("files have a parent");
+    match *style {
+        BackupStyle::Suffix(ref suffix) => {
+            let to = dir.join(format!("{}{}", name, suffix));
+            faults::check(FsOp::Copy, &to)
+                .and_then(|()| fs::copy(file, &to))
+                .map_err(|err| {
+                    Error::with_paths(err, "backing up", PathArc::new(file), PathArc::new(&to))
+                })?;
+            Ok(PathFile::new(to)?)
+        }
+        BackupStyle::Numbered => loop {
+            let to = dir.join(format!("{}.~{}~", name, next_number(dir, &name)?));
+            match copy_new(file, &to) {
+                Ok(()) => return Ok(PathFile::new(to)?),
+                // another process made the same backup at the same time
+                Err(ref err) if err.kind() == io::ErrorKind::AlreadyExists => continue,
+                Err(err) => {
+                    return Err(Error::with_paths(
+                        err,
+                        "backing up",
+                        PathArc::new(file),
+                        PathArc::new(&to),
+                    ))
+                }
+            }
+        },
+    }
+}
+
+/// One more than the largest number of the existing numbered backups of `name`.
+fn next_number(dir: &Path, name: &str) -> Result<u64> {
+    let err = |err| Error::new(err, "reading dir", PathArc::new(dir));
+    let prefix = format!("{}.~", name);
+    let mut next = 1;
+    for entry in fs::read_dir(dir).map_err(err)? {
+        let entry = entry.map_err(err)?.file_name();
+        let number = entry
+            .to_str()
+            .and_then(|n| n.strip_prefix(&prefix))
+            .and_then(|n| n.strip_suffix('~'))
+            .and_then(|n| n.parse::<u64>().ok());
+        if let Some(n) = number {
+            next = next.max(n + 1);
+        }
+    }
+    Ok(next)
+}
+
+/// Copy `from` to the new file `to`, failing if `to` already exists.
+fn copy_new(from: &Path, to: &Path) -> io::Result<()> {
+    faults::check(FsOp::Copy, to)?;
+    let mut src = fs::File::open(from)?;
+    let perms = src.metadata()?.permissions();
+    let mut dst = OpenOptions::new().write(true).create_new(true).open(to)?;
+    io::copy(&mut src, &mut dst)?;
+    fs::set_permissions(to, perms)
+}
@@ -0,0 +1,285 @@
+/* Copyright (c) 2018 Garrett Berg, vitiral@gmail.com
+ *
+ * Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+ * http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+ * http://opensource.org/licenses/MIT>, at your option. This file may not be
+ * copied, modified, or distributed except according to those terms.
+ */
+//! A key-value cache of files in a directory.
+
+use cas::Digest;
+use dirs;
+use error::{Error, Result};
+use path_abs::{FileWrite, PathArc, PathDir, PathFile};
+use std::fs::{self, File, OpenOptions};
+use std::io;
+use std::time::SystemTime;
+use std_prelude::*;
+use unique::create_unique;
+
+/// A cache of files stored by key in a directory, shared safely between threads and processes.
+///
+/// Entries are created with [`get_or_create`], which runs the closure to write the file only if
+/// there is no (unexpired) entry for the key. Concurrent callers (in any process) which ask for
+/// the same key wait on a file lock while the first one creates it, and the file is written to
+/// a temporary path and renamed into place so readers never see a partial entry.
+///
+/// Entries older than the [`ttl`] are treated as missing and recreated.
+/// [`evict_expired`] removes them and [`prune`] removes the oldest entries until the cache fits
+/// in a size.
+///
+/// Keys can be any string. Characters which are not safe in file names (including upper case
+/// letters, for case-insensitive filesystems) are escaped, and keys which would still be too long
+/// for a file name are shortened with a hash.
+///
+/// [`get_or_create`]: #method.get_or_create
+/// [`ttl`]: #method.ttl
+/// [`evict_expired`]: #method.evict_expired
+/// [`prune`]: #method.prune
+///
+/// # Examples
+/// ```rust
+/// # extern crate ergo_fs;
+/// use std::io::Write;
+/// use std::time::Duration;
+/// use ergo_fs::*;
+///
+/// # fn try_main() -> ::std::io::Result<()> {
+/// # let tmp = PathTmp::create("cache")?;
+/// // normally `CacheDir::new("myapp")?`, which is inside the platform's cache dir
+/// let cache = CacheDir::in_dir(PathDir::create(tmp.join("myapp"))?)?
+///     .ttl(Duration::from_secs(60 * 60 * 24));
+///
+/// let mut runs = 0;
+/// for _ in 0..2 {
+///     let file = cache.get_or_create("https://example.com/data.json", |f| {
+///         runs += 1;
+///         f.write_all(b"{\"downloaded\": true}")
+///     })?;
+///     assert_eq!("{\"downloaded\": true}", file.read_string()?);
+/// }
+/// assert_eq!(1, runs);
+///
+/// assert!(cache.get("https://example.com/data.json").is_some());
+/// cache.prune(0)?;
+/// assert!(cache.get("https://example.com/data.json").is_none());
+///
+/// // keys which differ in case and very long keys are separate entries too
+/// let long = "x".repeat(1000);
+/// for key in &["Key", "key", &long] {
+///     cache.get_or_create(key, |f| f.write_all(key.as_bytes()))?;
+/// }
+/// assert_eq!("Key", cache.get("Key").unwrap().read_string()?);
+/// assert_eq!(long, cache.get(&long).unwrap().read_string()?);
+/// # Ok(()) } fn main() { try_main().unwrap() }
+/// ```
+#[derive(Debug, Clone)]
+pub struct CacheDir {
+    dir: PathDir,
+    ttl: Option<Duration>,
+}
+
+impl CacheDir {
+    /// Open the cache of `app` inside the platform's cache directory, see
+    /// [`dirs::cache_dir`](dirs/fn.cache_dir.html).
+    pub fn new(app: &str) -> Result<CacheDir> {
+        CacheDir::in_dir(dirs::cache_dir(app)?)
+    }
+
+    /// Open a cache stored in `dir`.
+    pub fn in_dir(dir: PathDir) -> Result<CacheDir> {
+        for sub in &["entries", "locks", "tmp"] {
+            PathDir::create(dir.join(sub))?;
+        }
+        Ok(CacheDir { dir, ttl: None })
+    }
+
+    /// Treat entries which are older than `ttl` as missing. Default: entries never expire.
+    pub fn ttl(mut self, ttl: Duration) -> Self {
+        self.ttl = Some(ttl);
+        self
+    }
+
+    /// The directory the cache is stored in.
+    pub fn dir(&self) -> &PathDir {
+        &self.dir
+    }
+
+    /// Get the file of `key` if it exists and has not expired.
+    pub fn get(&self, key: &str) -> Option<PathFile> {
+        let path = self.entry_path(key);
+        match fs::metadata(&path) {
+            Ok(ref m) if m.is_file() && !self.is_expired(m) => PathFile::new(path).ok(),
+            _ => None,
+        }
+    }
+
+    /// Get the file of `key`, calling `create` to write it if it does not exist or expired.
+    ///
+    /// Only one caller creates an entry at a time, any others (in this or another process) block
+    /// until it is done and then use the created file. If `create` fails nothing is stored.
+    pub fn get_or_create<F>(&self, key: &str, create: F) -> Result<PathFile>
+    where
+        F: FnOnce(&mut FileWrite) -> io::Result<()>,
+    {
+        if let Some(file) = self.get(key) {
+            return Ok(file);
+        }
+
+        let _lock = self.lock(key)?;
+        // another process may have created it while we were waiting
+        if let Some(file) = self.get(key) {
+            return Ok(file);
+        }
+
+        let (tmp, mut write) = create_unique(&self.dir.as_path().join("tmp"), "", ".tmp")?;
+        let written = create(&mut write)
+            .map_err(|err| Error::new(err, "creating cache entry", PathArc::new(&tmp)))
+            .and_then(|_| write.flush().map_err(Error::from))
+            .and_then(|_| write.sync_all().map_err(Error::from));
+        drop(write);
+        if let Err(err) = written {
+            let _ = fs::remove_file(&tmp);
+            return Err(err);
+        }
+        let path = self.entry_path(key);
+        fs::rename(&tmp, &path).map_err(|err| {
+            let _ = fs::remove_file(&tmp);
+            Error::with_paths(err, "renaming", PathArc::new(&tmp), PathArc::new(&path))
+        })?;
+        Ok(PathFile::new(path)?)
+    }
+
+    /// Remove the entry of `key`, returning whether it existed.
+    pub fn remove(&self, key: &str) -> Result<bool> {
+        let _lock = self.lock(key)?;
+        remove_entry(&self.entry_path(key))
+    }
+
+    /// Remove the entries which are older than the [`ttl`](#method.ttl), returning how many
+    /// were removed.
+    pub fn evict_expired(&self) -> Result<usize> {
+        let mut removed = 0;
+        for (path, meta) in self.entries()? {
+            if self.is_expired(&meta) && remove_entry(&path)? {
+                removed += 1;
+            }
+        }
+        Ok(removed)
+    }
+
+    /// Remove the oldest entries until the total size of the cache is at most `max_bytes`,
+    /// returning the number of bytes removed.
+    ///
+    /// Expired entries are removed first.
+    pub fn prune(&self, max_bytes: u64) -> Result<u64> {
+        let mut entries = self.entries()?;
+        // expired first, then oldest first
+        entries.sort_by_key(|(_, m)| (!self.is_expired(m), m.modified().ok()));
+        let mut total: u64 = entries.iter().map(|(_, m)| m.len()).sum();
+        let mut removed = 0;
+        for (path, meta) in entries {
+            if total <= max_bytes {
+                break;
+            }
+            if remove_entry(&path)? {
+                removed += meta.len();
+            }
+            total -= meta.len();
+        }
+        Ok(removed)
+    }
+
+    /// The total size of the entries in bytes.
+    pub fn size(&self) -> Result<u64> {
+        Ok(self.entries()?.iter().map(|(_, m)| m.len()).sum())
+    }
+
+    fn entry_path(&self, key: &str) -> PathBuf {
+        self.dir.as_path().join("entries").join(escape_key(key))
+    }
+
+    fn is_expired(&self, meta: &fs::Metadata) -> bool {
+        let ttl = match self.ttl {
+            Some(t) => t,
+            None => return false,
+        };
+        meta.modified()
+            .ok()
+            .and_then(|m| SystemTime::now().duration_since(m).ok())
+            .map(|age| age > ttl)
+            .unwrap_or(false)
+    }
+
+    /// Lock `key` against being created or removed by anyone else.
+    fn lock(&self, key: &str) -> Result<File> {
+        let path = self.dir.as_path().join("locks").join(escape_key(key));
+        let file = OpenOptions::new()
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(&path)
+            .map_err(|err| Error::new(err, "opening lock", PathArc::new(&path)))?;
+        file.lock()
+            .map_err(|err| Error::new(err, "locking", PathArc::new(&path)))?;
+        Ok(file)
+    }
+
+    fn entries(&self) -> Result<Vec<(PathBuf, fs::Metadata)>> {
+        let dir = self.dir.as_path().join("entries");
+        let read_dir =
+            fs::read_dir(&dir).map_err(|err| Error::new(err, "reading dir", PathArc::new(&dir)))?;
+        let mut out = Vec::new();
+        for entry in read_dir {
+            let entry = entry.map_err(|err| Error::new(err, "reading dir", PathArc::new(&dir)))?;
+            match entry.metadata() {
+                Ok(meta) => out.push((entry.path(), meta)),
+                // removed by someone else
+                Err(ref err) if err.kind() == io::ErrorKind::NotFound => {}
+                Err(err) => return Err(Error::new(err, "reading", PathArc::new(entry.path()))),
+            }
+        }
+        Ok(out)
+    }
+}
+
+/// Remove an entry, returning false if it was already removed.
+fn remove_entry(path: &Path) -> Result<bool> {
+    match fs::remove_file(path) {
+        Ok(()) => Ok(true),
+        Err(ref err) if err.kind() == io::ErrorKind::NotFound => Ok(false),
+        Err(err) => Err(Error::new(err, "removing cache entry", PathArc::new(path))),
+    }
+}
+
+/// The longest escaped key which is used as a file name as it is, most filesystems allow 255
+/// bytes.
+const MAX_KEY_LEN: usize = 200;
+
+/// How much of a longer escaped key is kept in front of its hash.
+const HASHED_KEY_PREFIX_LEN: usize = 100;
+
+/// Escape a key to a file name, using `%XX` for bytes which are not lower case alphanumeric, `-`
+/// or `_`, so that keys which only differ in case don't collide on case-insensitive
+/// filesystems.
+///
+/// Keys longer than `MAX_KEY_LEN` once escaped are shortened to a prefix followed by `~` and
+/// the SHA-256 of the whole key. `~` is always escaped, so these never collide with short keys.
+fn escape_key(key: &str) -> String {
+    if key.is_empty() {
+        return "%".into();
+    }
+    let mut out = String::with_capacity(key.len());
+    for b in key.bytes() {
+        match b {
+            b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' => out.push(b as char),
+            _ => out.push_str(&format!("%{:02X}", b)),
+        }
+    }
+    if out.len() > MAX_KEY_LEN {
+        out.truncate(HASHED_KEY_PREFIX_LEN);
+        out.push('~');
+        out.push_str(&Digest::of_bytes(key.as_bytes()).to_string());
+    }
+    out
+}
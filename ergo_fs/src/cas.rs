@@ -0,0 +1,364 @@
+/* Copyright (c) 2018 Garrett Berg, vitiral@gmail.com
+ *
+ * Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+ * http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+ * http://opensource.org/licenses/MIT>, at your option. This file may not be
+ * copied, modified, or distributed except according to those terms.
+ */
+//! A content-addressed store of files.
+//!
+//! Files are [`put`] into a [`Store`] and stored by the SHA-256 [`Digest`] of their contents, so
+//! storing the same contents twice only stores them once. They are retrieved with [`get`] or
+//! [`materialize`]d anywhere on the same filesystem as a hard link, which takes no space or time
+//! no matter how large the file is.
+//!
+//! Objects are read-only, and so are materialized files since they share the object's data.
+//! Objects which are no longer needed are removed with [`gc`].
+//!
+//! [`Store`]: struct.Store.html
+//! [`Digest`]: struct.Digest.html
+//! [`put`]: struct.Store.html#method.put
+//! [`get`]: struct.Store.html#method.get
+//! [`materialize`]: struct.Store.html#method.materialize
+//! [`gc`]: struct.Store.html#method.gc
+//!
+//! # Examples
+//! ```rust
+//! # extern crate ergo_fs;
+//! use ergo_fs::*;
+//! use ergo_fs::cas::Store;
+//!
+//! # fn try_main() -> ::std::io::Result<()> {
+//! let tmp = PathTmp::create("cas")?;
+//! let store = Store::open(&PathDir::create(tmp.join("store"))?)?;
+//!
+//! let output = PathFile::create(tmp.join("output.bin"))?;
+//! output.write_str("built artifact")?;
+//! let digest = store.put(&output)?;
+//! assert_eq!(digest, store.put_bytes(b"built artifact")?);
+//! assert_eq!("built artifact", store.get(&digest)?.read_string()?);
+//!
+//! // later, i.e. in another build
+//! let restored = store.materialize(&digest, tmp.join("restored.bin"))?;
+//! assert_eq!("built artifact", restored.read_string()?);
+//!
+//! // nothing references the object anymore
+//! assert_eq!(1, store.gc(Vec::new())?);
+//! assert!(!store.contains(&digest));
+//! # Ok(()) } fn main() { try_main().unwrap() }
+//! ```
+
+use error::{Error, Result};
+use path_abs::{FileWrite, PathArc, PathDir, PathFile};
+use sha2::{Digest as Sha2Digest, Sha256};
+use std::collections::HashSet;
+use std::fmt;
+use std::fs;
+use std::io::{self, Read, Write};
+use std::str::FromStr;
+use std_prelude::*;
+use unique::create_unique;
+
+const BUF_SIZE: usize = 64 * 1024;
+
+/// How long a temporary file is left alone by [`Store::gc`], since it may belong to a write
+/// which is still in progress in another process.
+///
+/// [`Store::gc`]: struct.Store.html#method.gc
+pub const TMP_GRACE_PERIOD: Duration = Duration::from_secs(60 * 60);
+
+/// The SHA-256 digest of the contents of a file, which is its address in a
+/// [`Store`](struct.Store.html).
+///
+/// It is displayed (and parsed) as 64 lowercase hex characters.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Digest([u8; 32]);
+
+impl Digest {
+    /// Compute the digest of `bytes`.
+    pub fn of_bytes(bytes: &[u8]) -> Digest {
+        Digest(Sha256::digest(bytes).into())
+    }
+
+    /// Compute the digest of everything read from `reader`.
+    pub fn of_reader<R: Read>(mut reader: R) -> io::Result<Digest> {
+        let mut hasher = Sha256::new();
+        let mut buf = vec![0; BUF_SIZE];
+        loop {
+            match reader.read(&mut buf) {
+                Ok(0) => return Ok(Digest(hasher.finalize().into())),
+                Ok(n) => hasher.update(&buf[..n]),
+                Err(ref err) if err.kind() == io::ErrorKind::Interrupted => {}
+                Err(err) => return Err(err),
+            }
+        }
+    }
+
+    pub(crate) fn from_hasher(hasher: Sha256) -> Digest {
+        Digest(hasher.finalize().into())
+    }
+
+    /// The raw bytes of the digest.
+    pub fn as_bytes(&self) -> &[u8; 32] {
+        &self.0
+    }
+}
+
+impl fmt::Display for Digest {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for b in &self.0 {
+            write!(f, "{:02x}", b)?;
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Debug for Digest {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Digest({})", self)
+    }
+}
+
+/// The error when parsing an invalid [`Digest`](struct.Digest.html).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseDigestError(String);
+
+impl fmt::Display for ParseDigestError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "invalid sha256 digest {:?}, expected 64 hex characters",
+            self.0
+        )
+    }
+}
+
+impl ::std::error::Error for ParseDigestError {}
+
+impl FromStr for Digest {
+    type Err = ParseDigestError;
+
+    fn from_str(s: &str) -> ::std::result::Result<Digest, ParseDigestError> {
+        let err = || ParseDigestError(s.to_string());
+        if s.len() != 64 || !s.is_ascii() {
+            return Err(err());
+        }
+        let mut out = [0; 32];
+        for (i, byte) in out.iter_mut().enumerate() {
+            *byte = u8::from_str_radix(&s[i * 2..i * 2 + 2], 16).map_err(|_| err())?;
+        }
+        Ok(Digest(out))
+    }
+}
+
+/// A content-addressed store of files in a directory, see the [module docs](index.html).
+///
+/// Objects are stored at `objects/{first 2 hex chars}/{remaining hex chars}`. Every object is
+/// written to a temporary file and renamed into place, so a `Store` can be shared between
+/// processes.
+#[derive(Debug, Clone)]
+pub struct Store {
+    dir: PathDir,
+}
+
+impl Store {
+    /// Open the store in `dir`, creating it if it is empty.
+    pub fn open(dir: &PathDir) -> Result<Store> {
+        PathDir::create(dir.join("objects"))?;
+        PathDir::create(dir.join("tmp"))?;
+        Ok(Store { dir: dir.clone() })
+    }
+
+    /// The directory of the store.
+    pub fn dir(&self) -> &PathDir {
+        &self.dir
+    }
+
+    /// Copy the file into the store, returning its digest.
+    pub fn put(&self, file: &PathFile) -> Result<Digest> {
+        let mut from =
+            fs::File::open(file).map_err(|err| Error::new(err, "opening", PathArc::new(file)))?;
+        self.write_object(|to| {
+            let mut hasher = Sha256::new();
+            let mut buf = vec![0; BUF_SIZE];
+            loop {
+                let n = match from.read(&mut buf) {
+                    Ok(0) => break,
+                    Ok(n) => n,
+                    Err(ref err) if err.kind() == io::ErrorKind::Interrupted => continue,
+                    Err(err) => return Err(Error::new(err, "reading", PathArc::new(file))),
+                };
+                hasher.update(&buf[..n]);
+                to.write_all(&buf[..n])
+                    .map_err(|err| write_error(err, to))?;
+            }
+            Ok(Digest(hasher.finalize().into()))
+        })
+    }
+
+    /// Store `bytes`, returning their digest.
+    pub fn put_bytes(&self, bytes: &[u8]) -> Result<Digest> {
+        self.write_object(|to| {
+            to.write_all(bytes).map_err(|err| write_error(err, to))?;
+            Ok(Digest::of_bytes(bytes))
+        })
+    }
+
+    /// Whether the store contains an object with the digest.
+    pub fn contains(&self, digest: &Digest) -> bool {
+        self.object_path(digest).is_file()
+    }
+
+    /// Get the file of the object with the digest. It is an error of kind `NotFound` if there is
+    /// none.
+    ///
+    /// The file is read-only and must not be modified.
+    pub fn get(&self, digest: &Digest) -> Result<PathFile> {
+        Ok(PathFile::new(self.object_path(digest))?)
+    }
+
+    /// Create the file `dest` with the contents of the object with the digest.
+    ///
+    /// `dest` is a hard link to the object if possible, otherwise (i.e. if it is on a different
+    /// filesystem) it is a copy. Either way it is read-only. It is an error if `dest` already
+    /// exists.
+    pub fn materialize<P: AsRef<Path>>(&self, digest: &Digest, dest: P) -> Result<PathFile> {
+        let object = self.get(digest)?;
+        let dest = dest.as_ref();
+        if let Err(link_err) = fs::hard_link(&object, dest) {
+            if link_err.kind() == io::ErrorKind::AlreadyExists {
+                return Err(Error::with_paths(
+                    link_err,
+                    "materializing",
+                    PathArc::new(&object),
+                    PathArc::new(dest),
+                ));
+            }
+            fs::copy(&object, dest).map_err(|err| {
+                Error::with_paths(err, "copying", PathArc::new(&object), PathArc::new(dest))
+            })?;
+        }
+        Ok(PathFile::new(dest)?)
+    }
+
+    /// Remove every object whose digest is not in `live`, returning how many were removed.
+    ///
+    /// Materialized hard links are not affected since they have their own link to the data.
+    /// Temporary files left by writes which were interrupted are removed as well, once they are
+    /// older than [`TMP_GRACE_PERIOD`](constant.TMP_GRACE_PERIOD.html).
+    pub fn gc<I: IntoIterator<Item = Digest>>(&self, live: I) -> Result<usize> {
+        let live: HashSet<Digest> = live.into_iter().collect();
+        let mut removed = 0;
+        for prefix in read_dir(&self.dir.join("objects"))? {
+            for object in read_dir(&prefix)? {
+                let digest = match object_digest(&prefix, &object) {
+                    Some(d) => d,
+                    None => continue,
+                };
+                if !live.contains(&digest) {
+                    remove(&object)?;
+                    removed += 1;
+                }
+            }
+        }
+        for tmp in read_dir(&self.dir.join("tmp"))? {
+            let age = fs::metadata(&tmp)
+                .and_then(|m| m.modified())
+                .ok()
+                .and_then(|modified| modified.elapsed().ok());
+            // a file which can't be dated (or was removed since) is left alone
+            if age.is_some_and(|age| age > TMP_GRACE_PERIOD) {
+                remove(&tmp)?;
+            }
+        }
+        Ok(removed)
+    }
+
+    fn object_path(&self, digest: &Digest) -> PathBuf {
+        let hex = digest.to_string();
+        self.dir
+            .as_path()
+            .join("objects")
+            .join(&hex[..2])
+            .join(&hex[2..])
+    }
+
+    /// Write an object to a temporary file with `write` (which returns its digest) and move it
+    /// into place.
+    fn write_object<F>(&self, write: F) -> Result<Digest>
+    where
+        F: FnOnce(&mut FileWrite) -> Result<Digest>,
+    {
+        let (tmp, mut file) = create_unique(&self.dir.as_path().join("tmp"), "", ".tmp")?;
+        let written = write(&mut file).and_then(|digest| {
+            file.flush()?;
+            file.sync_all()?;
+            Ok(digest)
+        });
+        drop(file);
+        let digest = match written {
+            Ok(d) => d,
+            Err(err) => {
+                let _ = fs::remove_file(&tmp);
+                return Err(err);
+            }
+        };
+
+        let path = self.object_path(&digest);
+        if path.is_file() {
+            // already stored
+            let _ = fs::remove_file(&tmp);
+            return Ok(digest);
+        }
+        let mut perms = tmp.metadata()?.permissions();
+        perms.set_readonly(true);
+        fs::set_permissions(&tmp, perms)
+            .map_err(|err| Error::new(err, "setting permissions of", PathArc::new(&tmp)))?;
+        let parent = path.parent().expect("objects have a parent");
+        fs::create_dir_all(parent)
+            .map_err(|err| Error::new(err, "creating dir", PathArc::new(parent)))?;
+        fs::rename(&tmp, &path).map_err(|err| {
+            // it is read-only by now
+            let _ = remove(&tmp);
+            Error::with_paths(err, "renaming", PathArc::new(&tmp), PathArc::new(&path))
+        })?;
+        Ok(digest)
+    }
+}
+
+/// The digest of an object from its path, `None` if it is not an object.
+fn object_digest(prefix: &Path, object: &Path) -> Option<Digest> {
+    let prefix = prefix.file_name()?.to_str()?;
+    let rest = object.file_name()?.to_str()?;
+    format!("{}{}", prefix, rest).parse().ok()
+}
+
+fn write_error(err: io::Error, file: &FileWrite) -> Error {
+    Error::new(err, "writing", PathArc::new(file.path()))
+}
+
+fn read_dir(dir: &Path) -> Result<Vec<PathBuf>> {
+    let to_err = |err| Error::new(err, "reading dir", PathArc::new(dir));
+    let mut out = Vec::new();
+    for entry in fs::read_dir(dir).map_err(to_err)? {
+        out.push(entry.map_err(to_err)?.path());
+    }
+    Ok(out)
+}
+
+fn remove(path: &Path) -> Result<()> {
+    // read-only files cannot be removed on windows
+    #[cfg(windows)]
+    {
+        if let Ok(meta) = fs::metadata(path) {
+            let mut perms = meta.permissions();
+            perms.set_readonly(false);
+            let _ = fs::set_permissions(path, perms);
+        }
+    }
+    match fs::remove_file(path) {
+        Ok(()) => Ok(()),
+        Err(ref err) if err.kind() == io::ErrorKind::NotFound => Ok(()),
+        Err(err) => Err(Error::new(err, "removing", PathArc::new(path))),
+    }
+}
@@ -0,0 +1,101 @@
+/* Copyright (c) 2018 Garrett Berg, vitiral@gmail.com
+ *
+ * Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+ * http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+ * http://opensource.org/licenses/MIT>, at your option. This file may not be
+ * copied, modified, or distributed except according to those terms.
+ */
+//! Reading parts of files.
+
+use error::{Error, Result};
+use path_abs::{FileRead, PathArc};
+use std::io::{self, Seek, SeekFrom};
+use std_prelude::*;
+
+/// Read up to `len` bytes starting at `offset`.
+pub(crate) fn read_range(file: &mut FileRead, offset: u64, len: usize) -> Result<Vec<u8>> {
+    let path = PathArc::new(file.path());
+    let err = |err| {
+        Error::new(
+            err,
+            &format!("reading {} bytes at offset {} of", len, offset),
+            path.clone(),
+        )
+    };
+    file.seek(SeekFrom::Start(offset)).map_err(err)?;
+    let mut buf = Vec::new();
+    file.by_ref()
+        .take(len as u64)
+        .read_to_end(&mut buf)
+        .map_err(err)?;
+    Ok(buf)
+}
+
+/// An iterator over the contents of a file in chunks, returned by
+/// [`FileReadExt::chunks`](trait.FileReadExt.html#method.chunks).
+#[derive(Debug)]
+pub struct Chunks {
+    file: FileRead,
+    chunk_size: usize,
+    offset: u64,
+    done: bool,
+}
+
+impl Chunks {
+    pub(crate) fn new(file: FileRead, chunk_size: usize) -> Chunks {
+        assert!(chunk_size > 0, "chunk_size must be greater than 0");
+        Chunks {
+            file,
+            chunk_size,
+            offset: 0,
+            done: false,
+        }
+    }
+
+    /// The number of bytes returned so far.
+    pub fn offset(&self) -> u64 {
+        self.offset
+    }
+
+    /// Return the file, positioned after the last chunk returned.
+    pub fn into_inner(self) -> FileRead {
+        self.file
+    }
+}
+
+impl Iterator for Chunks {
+    type Item = Result<Vec<u8>>;
+
+    fn next(&mut self) -> Option<Result<Vec<u8>>> {
+        if self.done {
+            return None;
+        }
+        let mut chunk = vec![0; self.chunk_size];
+        let mut filled = 0;
+        while filled < chunk.len() {
+            match self.file.read(&mut chunk[filled..]) {
+                Ok(0) => break,
+                Ok(n) => filled += n,
+                Err(ref err) if err.kind() == io::ErrorKind::Interrupted => {}
+                Err(err) => {
+                    self.done = true;
+                    let action = format!("reading chunk at offset {} of", self.offset);
+                    return Some(Err(Error::new(
+                        err,
+                        &action,
+                        PathArc::new(self.file.path()),
+                    )));
+                }
+            }
+        }
+        if filled < chunk.len() {
+            self.done = true;
+            if filled == 0 {
+                return None;
+            }
+            chunk.truncate(filled);
+        }
+        self.offset += filled as u64;
+        Some(Ok(chunk))
+    }
+}
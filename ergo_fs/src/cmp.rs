@@ -0,0 +1,233 @@
+/* Copyright (c) 2018 Garrett Berg, vitiral@gmail.com
+ *
+ * Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+ * http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+ * http://opensource.org/licenses/MIT>, at your option. This file may not be
+ * copied, modified, or distributed except according to those terms.
+ */
+//! Comparing the contents of files and directory trees, i.e. to check the output of a copy or
+//! an extraction in tests without shelling out to `diff -r`.
+//!
+//! With the `diff` feature it also diffs the lines of text files with [`diff_lines`] and
+//! [`diff_str`], i.e. to show a user what will change before overwriting their file.
+//!
+//! [`diff_lines`]: fn.diff_lines.html
+//! [`diff_str`]: fn.diff_str.html
+//!
+//! # Examples
+//! ```rust
+//! # extern crate ergo_fs;
+//! use ergo_fs::*;
+//! use ergo_fs::cmp::{dirs_equal, files_equal};
+//!
+//! # fn try_main() -> ::std::io::Result<()> {
+//! let tmp = PathTmp::create("cmp")?;
+//! let a = PathDir::create(tmp.join("a"))?;
+//! let b = PathDir::create(tmp.join("b"))?;
+//! for dir in &[&a, &b] {
+//!     PathFile::create(dir.join("same.txt"))?.write_str("same")?;
+//! }
+//! PathFile::create(a.join("changed.txt"))?.write_str("old")?;
+//! PathFile::create(b.join("changed.txt"))?.write_str("new")?;
+//! PathFile::create(b.join("added.txt"))?;
+//!
+//! assert!(files_equal(a.join("same.txt"), b.join("same.txt"))?);
+//!
+//! let diff = dirs_equal(&a, &b, &WalkOptions::new())?;
+//! assert!(!diff.is_empty());
+//! assert_eq!(vec![Path::new("changed.txt")], diff.different);
+//! assert_eq!(vec![Path::new("added.txt")], diff.only_in_b);
+//! assert_eq!("! changed.txt\n+ added.txt\n", diff.to_string());
+//! # Ok(()) } fn main() { try_main().unwrap() }
+//! ```
+
+use error::{Error, Result};
+use path_abs::{PathAbs, PathArc};
+use sort;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fs::{self, File};
+use std::io;
+use std_prelude::*;
+use walk::WalkOptions;
+
+#[cfg(feature = "diff")]
+pub use diff::{diff_lines, diff_str, DiffHunk, DiffLine};
+
+/// The size of the chunks compared at a time.
+const CHUNK_LEN: usize = 64 * 1024;
+
+/// Whether the files `a` and `b` have the same contents.
+///
+/// Files with different sizes are never read. Otherwise they are compared a chunk at a time,
+/// stopping at the first difference.
+pub fn files_equal<A: AsRef<Path>, B: AsRef<Path>>(a: A, b: B) -> Result<bool> {
+    let (a, b) = (a.as_ref(), b.as_ref());
+    let open = |path: &Path| -> Result<(File, fs::Metadata)> {
+        let err = |err| Error::new(err, "comparing", PathArc::new(path));
+        let file = File::open(path).map_err(err)?;
+        let meta = file.metadata().map_err(err)?;
+        Ok((file, meta))
+    };
+    let (mut file_a, meta_a) = open(a)?;
+    let (mut file_b, meta_b) = open(b)?;
+    if meta_a.len() != meta_b.len() {
+        return Ok(false);
+    }
+    if same_file(&meta_a, &meta_b) {
+        return Ok(true);
+    }
+
+    let mut buf_a = vec![0; CHUNK_LEN];
+    let mut buf_b = vec![0; CHUNK_LEN];
+    loop {
+        let n = fill(&mut file_a, &mut buf_a)
+            .map_err(|err| Error::new(err, "comparing", PathArc::new(a)))?;
+        let m = fill(&mut file_b, &mut buf_b)
+            .map_err(|err| Error::new(err, "comparing", PathArc::new(b)))?;
+        if buf_a[..n] != buf_b[..m] {
+            return Ok(false);
+        }
+        if n == 0 {
+            return Ok(true);
+        }
+    }
+}
+
+/// Compare the trees of the directories `a` and `b`, walking both with the `options` (i.e. to
+/// exclude build output).
+///
+/// Files are compared with [`files_equal`]. A directory which exists in only one of the trees
+/// is reported without its contents, the same as `diff -r`.
+///
+/// [`files_equal`]: fn.files_equal.html
+pub fn dirs_equal<A: AsRef<Path>, B: AsRef<Path>>(
+    a: A,
+    b: B,
+    options: &WalkOptions,
+) -> Result<DirDiff> {
+    let (a, b) = (a.as_ref(), b.as_ref());
+    let tree_a = tree(a, options)?;
+    let tree_b = tree(b, options)?;
+
+    let mut diff = DirDiff::default();
+    for (rel, &is_dir) in &tree_a {
+        match tree_b.get(rel) {
+            None => {
+                if reported_parent(rel, &tree_b) {
+                    continue;
+                }
+                diff.only_in_a.push(rel.clone());
+            }
+            Some(&other) if other != is_dir => diff.different.push(rel.clone()),
+            Some(_) if is_dir => {}
+            Some(_) => {
+                if !files_equal(a.join(rel), b.join(rel))? {
+                    diff.different.push(rel.clone());
+                }
+            }
+        }
+    }
+    for rel in tree_b.keys() {
+        if !tree_a.contains_key(rel) && !reported_parent(rel, &tree_a) {
+            diff.only_in_b.push(rel.clone());
+        }
+    }
+    sort::natural(&mut diff.only_in_a);
+    sort::natural(&mut diff.only_in_b);
+    sort::natural(&mut diff.different);
+    Ok(diff)
+}
+
+/// The differences between two directory trees, returned by [`dirs_equal`].
+///
+/// The paths are relative to the roots of the trees and sorted in
+/// [natural order](../sort/index.html). It is displayed like a diff, one path per line: `-`
+/// for paths only in `a`, `+` for paths only in `b` and `!` for paths which differ.
+///
+/// [`dirs_equal`]: fn.dirs_equal.html
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct DirDiff {
+    /// The paths which only exist in `a`.
+    pub only_in_a: Vec<PathBuf>,
+    /// The paths which only exist in `b`.
+    pub only_in_b: Vec<PathBuf>,
+    /// The files whose contents differ, and the paths which are a file in one tree and a
+    /// directory in the other.
+    pub different: Vec<PathBuf>,
+}
+
+impl DirDiff {
+    /// Whether the trees are the same.
+    pub fn is_empty(&self) -> bool {
+        self.only_in_a.is_empty() && self.only_in_b.is_empty() && self.different.is_empty()
+    }
+}
+
+impl fmt::Display for DirDiff {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for path in &self.only_in_a {
+            writeln!(f, "- {}", path.display())?;
+        }
+        for path in &self.different {
+            writeln!(f, "! {}", path.display())?;
+        }
+        for path in &self.only_in_b {
+            writeln!(f, "+ {}", path.display())?;
+        }
+        Ok(())
+    }
+}
+
+/// The paths below `root` relative to it, and whether each is a directory.
+fn tree(root: &Path, options: &WalkOptions) -> Result<BTreeMap<PathBuf, bool>> {
+    // walked entries are absolute
+    let root = PathAbs::new(root)?;
+    let mut out = BTreeMap::new();
+    for entry in options.walk(&root) {
+        let entry = entry?;
+        if entry.depth() == 0 {
+            continue;
+        }
+        let rel = entry
+            .strip_prefix(&root)
+            .expect("walked paths are below the root")
+            .to_path_buf();
+        out.insert(rel, entry.file_type().is_dir());
+    }
+    Ok(out)
+}
+
+/// Whether the parent of `rel` is not a directory in `other`, so `rel` is already reported by
+/// its parent.
+fn reported_parent(rel: &Path, other: &BTreeMap<PathBuf, bool>) -> bool {
+    match rel.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => other.get(parent) != Some(&true),
+        _ => false,
+    }
+}
+
+/// Read until `buf` is full or the file ends.
+fn fill(file: &mut File, buf: &mut [u8]) -> io::Result<usize> {
+    let mut filled = 0;
+    while filled < buf.len() {
+        match file.read(&mut buf[filled..]) {
+            Ok(0) => break,
+            Ok(n) => filled += n,
+            Err(ref err) if err.kind() == io::ErrorKind::Interrupted => {}
+            Err(err) => return Err(err),
+        }
+    }
+    Ok(filled)
+}
+
+#[cfg(unix)]
+fn same_file(a: &fs::Metadata, b: &fs::Metadata) -> bool {
+    use std::os::unix::fs::MetadataExt;
+    a.dev() == b.dev() && a.ino() == b.ino()
+}
+
+#[cfg(not(unix))]
+fn same_file(_a: &fs::Metadata, _b: &fs::Metadata) -> bool {
+    false
+}
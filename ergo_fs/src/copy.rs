@@ -0,0 +1,514 @@
+/* Copyright (c) 2018 Garrett Berg, vitiral@gmail.com
+ *
+ * Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+ * http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+ * http://opensource.org/licenses/MIT>, at your option. This file may not be
+ * copied, modified, or distributed except according to those terms.
+ */
+//! Copying files with options.
+
+use cas::Digest;
+use error::{Error, Result};
+use faults::{self, FsOp};
+use path_abs::{PathArc, PathFile};
+use std::error;
+use std::fmt;
+use std::fs::{self, File, OpenOptions};
+use std::io;
+use std_prelude::*;
+
+/// Options for copying files, used by [`PathFileExt::copy_with`].
+///
+/// [`PathFileExt::copy_with`]: trait.PathFileExt.html#method.copy_with
+///
+/// # Examples
+/// ```rust
+/// # extern crate ergo_fs;
+/// use ergo_fs::*;
+///
+/// # fn try_main() -> ::std::io::Result<()> {
+/// let tmp = PathTmp::create("copy")?;
+/// let file = PathFile::create(tmp.join("example.txt"))?;
+/// file.write_str("example")?;
+///
+/// let options = CopyOptions::new()
+///     .preserve_sparse(false)
+///     .buffer_size(1024 * 1024)
+///     .fsync(FsyncPolicy::PerFile);
+/// let copied = file.copy_with(tmp.join("copied.txt"), &options)?;
+/// assert_eq!("example", copied.read_string()?);
+/// # Ok(()) } fn main() { try_main().unwrap() }
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CopyOptions {
+    preserve_sparse: bool,
+    reflink: bool,
+    buffer_size: Option<usize>,
+    fsync: FsyncPolicy,
+    verify: Option<Verify>,
+}
+
+/// When copied files are flushed to disk with `fsync`, see
+/// [`CopyOptions::fsync`](struct.CopyOptions.html#method.fsync).
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum FsyncPolicy {
+    /// Never sync, leave it to the OS. Fastest, but a crash can lose copies which appeared to
+    /// have completed. Fine for staging build artifacts.
+    #[default]
+    None,
+    /// Sync each file as soon as it has been copied.
+    PerFile,
+    /// Sync all copied files, followed by the directories containing them, once the whole
+    /// operation is complete. For a single file this is the file followed by its parent
+    /// directory. Use this when installing software: afterwards every file _and_ its directory
+    /// entry is durable, and the OS is able to write back in bulk.
+    DirAtEnd,
+}
+
+/// How copied files are checked after copying, see
+/// [`CopyOptions::verify`](struct.CopyOptions.html#method.verify).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Verify {
+    /// Check that the copy has the same size as the original. This only reads metadata.
+    Size,
+    /// Check that the copy has the same SHA-256 digest as the original. This reads both files
+    /// again.
+    Hash,
+}
+
+impl CopyOptions {
+    /// Create the default copy options.
+    pub fn new() -> CopyOptions {
+        CopyOptions {
+            preserve_sparse: true,
+            reflink: false,
+            buffer_size: None,
+            fsync: FsyncPolicy::None,
+            verify: None,
+        }
+    }
+
+    /// Preserve the holes of sparse files (default `true`).
+    ///
+    /// Sparse files (i.e. VM images and database files) only allocate disk space for the regions
+    /// which have been written. A naive copy reads the holes as zeros and writes them out, so a
+    /// 100GB image with 2GB of data becomes 100GB on disk. With this option only the data
+    /// regions are copied and the holes are recreated in the destination.
+    ///
+    /// The data regions are found with `SEEK_DATA`/`SEEK_HOLE` on Linux, Android, macOS and
+    /// FreeBSD. On other platforms (and filesystems which do not support them) the file is
+    /// copied normally.
+    pub fn preserve_sparse(mut self, preserve: bool) -> CopyOptions {
+        self.preserve_sparse = preserve;
+        self
+    }
+
+    /// Clone the file copy-on-write (a "reflink") when possible (default `false`).
+    ///
+    /// A clone shares the data blocks of the original until either is modified, so it is
+    /// instant and uses no extra space no matter how large the file is. Cloning requires the
+    /// source and destination to be on the same filesystem and the filesystem to support it:
+    /// Btrfs, XFS and others with `FICLONE` on Linux, and APFS with `clonefile` on macOS.
+    ///
+    /// When the file cannot be cloned it is transparently copied normally instead.
+    pub fn reflink(mut self, reflink: bool) -> CopyOptions {
+        self.reflink = reflink;
+        self
+    }
+
+    /// Copy through a buffer of `bytes` bytes.
+    ///
+    /// By default the OS's fastest copy (i.e. `copy_file_range`) is used. Larger buffers can
+    /// improve throughput to network filesystems and smaller ones reduce memory when copying
+    /// with many threads.
+    ///
+    /// # Panics
+    /// Panics if `bytes` is 0.
+    pub fn buffer_size(mut self, bytes: usize) -> CopyOptions {
+        assert!(bytes > 0, "buffer_size must be greater than 0");
+        self.buffer_size = Some(bytes);
+        self
+    }
+
+    /// Set when copied files are flushed to disk (default `FsyncPolicy::None`).
+    pub fn fsync(mut self, policy: FsyncPolicy) -> CopyOptions {
+        self.fsync = policy;
+        self
+    }
+
+    /// The fsync policy of these options.
+    pub fn fsync_policy(&self) -> FsyncPolicy {
+        self.fsync
+    }
+
+    /// Check each copied file against the original after copying (and syncing) it (default: no
+    /// verification).
+    ///
+    /// Use this when copying to unreliable filesystems (i.e. network mounts). When the copy
+    /// differs the error's [`verification_failed`] returns the [`VerificationFailed`] with the
+    /// expected and actual values. The copy is left in place.
+    ///
+    /// [`verification_failed`]: struct.Error.html#method.verification_failed
+    /// [`VerificationFailed`]: struct.VerificationFailed.html
+    ///
+    /// # Examples
+    /// ```rust
+    /// # extern crate ergo_fs;
+    /// use ergo_fs::*;
+    ///
+    /// # fn try_main() -> ::std::io::Result<()> {
+    /// let tmp = PathTmp::create("verify")?;
+    /// let file = PathFile::create(tmp.join("example.txt"))?;
+    /// file.write_str("example")?;
+    ///
+    /// let options = CopyOptions::new().verify(Verify::Hash);
+    /// let copied = file.copy_with(tmp.join("copied.txt"), &options)?;
+    /// assert_eq!("example", copied.read_string()?);
+    /// # Ok(()) } fn main() { try_main().unwrap() }
+    /// ```
+    pub fn verify(mut self, verify: Verify) -> CopyOptions {
+        self.verify = Some(verify);
+        self
+    }
+}
+
+impl Default for CopyOptions {
+    fn default() -> CopyOptions {
+        CopyOptions::new()
+    }
+}
+
+/// Copy the file `from` to `to` using the `options`.
+pub(crate) fn copy_file(from: &PathFile, to: &Path, options: &CopyOptions) -> Result<PathFile> {
+    let map_err = |err| Error::with_paths(err, "copying", PathArc::new(from), PathArc::new(to));
+    faults::check(FsOp::Copy, to).map_err(&map_err)?;
+    let copied = (options.reflink && reflink::clone(from, to).map_err(&map_err)?)
+        || (options.preserve_sparse && sparse::copy(from, to).map_err(&map_err)?);
+    if !copied {
+        match options.buffer_size {
+            Some(size) => buffered_copy(from, to, size).map_err(&map_err)?,
+            None => {
+                fs::copy(from, to).map_err(&map_err)?;
+            }
+        }
+    }
+    match options.fsync {
+        FsyncPolicy::None => {}
+        FsyncPolicy::PerFile => sync_file(to)?,
+        FsyncPolicy::DirAtEnd => {
+            sync_file(to)?;
+            if let Some(parent) = to.parent() {
+                sync_dir(if parent.as_os_str().is_empty() {
+                    Path::new(".")
+                } else {
+                    parent
+                })?;
+            }
+        }
+    }
+    if let Some(verify) = options.verify {
+        verify_copy(from, to, verify)?;
+    }
+    Ok(PathFile::new(to)?)
+}
+
+/// Check that the copy `to` matches `from`.
+fn verify_copy(from: &Path, to: &Path, verify: Verify) -> Result<()> {
+    let fingerprint = |path: &Path| -> Result<Fingerprint> {
+        let err = |err| Error::new(err, "verifying", PathArc::new(path));
+        match verify {
+            Verify::Size => Ok(Fingerprint::Size(fs::metadata(path).map_err(err)?.len())),
+            Verify::Hash => {
+                let file = File::open(path).map_err(err)?;
+                Ok(Fingerprint::Hash(Digest::of_reader(file).map_err(err)?))
+            }
+        }
+    };
+    let expected = fingerprint(from)?;
+    let actual = fingerprint(to)?;
+    if expected == actual {
+        return Ok(());
+    }
+    let failed = VerificationFailed {
+        path: PathArc::new(to),
+        expected,
+        actual,
+    };
+    Err(Error::with_paths(
+        failed.into(),
+        "verifying copy",
+        PathArc::new(from),
+        PathArc::new(to),
+    ))
+}
+
+/// What was compared when verifying a copy, see [`Verify`](enum.Verify.html).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Fingerprint {
+    /// The size of the file in bytes.
+    Size(u64),
+    /// The SHA-256 digest of the file's contents.
+    Hash(Digest),
+}
+
+impl fmt::Display for Fingerprint {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Fingerprint::Size(size) => write!(f, "{} bytes", size),
+            Fingerprint::Hash(ref digest) => write!(f, "sha256 {}", digest),
+        }
+    }
+}
+
+/// A copy which does not match the original, the cause of an `Error` when copying with
+/// [`CopyOptions::verify`](struct.CopyOptions.html#method.verify).
+///
+/// Get it from the error with [`Error::verification_failed`].
+///
+/// [`Error::verification_failed`]: struct.Error.html#method.verification_failed
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VerificationFailed {
+    path: PathArc,
+    expected: Fingerprint,
+    actual: Fingerprint,
+}
+
+impl VerificationFailed {
+    /// The path of the copy.
+    pub fn path(&self) -> &Path {
+        self.path.as_ref()
+    }
+
+    /// The fingerprint of the original.
+    pub fn expected(&self) -> Fingerprint {
+        self.expected
+    }
+
+    /// The fingerprint of the copy.
+    pub fn actual(&self) -> Fingerprint {
+        self.actual
+    }
+}
+
+impl fmt::Display for VerificationFailed {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "copy {} does not match: expected {}, found {}",
+            self.path.display(),
+            self.expected,
+            self.actual
+        )
+    }
+}
+
+impl error::Error for VerificationFailed {}
+
+impl From<VerificationFailed> for io::Error {
+    fn from(err: VerificationFailed) -> io::Error {
+        io::Error::new(io::ErrorKind::InvalidData, err)
+    }
+}
+
+fn buffered_copy(from: &Path, to: &Path, size: usize) -> io::Result<()> {
+    let mut src = File::open(from)?;
+    let perms = src.metadata()?.permissions();
+    let mut dst = File::create(to)?;
+    let mut buf = vec![0; size];
+    loop {
+        let n = match src.read(&mut buf) {
+            Ok(0) => break,
+            Ok(n) => n,
+            Err(ref e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e),
+        };
+        dst.write_all(&buf[..n])?;
+    }
+    fs::set_permissions(to, perms)
+}
+
+/// Flush the data and metadata of the file at `path` to disk.
+pub fn sync_file<P: AsRef<Path>>(path: P) -> Result<()> {
+    let path = path.as_ref();
+    faults::check(FsOp::Sync, path)
+        .and_then(|()| OpenOptions::new().write(true).open(path))
+        .and_then(|f| f.sync_all())
+        .map_err(|err| Error::new(err, "syncing", PathArc::new(path)))
+}
+
+/// Flush the entries of the directory at `path` to disk, so that files which were created in
+/// (or removed from) it survive a crash.
+///
+/// This does nothing on windows, where directories cannot be synced.
+pub fn sync_dir<P: AsRef<Path>>(path: P) -> Result<()> {
+    let path = path.as_ref();
+    if cfg!(windows) {
+        return Ok(());
+    }
+    faults::check(FsOp::Sync, path)
+        .and_then(|()| File::open(path))
+        .and_then(|f| f.sync_all())
+        .map_err(|err| Error::new(err, "syncing dir", PathArc::new(path)))
+}
+
+#[cfg(any(
+    target_os = "linux",
+    target_os = "android",
+    target_os = "macos",
+    target_os = "freebsd"
+))]
+mod sparse {
+    use libc;
+    use std::fs::{self, File};
+    use std::io::{self, Seek, SeekFrom};
+    use std::os::unix::fs::MetadataExt;
+    use std::os::unix::io::AsRawFd;
+    use std_prelude::*;
+
+    /// Copy the file preserving holes, returning `false` (without creating `to`) if the file is
+    /// not sparse or holes cannot be detected.
+    pub(super) fn copy(from: &Path, to: &Path) -> io::Result<bool> {
+        let mut src = File::open(from)?;
+        let meta = src.metadata()?;
+        let len = meta.len();
+        // A file with fewer blocks allocated than its length has holes.
+        if meta.blocks() * 512 >= len {
+            return Ok(false);
+        }
+
+        let first = match seek(&src, 0, libc::SEEK_DATA)? {
+            Offset::Unsupported => return Ok(false),
+            Offset::End => len,
+            Offset::At(pos) => pos,
+        };
+
+        let mut dst = File::create(to)?;
+        // Extending the file creates a hole of the full length, then fill in the data.
+        dst.set_len(len)?;
+        let mut data = first;
+        while data < len {
+            let hole = match seek(&src, data, libc::SEEK_HOLE)? {
+                Offset::At(pos) => pos,
+                _ => len,
+            };
+            src.seek(SeekFrom::Start(data))?;
+            dst.seek(SeekFrom::Start(data))?;
+            io::copy(&mut (&mut src).take(hole - data), &mut dst)?;
+            data = match seek(&src, hole, libc::SEEK_DATA)? {
+                Offset::At(pos) => pos,
+                _ => len,
+            };
+        }
+        fs::set_permissions(to, meta.permissions())?;
+        Ok(true)
+    }
+
+    enum Offset {
+        At(u64),
+        /// There is no more data after the offset.
+        End,
+        /// The filesystem does not support `SEEK_DATA`/`SEEK_HOLE`.
+        Unsupported,
+    }
+
+    fn seek(file: &File, offset: u64, whence: i32) -> io::Result<Offset> {
+        let pos = unsafe { libc::lseek(file.as_raw_fd(), offset as libc::off_t, whence) };
+        if pos >= 0 {
+            return Ok(Offset::At(pos as u64));
+        }
+        let err = io::Error::last_os_error();
+        match err.raw_os_error() {
+            Some(libc::ENXIO) => Ok(Offset::End),
+            Some(libc::EINVAL) => Ok(Offset::Unsupported),
+            _ => Err(err),
+        }
+    }
+}
+
+#[cfg(not(any(
+    target_os = "linux",
+    target_os = "android",
+    target_os = "macos",
+    target_os = "freebsd"
+)))]
+mod sparse {
+    use std::io;
+    use std_prelude::*;
+
+    pub(super) fn copy(_from: &Path, _to: &Path) -> io::Result<bool> {
+        Ok(false)
+    }
+}
+
+#[cfg(any(target_os = "linux", target_os = "android"))]
+mod reflink {
+    use libc;
+    use std::fs::{self, File};
+    use std::io;
+    use std::os::unix::io::AsRawFd;
+    use std_prelude::*;
+
+    /// Clone the file with `FICLONE`, returning `false` (without creating `to`) if the
+    /// filesystem cannot clone it.
+    pub(super) fn clone(from: &Path, to: &Path) -> io::Result<bool> {
+        let src = File::open(from)?;
+        let meta = src.metadata()?;
+        let dst = File::create(to)?;
+        let res = unsafe { libc::ioctl(dst.as_raw_fd(), libc::FICLONE, src.as_raw_fd()) };
+        if res == 0 {
+            fs::set_permissions(to, meta.permissions())?;
+            return Ok(true);
+        }
+        let err = io::Error::last_os_error();
+        drop(dst);
+        match err.raw_os_error() {
+            Some(libc::EOPNOTSUPP)
+            | Some(libc::EXDEV)
+            | Some(libc::EINVAL)
+            | Some(libc::ENOTTY)
+            | Some(libc::EPERM) => {
+                fs::remove_file(to)?;
+                Ok(false)
+            }
+            _ => Err(err),
+        }
+    }
+}
+
+#[cfg(target_os = "macos")]
+mod reflink {
+    use libc;
+    use std::ffi::CString;
+    use std::io;
+    use std::os::unix::ffi::OsStrExt;
+    use std_prelude::*;
+
+    /// Clone the file with `clonefile`, returning `false` if the filesystem cannot clone it.
+    ///
+    /// `clonefile` refuses to overwrite, so an existing destination is copied over normally.
+    pub(super) fn clone(from: &Path, to: &Path) -> io::Result<bool> {
+        let cstr = |p: &Path| {
+            CString::new(p.as_os_str().as_bytes())
+                .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))
+        };
+        let (src, dst) = (cstr(from)?, cstr(to)?);
+        if unsafe { libc::clonefile(src.as_ptr(), dst.as_ptr(), 0) } == 0 {
+            return Ok(true);
+        }
+        let err = io::Error::last_os_error();
+        match err.raw_os_error() {
+            Some(libc::ENOTSUP) | Some(libc::EXDEV) | Some(libc::EEXIST) => Ok(false),
+            _ => Err(err),
+        }
+    }
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "android", target_os = "macos")))]
+mod reflink {
+    use std::io;
+    use std_prelude::*;
+
+    pub(super) fn clone(_from: &Path, _to: &Path) -> io::Result<bool> {
+        Ok(false)
+    }
+}
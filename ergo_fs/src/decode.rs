@@ -0,0 +1,148 @@
+/* Copyright (c) 2018 Garrett Berg, vitiral@gmail.com
+ *
+ * Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+ * http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+ * http://opensource.org/licenses/MIT>, at your option. This file may not be
+ * copied, modified, or distributed except according to those terms.
+ */
+//! Reading text which is not (necessarily) UTF-8.
+
+use error::{Error, Result};
+use path_abs::{FileRead, PathArc};
+use sniff::TextEncoding;
+use std::char;
+use std::error;
+use std::fmt;
+use std::io::{self, Seek};
+use std::str;
+use std_prelude::*;
+
+/// The cause of an [`Error`] when text could not be decoded with
+/// [`FileReadExt::read_string_with`].
+///
+/// [`Error`]: struct.Error.html
+/// [`FileReadExt::read_string_with`]: trait.FileReadExt.html#method.read_string_with
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DecodeError {
+    encoding: TextEncoding,
+    offset: u64,
+}
+
+impl DecodeError {
+    /// The encoding which was attempted.
+    pub fn encoding(&self) -> TextEncoding {
+        self.encoding
+    }
+
+    /// The offset in the file of the first byte which could not be decoded.
+    pub fn offset(&self) -> u64 {
+        self.offset
+    }
+}
+
+impl fmt::Display for DecodeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "invalid {} at byte {}", self.encoding, self.offset)
+    }
+}
+
+impl error::Error for DecodeError {}
+
+impl From<DecodeError> for io::Error {
+    fn from(err: DecodeError) -> io::Error {
+        io::Error::new(io::ErrorKind::InvalidData, err)
+    }
+}
+
+/// Read the rest of the file and decode it as `encoding`, skipping its byte order mark.
+pub(crate) fn read_string_with(file: &mut FileRead, encoding: TextEncoding) -> Result<String> {
+    let (start, bytes) = read_rest(file)?;
+    decode(&bytes, encoding).map_err(|offset| {
+        let err = DecodeError {
+            encoding,
+            offset: start + offset as u64,
+        };
+        Error::new(err.into(), "decoding", PathArc::new(file.path()))
+    })
+}
+
+/// Read the rest of the file as text in whichever encoding it looks like, never failing to
+/// decode.
+pub(crate) fn read_string_lossy(file: &mut FileRead) -> Result<String> {
+    let (_, bytes) = read_rest(file)?;
+    Ok(match TextEncoding::from_bom(&bytes) {
+        Some(encoding @ TextEncoding::Utf16Le) | Some(encoding @ TextEncoding::Utf16Be) => {
+            char::decode_utf16(utf16_units(&bytes[2..], encoding))
+                .map(|c| c.unwrap_or(char::REPLACEMENT_CHARACTER))
+                .collect()
+        }
+        Some(TextEncoding::Utf8) => String::from_utf8_lossy(&bytes[3..]).into_owned(),
+        _ => match String::from_utf8(bytes) {
+            Ok(s) => s,
+            Err(err) => decode_latin1(err.as_bytes()),
+        },
+    })
+}
+
+/// Read the rest of the file, returning the offset it was read from.
+fn read_rest(file: &mut FileRead) -> Result<(u64, Vec<u8>)> {
+    let path = PathArc::new(file.path());
+    let err = |err| Error::new(err, "reading", path.clone());
+    let start = file.stream_position().map_err(err)?;
+    let mut bytes = Vec::new();
+    file.read_to_end(&mut bytes).map_err(err)?;
+    Ok((start, bytes))
+}
+
+/// Decode `bytes`, returning the offset of the first invalid byte on failure.
+fn decode(bytes: &[u8], encoding: TextEncoding) -> ::std::result::Result<String, usize> {
+    let skip = if bytes.starts_with(encoding.bom()) {
+        encoding.bom().len()
+    } else {
+        0
+    };
+    let bytes = &bytes[skip..];
+    let decoded = match encoding {
+        TextEncoding::Utf8 => str::from_utf8(bytes)
+            .map(|s| s.to_string())
+            .map_err(|e| e.valid_up_to()),
+        TextEncoding::Utf16Le | TextEncoding::Utf16Be => decode_utf16(bytes, encoding),
+        TextEncoding::Latin1 => Ok(decode_latin1(bytes)),
+    };
+    decoded.map_err(|offset| skip + offset)
+}
+
+fn decode_utf16(bytes: &[u8], encoding: TextEncoding) -> ::std::result::Result<String, usize> {
+    let mut out = String::with_capacity(bytes.len() / 2);
+    let mut offset = 0;
+    for c in char::decode_utf16(utf16_units(bytes, encoding)) {
+        match c {
+            Ok(c) => {
+                out.push(c);
+                offset += c.len_utf16() * 2;
+            }
+            Err(_) => return Err(offset),
+        }
+    }
+    if bytes.len() % 2 == 1 {
+        // a truncated last unit
+        return Err(bytes.len() - 1);
+    }
+    Ok(out)
+}
+
+fn utf16_units<'a>(bytes: &'a [u8], encoding: TextEncoding) -> impl Iterator<Item = u16> + 'a {
+    let little = encoding == TextEncoding::Utf16Le;
+    bytes.chunks_exact(2).map(move |c| {
+        if little {
+            u16::from_le_bytes([c[0], c[1]])
+        } else {
+            u16::from_be_bytes([c[0], c[1]])
+        }
+    })
+}
+
+/// Every byte is the code point of the same value.
+fn decode_latin1(bytes: &[u8]) -> String {
+    bytes.iter().map(|&b| b as char).collect()
+}
@@ -0,0 +1,285 @@
+/* Copyright (c) 2018 Garrett Berg, vitiral@gmail.com
+ *
+ * Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+ * http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+ * http://opensource.org/licenses/MIT>, at your option. This file may not be
+ * copied, modified, or distributed except according to those terms.
+ */
+//! Finding duplicate files.
+
+use cas::Digest;
+use crossbeam_channel::{self as cb, Receiver, Sender};
+use error::{Error, Result};
+use path_abs::{PathArc, PathFile};
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::io::Read;
+use std::thread;
+use std_prelude::*;
+use walk::WalkOptions;
+
+/// The number of bytes at the start of files which are hashed before hashing the whole file.
+const PREFIX_LEN: u64 = 4096;
+
+/// Options for [`find_duplicates`](fn.find_duplicates.html).
+#[derive(Debug, Clone)]
+pub struct DuplicateOptions {
+    walk: WalkOptions,
+    min_size: u64,
+    threads: usize,
+}
+
+impl DuplicateOptions {
+    /// Create the default options: walk with the default `WalkOptions`, skip empty files and
+    /// use a thread per cpu.
+    pub fn new() -> DuplicateOptions {
+        DuplicateOptions {
+            walk: WalkOptions::new(),
+            min_size: 1,
+            threads: thread::available_parallelism()
+                .map(|n| n.get())
+                .unwrap_or(4),
+        }
+    }
+
+    /// The options for walking the directories, i.e. to exclude patterns.
+    pub fn walk(mut self, walk: WalkOptions) -> DuplicateOptions {
+        self.walk = walk;
+        self
+    }
+
+    /// Ignore files smaller than `bytes`. Default: 1, so empty files are not reported.
+    pub fn min_size(mut self, bytes: u64) -> DuplicateOptions {
+        self.min_size = bytes;
+        self
+    }
+
+    /// The number of threads for hashing. Default: the number of cpus.
+    pub fn threads(mut self, threads: usize) -> DuplicateOptions {
+        self.threads = threads.max(1);
+        self
+    }
+}
+
+impl Default for DuplicateOptions {
+    fn default() -> DuplicateOptions {
+        DuplicateOptions::new()
+    }
+}
+
+/// Files with the same contents, found by [`find_duplicates`](fn.find_duplicates.html).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DuplicateGroup {
+    /// The size of each file.
+    pub size: u64,
+    /// The SHA-256 digest of the contents of each file.
+    pub digest: Digest,
+    /// The files, sorted by path. There are always at least two.
+    pub files: Vec<PathFile>,
+}
+
+impl DuplicateGroup {
+    /// The bytes which would be freed by keeping only one of the files.
+    pub fn wasted(&self) -> u64 {
+        self.size * (self.files.len() as u64 - 1)
+    }
+}
+
+/// Find the files with the same contents in `dirs`, sending each group of duplicates over the
+/// returned channel as soon as it is found.
+///
+/// The directories are walked in parallel and the files grouped by size. Only files with the
+/// same size are read: first the first 4KB of each is hashed and then, for those which still
+/// match, the whole file. The hashing is spread over [`threads`] threads. Hard links to the same
+/// file (and a file found through two of the `dirs`) are only reported once.
+///
+/// Errors (i.e. from unreadable files) are sent over the channel as well, the search continues
+/// without the file. The channel is closed when the search is done.
+///
+/// [`threads`]: struct.DuplicateOptions.html#method.threads
+///
+/// # Examples
+/// ```rust
+/// # extern crate ergo_fs;
+/// use ergo_fs::*;
+///
+/// # fn try_main() -> ::std::io::Result<()> {
+/// let tmp = PathTmp::create("dups")?;
+/// let a = PathDir::create(tmp.join("a"))?;
+/// let b = PathDir::create(tmp.join("b"))?;
+/// PathFile::create(a.join("photo.jpg"))?.write_str("the same")?;
+/// PathFile::create(b.join("photo copy.jpg"))?.write_str("the same")?;
+/// PathFile::create(b.join("other.jpg"))?.write_str("not same")?;
+///
+/// let groups: Vec<DuplicateGroup> = find_duplicates(&[&a, &b], &DuplicateOptions::new())
+///     .iter()
+///     .collect::<Result<_, _>>()?;
+/// assert_eq!(1, groups.len());
+/// assert_eq!(8, groups[0].size);
+/// assert_eq!(
+///     vec![a.as_path().join("photo.jpg"), b.as_path().join("photo copy.jpg")],
+///     groups[0].files.iter().map(|f| f.to_path_buf()).collect::<Vec<_>>()
+/// );
+/// # Ok(()) } fn main() { try_main().unwrap() }
+/// ```
+pub fn find_duplicates<P: AsRef<Path>>(
+    dirs: &[P],
+    options: &DuplicateOptions,
+) -> Receiver<Result<DuplicateGroup>> {
+    let (send, recv) = cb::unbounded();
+    let dirs: Vec<PathBuf> = dirs.iter().map(|d| d.as_ref().to_path_buf()).collect();
+    let options = options.clone();
+    thread::spawn(move || {
+        let by_size = walk_sizes(&dirs, &options, &send);
+
+        let (send_group, recv_group) = cb::unbounded();
+        for group in by_size.into_iter().filter(|(_, f)| f.len() > 1) {
+            let _ = send_group.send(group);
+        }
+        drop(send_group);
+        let workers: Vec<_> = (0..options.threads)
+            .map(|_| {
+                let recv_group = recv_group.clone();
+                let send = send.clone();
+                thread::spawn(move || {
+                    for (size, files) in recv_group.iter() {
+                        for group in hash_group(size, files, &send) {
+                            if send.send(Ok(group)).is_err() {
+                                return;
+                            }
+                        }
+                    }
+                })
+            })
+            .collect();
+        for worker in workers {
+            let _ = worker.join();
+        }
+    });
+    recv
+}
+
+/// Walk the dirs in parallel, grouping the unique files by size.
+fn walk_sizes(
+    dirs: &[PathBuf],
+    options: &DuplicateOptions,
+    errors: &Sender<Result<DuplicateGroup>>,
+) -> HashMap<u64, Vec<PathFile>> {
+    let (send_file, recv_file) = cb::unbounded();
+    let walkers: Vec<_> = dirs
+        .iter()
+        .map(|dir| {
+            let dir = dir.clone();
+            let options = options.clone();
+            let send_file = send_file.clone();
+            let errors = errors.clone();
+            thread::spawn(move || {
+                for entry in options.walk.walk(&dir) {
+                    let entry = match entry {
+                        Ok(e) => e,
+                        Err(err) => {
+                            let _ = errors.send(Err(err));
+                            continue;
+                        }
+                    };
+                    let size = entry.metadata().len();
+                    if size < options.min_size {
+                        continue;
+                    }
+                    if let Some(file) = entry.as_file() {
+                        let id = file_id(entry.metadata(), file);
+                        let _ = send_file.send((id, size, file.clone()));
+                    }
+                }
+            })
+        })
+        .collect();
+    drop(send_file);
+
+    let mut seen = HashSet::new();
+    let mut by_size: HashMap<u64, Vec<PathFile>> = HashMap::new();
+    for (id, size, file) in recv_file.iter() {
+        if seen.insert(id) {
+            by_size.entry(size).or_default().push(file);
+        }
+    }
+    for walker in walkers {
+        let _ = walker.join();
+    }
+    by_size
+}
+
+/// Split files of the same size into the groups with the same contents.
+fn hash_group(
+    size: u64,
+    files: Vec<PathFile>,
+    errors: &Sender<Result<DuplicateGroup>>,
+) -> Vec<DuplicateGroup> {
+    let mut out = Vec::new();
+    for (prefix_digest, files) in group_by_digest(files, Some(PREFIX_LEN), errors) {
+        let groups = if size <= PREFIX_LEN {
+            // the prefix was the whole file
+            vec![(prefix_digest, files)]
+        } else {
+            group_by_digest(files, None, errors)
+        };
+        for (digest, mut files) in groups {
+            files.sort();
+            out.push(DuplicateGroup {
+                size,
+                digest,
+                files,
+            });
+        }
+    }
+    out
+}
+
+/// Group the files by the digest of their first `len` bytes (or all of them), returning only
+/// the groups with more than one file.
+fn group_by_digest(
+    files: Vec<PathFile>,
+    len: Option<u64>,
+    errors: &Sender<Result<DuplicateGroup>>,
+) -> Vec<(Digest, Vec<PathFile>)> {
+    let mut groups: HashMap<Digest, Vec<PathFile>> = HashMap::new();
+    for file in files {
+        match digest_file(&file, len) {
+            Ok(digest) => groups.entry(digest).or_default().push(file),
+            Err(err) => {
+                let _ = errors.send(Err(err));
+            }
+        }
+    }
+    groups.into_iter().filter(|(_, f)| f.len() > 1).collect()
+}
+
+fn digest_file(file: &PathFile, len: Option<u64>) -> Result<Digest> {
+    let to_err = |err| Error::new(err, "hashing", PathArc::new(file));
+    let f = fs::File::open(file).map_err(to_err)?;
+    match len {
+        Some(len) => Digest::of_reader(f.take(len)),
+        None => Digest::of_reader(f),
+    }
+    .map_err(to_err)
+}
+
+/// Identify a file so that hard links to it are only counted once.
+#[cfg(unix)]
+fn file_id(meta: &fs::Metadata, _file: &PathFile) -> FileId {
+    use std::os::unix::fs::MetadataExt;
+    FileId::Inode(meta.dev(), meta.ino())
+}
+
+#[cfg(not(unix))]
+fn file_id(_meta: &fs::Metadata, file: &PathFile) -> FileId {
+    FileId::Path(file.to_path_buf())
+}
+
+#[derive(Hash, PartialEq, Eq)]
+enum FileId {
+    #[cfg(unix)]
+    Inode(u64, u64),
+    #[cfg(not(unix))]
+    Path(PathBuf),
+}
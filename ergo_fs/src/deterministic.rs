@@ -0,0 +1,187 @@
+/* Copyright (c) 2018 Garrett Berg, vitiral@gmail.com
+ *
+ * Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+ * http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+ * http://opensource.org/licenses/MIT>, at your option. This file may not be
+ * copied, modified, or distributed except according to those terms.
+ */
+//! Reproducible archives.
+
+use error::{Error, Result};
+use path_abs::PathArc;
+use sort;
+use std::fs;
+use std::io::{self, Write};
+use std_prelude::*;
+use tar;
+use walkdir::WalkDir;
+
+/// Settings for creating archives which are byte-identical no matter when, where or by whom
+/// they are created, for reproducible builds.
+///
+/// - Entries are sorted by path, in [natural order](sort/index.html).
+/// - Modification times are set to a fixed time ([`mtime`], `0` by default).
+/// - Owners are `0` with no user or group names.
+/// - Permissions are normalized: `0o755` for directories and executable files, `0o644` for
+///   other files and `0o777` for symlinks.
+///
+/// The normalization is defined here once so that every archive format applies it the same way.
+///
+/// [`mtime`]: struct.Deterministic.html#method.mtime
+///
+/// # Examples
+/// ```rust
+/// # extern crate ergo_fs;
+/// use ergo_fs::*;
+///
+/// # fn try_main() -> ::std::io::Result<()> {
+/// let tmp = PathTmp::create("deterministic")?;
+///
+/// // The same files, created in a different order at different times.
+/// let first = PathDir::create(tmp.join("first"))?;
+/// PathFile::create(first.join("a.txt"))?.write_str("a")?;
+/// PathFile::create(first.join("b.txt"))?.write_str("b")?;
+/// let second = PathDir::create(tmp.join("second"))?;
+/// PathFile::create(second.join("b.txt"))?.write_str("b")?;
+/// PathFile::create(second.join("a.txt"))?.write_str("a")?;
+///
+/// let archive = |dir: &PathDir| -> ::std::io::Result<Vec<u8>> {
+///     let mut builder = tar::Builder::new(Vec::new());
+///     Deterministic::new().append_tar_dir(&mut builder, "src", dir)?;
+///     Ok(builder.into_inner()?)
+/// };
+/// assert_eq!(archive(&first)?, archive(&second)?);
+/// # Ok(()) } fn main() { try_main().unwrap() }
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct Deterministic {
+    mtime: u64,
+}
+
+/// The type of an entry being archived.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+enum Kind {
+    File { executable: bool },
+    Dir,
+    Symlink,
+}
+
+impl Deterministic {
+    /// The default deterministic settings, with all times set to the unix epoch.
+    pub fn new() -> Deterministic {
+        Deterministic::default()
+    }
+
+    /// Set every modification time to `secs` since the unix epoch, i.e. the value of
+    /// `SOURCE_DATE_EPOCH`.
+    pub fn mtime(mut self, secs: u64) -> Deterministic {
+        self.mtime = secs;
+        self
+    }
+
+    /// Append the contents of `dir` to a tar archive under the path `name`, applying the
+    /// deterministic settings. Symlinks are archived as links, not followed.
+    ///
+    /// Special files (fifos, sockets and devices) are an error with kind `InvalidInput`, since
+    /// reading them would block or archive something other than their contents.
+    pub fn append_tar_dir<W, N, D>(
+        &self,
+        builder: &mut tar::Builder<W>,
+        name: N,
+        dir: D,
+    ) -> Result<()>
+    where
+        W: Write,
+        N: AsRef<Path>,
+        D: AsRef<Path>,
+    {
+        let (name, dir) = (name.as_ref(), dir.as_ref());
+        let walk =
+            WalkDir::new(dir).sort_by(|a, b| sort::natural_cmp_names(a.file_name(), b.file_name()));
+        for entry in walk {
+            let entry = entry?;
+            let err = |e| Error::new(e, "archiving", PathArc::new(entry.path()));
+            let rel = entry
+                .path()
+                .strip_prefix(dir)
+                .map_err(|e| err(io::Error::other(e)))?;
+            let path = name.join(rel);
+            let meta = entry.metadata()?;
+            let kind = Kind::from_metadata(&meta).map_err(&err)?;
+
+            let mut header = tar::Header::new_gnu();
+            header.set_mtime(self.mtime);
+            header.set_uid(0);
+            header.set_gid(0);
+            header.set_username("").map_err(&err)?;
+            header.set_groupname("").map_err(&err)?;
+            header.set_mode(self.mode(kind));
+            match kind {
+                Kind::File { .. } => {
+                    header.set_entry_type(tar::EntryType::Regular);
+                    header.set_size(meta.len());
+                    let file = fs::File::open(entry.path()).map_err(&err)?;
+                    builder
+                        .append_data(&mut header, &path, file)
+                        .map_err(&err)?;
+                }
+                Kind::Dir => {
+                    header.set_entry_type(tar::EntryType::Directory);
+                    header.set_size(0);
+                    builder
+                        .append_data(&mut header, &path, io::empty())
+                        .map_err(&err)?;
+                }
+                Kind::Symlink => {
+                    header.set_entry_type(tar::EntryType::Symlink);
+                    header.set_size(0);
+                    let target = fs::read_link(entry.path()).map_err(&err)?;
+                    builder
+                        .append_link(&mut header, &path, target)
+                        .map_err(&err)?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// The normalized permissions of an entry.
+    fn mode(&self, kind: Kind) -> u32 {
+        match kind {
+            Kind::Dir | Kind::File { executable: true } => 0o755,
+            Kind::File { executable: false } => 0o644,
+            Kind::Symlink => 0o777,
+        }
+    }
+}
+
+impl Kind {
+    fn from_metadata(meta: &fs::Metadata) -> io::Result<Kind> {
+        let ty = meta.file_type();
+        if ty.is_dir() {
+            Ok(Kind::Dir)
+        } else if ty.is_symlink() {
+            Ok(Kind::Symlink)
+        } else if ty.is_file() {
+            Ok(Kind::File {
+                executable: is_executable(meta),
+            })
+        } else {
+            Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "special files cannot be archived",
+            ))
+        }
+    }
+}
+
+#[cfg(unix)]
+fn is_executable(meta: &fs::Metadata) -> bool {
+    use std::os::unix::fs::PermissionsExt;
+    meta.permissions().mode() & 0o111 != 0
+}
+
+#[cfg(not(unix))]
+fn is_executable(_meta: &fs::Metadata) -> bool {
+    false
+}
@@ -0,0 +1,289 @@
+/* Copyright (c) 2018 Garrett Berg, vitiral@gmail.com
+ *
+ * Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+ * http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+ * http://opensource.org/licenses/MIT>, at your option. This file may not be
+ * copied, modified, or distributed except according to those terms.
+ */
+//! Line diffs of text, exported from the `cmp` module.
+
+use error::Result;
+use path_abs::PathFile;
+use std::fmt;
+
+/// The number of unchanged lines shown around each change.
+const CONTEXT: usize = 3;
+
+/// The most edits searched for before falling back to replacing everything between the common
+/// start and end, which bounds the memory used for very different texts.
+const MAX_EDITS: usize = 2000;
+
+/// Diff the lines of the files `a` and `b`, returning the hunks of a unified diff.
+///
+/// Both files must be UTF-8. See [`diff_str`](fn.diff_str.html).
+///
+/// # Examples
+/// ```rust
+/// # extern crate ergo_fs;
+/// use ergo_fs::*;
+/// use ergo_fs::cmp::diff_lines;
+///
+/// # fn try_main() -> ::std::io::Result<()> {
+/// let tmp = PathTmp::create("diff")?;
+/// let a = PathFile::create(tmp.join("a.toml"))?;
+/// a.write_str("[package]\nname = \"ergo\"\nversion = \"0.1.0\"\n")?;
+/// let b = PathFile::create(tmp.join("b.toml"))?;
+/// b.write_str("[package]\nname = \"ergo\"\nversion = \"0.2.0\"\n")?;
+///
+/// let hunks = diff_lines(&a, &b)?;
+/// assert_eq!(1, hunks.len());
+/// assert_eq!(
+///     "@@ -1,3 +1,3 @@\n [package]\n name = \"ergo\"\n-version = \"0.1.0\"\n+version = \"0.2.0\"\n",
+///     hunks[0].to_string()
+/// );
+/// # Ok(()) } fn main() { try_main().unwrap() }
+/// ```
+pub fn diff_lines(a: &PathFile, b: &PathFile) -> Result<Vec<DiffHunk>> {
+    Ok(diff_str(&a.read_string()?, &b.read_string()?))
+}
+
+/// Diff the lines of `old` and `new`, returning the hunks of a unified diff with 3 lines of
+/// context.
+///
+/// Use this to show a user what will change before overwriting their file with `new`. Line
+/// endings are not compared, so a missing newline at the end of the file is not a difference.
+/// The diff is minimal unless the texts are very different, in which case everything between
+/// their common start and end is replaced.
+///
+/// # Examples
+/// ```rust
+/// # extern crate ergo_fs;
+/// use ergo_fs::cmp::diff_str;
+///
+/// # fn main() {
+/// let old = "a\nb\nc\n";
+/// let new = "a\nc\nd\n";
+/// let diff: String = diff_str(old, new).iter().map(|h| h.to_string()).collect();
+/// assert_eq!("@@ -1,3 +1,3 @@\n a\n-b\n c\n+d\n", diff);
+/// # }
+/// ```
+pub fn diff_str(old: &str, new: &str) -> Vec<DiffHunk> {
+    let a: Vec<&str> = old.lines().collect();
+    let b: Vec<&str> = new.lines().collect();
+    hunks(&a, &b, &edits(&a, &b))
+}
+
+/// A group of changes which are close together, with the unchanged lines around them.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DiffHunk {
+    /// The line in the old text where the hunk starts, counting from 1.
+    pub old_start: usize,
+    /// The number of lines of the old text in the hunk.
+    pub old_len: usize,
+    /// The line in the new text where the hunk starts, counting from 1.
+    pub new_start: usize,
+    /// The number of lines of the new text in the hunk.
+    pub new_len: usize,
+    /// The lines of the hunk.
+    pub lines: Vec<DiffLine>,
+}
+
+/// A line of a [`DiffHunk`](struct.DiffHunk.html).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DiffLine {
+    /// An unchanged line.
+    Context(String),
+    /// A line which is only in the old text.
+    Removed(String),
+    /// A line which is only in the new text.
+    Added(String),
+}
+
+impl fmt::Display for DiffHunk {
+    /// Format the hunk as in a unified diff: the `@@ -old +new @@` header followed by the lines
+    /// prefixed by ` `, `-` or `+`.
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(
+            f,
+            "@@ -{} +{} @@",
+            Range(self.old_start, self.old_len),
+            Range(self.new_start, self.new_len)
+        )?;
+        for line in &self.lines {
+            match *line {
+                DiffLine::Context(ref l) => writeln!(f, " {}", l)?,
+                DiffLine::Removed(ref l) => writeln!(f, "-{}", l)?,
+                DiffLine::Added(ref l) => writeln!(f, "+{}", l)?,
+            }
+        }
+        Ok(())
+    }
+}
+
+/// A range of a hunk header, where an empty range starts at the line before it.
+struct Range(usize, usize);
+
+impl fmt::Display for Range {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.1 {
+            0 => write!(f, "{},0", self.0 - 1),
+            1 => write!(f, "{}", self.0),
+            len => write!(f, "{},{}", self.0, len),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Edit {
+    Equal,
+    Delete,
+    Insert,
+}
+
+/// The edits which turn `a` into `b`.
+fn edits(a: &[&str], b: &[&str]) -> Vec<Edit> {
+    let prefix = a.iter().zip(b).take_while(|&(x, y)| x == y).count();
+    let suffix = a[prefix..]
+        .iter()
+        .rev()
+        .zip(b[prefix..].iter().rev())
+        .take_while(|&(x, y)| x == y)
+        .count();
+    let (mid_a, mid_b) = (&a[prefix..a.len() - suffix], &b[prefix..b.len() - suffix]);
+
+    let mut out = vec![Edit::Equal; prefix];
+    match myers(mid_a, mid_b) {
+        Some(mid) => out.extend(mid),
+        None => {
+            out.extend(mid_a.iter().map(|_| Edit::Delete));
+            out.extend(mid_b.iter().map(|_| Edit::Insert));
+        }
+    }
+    out.extend(vec![Edit::Equal; suffix]);
+    out
+}
+
+/// The shortest edit script with Myers' algorithm, or `None` if it has more than `MAX_EDITS`
+/// edits.
+fn myers(a: &[&str], b: &[&str]) -> Option<Vec<Edit>> {
+    let (n, m) = (a.len() as isize, b.len() as isize);
+    let max = (n + m) as usize;
+    let offset = max as isize + 1;
+    let mut v = vec![0isize; 2 * max + 3];
+    // The furthest x on the diagonals `-d - 1..=d + 1` before every round `d`, to walk back
+    // through. Only these are read in the round, so the trace is `O(d^2)`.
+    let mut trace: Vec<Vec<isize>> = Vec::new();
+
+    'search: for d in 0..=max.min(MAX_EDITS) as isize {
+        trace.push(v[(offset - d - 1) as usize..=(offset + d + 1) as usize].to_vec());
+        let mut k = -d;
+        while k <= d {
+            let i = (k + offset) as usize;
+            let mut x = if k == -d || (k != d && v[i - 1] < v[i + 1]) {
+                v[i + 1]
+            } else {
+                v[i - 1] + 1
+            };
+            let mut y = x - k;
+            while x < n && y < m && a[x as usize] == b[y as usize] {
+                x += 1;
+                y += 1;
+            }
+            v[i] = x;
+            if x >= n && y >= m {
+                break 'search;
+            }
+            k += 2;
+        }
+        if d as usize == max.min(MAX_EDITS) {
+            return None;
+        }
+    }
+
+    let mut out = Vec::new();
+    let (mut x, mut y) = (n, m);
+    for (d, v) in trace.iter().enumerate().rev() {
+        let d = d as isize;
+        let at = |k: isize| v[(k + d + 1) as usize];
+        let k = x - y;
+        let prev_k = if k == -d || (k != d && at(k - 1) < at(k + 1)) {
+            k + 1
+        } else {
+            k - 1
+        };
+        let prev_x = at(prev_k);
+        let prev_y = prev_x - prev_k;
+        while x > prev_x && y > prev_y {
+            out.push(Edit::Equal);
+            x -= 1;
+            y -= 1;
+        }
+        if d > 0 {
+            out.push(if x == prev_x {
+                Edit::Insert
+            } else {
+                Edit::Delete
+            });
+        }
+        x = prev_x;
+        y = prev_y;
+    }
+    out.reverse();
+    Some(out)
+}
+
+/// Group the edits into hunks with `CONTEXT` lines around each change.
+fn hunks(a: &[&str], b: &[&str], edits: &[Edit]) -> Vec<DiffHunk> {
+    // the position in `a` and `b` before each edit
+    let mut positions = Vec::with_capacity(edits.len() + 1);
+    let (mut x, mut y) = (0, 0);
+    for edit in edits {
+        positions.push((x, y));
+        match *edit {
+            Edit::Equal => {
+                x += 1;
+                y += 1;
+            }
+            Edit::Delete => x += 1,
+            Edit::Insert => y += 1,
+        }
+    }
+    positions.push((x, y));
+
+    let changes: Vec<usize> = (0..edits.len())
+        .filter(|&i| edits[i] != Edit::Equal)
+        .collect();
+    let mut out = Vec::new();
+    let mut i = 0;
+    while i < changes.len() {
+        let start = changes[i].saturating_sub(CONTEXT);
+        let mut last = changes[i];
+        while i + 1 < changes.len() && changes[i + 1] - last <= 2 * CONTEXT {
+            i += 1;
+            last = changes[i];
+        }
+        let end = (last + 1 + CONTEXT).min(edits.len());
+
+        let lines = (start..end)
+            .map(|e| {
+                let (x, y) = positions[e];
+                match edits[e] {
+                    Edit::Equal => DiffLine::Context(a[x].to_string()),
+                    Edit::Delete => DiffLine::Removed(a[x].to_string()),
+                    Edit::Insert => DiffLine::Added(b[y].to_string()),
+                }
+            })
+            .collect();
+        let (old_start, new_start) = positions[start];
+        let (old_end, new_end) = positions[end];
+        out.push(DiffHunk {
+            old_start: old_start + 1,
+            old_len: old_end - old_start,
+            new_start: new_start + 1,
+            new_len: new_end - new_start,
+            lines,
+        });
+        i += 1;
+    }
+    out
+}
@@ -0,0 +1,151 @@
+/* Copyright (c) 2018 Garrett Berg, vitiral@gmail.com
+ *
+ * Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+ * http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+ * http://opensource.org/licenses/MIT>, at your option. This file may not be
+ * copied, modified, or distributed except according to those terms.
+ */
+//! Platform directories for applications, wrapping the [`dirs`] crate.
+//!
+//! Each function takes the name of your application and returns a `PathDir` for it inside of
+//! the platform's standard location, creating it (and any parents) if it does not yet exist.
+//!
+//! | Function        | Linux (XDG)                          | macOS                           | Windows                     |
+//! | --------------- | ------------------------------------ | ------------------------------- | --------------------------- |
+//! | [`config_dir`]  | `$XDG_CONFIG_HOME` or `~/.config`    | `~/Library/Preferences`         | `{FOLDERID_RoamingAppData}` |
+//! | [`cache_dir`]   | `$XDG_CACHE_HOME` or `~/.cache`      | `~/Library/Caches`              | `{FOLDERID_LocalAppData}`   |
+//! | [`data_dir`]    | `$XDG_DATA_HOME` or `~/.local/share` | `~/Library/Application Support` | `{FOLDERID_RoamingAppData}` |
+//! | [`runtime_dir`] | `$XDG_RUNTIME_DIR` (or an error)     | (temp dir)                      | (temp dir)                  |
+//!
+//! In addition, [`home_dir`] returns the current user's home directory.
+//!
+//! The `dirs` crate itself is exported as [`dirs_crate`] for the other directories it supports.
+//!
+//! [`dirs`]: https://github.com/soc/dirs-rs
+//! [`dirs_crate`]: ../../dirs/index.html
+//! [`home_dir`]: fn.home_dir.html
+//! [`config_dir`]: fn.config_dir.html
+//! [`cache_dir`]: fn.cache_dir.html
+//! [`data_dir`]: fn.data_dir.html
+//! [`runtime_dir`]: fn.runtime_dir.html
+//!
+//! # Examples
+//! ```rust,no_run
+//! # extern crate ergo_fs;
+//! use ergo_fs::*;
+//!
+//! # fn try_main() -> ::std::io::Result<()> {
+//! let config = dirs::config_dir("myapp")?;
+//! let settings = config.join("settings.toml");
+//! # Ok(()) } fn main() { try_main().unwrap() }
+//! ```
+
+use dirs_crate;
+use error::Error;
+use path_abs::{PathArc, PathDir};
+use std::env;
+use std::fs;
+use std::io;
+use std_prelude::*;
+
+/// Get the home directory of the current user.
+///
+/// Unlike the deprecated `std::env::home_dir`, this resolves the directory correctly on every
+/// platform. The first of the following which is set (and absolute) is used:
+///
+/// - The `HOME` environment variable.
+/// - On windows, the `USERPROFILE` environment variable.
+/// - On unix, the home directory of the user from the password database (`getpwuid_r`). On
+///   windows, the `Profile` known folder. This is the fallback for setuid and daemon contexts
+///   where the environment is not set.
+///
+/// # Examples
+/// ```rust
+/// # extern crate ergo_fs;
+/// use ergo_fs::*;
+///
+/// # fn try_main() -> ::std::io::Result<()> {
+/// let home = home_dir()?;
+/// assert!(home.is_absolute());
+/// # Ok(()) } fn main() { try_main().unwrap() }
+/// ```
+pub fn home_dir() -> io::Result<PathDir> {
+    let home = match home_path() {
+        Some(h) => h,
+        None => {
+            return Err(io::Error::new(
+                io::ErrorKind::NotFound,
+                "could not determine the home directory of the current user",
+            ))
+        }
+    };
+    Ok(PathDir::new(home)?)
+}
+
+/// Find the path of the home directory without checking that it exists.
+///
+/// It is canonicalized if it does exist, so that `~` expands to the same path as `home_dir`.
+pub(crate) fn home_path() -> Option<PathBuf> {
+    let from_env = |var| {
+        env::var_os(var)
+            .map(PathBuf::from)
+            .filter(|p| p.is_absolute())
+    };
+    let home = from_env("HOME");
+    #[cfg(windows)]
+    let home = home.or_else(|| from_env("USERPROFILE"));
+    home.or_else(dirs_crate::home_dir)
+        .map(|h| fs::canonicalize(&h).unwrap_or(h))
+}
+
+/// The directory for configuration files of `app`.
+pub fn config_dir(app: &str) -> Result<PathDir, Error> {
+    app_dir(dirs_crate::config_dir(), "config", app)
+}
+
+/// The directory for cached (non-essential) files of `app`.
+pub fn cache_dir(app: &str) -> Result<PathDir, Error> {
+    app_dir(dirs_crate::cache_dir(), "cache", app)
+}
+
+/// The directory for persistent data files of `app`.
+pub fn data_dir(app: &str) -> Result<PathDir, Error> {
+    app_dir(dirs_crate::data_dir(), "data", app)
+}
+
+/// The directory for runtime files of `app`, such as sockets and pid files.
+///
+/// On Linux and other XDG platforms it is an error if `$XDG_RUNTIME_DIR` is not set: a shared
+/// directory such as `/tmp` would let other users plant or read the files. macOS and windows
+/// have no runtime directory and fall back to `std::env::temp_dir()`, which is private to the
+/// user on both.
+pub fn runtime_dir(app: &str) -> Result<PathDir, Error> {
+    let base = dirs_crate::runtime_dir();
+    #[cfg(any(target_os = "macos", windows))]
+    let base = base.or_else(|| Some(env::temp_dir()));
+    if base.is_none() && cfg!(not(any(target_os = "macos", windows))) {
+        return Err(Error::new(
+            io::Error::new(io::ErrorKind::NotFound, "$XDG_RUNTIME_DIR is not set"),
+            "locating runtime dir for",
+            PathArc::new(app),
+        ));
+    }
+    app_dir(base, "runtime", app)
+}
+
+fn app_dir(base: Option<PathBuf>, kind: &str, app: &str) -> Result<PathDir, Error> {
+    let base = match base {
+        Some(b) => b,
+        None => {
+            return Err(Error::new(
+                io::Error::new(
+                    io::ErrorKind::NotFound,
+                    format!("no {} directory is known for this platform", kind),
+                ),
+                &format!("locating {} dir for", kind),
+                PathArc::new(app),
+            ))
+        }
+    };
+    Ok(PathDir::create_all(base.join(app))?)
+}
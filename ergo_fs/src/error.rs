@@ -0,0 +1,310 @@
+/* Copyright (c) 2018 Garrett Berg, vitiral@gmail.com
+ *
+ * Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+ * http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+ * http://opensource.org/licenses/MIT>, at your option. This file may not be
+ * copied, modified, or distributed except according to those terms.
+ */
+//! The error type for `ergo_fs`.
+
+use copy::VerificationFailed;
+use decode::DecodeError;
+use jail::JailEscape;
+use path_abs::{self, PathArc};
+use std::error;
+use std::fmt;
+use std::io;
+use std_prelude::*;
+use walkdir;
+
+/// A `Result` with the `ergo_fs` error type.
+pub(crate) type Result<T> = ::std::result::Result<T, Error>;
+
+/// An error produced by a filesystem operation in `ergo_fs`.
+///
+/// This is an extension of [`path_abs::Error`] which gives programmatic access to everything that
+/// is in the `Display` string:
+///
+/// - [`kind`]: the `io::ErrorKind`, so you can branch on `NotFound` vs `PermissionDenied`.
+/// - [`action`]: the action being performed when the error occured.
+/// - [`path`]: the path associated with the error.
+/// - [`second_path`]: for operations with two paths (i.e. copying), the other path.
+///
+/// A `path_abs::Error` can be converted into this type and this type can be converted into an
+/// `io::Error`, so the `?` operator works in both directions.
+///
+/// [`path_abs::Error`]: ../path_abs/struct.Error.html
+/// [`kind`]: struct.Error.html#method.kind
+/// [`action`]: struct.Error.html#method.action
+/// [`path`]: struct.Error.html#method.path
+/// [`second_path`]: struct.Error.html#method.second_path
+///
+/// # Examples
+/// ```rust
+/// # extern crate ergo_fs;
+/// use std::io;
+/// use ergo_fs::*;
+///
+/// # fn try_main() -> ::std::io::Result<()> {
+/// let err = match PathTmp::create_in("/this/does/not/exist", "example") {
+///     Err(err) => err,
+///     Ok(_) => unreachable!(),
+/// };
+/// assert_eq!(io::ErrorKind::NotFound, err.kind());
+/// assert_eq!(Path::new("/this/does/not/exist"), err.path());
+/// assert_eq!(None, err.second_path());
+/// # Ok(()) } fn main() { try_main().unwrap() }
+/// ```
+pub struct Error {
+    io_err: io::Error,
+    action: String,
+    path: PathArc,
+    second_path: Option<PathArc>,
+}
+
+impl Error {
+    /// Create a new error when the path and action are known.
+    pub fn new(io_err: io::Error, action: &str, path: PathArc) -> Error {
+        Error {
+            io_err,
+            action: action.into(),
+            path,
+            second_path: None,
+        }
+    }
+
+    /// Create a new error for an action which involves two paths, i.e. `"copying"` from `path`
+    /// to `second_path`.
+    pub fn with_paths(io_err: io::Error, action: &str, path: PathArc, second: PathArc) -> Error {
+        Error {
+            io_err,
+            action: action.into(),
+            path,
+            second_path: Some(second),
+        }
+    }
+
+    /// Returns the kind of the underlying `io::Error`.
+    pub fn kind(&self) -> io::ErrorKind {
+        self.io_err.kind()
+    }
+
+    /// Returns the path associated with this error.
+    pub fn path(&self) -> &Path {
+        self.path.as_ref()
+    }
+
+    /// Returns the second path associated with this error, if the action involved two paths.
+    pub fn second_path(&self) -> Option<&Path> {
+        self.second_path.as_ref().map(|p| p.as_ref())
+    }
+
+    /// Returns the `std::io::Error` associated with this error.
+    pub fn io_error(&self) -> &io::Error {
+        &self.io_err
+    }
+
+    /// Returns the action being performed when this error occured.
+    pub fn action(&self) -> &str {
+        &self.action
+    }
+
+    /// Returns the [`SymlinkLoop`] if this error was caused by one.
+    ///
+    /// [`SymlinkLoop`]: struct.SymlinkLoop.html
+    pub fn symlink_loop(&self) -> Option<&SymlinkLoop> {
+        self.io_err
+            .get_ref()
+            .and_then(|e| e.downcast_ref::<SymlinkLoop>())
+    }
+
+    /// Returns the [`VerificationFailed`] if this error was caused by a copy which does not
+    /// match its original.
+    ///
+    /// [`VerificationFailed`]: struct.VerificationFailed.html
+    pub fn verification_failed(&self) -> Option<&VerificationFailed> {
+        self.io_err
+            .get_ref()
+            .and_then(|e| e.downcast_ref::<VerificationFailed>())
+    }
+
+    /// Returns the [`JailEscape`] if this error was caused by a path which would leave the root
+    /// of a `PathJail`.
+    ///
+    /// [`JailEscape`]: struct.JailEscape.html
+    pub fn jail_escape(&self) -> Option<&JailEscape> {
+        self.io_err
+            .get_ref()
+            .and_then(|e| e.downcast_ref::<JailEscape>())
+    }
+
+    /// Returns the [`DecodeError`] if this error was caused by text which is not valid in the
+    /// encoding it was read with.
+    ///
+    /// [`DecodeError`]: struct.DecodeError.html
+    pub fn decode_error(&self) -> Option<&DecodeError> {
+        self.io_err
+            .get_ref()
+            .and_then(|e| e.downcast_ref::<DecodeError>())
+    }
+}
+
+/// A symlink which points to one of its own ancestors, found while walking a directory and
+/// following symlinks.
+///
+/// Following such a link would recurse forever, so walks (and `ergo::deep_copy`) stop there and
+/// report this error instead. Loops are detected by comparing the device and inode (or windows
+/// file index) of each directory against those of its ancestors, so they are found no matter how
+/// the link is spelled.
+///
+/// When walking, convert the `walkdir::Error` into an [`Error`] to get at it.
+///
+/// [`Error`]: struct.Error.html
+///
+/// # Examples
+/// ```rust
+/// # extern crate ergo_fs;
+/// use ergo_fs::*;
+///
+/// # fn try_main() -> ::std::io::Result<()> {
+/// # #[cfg(unix)] {
+/// let tmp = PathTmp::create("loop")?;
+/// let dir = PathDir::create(tmp.join("dir"))?;
+/// ::std::os::unix::fs::symlink(&dir, dir.join("link"))?;
+///
+/// let errors: Vec<Error> = dir
+///     .walk()
+///     .follow_links(true)
+///     .into_iter()
+///     .filter_map(|e| e.err())
+///     .map(Error::from)
+///     .collect();
+/// assert_eq!(1, errors.len());
+///
+/// let symlink_loop = errors[0].symlink_loop().expect("loop");
+/// assert_eq!(dir.join("link").as_path(), symlink_loop.at());
+/// assert_eq!(dir.as_path(), symlink_loop.ancestor());
+/// # }
+/// # Ok(()) } fn main() { try_main().unwrap() }
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SymlinkLoop {
+    at: PathArc,
+    ancestor: PathArc,
+}
+
+impl SymlinkLoop {
+    /// Create a new symlink loop error for the link `at` pointing to its `ancestor`.
+    pub fn new(at: PathArc, ancestor: PathArc) -> SymlinkLoop {
+        SymlinkLoop { at, ancestor }
+    }
+
+    /// The path of the symlink which creates the loop.
+    pub fn at(&self) -> &Path {
+        self.at.as_ref()
+    }
+
+    /// The ancestor directory which the symlink points to.
+    pub fn ancestor(&self) -> &Path {
+        self.ancestor.as_ref()
+    }
+}
+
+impl fmt::Display for SymlinkLoop {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "symlink loop: {} points to its ancestor {}",
+            self.at.display(),
+            self.ancestor.display()
+        )
+    }
+}
+
+impl error::Error for SymlinkLoop {}
+
+impl From<SymlinkLoop> for io::Error {
+    fn from(err: SymlinkLoop) -> io::Error {
+        io::Error::other(err)
+    }
+}
+
+/// What bulk operations on many paths (i.e. removing or copying a tree) do when an error
+/// occurs.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ErrorPolicy {
+    /// Keep going with the rest of the paths and report every error.
+    #[default]
+    Continue,
+    /// Stop at the first error and return it, leaving the rest of the paths untouched. For
+    /// callers which only want the first error rather than a list of what went wrong. What was
+    /// done before the error is not undone.
+    FailFast,
+}
+
+/// Create an owned copy of an `io::Error`, preserving the OS error code when there is one.
+pub(crate) fn copy_io_error(err: &io::Error) -> io::Error {
+    match err.raw_os_error() {
+        Some(code) => io::Error::from_raw_os_error(code),
+        None => io::Error::new(err.kind(), err.to_string()),
+    }
+}
+
+impl fmt::Debug for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Error<{}>", self)
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{} when {} {}",
+            self.io_err,
+            self.action,
+            self.path.display()
+        )?;
+        if let Some(ref second) = self.second_path {
+            write!(f, " to {}", second.display())?;
+        }
+        Ok(())
+    }
+}
+
+impl error::Error for Error {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        Some(&self.io_err)
+    }
+}
+
+impl From<path_abs::Error> for Error {
+    fn from(err: path_abs::Error) -> Error {
+        Error::new(
+            copy_io_error(err.io_error()),
+            err.action(),
+            PathArc::new(err.path()),
+        )
+    }
+}
+
+impl From<walkdir::Error> for Error {
+    fn from(err: walkdir::Error) -> Error {
+        let path = PathArc::new(err.path().unwrap_or_else(|| Path::new("")));
+        if let Some(ancestor) = err.loop_ancestor() {
+            let symlink_loop = SymlinkLoop::new(path.clone(), PathArc::new(ancestor));
+            return Error::new(symlink_loop.into(), "walking", path);
+        }
+        let io_err = match err.io_error() {
+            Some(e) => copy_io_error(e),
+            None => io::Error::other(err.to_string()),
+        };
+        Error::new(io_err, "walking", path)
+    }
+}
+
+impl From<Error> for io::Error {
+    fn from(err: Error) -> io::Error {
+        io::Error::new(err.io_err.kind(), err)
+    }
+}
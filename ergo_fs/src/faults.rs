@@ -0,0 +1,280 @@
+/* Copyright (c) 2018 Garrett Berg, vitiral@gmail.com
+ *
+ * Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+ * http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+ * http://opensource.org/licenses/MIT>, at your option. This file may not be
+ * copied, modified, or distributed except according to those terms.
+ */
+//! Injecting failures into filesystem operations, for testing error handling.
+//!
+//! Disks fill up, network mounts drop and renames fail, but tests rarely get to see it. With
+//! the `fault_injection` feature, install [`ErgoFsHooks`] in a test to make the operations of
+//! this crate fail for the paths matching a glob, i.e. "fail the 3rd write to `*.tmp`".
+//!
+//! The operations which can fail are listed in [`FsOp`]. They are the ones performed by this
+//! crate's own functions (copies, atomic edits, unique files, syncs, the `HashingWriter` and the
+//! `RotatingFile`), not the methods of the `path_abs` types. Crates built on `ergo_fs` call
+//! [`check`] before their own operations so that they can fail too.
+//!
+//! Only one set of hooks is installed at a time: installing them waits until the hooks of any
+//! other test are dropped, so tests with faults run one after the other.
+//!
+//! [`ErgoFsHooks`]: struct.ErgoFsHooks.html
+//! [`FsOp`]: enum.FsOp.html
+//! [`check`]: fn.check.html
+
+use std::fmt;
+use std::io;
+use std_prelude::*;
+
+#[cfg(feature = "fault_injection")]
+pub use self::hooks::{ErgoFsHooks, Fault, HooksGuard};
+
+/// A filesystem operation which can be made to fail, see the [module](index.html) docs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum FsOp {
+    /// Creating a new file, i.e. the temporary file of an atomic edit.
+    Create,
+    /// Creating a directory.
+    CreateDir,
+    /// A write to a file. Writers check every call to `write`.
+    Write,
+    /// Copying a file, checked with the destination.
+    Copy,
+    /// Renaming a file over another, checked with the destination.
+    Rename,
+    /// Syncing a file or directory to disk.
+    Sync,
+}
+
+impl fmt::Display for FsOp {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match *self {
+            FsOp::Create => "create",
+            FsOp::CreateDir => "create dir",
+            FsOp::Write => "write",
+            FsOp::Copy => "copy",
+            FsOp::Rename => "rename",
+            FsOp::Sync => "sync",
+        })
+    }
+}
+
+/// Return the injected error if the installed [`ErgoFsHooks`](struct.ErgoFsHooks.html) fail
+/// `op` on `path`.
+///
+/// Call this before performing an operation so that tests can make it fail. Without the
+/// `fault_injection` feature this always succeeds and compiles to nothing.
+#[cfg(not(feature = "fault_injection"))]
+#[inline(always)]
+pub fn check(op: FsOp, path: &Path) -> io::Result<()> {
+    let _ = (op, path);
+    Ok(())
+}
+
+/// Return the injected error if the installed [`ErgoFsHooks`](struct.ErgoFsHooks.html) fail
+/// `op` on `path`.
+///
+/// Call this before performing an operation so that tests can make it fail. Without the
+/// `fault_injection` feature this always succeeds and compiles to nothing.
+#[cfg(feature = "fault_injection")]
+pub fn check(op: FsOp, path: &Path) -> io::Result<()> {
+    hooks::check(op, path)
+}
+
+#[cfg(feature = "fault_injection")]
+mod hooks {
+    use super::FsOp;
+    use glob_crate::Pattern;
+    use std::io;
+    use std::sync::{MutexGuard, PoisonError};
+    use std_prelude::*;
+
+    /// Serializes the tests which install hooks.
+    static INSTALLED: Mutex<()> = Mutex::new(());
+    static ACTIVE: Mutex<Option<Active>> = Mutex::new(None);
+
+    struct Active {
+        faults: Vec<Fault>,
+        injected: usize,
+    }
+
+    /// A failure to inject into the operations matching an [`FsOp`](enum.FsOp.html) and a glob.
+    #[derive(Debug, Clone)]
+    pub struct Fault {
+        op: FsOp,
+        pattern: Pattern,
+        nth: Option<usize>,
+        kind: io::ErrorKind,
+        seen: usize,
+    }
+
+    impl Fault {
+        /// Fail every `op` on a path matching the glob `pattern`.
+        ///
+        /// The pattern is matched against the whole path, and `*` also matches `/`. So `*.tmp`
+        /// matches every file ending in `.tmp` and `*/notes.txt` every file named `notes.txt`.
+        ///
+        /// # Panics
+        /// Panics if `pattern` is not a valid glob.
+        pub fn new(op: FsOp, pattern: &str) -> Fault {
+            Fault {
+                op,
+                pattern: Pattern::new(pattern)
+                    .unwrap_or_else(|e| panic!("invalid fault pattern {:?}: {}", pattern, e)),
+                nth: None,
+                kind: io::ErrorKind::Other,
+                seen: 0,
+            }
+        }
+
+        /// Only fail the `nth` matching operation (counting from 1), i.e. the 3rd write.
+        ///
+        /// # Examples
+        /// ```rust
+        /// # extern crate ergo_fs;
+        /// use ergo_fs::*;
+        /// use ergo_fs::faults::{ErgoFsHooks, Fault, FsOp};
+        ///
+        /// # fn try_main() -> ::std::io::Result<()> {
+        /// let tmp = PathTmp::create("faults_nth")?;
+        /// let _hooks = ErgoFsHooks::new()
+        ///     .fault(Fault::new(FsOp::Write, "*.tmp").nth(3))
+        ///     .install();
+        ///
+        /// let mut out = HashingWriter::create(tmp.join("out.tmp"))?;
+        /// out.write_all(b"1")?;
+        /// out.write_all(b"2")?;
+        /// assert!(out.write_all(b"3").is_err());
+        /// out.write_all(b"4")?;
+        /// # Ok(()) } fn main() { try_main().unwrap() }
+        /// ```
+        pub fn nth(mut self, nth: usize) -> Fault {
+            self.nth = Some(nth);
+            self
+        }
+
+        /// The kind of the injected error. The default is `Other`, use a transient kind (i.e.
+        /// `TimedOut`) to test retries.
+        pub fn kind(mut self, kind: io::ErrorKind) -> Fault {
+            self.kind = kind;
+            self
+        }
+
+        fn matches(&mut self, op: FsOp, path: &Path) -> bool {
+            if op != self.op || !self.pattern.matches_path(path) {
+                return false;
+            }
+            self.seen += 1;
+            self.nth.is_none_or(|nth| nth == self.seen)
+        }
+    }
+
+    /// The faults to inject into filesystem operations, see the [module](index.html) docs.
+    ///
+    /// # Examples
+    /// ```rust
+    /// # extern crate ergo_fs;
+    /// use ergo_fs::*;
+    /// use ergo_fs::faults::{ErgoFsHooks, Fault, FsOp};
+    ///
+    /// # fn try_main() -> ::std::io::Result<()> {
+    /// let tmp = PathTmp::create("faults")?;
+    /// let file = PathFile::create(tmp.join("notes.txt"))?;
+    /// file.write_str("old\n")?;
+    ///
+    /// {
+    ///     let hooks = ErgoFsHooks::new()
+    ///         .fault(Fault::new(FsOp::Rename, "*/notes.txt"))
+    ///         .install();
+    ///     let err = file.edit_lines(|_| LineEdit::Replace("new".into())).unwrap_err();
+    ///     assert!(err.to_string().contains("injected fault"));
+    ///     assert_eq!(1, hooks.injected());
+    /// }
+    ///
+    /// // the edit was atomic, and works again once the hooks are dropped
+    /// assert_eq!("old\n", file.read_string()?);
+    /// assert_eq!(1, PathDir::new(&tmp)?.list()?.count());
+    /// file.edit_lines(|_| LineEdit::Replace("new".into()))?;
+    /// assert_eq!("new\n", file.read_string()?);
+    /// # Ok(()) } fn main() { try_main().unwrap() }
+    /// ```
+    #[derive(Debug, Clone, Default)]
+    pub struct ErgoFsHooks {
+        faults: Vec<Fault>,
+    }
+
+    impl ErgoFsHooks {
+        /// Create hooks which don't inject anything.
+        pub fn new() -> ErgoFsHooks {
+            ErgoFsHooks::default()
+        }
+
+        /// Add a fault. An operation fails with the first of the faults which matches it.
+        pub fn fault(mut self, fault: Fault) -> ErgoFsHooks {
+            self.faults.push(fault);
+            self
+        }
+
+        /// Install the hooks until the returned guard is dropped.
+        ///
+        /// This blocks while the hooks of another thread are installed.
+        pub fn install(self) -> HooksGuard {
+            // a test which panicked with its hooks installed does not affect the next one
+            let serial = INSTALLED.lock().unwrap_or_else(PoisonError::into_inner);
+            *active() = Some(Active {
+                faults: self.faults,
+                injected: 0,
+            });
+            HooksGuard { _serial: serial }
+        }
+    }
+
+    /// Keeps [`ErgoFsHooks`](struct.ErgoFsHooks.html) installed until it is dropped.
+    #[derive(Debug)]
+    pub struct HooksGuard {
+        _serial: MutexGuard<'static, ()>,
+    }
+
+    impl HooksGuard {
+        /// The number of operations which failed because of the hooks so far.
+        pub fn injected(&self) -> usize {
+            active().as_ref().map_or(0, |a| a.injected)
+        }
+    }
+
+    impl Drop for HooksGuard {
+        fn drop(&mut self) {
+            *active() = None;
+        }
+    }
+
+    pub(super) fn check(op: FsOp, path: &Path) -> io::Result<()> {
+        let mut active = active();
+        let active = match *active {
+            Some(ref mut a) => a,
+            None => return Ok(()),
+        };
+        // every fault counts the operation, even if an earlier one fails it
+        let mut failed = None;
+        for fault in &mut active.faults {
+            if fault.matches(op, path) && failed.is_none() {
+                failed = Some(fault.kind);
+            }
+        }
+        match failed {
+            Some(kind) => {
+                active.injected += 1;
+                Err(io::Error::new(
+                    kind,
+                    format!("injected fault: {} {}", op, path.display()),
+                ))
+            }
+            None => Ok(()),
+        }
+    }
+
+    fn active() -> MutexGuard<'static, Option<Active>> {
+        ACTIVE.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
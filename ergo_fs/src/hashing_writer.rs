@@ -0,0 +1,103 @@
+/* Copyright (c) 2018 Garrett Berg, vitiral@gmail.com
+ *
+ * Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+ * http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+ * http://opensource.org/licenses/MIT>, at your option. This file may not be
+ * copied, modified, or distributed except according to those terms.
+ */
+//! Computing the digest of a file while writing it.
+
+use cas::Digest;
+use error::Result;
+use faults::{self, FsOp};
+use path_abs::{FileWrite, PathFile};
+use sha2::{Digest as Sha2Digest, Sha256};
+use std::fmt;
+use std::io::{self, Write};
+use std_prelude::*;
+
+/// A `FileWrite` which computes the SHA-256 [`Digest`] of everything written to it.
+///
+/// Use it when writing large files (i.e. archives or downloads) to record their digest without
+/// reading them again. [`finish`] flushes the file and returns it with its digest.
+///
+/// [`Digest`]: cas/struct.Digest.html
+/// [`finish`]: #method.finish
+///
+/// # Examples
+/// ```rust
+/// # extern crate ergo_fs;
+/// use ergo_fs::*;
+/// use ergo_fs::cas::Digest;
+///
+/// # fn try_main() -> ::std::io::Result<()> {
+/// let tmp = PathTmp::create("hashing")?;
+/// let mut writer = HashingWriter::create(tmp.join("download.bin"))?;
+/// writer.write_all(b"downloaded ")?;
+/// writer.write_all(b"data")?;
+///
+/// let (file, digest) = writer.finish()?;
+/// assert_eq!(Digest::of_bytes(b"downloaded data"), digest);
+/// assert_eq!("downloaded data", file.read_string()?);
+/// # Ok(()) } fn main() { try_main().unwrap() }
+/// ```
+pub struct HashingWriter {
+    file: FileWrite,
+    hasher: Sha256,
+    written: u64,
+}
+
+impl HashingWriter {
+    /// Wrap `file`, hashing everything written to it from now on.
+    pub fn new(file: FileWrite) -> HashingWriter {
+        HashingWriter {
+            file,
+            hasher: Sha256::new(),
+            written: 0,
+        }
+    }
+
+    /// Create (or truncate) the file at `path` and hash everything written to it.
+    pub fn create<P: AsRef<Path>>(path: P) -> Result<HashingWriter> {
+        Ok(HashingWriter::new(FileWrite::create(path)?))
+    }
+
+    /// The path of the file.
+    pub fn path(&self) -> &PathFile {
+        self.file.path()
+    }
+
+    /// The number of bytes written (and hashed) so far.
+    pub fn written(&self) -> u64 {
+        self.written
+    }
+
+    /// Flush the file and return it with the digest of everything written.
+    pub fn finish(mut self) -> Result<(PathFile, Digest)> {
+        self.file.flush()?;
+        Ok((self.file.path().clone(), Digest::from_hasher(self.hasher)))
+    }
+}
+
+impl Write for HashingWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        faults::check(FsOp::Write, self.file.path())?;
+        let n = self.file.write(buf)?;
+        self.hasher.update(&buf[..n]);
+        self.written += n as u64;
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Write::flush(&mut self.file)
+    }
+}
+
+impl fmt::Debug for HashingWriter {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("HashingWriter")
+            .field("path", self.file.path())
+            .field("written", &self.written)
+            .finish()
+    }
+}
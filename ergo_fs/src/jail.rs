@@ -0,0 +1,213 @@
+/* Copyright (c) 2018 Garrett Berg, vitiral@gmail.com
+ *
+ * Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+ * http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+ * http://opensource.org/licenses/MIT>, at your option. This file may not be
+ * copied, modified, or distributed except according to those terms.
+ */
+//! Paths which cannot leave a root directory.
+
+use error::{Error, Result};
+use path_abs::{FileRead, PathArc, PathDir};
+use std::error;
+use std::ffi::OsString;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::Component;
+use std_prelude::*;
+
+/// The maximum number of symlinks followed when resolving a path, like `ELOOP` on Linux.
+const MAX_LINKS: usize = 40;
+
+/// A directory which paths are resolved inside of, like a `chroot`.
+///
+/// Use it wherever a path comes from someone you don't trust, i.e. the names in an uploaded
+/// archive or the path of a request to a file server. [`join`] resolves `..` and every symlink
+/// which exists and fails with a [`JailEscape`] if the path would end up outside of the root at
+/// any point, so there is no way to reach `/etc/passwd` with `../../etc/passwd` or a planted
+/// symlink.
+///
+/// Absolute paths are taken relative to the root, so `/index.html` is the `index.html` in the
+/// root. Symlinks are followed the way the OS would follow them: an absolute target is only
+/// allowed if it is inside of the root.
+///
+/// The path is checked when it is joined: a symlink which is swapped in between [`join`] and
+/// using the path is not caught. Don't give untrusted processes write access to the root.
+///
+/// [`join`]: struct.PathJail.html#method.join
+/// [`JailEscape`]: struct.JailEscape.html
+///
+/// # Examples
+/// ```rust
+/// # extern crate ergo_fs;
+/// use ergo_fs::*;
+///
+/// # fn try_main() -> ::std::io::Result<()> {
+/// let tmp = PathTmp::create("jail")?;
+/// let root = PathDir::create(tmp.join("public"))?;
+/// PathFile::create(root.join("index.html"))?.write_str("<h1>hi</h1>")?;
+/// let jail = PathJail::new(root)?;
+///
+/// assert_eq!("<h1>hi</h1>", jail.open("docs/../index.html")?.read_string()?);
+/// assert_eq!(jail.join("index.html")?, jail.join("/index.html")?);
+///
+/// let err = jail.join("../secret.txt").unwrap_err();
+/// assert!(err.jail_escape().is_some());
+///
+/// # #[cfg(unix)] {
+/// ::std::os::unix::fs::symlink("/etc", jail.root().join("etc"))?;
+/// let err = jail.open("etc/passwd").unwrap_err();
+/// assert_eq!(Path::new("etc/passwd"), err.jail_escape().unwrap().path());
+/// # }
+/// # Ok(()) } fn main() { try_main().unwrap() }
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct PathJail {
+    root: PathDir,
+}
+
+/// The cause of an [`Error`] when a path joined onto a [`PathJail`] would leave its root.
+///
+/// The `kind()` of the error is `PermissionDenied`, use [`Error::jail_escape`] to tell it apart
+/// from a permission error of the filesystem.
+///
+/// [`Error`]: struct.Error.html
+/// [`PathJail`]: struct.PathJail.html
+/// [`Error::jail_escape`]: struct.Error.html#method.jail_escape
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct JailEscape {
+    root: PathArc,
+    path: PathArc,
+}
+
+enum Step {
+    Name(OsString),
+    Parent,
+}
+
+impl PathJail {
+    /// Create a jail for the directory `root`. The root is canonicalized, so it may be a
+    /// symlink itself.
+    pub fn new(root: PathDir) -> Result<PathJail> {
+        Ok(PathJail {
+            root: root.canonicalize()?,
+        })
+    }
+
+    /// The (canonical) root directory of the jail.
+    pub fn root(&self) -> &PathDir {
+        &self.root
+    }
+
+    /// Resolve `path` inside of the root, failing with a [`JailEscape`] if it would leave it.
+    ///
+    /// Every component which exists is resolved, so the returned path contains no symlinks or
+    /// `..` up to the first component which does not exist yet. The path does not have to exist.
+    ///
+    /// [`JailEscape`]: struct.JailEscape.html
+    pub fn join<P: AsRef<Path>>(&self, path: P) -> Result<PathArc> {
+        let path = path.as_ref();
+        self.resolve(path)
+            .map(PathArc::new)
+            .map_err(|err| Error::new(err, "resolving in jail", PathArc::new(path)))
+    }
+
+    /// Open the file at `path` inside of the root for reading, see [`join`](#method.join).
+    pub fn open<P: AsRef<Path>>(&self, path: P) -> Result<FileRead> {
+        Ok(FileRead::read(self.join(path)?)?)
+    }
+
+    pub(crate) fn resolve(&self, path: &Path) -> io::Result<PathBuf> {
+        let root: &Path = self.root.as_ref();
+        let escape = || -> io::Error {
+            JailEscape {
+                root: PathArc::new(root),
+                path: PathArc::new(path),
+            }
+            .into()
+        };
+        // the steps left to take, in reverse
+        let mut steps = Vec::new();
+        push_steps(&mut steps, path);
+        let mut out = root.to_path_buf();
+        let mut links = 0;
+        while let Some(step) = steps.pop() {
+            let name = match step {
+                Step::Parent if out == root => return Err(escape()),
+                Step::Parent => {
+                    out.pop();
+                    continue;
+                }
+                Step::Name(name) => name,
+            };
+            out.push(name);
+            match fs::symlink_metadata(&out) {
+                Ok(ref meta) if meta.file_type().is_symlink() => {
+                    links += 1;
+                    if links > MAX_LINKS {
+                        return Err(io::Error::other("too many levels of symbolic links"));
+                    }
+                    let target = fs::read_link(&out)?;
+                    out.pop();
+                    if target.is_absolute() {
+                        let inside = target.strip_prefix(root).map_err(|_| escape())?;
+                        out = root.to_path_buf();
+                        push_steps(&mut steps, inside);
+                    } else {
+                        push_steps(&mut steps, &target);
+                    }
+                }
+                Ok(_) => {}
+                Err(ref err) if err.kind() == io::ErrorKind::NotFound => {}
+                Err(err) => return Err(err),
+            }
+        }
+        Ok(out)
+    }
+}
+
+/// Push the components of `path` onto `steps` so that the first is popped first. The root and
+/// prefix of an absolute path are skipped.
+fn push_steps(steps: &mut Vec<Step>, path: &Path) {
+    let start = steps.len();
+    for comp in path.components() {
+        match comp {
+            Component::Normal(name) => steps.push(Step::Name(name.to_os_string())),
+            Component::ParentDir => steps.push(Step::Parent),
+            Component::CurDir | Component::RootDir | Component::Prefix(_) => {}
+        }
+    }
+    steps[start..].reverse();
+}
+
+impl JailEscape {
+    /// The root of the jail.
+    pub fn root(&self) -> &Path {
+        self.root.as_ref()
+    }
+
+    /// The path which would have escaped the root, as it was given.
+    pub fn path(&self) -> &Path {
+        self.path.as_ref()
+    }
+}
+
+impl fmt::Display for JailEscape {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{} resolves to outside of the jail {}",
+            self.path.display(),
+            self.root.display()
+        )
+    }
+}
+
+impl error::Error for JailEscape {}
+
+impl From<JailEscape> for io::Error {
+    fn from(err: JailEscape) -> io::Error {
+        io::Error::new(io::ErrorKind::PermissionDenied, err)
+    }
+}
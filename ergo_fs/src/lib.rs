@@ -18,6 +18,8 @@
 //!
 //! The crates it wraps/rexports are:
 //!
+//! - [`dirs`](https://github.com/soc/dirs-rs): Platform-specific standard locations of
+//!   directories.
 //! - [`glob`](https://github.com/rust-lang-nursery/glob): Support for matching file paths against
 //!   Unix shell style patterns.
 //! - [`path_abs`](https://github.com/vitiral/path_abs): Ergonomic paths and files in rust.
@@ -25,8 +27,6 @@
 //!   variables in strings.
 //! - [`tar-rs`](https://github.com/alexcrichton/tar-rs): A library for reading and writing TAR
 //!   archives.
-//! - [`tempdir`](https://github.com/rust-lang-nursery/tempdir): Temporary directories
-//!   of files.
 //! - [`walkdir`](https://github.com/BurntSushi/walkdir): Provides an efficient and cross platform
 //!   implementation of recursive directory traversal.
 //!
@@ -62,8 +62,8 @@
 //! - [`PathType`](struct.PathType.html): an enum containing either a PathFile or a PathDir.
 //!   Returned by [`PathDir::list`][dir_list]
 //! - [`PathTmp`](struct.PathTmp.html): a `PathDir` that is deleted when it goes out of scope.
-//!   This is a wrapper around the crate `tempdir::TempDir` with methods that mimick the `Path`
-//!   types in this crate.
+//!   Its name is random (see [`PathTmp::builder`](struct.PathTmp.html#method.builder)) and it
+//!   has the same methods as the other `Path` types in this crate.
 //! - [`FileRead`](struct.FileRead.html): a read-only file handle with `path()` attached and
 //!   improved error messages. Contains only the methods and trait implementations which are
 //!   allowed by a read-only file.
@@ -75,6 +75,84 @@
 //!   writeable files.
 //! - [`WalkDir`](struct.WalkDir.html): used for recursively walking directories _quickly_.
 //!   See the **Walkdir** section below.
+//! - [`WalkEntry`](struct.WalkEntry.html): a `PathType` from a walk which keeps the depth and
+//!   metadata that the walk already read.
+//! - [`WalkOptions`](struct.WalkOptions.html): options for walking (depth, symlinks, mount
+//!   points, include/exclude globs and hidden files) which can be shared between walks and
+//!   copies. Attach an [`FsObserver`](trait.FsObserver.html) to a walk with
+//!   `WalkEntries::observe` for logging, metrics or progress. With the `parallel` feature,
+//!   [`ParWalk`](struct.ParWalk.html) walks on rayon's thread pool.
+//! - [`CopyOptions`](struct.CopyOptions.html): options for copying files with
+//!   [`PathFileExt::copy_with`](trait.PathFileExt.html#method.copy_with), i.e. preserving the
+//!   holes of sparse files, cloning them copy-on-write (reflinks), the buffer size, when
+//!   to `fsync` ([`FsyncPolicy`](enum.FsyncPolicy.html)) and how to verify the copy
+//!   ([`Verify`](enum.Verify.html)).
+//! - [`CacheDir`](struct.CacheDir.html): a key-value cache of files (i.e. downloads or build
+//!   outputs) with expiry and size limits, which is safe to share between processes.
+//! - [`PathTemplate`](struct.PathTemplate.html): a template for output paths with `strftime`
+//!   specifiers, variables and a `{seq}` counter, i.e. `logs/%Y-%m-%d/run-{seq}.log`.
+//! - [`RotatingFile`](struct.RotatingFile.html): a file (i.e. a log) which is rotated atomically
+//!   by size or daily ([`RotatePolicy`](enum.RotatePolicy.html)), keeping a maximum number of
+//!   the rotated files and optionally compressing them with the `gzip` feature.
+//! - [`HashingWriter`](struct.HashingWriter.html): a `FileWrite` which computes the SHA-256
+//!   digest of everything written to it, so large files don't have to be read again.
+//! - [`Sniff`](struct.Sniff.html): whether a file is binary, its guessed MIME type and its
+//!   [`TextEncoding`](enum.TextEncoding.html) byte order mark, from `PathFileExt::sniff`.
+//! - [`RetryPolicy`](struct.RetryPolicy.html): an opt-in policy for retrying operations which
+//!   fail with transient errors (i.e. on network mounts) using exponential backoff.
+//! - [`Error`](struct.Error.html): the error type of this crate. Exposes the `kind()`,
+//!   `action()`, `path()` and `second_path()` of the error programmatically so that you can
+//!   branch on them instead of parsing messages.
+//! - [`SymlinkLoop`](struct.SymlinkLoop.html): the cause of an `Error` when a walk which follows
+//!   symlinks finds a link to one of its own ancestors.
+//! - [`VerificationFailed`](struct.VerificationFailed.html): the cause of an `Error` when a copy
+//!   which is verified does not match its original.
+//! - [`DecodeError`](struct.DecodeError.html): the cause of an `Error` when reading text in a
+//!   [`TextEncoding`](enum.TextEncoding.html) which it is not valid in.
+//! - [`PathJail`](struct.PathJail.html): a root directory which untrusted paths are joined onto
+//!   without being able to leave it through `..` or symlinks, failing with a
+//!   [`JailEscape`](struct.JailEscape.html) instead.
+//! - [`check_access`](fn.check_access.html): check the [`Access`](enum.Access.html) of the
+//!   current user to a path before starting an operation which would fail midway.
+//!
+//! The [`ErgoMetadata`](trait.ErgoMetadata.html) extension trait adds portable methods to
+//! `std::fs::Metadata`, i.e. the `device_id()` of the filesystem containing a file.
+//!
+//! Newline delimited JSON files (i.e. logs of events) are read with
+//! [`PathFileExt::read_ndjson`](trait.PathFileExt.html#method.read_ndjson) and written with
+//! [`FileWriteExt::append_json`](trait.FileWriteExt.html#method.append_json).
+//!
+//! Parts of a file (i.e. to hash or upload it) are read with
+//! [`FileReadExt::read_range`](trait.FileReadExt.html#method.read_range) and
+//! [`FileReadExt::chunks`](trait.FileReadExt.html#method.chunks), without seeking manually.
+//!
+//! The end of a file (i.e. a log) is read with
+//! [`PathFileExt::tail`](trait.PathFileExt.html#method.tail) and the lines appended to it are
+//! received with [`PathFileExt::follow`](trait.PathFileExt.html#method.follow), which keeps
+//! following it when it is rotated.
+//!
+//! Files are copied aside before editing them in place with
+//! [`PathFileExt::backup`](trait.PathFileExt.html#method.backup), named according to a
+//! [`BackupStyle`](enum.BackupStyle.html), and edited line by line (atomically) with
+//! [`PathFileExt::edit_lines`](trait.PathFileExt.html#method.edit_lines). Blocks managed by a
+//! tool (i.e. in dotfiles or `/etc/hosts`) are kept up to date with
+//! [`PathFileExt::ensure_block`](trait.PathFileExt.html#method.ensure_block).
+//!
+//! Directory trees for tests are created in one expression with the [`tree!`](macro.tree.html)
+//! macro (or [`PathDirExt::build`](trait.PathDirExt.html#method.build)), which returns the
+//! created paths as a [`Tree`](struct.Tree.html).
+//!
+//! The [`PathInfo`](trait.PathInfo.html) extension trait is implemented for all of these types
+//! (and `std`'s). Use `display_clean()` to print paths to users without the windows verbatim
+//! (`\\?\`) prefix, `same_file()` to check whether two paths refer to the same file and
+//! `set_readonly()` to clear the read-only attribute which makes copies fail on windows.
+//! `full_extension()` and `with_full_extension()` handle extensions with several parts, i.e.
+//! `tar.gz`. `eq_normalized()` compares paths after unicode normalization, so names written
+//! on macOS (decomposed, see [`normalize_nfd`](fn.normalize_nfd.html)) equal the same names
+//! from Linux or windows (composed, see [`normalize_nfc`](fn.normalize_nfc.html)).
+//!
+//! To create files named after user input (i.e. titles or URLs), first make the name a valid
+//! component with [`sanitize_filename`](fn.sanitize_filename.html).
 //!
 //! In addition, it exports the following from [`std_prelude`](../std_prelude/index.html)
 //!
@@ -89,10 +167,43 @@
 //!   environment variables with the user's home directory + env variables. Also see the
 //!   exported [`shellexpand`](shellexpand/index.html) crate itself. Consider using with
 //!   `glob` (see below).
+//! - [`home_dir`](fn.home_dir.html): the current user's home directory, resolved correctly on
+//!   every platform.
 //! - [`glob`](fn.glob.html): a lightweight wrapper around [`glob::glob`](../glob/fn.glob.html) that
 //!   returns `PathType` objects.
 //! - [`glob_with`](fn.glob_with.html): a lightweight wrapper around
-//!   [`glob::glob_with`](../glob/fn.glob_with.html) that returns `PathType` objects.
+//!   [`glob::glob_with`](../glob/fn.glob_with.html) that returns `PathType` objects. Use
+//!   [`par_collect`](struct.GlobPathTypes.html#method.par_collect) for patterns matching many
+//!   files.
+//! - [`sync_file`](fn.sync_file.html) and [`sync_dir`](fn.sync_dir.html): flush a file or the
+//!   entries of a directory to disk.
+//! - [`find_duplicates`](fn.find_duplicates.html): find the files with the same contents in
+//!   directories, walking and hashing in parallel.
+//! - [`readahead`](fn.readahead.html): hint to the OS that a file will be read soon. Only
+//!   does something on Linux with the `readahead` feature enabled.
+//! - [`watch_glob`](fn.watch_glob.html): watch the files matching a glob pattern for changes,
+//!   scanning only the directories the pattern can match. See
+//!   [`GlobWatcher`](struct.GlobWatcher.html).
+//!
+//! # Modules
+//!
+//! - [`cas`](cas/index.html): a content-addressed store of files, which can be materialized
+//!   as hard links.
+//! - [`cmp`](cmp/index.html): compare the contents of files and directory trees, i.e. to
+//!   check the output of a copy in tests.
+//! - [`dirs`](dirs/index.html): the platform's config, cache, data and runtime directories for
+//!   your application (XDG on Linux, Known Folders on macOS/Windows).
+//! - [`faults`](faults/index.html): with the `fault_injection` feature, make this crate's
+//!   filesystem operations fail for chosen paths in tests.
+//! - [`sort`](sort/index.html): sorting paths in natural order (`file2` before `file10`), which
+//!   is the order of every listing in this crate which is deterministic.
+//! - [`snapshot`](snapshot/index.html): render directory trees and compare them against stored
+//!   snapshot files in tests with [`assert_tree_matches!`](macro.assert_tree_matches.html).
+//! - [`testing`](testing/index.html): compare generated files against stored golden files in
+//!   tests with [`assert_matches_golden`](testing/fn.assert_matches_golden.html).
+//! - [`vfs`](vfs/index.html): a `Vfs` trait for reading, writing, listing and walking files
+//!   with the real filesystem or an in-memory `MemFs`, so code built on it can be unit tested
+//!   without touching the disk.
 //!
 //! # Details
 //! Bellow are some additional details about imported types.
@@ -102,7 +213,8 @@
 //! Use `PathDir::walk` to walk a directory. This returns the [`Walkdir`](struct.WalkDir.html)
 //! iterator, which is a direct export from the [`walkdir`](../walkdir/index.html) crate.  The
 //! crate already has excellent error messages, and although it returns the regular
-//! `std::path::PathBuf` type, you can convert to a `PathType` using `PathType::from_entry`.
+//! `std::path::PathBuf` type, you can convert to a `PathType` using `PathType::from_entry`, or
+//! to a [`WalkEntry`](struct.WalkEntry.html) to also keep its depth and metadata.
 //!
 //! > TODO: although the WalkDir error can be auto-converted to std::io::Error, it
 //! > does not preserve the pretty output. See
@@ -150,38 +262,136 @@
 //! # Ok(()) } fn main() { try_main().unwrap() }
 //! ```
 
+extern crate chrono;
+extern crate crossbeam_channel;
+extern crate ctrlc;
+pub extern crate dirs as dirs_crate;
+extern crate ergo_sys;
+#[cfg(feature = "gzip")]
+extern crate flate2;
 pub extern crate glob as glob_crate;
+#[cfg(unix)]
+extern crate libc;
 pub extern crate path_abs;
+#[cfg(feature = "parallel")]
+pub extern crate rayon;
+extern crate serde;
+extern crate serde_json;
+extern crate sha2;
 pub extern crate shellexpand;
 pub extern crate std_prelude;
 pub extern crate tar;
-pub extern crate tempdir;
 pub extern crate walkdir;
 
 // -------------------------------
 // External Crate Exports
 
+use crossbeam_channel::Receiver;
+#[cfg(unix)]
+use ergo_sys::unix::User;
+pub use path_abs::{FileEdit, FileRead, FileWrite, PathAbs, PathArc, PathDir, PathFile, PathType};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
 use std::borrow::Cow; // FIXME: remove this
 use std_prelude::*;
-pub use path_abs::{FileEdit, FileRead, FileWrite, PathAbs, PathArc, PathDir, PathFile, PathType};
+pub use std_prelude::{IoWrite, Path, PathBuf, Read};
 pub use walkdir::{Error as WalkError, WalkDir};
-pub use std_prelude::{Read, IoWrite, Path, PathBuf};
 
 // -------------------------------
 // Local Modules and Exports
 
-mod tmp;
+mod access;
+mod backup;
+mod cache_dir;
+pub mod cas;
+mod chunks;
+pub mod cmp;
+mod copy;
+mod decode;
+mod dedup;
+mod deterministic;
+#[cfg(feature = "diff")]
+mod diff;
+pub mod dirs;
+mod error;
+pub mod faults;
 mod glob_wrapper;
+mod hashing_writer;
+mod jail;
+mod metadata;
+mod ndjson;
+mod normalize;
+mod normalize_tables;
+mod observer;
+#[cfg(unix)]
+mod owner;
+mod path_info;
+mod path_template;
+mod read_sorted;
+mod readahead;
+mod replace;
+mod retry;
+mod rotating_file;
+mod sanitize;
+pub mod snapshot;
+mod sniff;
+pub mod sort;
+mod tail;
+mod tar_append;
+mod tar_list;
+pub mod testing;
+mod tmp;
+mod tree;
+mod unique;
+pub mod vfs;
+mod walk;
+mod watch;
 
+pub use access::{check_access, Access};
+pub use backup::BackupStyle;
+pub use cache_dir::CacheDir;
+pub use chunks::Chunks;
+pub use copy::{
+    sync_dir, sync_file, CopyOptions, Fingerprint, FsyncPolicy, VerificationFailed, Verify,
+};
+pub use decode::DecodeError;
+pub use dedup::{find_duplicates, DuplicateGroup, DuplicateOptions};
+pub use deterministic::Deterministic;
+pub use dirs::home_dir;
+pub use error::{Error, ErrorPolicy, SymlinkLoop};
 pub use glob_wrapper::{
     // functions
     glob, glob_with,
     // renamed types
     GlobOptions, GlobPatternError,
+    // types
+    GlobPattern,
     // new iterators
     GlobPathDirs, GlobPathFiles, GlobPathTypes,
 };
-pub use tmp::PathTmp;
+pub use hashing_writer::HashingWriter;
+pub use jail::{JailEscape, PathJail};
+pub use metadata::ErgoMetadata;
+pub use ndjson::NdjsonLines;
+pub use normalize::{normalize_nfc, normalize_nfd};
+pub use observer::{FsEvent, FsObserver};
+pub use path_info::PathInfo;
+pub use path_template::PathTemplate;
+pub use read_sorted::ReadSorted;
+pub use readahead::{readahead, READAHEAD_LEN};
+pub use replace::LineEdit;
+pub use retry::{RetryPolicy, Transient};
+pub use rotating_file::{RotatePolicy, RotatingFile};
+pub use sanitize::{sanitize_filename, Platform};
+pub use sniff::{Sniff, TextEncoding, SNIFF_LEN};
+pub use tar_append::tar_append;
+pub use tar_list::{TarEntry, TarEntryType, TarList};
+pub use tmp::{PathTmp, PathTmpBuilder};
+pub use tree::{Tree, TreeEntry};
+#[cfg(feature = "parallel")]
+pub use walk::ParWalk;
+pub use walk::{WalkEntries, WalkEntry, WalkOptions};
+pub use watch::{watch_glob, GlobWatcher, WatchEvent};
 
 /// Extension method on the `Path` type.
 pub trait PathDirExt
@@ -190,6 +400,9 @@ where
 {
     /// Walk the `PathDir`, returning the `WalkDir` builder.
     ///
+    /// Use `.same_file_system(true)` to avoid descending into other filesystems, i.e. `/proc` or
+    /// network mounts.
+    ///
     /// # Examples
     /// ```rust
     /// # extern crate ergo_fs;
@@ -208,15 +421,297 @@ where
     fn walk(&self) -> walkdir::WalkDir {
         walkdir::WalkDir::new(&self)
     }
+
+    /// Walk the `PathDir` with the [`WalkOptions`](struct.WalkOptions.html).
+    ///
+    /// This is the same as `options.walk(self)`.
+    fn walk_with(&self, options: &WalkOptions) -> WalkEntries {
+        options.walk(self.as_ref())
+    }
+
+    /// Walk the `PathDir` on rayon's thread pool, for CPU heavy work on every entry such as
+    /// hashing or parsing files.
+    ///
+    /// The walk is shared by the threads and advanced as they finish their entries, so there is
+    /// no need to wire up channels. Needs the `parallel` feature.
+    ///
+    /// # Examples
+    /// ```rust
+    /// # extern crate ergo_fs;
+    /// use ergo_fs::*;
+    /// use ergo_fs::rayon::prelude::*;
+    ///
+    /// # fn try_main() -> ::std::io::Result<()> {
+    /// let tmp = PathTmp::create("par_walk")?;
+    /// PathFile::create(tmp.join("a.txt"))?.write_str("hello")?;
+    /// PathDir::create(tmp.join("sub"))?;
+    /// PathFile::create(tmp.join("sub").join("b.txt"))?.write_str("world!")?;
+    ///
+    /// let bytes: u64 = PathDir::new(&tmp)?
+    ///     .par_walk()
+    ///     .filter_map(|entry| entry.ok())
+    ///     .filter_map(|entry| entry.as_file().map(|f| f.read_string().unwrap().len() as u64))
+    ///     .sum();
+    /// assert_eq!(11, bytes);
+    /// # Ok(()) } fn main() { try_main().unwrap() }
+    /// ```
+    #[cfg(feature = "parallel")]
+    fn par_walk(&self) -> ParWalk {
+        ParWalk::from(self.walk_with(&WalkOptions::new()))
+    }
+
+    /// Walk the `PathDir` contents-first (post-order): the contents of every directory are
+    /// yielded before the directory itself, and the `PathDir` is yielded last.
+    ///
+    /// This is the order needed to remove or modify a tree from the bottom up, see
+    /// [`deep_remove`](#method.deep_remove). Symlinks are not followed.
+    ///
+    /// # Examples
+    /// ```rust
+    /// # extern crate ergo_fs;
+    /// use ergo_fs::*;
+    ///
+    /// # fn try_main() -> ::std::io::Result<()> {
+    /// let tmp = PathTmp::create("walk_post_order")?;
+    /// let dir = PathDir::create(tmp.join("dir"))?;
+    /// PathFile::create(dir.join("a.txt"))?;
+    ///
+    /// let depths = dir
+    ///     .walk_post_order()
+    ///     .map(|entry| entry.map(|e| e.depth()))
+    ///     .collect::<Result<Vec<_>, _>>()?;
+    /// assert_eq!(vec![1, 0], depths);
+    /// # Ok(()) } fn main() { try_main().unwrap() }
+    /// ```
+    fn walk_post_order(&self) -> WalkEntries {
+        WalkEntries::from(walkdir::WalkDir::new(self.as_ref()).contents_first(true))
+    }
+
+    /// Remove the `PathDir` and all of its contents.
+    ///
+    /// Unlike `PathDir::remove_all` the error includes the path of the exact entry which could
+    /// not be removed. Symlinks are removed, not followed.
+    ///
+    /// # Examples
+    /// ```rust
+    /// # extern crate ergo_fs;
+    /// use ergo_fs::*;
+    ///
+    /// # fn try_main() -> ::std::io::Result<()> {
+    /// let tmp = PathTmp::create("deep_remove")?;
+    /// let dir = PathDir::create(tmp.join("dir"))?;
+    /// PathDir::create(dir.join("sub"))?;
+    /// PathFile::create(dir.join("sub").join("a.txt"))?;
+    ///
+    /// dir.deep_remove()?;
+    /// assert!(!tmp.join("dir").exists());
+    /// # Ok(()) } fn main() { try_main().unwrap() }
+    /// ```
+    fn deep_remove(&self) -> Result<(), Error> {
+        self.deep_remove_with(ErrorPolicy::FailFast)
+    }
+
+    /// Remove the `PathDir` and all of its contents, with the [`ErrorPolicy`] `policy`.
+    ///
+    /// With `ErrorPolicy::Continue` as much as possible is removed and the first error is
+    /// returned at the end (the parents of an entry which could not be removed are not empty, so
+    /// only the first error is interesting). `ErrorPolicy::FailFast` is the same as
+    /// [`deep_remove`](#method.deep_remove).
+    ///
+    /// [`ErrorPolicy`]: enum.ErrorPolicy.html
+    fn deep_remove_with(&self, policy: ErrorPolicy) -> Result<(), Error> {
+        let mut first_err = None;
+        for entry in self.walk_post_order() {
+            let removed = entry.and_then(|entry| {
+                let path = entry.as_path();
+                let dir = entry.file_type().is_dir();
+                let removed = if entry.path_is_symlink() && !(cfg!(windows) && dir) {
+                    ::std::fs::remove_file(path)
+                } else if dir {
+                    ::std::fs::remove_dir(path)
+                } else {
+                    ::std::fs::remove_file(path)
+                };
+                removed.map_err(|e| Error::new(e, "removing", PathArc::new(path)))
+            });
+            match (removed, policy) {
+                (Ok(()), _) => {}
+                (Err(err), ErrorPolicy::FailFast) => return Err(err),
+                (Err(err), ErrorPolicy::Continue) => {
+                    first_err.get_or_insert(err);
+                }
+            }
+        }
+        match first_err {
+            Some(err) => Err(err),
+            None => Ok(()),
+        }
+    }
+
+    /// Atomically create a new file named `{prefix}{random}{suffix}` in the directory, returning
+    /// it along with an open handle for writing.
+    ///
+    /// The file is created exclusively (`O_EXCL`), so it never clobbers an existing file even
+    /// when other processes are creating files in the same directory. Unlike [`PathTmp`] the
+    /// file is not removed when it is dropped.
+    ///
+    /// [`PathTmp`]: struct.PathTmp.html
+    ///
+    /// # Examples
+    /// ```rust
+    /// # extern crate ergo_fs;
+    /// use ergo_fs::*;
+    ///
+    /// # fn try_main() -> ::std::io::Result<()> {
+    /// let tmp = PathTmp::create("unique")?;
+    /// let dir = PathDir::new(&tmp)?;
+    /// let (first, mut write) = dir.create_unique("output-", ".txt")?;
+    /// write.write_str("first")?;
+    /// let (second, _) = dir.create_unique("output-", ".txt")?;
+    ///
+    /// assert_ne!(first, second);
+    /// let name = first.file_name().unwrap().to_str().unwrap();
+    /// assert!(name.starts_with("output-") && name.ends_with(".txt"));
+    /// assert_eq!("first", first.read_string()?);
+    /// # Ok(()) } fn main() { try_main().unwrap() }
+    /// ```
+    fn create_unique(&self, prefix: &str, suffix: &str) -> Result<(PathFile, FileWrite), Error> {
+        unique::create_unique(self.as_ref(), prefix, suffix)
+    }
+
+    /// List the entries of the directory sorted by name in natural order, so `file2` comes
+    /// before `file10`.
+    ///
+    /// Unlike `PathDir::list` (whose order depends on the filesystem) the order is always the
+    /// same, for output which can be diffed and for snapshot tests. Letters are compared
+    /// ignoring case, names which only differ in case are ordered by their bytes.
+    ///
+    /// # Examples
+    /// ```rust
+    /// # extern crate ergo_fs;
+    /// use ergo_fs::*;
+    ///
+    /// # fn try_main() -> ::std::io::Result<()> {
+    /// let tmp = PathTmp::create("read_sorted")?;
+    /// let dir = PathDir::new(&tmp)?;
+    /// for name in &["file10.txt", "File1.txt", "file2.txt"] {
+    ///     PathFile::create(dir.join(name))?;
+    /// }
+    /// PathDir::create(dir.join("file3"))?;
+    ///
+    /// let names = dir
+    ///     .read_sorted()?
+    ///     .map(|entry| entry.map(|e| e.file_name().unwrap().to_string_lossy().into_owned()))
+    ///     .collect::<Result<Vec<_>, _>>()?;
+    /// assert_eq!(vec!["File1.txt", "file2.txt", "file3", "file10.txt"], names);
+    /// # Ok(()) } fn main() { try_main().unwrap() }
+    /// ```
+    fn read_sorted(&self) -> Result<ReadSorted, Error> {
+        ReadSorted::new(self.as_ref())
+    }
+
+    /// Create the `entries` in the directory, returning the created paths. Usually written with
+    /// the [`tree!`](macro.tree.html) macro.
+    ///
+    /// # Examples
+    /// ```rust
+    /// # extern crate ergo_fs;
+    /// use ergo_fs::*;
+    ///
+    /// # fn try_main() -> ::std::io::Result<()> {
+    /// let tmp = PathTmp::create("build")?;
+    /// let dir = PathDir::new(&tmp)?;
+    /// let tree = dir.build(&[
+    ///     ("a/b.txt".into(), TreeEntry::file("b")),
+    ///     ("c".into(), TreeEntry::Dir(vec![("d.txt".into(), TreeEntry::file("d"))])),
+    /// ])?;
+    ///
+    /// assert_eq!("b", tree.file("a/b.txt").read_string()?);
+    /// assert!(tree.dir("c").join("d.txt").exists());
+    /// assert_eq!(4, tree.iter().count());
+    /// # Ok(()) } fn main() { try_main().unwrap() }
+    /// ```
+    fn build(&self, entries: &[(PathBuf, TreeEntry)]) -> Result<Tree, Error> {
+        tree::build(&PathDir::new(self)?, entries)
+    }
+
+    /// Check that the current (effective) user has `access` to the directory, failing with
+    /// `PermissionDenied` if it does not.
+    ///
+    /// Use it to validate before starting an operation which would otherwise fail midway. On
+    /// unix this asks the OS with `faccessat`, elsewhere only `Read` is checked exactly and
+    /// `Write` fails if the directory is read-only.
+    ///
+    /// # Examples
+    /// ```rust
+    /// # extern crate ergo_fs;
+    /// use ergo_fs::*;
+    ///
+    /// # fn try_main() -> ::std::io::Result<()> {
+    /// let tmp = PathTmp::create("check_access")?;
+    /// let dir = PathDir::new(&tmp)?;
+    /// dir.check_access(Access::Read)?;
+    /// dir.check_access(Access::Write)?;
+    ///
+    /// // a missing directory is an error of its own kind
+    /// let missing = PathDir::create(tmp.join("missing"))?;
+    /// missing.clone().remove()?;
+    /// let err = missing.check_access(Access::Read).unwrap_err();
+    /// assert_eq!(::std::io::ErrorKind::NotFound, err.kind());
+    /// # Ok(()) } fn main() { try_main().unwrap() }
+    /// ```
+    fn check_access(&self, access: Access) -> Result<(), Error> {
+        access::check_access(self, access)
+    }
+
+    /// Create the directory at `path` owned by `user` and their primary group, with the
+    /// permission bits `mode`. Only on Unix.
+    ///
+    /// Unlike `create` it is an error if anything already exists at `path`, so that the owner
+    /// and permissions are never changed through a symlink.
+    ///
+    /// Changing the owner to another user needs root, i.e. in an installer. Get the user with
+    /// `ergo_sys::unix::User::from_name`.
+    ///
+    /// # Examples
+    /// ```rust
+    /// # extern crate ergo_fs;
+    /// # extern crate ergo_sys;
+    /// use std::os::unix::fs::MetadataExt;
+    /// use ergo_fs::*;
+    /// use ergo_sys::unix::effective_user;
+    ///
+    /// # fn try_main() -> ::std::io::Result<()> {
+    /// let tmp = PathTmp::create("create_owned")?;
+    /// let user = effective_user()?;
+    /// let dir = PathDir::create_owned(tmp.join("data"), &user, 0o750)?;
+    ///
+    /// let meta = dir.metadata()?;
+    /// assert_eq!(0o750, meta.mode() & 0o7777);
+    /// assert_eq!(user.uid(), meta.uid());
+    ///
+    /// let err = PathDir::create_owned(&dir, &user, 0o750).unwrap_err();
+    /// assert_eq!(::std::io::ErrorKind::AlreadyExists, err.kind());
+    /// # Ok(()) } fn main() { try_main().unwrap() }
+    /// ```
+    #[cfg(unix)]
+    fn create_owned<P: AsRef<Path>>(path: P, user: &User, mode: u32) -> Result<PathDir, Error>
+    where
+        Self: Sized,
+    {
+        owner::create_owned(path.as_ref(), user, mode)
+    }
 }
 
 /// Extended methods for `PathType`
 pub trait PathTypeExt {
     /// Create a `PathType` from a `walkdir::DirEntry` using fewer syscalls.
     ///
-    /// See [`PathDir::walk`]
+    /// See [`PathDir::walk`]. Use [`WalkEntry::from_entry`] to also keep the metadata of the
+    /// entry.
     ///
     /// [`PathDir::walk`]: trait.PathDirExt.html#method.walk
+    /// [`WalkEntry::from_entry`]: struct.WalkEntry.html#method.from_entry
     fn from_entry(entry: walkdir::DirEntry) -> path_abs::Result<PathType> {
         let abs = PathAbs::new(entry.path())?;
         let ty = entry.file_type();
@@ -231,6 +726,428 @@ pub trait PathTypeExt {
     }
 }
 
+/// Extension methods on the `PathFile` type.
+pub trait PathFileExt
+where
+    Self: AsRef<Path>,
+{
+    /// Copy the file to `to` using the [`CopyOptions`](struct.CopyOptions.html), i.e. to
+    /// preserve the holes of sparse files.
+    ///
+    /// This is the same as `PathFile::copy` but with options and an [`Error`](struct.Error.html)
+    /// which includes both paths.
+    fn copy_with<P: AsRef<Path>>(&self, to: P, options: &CopyOptions) -> Result<PathFile, Error>;
+
+    /// Clone the file copy-on-write to `to` when the filesystem supports it, otherwise copy it
+    /// normally.
+    ///
+    /// This is the same as `copy_with(to, &CopyOptions::new().reflink(true))`, see
+    /// [`CopyOptions::reflink`](struct.CopyOptions.html#method.reflink).
+    ///
+    /// # Examples
+    /// ```rust
+    /// # extern crate ergo_fs;
+    /// use ergo_fs::*;
+    ///
+    /// # fn try_main() -> ::std::io::Result<()> {
+    /// let tmp = PathTmp::create("reflink")?;
+    /// let file = PathFile::create(tmp.join("example.txt"))?;
+    /// file.write_str("example")?;
+    ///
+    /// let cloned = file.copy_reflink(tmp.join("cloned.txt"))?;
+    /// assert_eq!("example", cloned.read_string()?);
+    /// # Ok(()) } fn main() { try_main().unwrap() }
+    /// ```
+    fn copy_reflink<P: AsRef<Path>>(&self, to: P) -> Result<PathFile, Error> {
+        self.copy_with(to, &CopyOptions::new().reflink(true))
+    }
+
+    /// Read a newline delimited JSON file (one value per line, i.e. a log of events), returning
+    /// an iterator which deserializes each line into `T`. Blank lines are skipped.
+    ///
+    /// Errors include the path and the line number. Use
+    /// [`FileWriteExt::append_json`](trait.FileWriteExt.html#method.append_json) to write them.
+    ///
+    /// # Examples
+    /// ```rust
+    /// # extern crate ergo_fs;
+    /// use ergo_fs::*;
+    ///
+    /// # fn try_main() -> ::std::io::Result<()> {
+    /// let tmp = PathTmp::create("ndjson")?;
+    /// let file = PathFile::create(tmp.join("events.ndjson"))?;
+    ///
+    /// let mut append = file.append()?;
+    /// append.append_json(&("start", 1))?;
+    /// append.append_json(&("stop", 2))?;
+    ///
+    /// let events: Vec<(String, u32)> = file.read_ndjson()?.collect::<Result<_, _>>()?;
+    /// assert_eq!(vec![("start".to_string(), 1), ("stop".to_string(), 2)], events);
+    ///
+    /// file.append_str("not json\n")?;
+    /// let err = file.read_ndjson::<(String, u32)>()?.last().unwrap().unwrap_err();
+    /// assert_eq!("parsing json on line 3 of", err.action());
+    /// # Ok(()) } fn main() { try_main().unwrap() }
+    /// ```
+    fn read_ndjson<T: DeserializeOwned>(&self) -> Result<NdjsonLines<T>, Error> {
+        NdjsonLines::open(self.as_ref())
+    }
+
+    /// Read the last `n_lines` lines of the file, like `tail -n`.
+    ///
+    /// Only the end of the file is read, so this is fast for large logs. The lines don't include
+    /// their line ending (`\n` or `\r\n`) and invalid UTF-8 is replaced with `U+FFFD`.
+    ///
+    /// # Examples
+    /// ```rust
+    /// # extern crate ergo_fs;
+    /// use ergo_fs::*;
+    ///
+    /// # fn try_main() -> ::std::io::Result<()> {
+    /// let tmp = PathTmp::create("tail")?;
+    /// let file = PathFile::create(tmp.join("app.log"))?;
+    /// file.write_str("one\ntwo\nthree\n")?;
+    ///
+    /// assert_eq!(vec!["two", "three"], file.tail(2)?);
+    /// assert_eq!(vec!["one", "two", "three"], file.tail(10)?);
+    /// # Ok(()) } fn main() { try_main().unwrap() }
+    /// ```
+    fn tail(&self, n_lines: usize) -> Result<Vec<String>, Error> {
+        tail::tail(self.as_ref(), n_lines)
+    }
+
+    /// Follow the lines appended to the file, like `tail -F`.
+    ///
+    /// Returns a channel which receives each line written after `follow` is called, without its
+    /// line ending. A line is received once its newline is written. Use [`tail`] first to also
+    /// get the lines which were already written.
+    ///
+    /// The file is polled by a [`GlobWatcher`] in a background thread. When the file is rotated
+    /// (renamed or removed and a new file created at its path) the rest of the old file is
+    /// received and then the new file is followed from its start. When it is truncated it is
+    /// followed from its new start. The thread stops when the receiver is dropped.
+    ///
+    /// [`tail`]: #method.tail
+    /// [`GlobWatcher`]: struct.GlobWatcher.html
+    ///
+    /// # Examples
+    /// ```rust
+    /// # extern crate ergo_fs;
+    /// use std::fs;
+    /// use std::time::Duration;
+    /// use ergo_fs::*;
+    ///
+    /// # fn try_main() -> ::std::io::Result<()> {
+    /// let tmp = PathTmp::create("follow")?;
+    /// let file = PathFile::create(tmp.join("app.log"))?;
+    /// file.write_str("old\n")?;
+    ///
+    /// let lines = file.follow()?;
+    /// file.append_str("started\n")?;
+    /// assert_eq!("started", lines.recv_timeout(Duration::from_secs(5)).unwrap());
+    ///
+    /// // rotate the log
+    /// fs::rename(&file, tmp.join("app.log.1"))?;
+    /// PathFile::create(tmp.join("app.log"))?.write_str("rotated\n")?;
+    /// assert_eq!("rotated", lines.recv_timeout(Duration::from_secs(5)).unwrap());
+    /// # Ok(()) } fn main() { try_main().unwrap() }
+    /// ```
+    fn follow(&self) -> Result<Receiver<String>, Error> {
+        tail::follow(self.as_ref())
+    }
+
+    /// Copy the file next to itself before modifying it in place, returning the backup.
+    ///
+    /// The backup is named according to the [`BackupStyle`](enum.BackupStyle.html) and has the
+    /// same permissions as the file.
+    ///
+    /// # Examples
+    /// ```rust
+    /// # extern crate ergo_fs;
+    /// use ergo_fs::*;
+    ///
+    /// # fn try_main() -> ::std::io::Result<()> {
+    /// let tmp = PathTmp::create("backup")?;
+    /// let file = PathFile::create(tmp.join("notes.txt"))?;
+    /// file.write_str("first")?;
+    ///
+    /// let bak = file.backup(&BackupStyle::Suffix(".bak".into()))?;
+    /// assert_eq!(tmp.join("notes.txt.bak").as_path(), bak.as_path());
+    ///
+    /// let numbered = file.backup(&BackupStyle::Numbered)?;
+    /// assert_eq!(tmp.join("notes.txt.~1~").as_path(), numbered.as_path());
+    /// file.write_str("second")?;
+    /// let numbered = file.backup(&BackupStyle::Numbered)?;
+    /// assert_eq!(tmp.join("notes.txt.~2~").as_path(), numbered.as_path());
+    /// assert_eq!("second", numbered.read_string()?);
+    /// # Ok(()) } fn main() { try_main().unwrap() }
+    /// ```
+    fn backup(&self, style: &BackupStyle) -> Result<PathFile, Error> {
+        backup::backup(self.as_ref(), style)
+    }
+
+    /// Back up the file with [`backup`] and then open it for editing, returning the backup and
+    /// the open file.
+    ///
+    /// [`backup`]: #method.backup
+    fn edit_with_backup(&self, style: &BackupStyle) -> Result<(PathFile, FileEdit), Error> {
+        let backup = self.backup(style)?;
+        Ok((backup, FileEdit::edit(self)?))
+    }
+
+    /// Edit the file line by line, like `sed -i`, returning the number of lines which changed
+    /// (were replaced with different contents or deleted).
+    ///
+    /// `edit` is called with each line (without its line ending) and returns whether to
+    /// [`Keep`], [`Replace`] or [`Delete`] it. The file is streamed into a temporary file next
+    /// to it which is renamed over it once every line has been edited, so readers never see a
+    /// partially edited file and an error leaves it untouched. If no line changed the file is
+    /// not rewritten at all.
+    ///
+    /// The file must be UTF-8.
+    ///
+    /// [`Keep`]: enum.LineEdit.html#variant.Keep
+    /// [`Replace`]: enum.LineEdit.html#variant.Replace
+    /// [`Delete`]: enum.LineEdit.html#variant.Delete
+    ///
+    /// # Examples
+    /// ```rust
+    /// # extern crate ergo_fs;
+    /// use ergo_fs::*;
+    ///
+    /// # fn try_main() -> ::std::io::Result<()> {
+    /// let tmp = PathTmp::create("edit_lines")?;
+    /// let file = PathFile::create(tmp.join("hosts"))?;
+    /// file.write_str("127.0.0.1 localhost\n# old comment\n10.0.0.1 db\n")?;
+    ///
+    /// let changed = file.edit_lines(|line| {
+    ///     if line.starts_with('#') {
+    ///         LineEdit::Delete
+    ///     } else if line.ends_with(" db") {
+    ///         LineEdit::Replace("10.0.0.2 db".to_string())
+    ///     } else {
+    ///         LineEdit::Keep
+    ///     }
+    /// })?;
+    /// assert_eq!(2, changed);
+    /// assert_eq!("127.0.0.1 localhost\n10.0.0.2 db\n", file.read_string()?);
+    /// # Ok(()) } fn main() { try_main().unwrap() }
+    /// ```
+    fn edit_lines<F: FnMut(&str) -> LineEdit>(&self, edit: F) -> Result<usize, Error> {
+        replace::edit_lines(self.as_ref(), edit)
+    }
+
+    /// Insert a block of `content` fenced by `marker` lines into the file, or replace it if it
+    /// is already there, returning whether the file changed.
+    ///
+    /// The block starts with the line `{marker} BEGIN` and ends with `{marker} END`, so the
+    /// marker should start with the file's comment syntax, i.e. `# managed by mytool, do not
+    /// edit`. A missing block is appended to the end of the file. The file is replaced
+    /// atomically (see [`edit_lines`]) and only if the block changed, so calling this again
+    /// with the same content does nothing.
+    ///
+    /// It is an error if the file contains the begin line but not the end line, since the
+    /// extent of the block is then unknown.
+    ///
+    /// [`edit_lines`]: #method.edit_lines
+    ///
+    /// # Examples
+    /// ```rust
+    /// # extern crate ergo_fs;
+    /// use ergo_fs::*;
+    ///
+    /// # fn try_main() -> ::std::io::Result<()> {
+    /// let tmp = PathTmp::create("ensure_block")?;
+    /// let file = PathFile::create(tmp.join("hosts"))?;
+    /// file.write_str("127.0.0.1 localhost\n")?;
+    ///
+    /// let marker = "# managed by provision";
+    /// assert!(file.ensure_block(marker, "10.0.0.1 db\n")?);
+    /// assert!(!file.ensure_block(marker, "10.0.0.1 db\n")?);
+    /// assert!(file.ensure_block(marker, "10.0.0.2 db\n")?);
+    /// let expected = concat!(
+    ///     "127.0.0.1 localhost\n",
+    ///     "# managed by provision BEGIN\n",
+    ///     "10.0.0.2 db\n",
+    ///     "# managed by provision END\n",
+    /// );
+    /// assert_eq!(expected, file.read_string()?);
+    /// # Ok(()) } fn main() { try_main().unwrap() }
+    /// ```
+    fn ensure_block(&self, marker: &str, content: &str) -> Result<bool, Error> {
+        replace::ensure_block(self.as_ref(), marker, content)
+    }
+
+    /// Guess whether the file is binary or text and its MIME type, from its extension and its
+    /// first [`SNIFF_LEN`](constant.SNIFF_LEN.html) bytes.
+    ///
+    /// Use it to skip binary files when searching, diffing or editing. See
+    /// [`Sniff`](struct.Sniff.html).
+    ///
+    /// # Examples
+    /// ```rust
+    /// # extern crate ergo_fs;
+    /// use ergo_fs::*;
+    ///
+    /// # fn try_main() -> ::std::io::Result<()> {
+    /// let tmp = PathTmp::create("sniff")?;
+    /// let text = PathFile::create(tmp.join("data.json"))?;
+    /// text.write_str("{\"a\": 1}")?;
+    /// let sniff = text.sniff()?;
+    /// assert!(!sniff.is_binary);
+    /// assert_eq!("application/json", sniff.mime_guess);
+    /// assert_eq!(None, sniff.bom);
+    ///
+    /// let binary = PathFile::create(tmp.join("data.bin"))?;
+    /// ::std::fs::write(&binary, b"\x1f\x8b\x08\0")?;
+    /// let sniff = binary.sniff()?;
+    /// assert!(sniff.is_binary);
+    /// assert_eq!("application/gzip", sniff.mime_guess);
+    /// # Ok(()) } fn main() { try_main().unwrap() }
+    /// ```
+    fn sniff(&self) -> Result<Sniff, Error> {
+        sniff::sniff(self.as_ref())
+    }
+}
+
+/// Extension methods on the `FileRead` type.
+pub trait FileReadExt {
+    /// Read up to `len` bytes starting at `offset`, i.e. a block to hash or upload.
+    ///
+    /// Fewer bytes are returned if the file ends first. The file is left positioned after the
+    /// bytes read.
+    ///
+    /// # Examples
+    /// ```rust
+    /// # extern crate ergo_fs;
+    /// use ergo_fs::*;
+    ///
+    /// # fn try_main() -> ::std::io::Result<()> {
+    /// let tmp = PathTmp::create("range")?;
+    /// let file = PathFile::create(tmp.join("data.bin"))?;
+    /// file.write_str("0123456789")?;
+    ///
+    /// let mut read = file.read()?;
+    /// assert_eq!(b"345", &read.read_range(3, 3)?[..]);
+    /// assert_eq!(b"89", &read.read_range(8, 100)?[..]);
+    /// # Ok(()) } fn main() { try_main().unwrap() }
+    /// ```
+    fn read_range(&mut self, offset: u64, len: usize) -> Result<Vec<u8>, Error>;
+
+    /// Read the rest of the file in chunks of `chunk_size` bytes. Every chunk is full except
+    /// the last one.
+    ///
+    /// # Panics
+    /// If `chunk_size` is 0.
+    ///
+    /// # Examples
+    /// ```rust
+    /// # extern crate ergo_fs;
+    /// use ergo_fs::*;
+    ///
+    /// # fn try_main() -> ::std::io::Result<()> {
+    /// let tmp = PathTmp::create("chunks")?;
+    /// let file = PathFile::create(tmp.join("data.bin"))?;
+    /// file.write_str("0123456789")?;
+    ///
+    /// let chunks = file.read()?.chunks(4).collect::<Result<Vec<_>, _>>()?;
+    /// assert_eq!(vec![b"0123".to_vec(), b"4567".to_vec(), b"89".to_vec()], chunks);
+    /// # Ok(()) } fn main() { try_main().unwrap() }
+    /// ```
+    fn chunks(self, chunk_size: usize) -> Chunks;
+
+    /// Read the rest of the file as text, whatever its encoding, instead of failing like
+    /// `read_string` does for text which is not UTF-8.
+    ///
+    /// Text with a UTF-16 byte order mark (i.e. written by windows tools) is decoded as UTF-16,
+    /// valid UTF-8 is returned as is and anything else is decoded as Latin-1. The byte order mark
+    /// is removed. Invalid UTF-16 and UTF-8 after a BOM are replaced with `U+FFFD`.
+    ///
+    /// # Examples
+    /// ```rust
+    /// # extern crate ergo_fs;
+    /// use ergo_fs::*;
+    ///
+    /// # fn try_main() -> ::std::io::Result<()> {
+    /// let tmp = PathTmp::create("read_string_lossy")?;
+    /// let file = PathFile::create(tmp.join("notes.txt"))?;
+    ///
+    /// ::std::fs::write(&file, b"\xff\xfec\x00a\x00f\x00\xe9\x00")?;
+    /// assert_eq!("caf\u{e9}", file.read()?.read_string_lossy()?);
+    ///
+    /// ::std::fs::write(&file, b"caf\xe9")?;
+    /// assert_eq!("caf\u{e9}", file.read()?.read_string_lossy()?);
+    /// # Ok(()) } fn main() { try_main().unwrap() }
+    /// ```
+    fn read_string_lossy(&mut self) -> Result<String, Error>;
+
+    /// Read the rest of the file as text in the `encoding`, skipping its byte order mark.
+    ///
+    /// Fails with `InvalidData` if the text is not valid in the encoding. The cause is a
+    /// [`DecodeError`](struct.DecodeError.html) with the offset of the first invalid byte.
+    ///
+    /// # Examples
+    /// ```rust
+    /// # extern crate ergo_fs;
+    /// use ergo_fs::*;
+    ///
+    /// # fn try_main() -> ::std::io::Result<()> {
+    /// let tmp = PathTmp::create("read_string_with")?;
+    /// let file = PathFile::create(tmp.join("notes.txt"))?;
+    /// ::std::fs::write(&file, b"\0h\0i\xd8\x00")?;
+    ///
+    /// let err = file.read()?.read_string_with(TextEncoding::Utf16Be).unwrap_err();
+    /// assert_eq!(::std::io::ErrorKind::InvalidData, err.kind());
+    /// assert_eq!(4, err.decode_error().unwrap().offset());
+    /// assert!(err.to_string().starts_with("invalid UTF-16BE at byte 4"));
+    ///
+    /// let text = file.read()?.read_string_with(TextEncoding::Latin1)?;
+    /// assert_eq!("\0h\0i\u{d8}\0", text);
+    /// # Ok(()) } fn main() { try_main().unwrap() }
+    /// ```
+    fn read_string_with(&mut self, encoding: TextEncoding) -> Result<String, Error>;
+}
+
+impl FileReadExt for FileRead {
+    fn read_range(&mut self, offset: u64, len: usize) -> Result<Vec<u8>, Error> {
+        chunks::read_range(self, offset, len)
+    }
+
+    fn chunks(self, chunk_size: usize) -> Chunks {
+        Chunks::new(self, chunk_size)
+    }
+
+    fn read_string_lossy(&mut self) -> Result<String, Error> {
+        decode::read_string_lossy(self)
+    }
+
+    fn read_string_with(&mut self, encoding: TextEncoding) -> Result<String, Error> {
+        decode::read_string_with(self, encoding)
+    }
+}
+
+/// Extension methods on the `FileWrite` type.
+pub trait FileWriteExt {
+    /// Serialize `value` as JSON and write it as a single line, i.e. to a file opened with
+    /// `PathFile::append` for a newline delimited JSON log.
+    ///
+    /// The line is written with a single write so lines from concurrent appenders are not
+    /// interleaved. See [`PathFileExt::read_ndjson`](trait.PathFileExt.html#method.read_ndjson).
+    fn append_json<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Error>;
+}
+
+impl FileWriteExt for FileWrite {
+    fn append_json<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Error> {
+        ndjson::append_json(self, value)
+    }
+}
+
+impl PathFileExt for PathFile {
+    fn copy_with<P: AsRef<Path>>(&self, to: P, options: &CopyOptions) -> Result<PathFile, Error> {
+        copy::copy_file(self, to.as_ref(), options)
+    }
+}
+
 impl PathDirExt for PathDir {}
 impl PathTypeExt for PathType {}
 
@@ -243,7 +1160,7 @@ pub type ExpandError = shellexpand::LookupError<::std::env::VarError>;
 
 /// Performs both tilde and environment shell expansions in the default system context. This is
 /// the same as [`shellexpand::full`](../shellexpand/fn.full.html) and is the "typical use case"
-/// for expanding strings.
+/// for expanding strings, except that `~` is resolved using [`home_dir`](fn.home_dir.html).
 ///
 /// Note that non-existant variables will result in an `Err` (in `sh` they are silently replaced
 /// with an empty string). Also, environment lookup is only done _as needed_ so this function is
@@ -261,9 +1178,7 @@ pub type ExpandError = shellexpand::LookupError<::std::env::VarError>;
 /// env::set_var("A", "a value");
 /// env::set_var("B", "b value");
 ///
-/// let home_dir = env::home_dir()
-///     .map(|p| p.display().to_string())
-///     .unwrap_or_else(|| "~".to_owned());
+/// let home_dir = home_dir().unwrap().display().to_string();
 ///
 /// // Performs both tilde and environment expansions using the system contexts
 /// assert_eq!(
@@ -285,5 +1200,5 @@ pub fn expand<SI: ?Sized>(input: &SI) -> Result<Cow<str>, ExpandError>
 where
     SI: AsRef<str>,
 {
-    shellexpand::full(input)
+    shellexpand::full_with_context(input, dirs::home_path, |s| ::std::env::var(s).map(Some))
 }
@@ -0,0 +1,49 @@
+/* Copyright (c) 2018 Garrett Berg, vitiral@gmail.com
+ *
+ * Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+ * http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+ * http://opensource.org/licenses/MIT>, at your option. This file may not be
+ * copied, modified, or distributed except according to those terms.
+ */
+//! Portable access to metadata.
+
+use std::fs;
+
+/// Extension methods on `std::fs::Metadata` which are portable across platforms.
+///
+/// # Examples
+/// ```rust
+/// # extern crate ergo_fs;
+/// use ergo_fs::*;
+///
+/// # fn try_main() -> ::std::io::Result<()> {
+/// let tmp = PathTmp::create("metadata")?;
+/// let file = PathFile::create(tmp.join("a.txt"))?;
+///
+/// // Files in the same directory are (almost always) on the same filesystem.
+/// let dir_dev = PathDir::new(&tmp)?.metadata()?.device_id();
+/// assert_eq!(dir_dev, file.metadata()?.device_id());
+/// # Ok(()) } fn main() { try_main().unwrap() }
+/// ```
+pub trait ErgoMetadata {
+    /// The id of the device (filesystem) containing the file, or `None` if it is not
+    /// available on this platform.
+    ///
+    /// Two files are on the same filesystem if their device ids are equal. This is what
+    /// [`WalkOptions::same_file_system`](struct.WalkOptions.html#method.same_file_system)
+    /// uses to avoid crossing mount points.
+    fn device_id(&self) -> Option<u64>;
+}
+
+impl ErgoMetadata for fs::Metadata {
+    #[cfg(unix)]
+    fn device_id(&self) -> Option<u64> {
+        use std::os::unix::fs::MetadataExt;
+        Some(self.dev())
+    }
+
+    #[cfg(not(unix))]
+    fn device_id(&self) -> Option<u64> {
+        None
+    }
+}
@@ -0,0 +1,83 @@
+/* Copyright (c) 2018 Garrett Berg, vitiral@gmail.com
+ *
+ * Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+ * http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+ * http://opensource.org/licenses/MIT>, at your option. This file may not be
+ * copied, modified, or distributed except according to those terms.
+ */
+//! Newline delimited JSON.
+
+use error::{Error, Result};
+use path_abs::{FileRead, FileWrite, PathArc};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use serde_json;
+use std::io::{self, BufRead, BufReader};
+use std::marker::PhantomData;
+use std_prelude::*;
+
+/// An iterator over the values of a newline delimited JSON file, see
+/// [`PathFileExt::read_ndjson`](trait.PathFileExt.html#method.read_ndjson).
+pub struct NdjsonLines<T> {
+    lines: io::Lines<BufReader<FileRead>>,
+    path: PathArc,
+    line: usize,
+    ty: PhantomData<fn() -> T>,
+}
+
+impl<T: DeserializeOwned> NdjsonLines<T> {
+    pub(crate) fn open(path: &Path) -> Result<NdjsonLines<T>> {
+        let file = FileRead::read(path)?;
+        Ok(NdjsonLines {
+            lines: BufReader::new(file).lines(),
+            path: PathArc::new(path),
+            line: 0,
+            ty: PhantomData,
+        })
+    }
+
+    /// The line number of the last value returned, counting from 1.
+    pub fn line(&self) -> usize {
+        self.line
+    }
+}
+
+impl<T: DeserializeOwned> Iterator for NdjsonLines<T> {
+    type Item = Result<T>;
+
+    fn next(&mut self) -> Option<Result<T>> {
+        loop {
+            let line = self.lines.next()?;
+            self.line += 1;
+            let line = match line {
+                Ok(l) => l,
+                Err(err) => return Some(Err(Error::new(err, "reading", self.path.clone()))),
+            };
+            if line.trim().is_empty() {
+                continue;
+            }
+            return Some(serde_json::from_str(&line).map_err(|err| {
+                Error::new(
+                    io::Error::new(io::ErrorKind::InvalidData, err),
+                    &format!("parsing json on line {} of", self.line),
+                    self.path.clone(),
+                )
+            }));
+        }
+    }
+}
+
+/// Serialize `value` as a single line and append it in a single write, so that lines appended by
+/// concurrent writers are not interleaved.
+pub(crate) fn append_json<T: Serialize + ?Sized>(file: &mut FileWrite, value: &T) -> Result<()> {
+    let mut line = serde_json::to_vec(value).map_err(|err| {
+        Error::new(
+            io::Error::new(io::ErrorKind::InvalidData, err),
+            "serializing json for",
+            PathArc::new(file.path()),
+        )
+    })?;
+    line.push(b'\n');
+    file.write_all(&line)
+        .map_err(|err| Error::new(err, "appending json to", PathArc::new(file.path())))
+}
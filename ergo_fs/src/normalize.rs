@@ -0,0 +1,197 @@
+/* Copyright (c) 2018 Garrett Berg, vitiral@gmail.com
+ *
+ * Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+ * http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+ * http://opensource.org/licenses/MIT>, at your option. This file may not be
+ * copied, modified, or distributed except according to those terms.
+ */
+//! Unicode normalization of file names.
+//!
+//! macOS stores file names decomposed (NFD, `e` followed by a combining accent) while Linux and
+//! windows keep whatever bytes they were given, which is almost always composed (NFC). The
+//! same name copied between them is then a different path.
+
+use normalize_tables::{COMBINING_CLASSES, COMPOSITIONS, DECOMPOSITIONS};
+use std::char;
+use std_prelude::*;
+
+// Hangul syllables are composed of a leading consonant, a vowel and an optional trailing
+// consonant, see chapter 3.12 of the unicode standard.
+const S_BASE: u32 = 0xAC00;
+const L_BASE: u32 = 0x1100;
+const V_BASE: u32 = 0x1161;
+const T_BASE: u32 = 0x11A7;
+const L_COUNT: u32 = 19;
+const V_COUNT: u32 = 21;
+const T_COUNT: u32 = 28;
+const N_COUNT: u32 = V_COUNT * T_COUNT;
+const S_COUNT: u32 = L_COUNT * N_COUNT;
+
+/// Return `s` in Normalization Form C: every character composed where possible.
+///
+/// This is the form Linux and windows file names are usually in. Strings which are already in
+/// NFC are not copied.
+///
+/// # Examples
+/// ```rust
+/// # extern crate ergo_fs;
+/// use ergo_fs::*;
+///
+/// assert_eq!("caf\u{e9}", normalize_nfc("cafe\u{301}"));
+/// assert_eq!("\u{d55c}", normalize_nfc("\u{1112}\u{1161}\u{11ab}"));
+/// ```
+pub fn normalize_nfc<'a>(s: &'a str) -> Cow<'a, str> {
+    // nothing below the combining diacritical marks decomposes or composes with what follows
+    if s.chars().all(|c| c < '\u{300}') {
+        return Cow::Borrowed(s);
+    }
+    Cow::Owned(compose(&decompose(s)))
+}
+
+/// Return `s` in Normalization Form D: every character decomposed into its base character and
+/// combining marks.
+///
+/// This is the form macOS stores file names in. Strings which are already in NFD are not copied.
+///
+/// # Examples
+/// ```rust
+/// # extern crate ergo_fs;
+/// use ergo_fs::*;
+///
+/// assert_eq!("cafe\u{301}", normalize_nfd("caf\u{e9}"));
+/// assert_eq!("plain", normalize_nfd("plain"));
+/// ```
+pub fn normalize_nfd<'a>(s: &'a str) -> Cow<'a, str> {
+    if s.chars().all(|c| c < '\u{c0}') {
+        return Cow::Borrowed(s);
+    }
+    Cow::Owned(decompose(s).into_iter().collect())
+}
+
+/// Return whether the paths are equal after normalizing both to NFC.
+pub(crate) fn eq_normalized(a: &Path, b: &Path) -> bool {
+    if a == b {
+        return true;
+    }
+    match (a.to_str(), b.to_str()) {
+        (Some(a), Some(b)) => {
+            Path::new(normalize_nfc(a).as_ref()) == Path::new(normalize_nfc(b).as_ref())
+        }
+        // names which are not unicode can not be normalized
+        _ => false,
+    }
+}
+
+/// Fully decompose `s` and put the combining marks in canonical order.
+fn decompose(s: &str) -> Vec<char> {
+    let mut out = Vec::with_capacity(s.len());
+    for c in s.chars() {
+        decompose_char(c, &mut out);
+    }
+    // sort every run of combining marks by their class, keeping the order of equal classes
+    let mut start = 0;
+    while start < out.len() {
+        if combining_class(out[start]) == 0 {
+            start += 1;
+            continue;
+        }
+        let end = out[start..]
+            .iter()
+            .position(|&c| combining_class(c) == 0)
+            .map_or(out.len(), |len| start + len);
+        out[start..end].sort_by_key(|&c| combining_class(c));
+        start = end;
+    }
+    out
+}
+
+fn decompose_char(c: char, out: &mut Vec<char>) {
+    let code = c as u32;
+    if (S_BASE..S_BASE + S_COUNT).contains(&code) {
+        let index = code - S_BASE;
+        out.push(from_u32(L_BASE + index / N_COUNT));
+        out.push(from_u32(V_BASE + (index % N_COUNT) / T_COUNT));
+        if !index.is_multiple_of(T_COUNT) {
+            out.push(from_u32(T_BASE + index % T_COUNT));
+        }
+        return;
+    }
+    match DECOMPOSITIONS.binary_search_by_key(&c, |&(composed, _, _)| composed) {
+        Ok(i) => {
+            let (_, first, second) = DECOMPOSITIONS[i];
+            decompose_char(first, out);
+            if second != '\0' {
+                decompose_char(second, out);
+            }
+        }
+        Err(_) => out.push(c),
+    }
+}
+
+/// Canonically compose decomposed and ordered characters.
+fn compose(chars: &[char]) -> String {
+    let mut out: Vec<char> = Vec::with_capacity(chars.len());
+    // the index in `out` of the last starter and the class of the last character pushed
+    let mut starter = None;
+    let mut last_class = 0;
+    for &c in chars {
+        let class = combining_class(c);
+        if let Some(s) = starter {
+            // a character between the starter and `c` blocks them from composing if it is a
+            // starter itself or its class is not lower than that of `c`
+            let adjacent = out.len() == s + 1;
+            if adjacent || (last_class != 0 && last_class < class) {
+                if let Some(composed) = compose_pair(out[s], c) {
+                    out[s] = composed;
+                    continue;
+                }
+            }
+        }
+        if class == 0 {
+            starter = Some(out.len());
+        }
+        last_class = class;
+        out.push(c);
+    }
+    out.into_iter().collect()
+}
+
+fn compose_pair(first: char, second: char) -> Option<char> {
+    let (a, b) = (first as u32, second as u32);
+    if (L_BASE..L_BASE + L_COUNT).contains(&a) && (V_BASE..V_BASE + V_COUNT).contains(&b) {
+        let lv = (a - L_BASE) * N_COUNT + (b - V_BASE) * T_COUNT;
+        return Some(from_u32(S_BASE + lv));
+    }
+    if (S_BASE..S_BASE + S_COUNT).contains(&a)
+        && (a - S_BASE).is_multiple_of(T_COUNT)
+        && (T_BASE + 1..T_BASE + T_COUNT).contains(&b)
+    {
+        return Some(from_u32(a + b - T_BASE));
+    }
+    COMPOSITIONS
+        .binary_search_by_key(&(first, second), |&(a, b, _)| (a, b))
+        .ok()
+        .map(|i| COMPOSITIONS[i].2)
+}
+
+fn combining_class(c: char) -> u8 {
+    if c < '\u{300}' {
+        return 0;
+    }
+    match COMBINING_CLASSES.binary_search_by(|&(start, end, _)| {
+        if end < c {
+            Ordering::Less
+        } else if start > c {
+            Ordering::Greater
+        } else {
+            Ordering::Equal
+        }
+    }) {
+        Ok(i) => COMBINING_CLASSES[i].2,
+        Err(_) => 0,
+    }
+}
+
+fn from_u32(code: u32) -> char {
+    char::from_u32(code).expect("hangul is valid")
+}
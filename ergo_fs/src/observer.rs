@@ -0,0 +1,104 @@
+/* Copyright (c) 2018 Garrett Berg, vitiral@gmail.com
+ *
+ * Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+ * http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+ * http://opensource.org/licenses/MIT>, at your option. This file may not be
+ * copied, modified, or distributed except according to those terms.
+ */
+//! Hooks for observing bulk operations.
+
+use std::error;
+use std::fmt;
+use std_prelude::*;
+
+/// What happened during a walk or a copy, see [`FsObserver`](trait.FsObserver.html).
+#[derive(Debug)]
+pub enum FsEvent<'a> {
+    /// A walk reached the directory (after its contents if the walk is contents-first) or a
+    /// copy created it.
+    EnteredDir(&'a Path),
+    /// A file was copied.
+    CopiedFile {
+        /// The source file.
+        from: &'a Path,
+        /// The destination file.
+        to: &'a Path,
+        /// The size of the file.
+        bytes: u64,
+        /// How long copying it took.
+        duration: Duration,
+    },
+    /// An entry was skipped, i.e. it was excluded from a walk or a resumed copy already copied
+    /// it.
+    Skipped(&'a Path),
+    /// An operation failed. The error is still returned or sent as usual.
+    Error(&'a dyn error::Error),
+}
+
+/// A hook which walks and copies call with structured [`FsEvent`]s, for logging, metrics or
+/// progress bars without building any of them into the operations.
+///
+/// Observers are called from whichever thread performs the operation, possibly from several at
+/// once, so they should be quick. Any `Fn(&FsEvent) + Send + Sync` closure is an observer.
+///
+/// Attach one with [`WalkEntries::observe`] or `DeepCopy::observer` from `ergo`. These are the
+/// only observed operations: tar archives ([`tar_append`], [`TarList`] and `tar_stream` or
+/// `tar_receive` from `ergo`) are read and written without reporting any events.
+///
+/// [`FsEvent`]: enum.FsEvent.html
+/// [`WalkEntries::observe`]: struct.WalkEntries.html#method.observe
+/// [`tar_append`]: fn.tar_append.html
+/// [`TarList`]: struct.TarList.html
+///
+/// # Examples
+/// ```rust
+/// # extern crate ergo_fs;
+/// use ergo_fs::*;
+/// use std::sync::Arc;
+/// use std::sync::atomic::{AtomicUsize, Ordering};
+///
+/// # fn try_main() -> ::std::io::Result<()> {
+/// let tmp = PathTmp::create("observer")?;
+/// PathDir::create(tmp.join("sub"))?;
+/// PathFile::create(tmp.join("sub").join("a.txt"))?;
+/// PathFile::create(tmp.join(".hidden"))?;
+///
+/// let dirs = Arc::new(AtomicUsize::new(0));
+/// let skipped = Arc::new(AtomicUsize::new(0));
+/// let (d, s) = (dirs.clone(), skipped.clone());
+/// let observer = Arc::new(move |event: &FsEvent| match *event {
+///     FsEvent::EnteredDir(_) => { d.fetch_add(1, Ordering::SeqCst); }
+///     FsEvent::Skipped(_) => { s.fetch_add(1, Ordering::SeqCst); }
+///     _ => {}
+/// });
+///
+/// let files = WalkOptions::new()
+///     .skip_hidden(true)
+///     .walk(&tmp)
+///     .observe(observer)
+///     .filter(|e| e.as_ref().map(|e| e.as_file().is_some()).unwrap_or(false))
+///     .count();
+/// assert_eq!(1, files);
+/// assert_eq!(2, dirs.load(Ordering::SeqCst));
+/// assert_eq!(1, skipped.load(Ordering::SeqCst));
+/// # Ok(()) } fn main() { try_main().unwrap() }
+/// ```
+pub trait FsObserver: Send + Sync {
+    /// Called for every event of the operations the observer is attached to.
+    fn on_event(&self, event: &FsEvent);
+}
+
+impl<F> FsObserver for F
+where
+    F: Fn(&FsEvent) + Send + Sync,
+{
+    fn on_event(&self, event: &FsEvent) {
+        self(event)
+    }
+}
+
+impl fmt::Debug for dyn FsObserver {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("FsObserver")
+    }
+}
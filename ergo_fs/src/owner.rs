@@ -0,0 +1,36 @@
+/* Copyright (c) 2018 Garrett Berg, vitiral@gmail.com
+ *
+ * Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+ * http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+ * http://opensource.org/licenses/MIT>, at your option. This file may not be
+ * copied, modified, or distributed except according to those terms.
+ */
+//! Creating paths owned by another user.
+
+use ergo_sys::unix::User;
+use error::{Error, Result};
+use libc;
+use path_abs::{PathArc, PathDir};
+use std::fs;
+use std::os::unix::fs::{fchown, OpenOptionsExt, PermissionsExt};
+use std_prelude::*;
+
+pub(crate) fn create_owned(path: &Path, user: &User, mode: u32) -> Result<PathDir> {
+    let err = |action| move |e| Error::new(e, action, PathArc::new(path));
+    // Fail if anything exists at the path: a symlink planted there would make us hand over
+    // ownership of its target.
+    fs::create_dir(path).map_err(err("creating dir"))?;
+    // Change the directory through a handle so that swapping it for a symlink after creating it
+    // doesn't redirect the changes either.
+    let handle = fs::OpenOptions::new()
+        .read(true)
+        .custom_flags(libc::O_NOFOLLOW | libc::O_DIRECTORY)
+        .open(path)
+        .map_err(err("opening dir"))?;
+    // chown may clear the setuid and setgid bits, so set the mode afterwards
+    fchown(&handle, Some(user.uid()), Some(user.gid())).map_err(err("changing owner of"))?;
+    handle
+        .set_permissions(fs::Permissions::from_mode(mode))
+        .map_err(err("setting permissions of"))?;
+    Ok(PathDir::new(path)?)
+}
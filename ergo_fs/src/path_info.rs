@@ -0,0 +1,391 @@
+/* Copyright (c) 2018 Garrett Berg, vitiral@gmail.com
+ *
+ * Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+ * http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+ * http://opensource.org/licenses/MIT>, at your option. This file may not be
+ * copied, modified, or distributed except according to those terms.
+ */
+//! Information about paths which is not provided by `std` or `path_abs`.
+
+use normalize;
+use path_abs::{PathAbs, PathArc, PathDir, PathFile, PathType};
+use std::fs;
+use std::io;
+use std_prelude::*;
+use tmp::PathTmp;
+
+/// Extension methods for getting information about any of the path types.
+pub trait PathInfo
+where
+    Self: AsRef<Path>,
+{
+    /// Return the path in a form suitable for displaying to users.
+    ///
+    /// On windows, absolute paths use the verbatim (`\\?\`) prefix so that paths longer than 260
+    /// characters are handled correctly. This is what `PathAbs` uses internally, but it is noisy
+    /// in user-facing messages and logs. `display_clean` strips the prefix whenever the path can
+    /// be represented without it (`\\?\C:\foo` becomes `C:\foo` and `\\?\UNC\server\share`
+    /// becomes `\\server\share`), otherwise the path is left unchanged.
+    ///
+    /// On other platforms this is the same as `path.display().to_string()`.
+    ///
+    /// # Examples
+    /// ```rust
+    /// # extern crate ergo_fs;
+    /// use ergo_fs::*;
+    ///
+    /// # fn try_main() -> ::std::io::Result<()> {
+    /// let src = PathDir::new("src")?;
+    /// let clean = src.display_clean();
+    /// assert!(!clean.starts_with(r"\\?\"));
+    /// assert!(clean.ends_with("src"));
+    /// # Ok(()) } fn main() { try_main().unwrap() }
+    /// ```
+    fn display_clean(&self) -> String {
+        simplified(self.as_ref()).display().to_string()
+    }
+
+    /// Return whether this path and `other` refer to the same file or directory.
+    ///
+    /// Unlike comparing the paths themselves, this follows symlinks and is correct on
+    /// case-insensitive filesystems. On unix the device and inode numbers are compared, on
+    /// windows the fully resolved (final) paths of both files are compared.
+    ///
+    /// Returns an error if either path does not exist.
+    ///
+    /// # Examples
+    /// ```rust
+    /// # extern crate ergo_fs;
+    /// use ergo_fs::*;
+    ///
+    /// # fn try_main() -> ::std::io::Result<()> {
+    /// let src = PathDir::new("src")?;
+    /// assert!(src.same_file("src/../src")?);
+    /// assert!(!src.same_file("src/lib.rs")?);
+    /// # Ok(()) } fn main() { try_main().unwrap() }
+    /// ```
+    fn same_file<P: AsRef<Path>>(&self, other: P) -> io::Result<bool> {
+        same_file(self.as_ref(), other.as_ref())
+    }
+
+    /// Return whether this path and `other` are equal after unicode normalization (NFC).
+    ///
+    /// macOS decomposes file names, so a `café.txt` created on Linux is listed as
+    /// `cafe\u{301}.txt` after a round trip through a Mac and no longer compares equal. This
+    /// only compares the paths, it does not touch the filesystem. Paths which are not unicode
+    /// are compared as they are.
+    ///
+    /// # Examples
+    /// ```rust
+    /// # extern crate ergo_fs;
+    /// use ergo_fs::*;
+    ///
+    /// let linux = Path::new("photos/caf\u{e9}.jpg");
+    /// let macos = Path::new("photos/cafe\u{301}.jpg");
+    /// assert_ne!(linux, macos);
+    /// assert!(linux.eq_normalized(macos));
+    /// assert!(!linux.eq_normalized("photos/cafe.jpg"));
+    /// ```
+    fn eq_normalized<P: AsRef<Path>>(&self, other: P) -> bool {
+        normalize::eq_normalized(self.as_ref(), other.as_ref())
+    }
+
+    /// Return the extension of the file name including every part of a compressed extension,
+    /// i.e. `tar.gz` for `backup.tar.gz` where `extension()` is only `gz`.
+    ///
+    /// The last extension is always included. If it is a compression format (`gz`, `bz2`, `xz`,
+    /// `zst`, `lz4`, `lzma`, `br` or `Z`) the extension before it is included too, unless it
+    /// has no letters (so the version in `app-1.2.gz` is not an extension). Like `extension()`,
+    /// leading dots are part of the name: `.bashrc` has no extension.
+    ///
+    /// Returns `None` if there is no extension or the file name is not unicode.
+    ///
+    /// # Examples
+    /// ```rust
+    /// # extern crate ergo_fs;
+    /// use ergo_fs::*;
+    ///
+    /// assert_eq!(Some("tar.gz"), Path::new("dist/app-1.2.tar.gz").full_extension());
+    /// assert_eq!(Some("gz"), Path::new("logs/app-1.2.gz").full_extension());
+    /// assert_eq!(Some("pdf"), Path::new("report.final.pdf").full_extension());
+    /// assert_eq!(None, Path::new(".bashrc").full_extension());
+    /// ```
+    fn full_extension(&self) -> Option<&str> {
+        full_extension(self.as_ref())
+    }
+
+    /// Return the path without its [`full_extension`](#method.full_extension).
+    ///
+    /// # Examples
+    /// ```rust
+    /// # extern crate ergo_fs;
+    /// use ergo_fs::*;
+    ///
+    /// assert_eq!(Path::new("dist/app-1.2"), Path::new("dist/app-1.2.tar.gz").strip_extensions());
+    /// assert_eq!(Path::new(".bashrc"), Path::new(".bashrc").strip_extensions());
+    /// ```
+    fn strip_extensions(&self) -> PathBuf {
+        let path = self.as_ref();
+        match full_extension(path) {
+            Some(ext) => {
+                let name = path
+                    .file_name()
+                    .and_then(|n| n.to_str())
+                    .expect("has extension");
+                path.with_file_name(&name[..name.len() - ext.len() - 1])
+            }
+            None => path.with_extension(""),
+        }
+    }
+
+    /// Return the path with its [`full_extension`](#method.full_extension) replaced by `ext`,
+    /// which may have several parts. An empty `ext` removes the extension.
+    ///
+    /// # Examples
+    /// ```rust
+    /// # extern crate ergo_fs;
+    /// use ergo_fs::*;
+    ///
+    /// let path = Path::new("dist/app-1.2.tar.gz");
+    /// assert_eq!(Path::new("dist/app-1.2.tar.zst"), path.with_full_extension("tar.zst"));
+    /// assert_eq!(Path::new("notes.md"), Path::new("notes").with_full_extension("md"));
+    /// ```
+    fn with_full_extension(&self, ext: &str) -> PathBuf {
+        let stripped = self.strip_extensions();
+        let ext = ext.trim_start_matches('.');
+        if ext.is_empty() {
+            return stripped;
+        }
+        let mut name = stripped.file_name().unwrap_or_default().to_os_string();
+        name.push(".");
+        name.push(ext);
+        stripped.with_file_name(name)
+    }
+
+    /// Return whether the file or directory is read-only.
+    ///
+    /// On windows this is the read-only attribute, on unix whether nobody has write permission.
+    fn readonly(&self) -> io::Result<bool> {
+        Ok(fs::metadata(self.as_ref())?.permissions().readonly())
+    }
+
+    /// Make the file or directory read-only, or writable again.
+    ///
+    /// On windows this sets the read-only attribute, which is the usual reason why copying over
+    /// or removing a file fails there. On unix making a path read-only removes every write
+    /// permission and making it writable adds write permission for the owner only (unlike
+    /// `Permissions::set_readonly(false)`, which makes it writable by everybody).
+    ///
+    /// # Examples
+    /// ```rust
+    /// # extern crate ergo_fs;
+    /// use ergo_fs::*;
+    ///
+    /// # fn try_main() -> ::std::io::Result<()> {
+    /// let tmp = PathTmp::create("readonly")?;
+    /// let file = PathFile::create(tmp.join("config.toml"))?;
+    /// assert!(!file.readonly()?);
+    ///
+    /// file.set_readonly(true)?;
+    /// assert!(file.readonly()?);
+    /// file.set_readonly(false)?;
+    /// file.write_str("fixed")?;
+    /// # Ok(()) } fn main() { try_main().unwrap() }
+    /// ```
+    fn set_readonly(&self, readonly: bool) -> io::Result<()> {
+        set_readonly(self.as_ref(), readonly)
+    }
+
+    /// Return whether the file or directory has the hidden attribute. Only on windows.
+    #[cfg(windows)]
+    fn hidden(&self) -> io::Result<bool> {
+        attributes::hidden(self.as_ref())
+    }
+
+    /// Set or clear the hidden attribute of the file or directory. Only on windows.
+    #[cfg(windows)]
+    fn set_hidden(&self, hidden: bool) -> io::Result<()> {
+        attributes::set_hidden(self.as_ref(), hidden)
+    }
+}
+
+impl PathInfo for Path {}
+impl PathInfo for PathBuf {}
+impl PathInfo for PathArc {}
+impl PathInfo for PathAbs {}
+impl PathInfo for PathDir {}
+impl PathInfo for PathFile {}
+impl PathInfo for PathType {}
+impl PathInfo for PathTmp {}
+
+#[cfg(unix)]
+fn same_file(a: &Path, b: &Path) -> io::Result<bool> {
+    use std::os::unix::fs::MetadataExt;
+    let (a, b) = (fs::metadata(a)?, fs::metadata(b)?);
+    Ok(a.dev() == b.dev() && a.ino() == b.ino())
+}
+
+#[cfg(not(unix))]
+fn same_file(a: &Path, b: &Path) -> io::Result<bool> {
+    // `canonicalize` returns the final path of the opened file, which resolves symlinks and the
+    // on-disk case of every component.
+    Ok(fs::canonicalize(a)? == fs::canonicalize(b)?)
+}
+
+/// Extensions of compression formats, which are usually stacked on another extension.
+const COMPRESSION_EXTENSIONS: &[&str] = &["gz", "bz2", "xz", "zst", "lz4", "lzma", "br", "z"];
+
+fn full_extension(path: &Path) -> Option<&str> {
+    let name = path.file_name()?.to_str()?;
+    // leading dots are part of the name, i.e. `.bashrc`
+    let start = name.len() - name.trim_start_matches('.').len();
+    let mut ext_start = None;
+    let mut end = name.len();
+    while let Some(dot) = name[start..end].rfind('.').map(|i| start + i) {
+        let ext = &name[dot + 1..end];
+        if ext.is_empty() || (ext_start.is_some() && !ext.chars().any(char::is_alphabetic)) {
+            break;
+        }
+        ext_start = Some(dot + 1);
+        end = dot;
+        if !COMPRESSION_EXTENSIONS.contains(&ext.to_ascii_lowercase().as_str()) {
+            break;
+        }
+    }
+    ext_start.map(|i| &name[i..])
+}
+
+#[cfg(unix)]
+fn set_readonly(path: &Path, readonly: bool) -> io::Result<()> {
+    use std::os::unix::fs::PermissionsExt;
+    let mut perms = fs::metadata(path)?.permissions();
+    let mode = perms.mode();
+    perms.set_mode(if readonly {
+        mode & !0o222
+    } else {
+        mode | 0o200
+    });
+    fs::set_permissions(path, perms)
+}
+
+#[cfg(not(unix))]
+fn set_readonly(path: &Path, readonly: bool) -> io::Result<()> {
+    let mut perms = fs::metadata(path)?.permissions();
+    perms.set_readonly(readonly);
+    fs::set_permissions(path, perms)
+}
+
+/// File attributes which `std` can read but not set.
+#[cfg(windows)]
+mod attributes {
+    use std::fs;
+    use std::io;
+    use std::os::windows::ffi::OsStrExt;
+    use std::os::windows::fs::MetadataExt;
+    use std_prelude::*;
+
+    const FILE_ATTRIBUTE_HIDDEN: u32 = 0x2;
+    /// No attributes, which `SetFileAttributesW` takes instead of `0`.
+    const FILE_ATTRIBUTE_NORMAL: u32 = 0x80;
+
+    #[link(name = "kernel32")]
+    extern "system" {
+        fn SetFileAttributesW(file_name: *const u16, attributes: u32) -> i32;
+    }
+
+    pub(super) fn hidden(path: &Path) -> io::Result<bool> {
+        Ok(fs::metadata(path)?.file_attributes() & FILE_ATTRIBUTE_HIDDEN != 0)
+    }
+
+    pub(super) fn set_hidden(path: &Path, hidden: bool) -> io::Result<()> {
+        let attrs = fs::metadata(path)?.file_attributes();
+        let attrs = match (hidden, attrs & !FILE_ATTRIBUTE_HIDDEN) {
+            (true, _) => attrs | FILE_ATTRIBUTE_HIDDEN,
+            (false, 0) => FILE_ATTRIBUTE_NORMAL,
+            (false, rest) => rest,
+        };
+        let wide: Vec<u16> = path.as_os_str().encode_wide().chain(Some(0)).collect();
+        match unsafe { SetFileAttributesW(wide.as_ptr(), attrs) } {
+            0 => Err(io::Error::last_os_error()),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Remove the verbatim prefix of the path if it can be represented without it.
+#[cfg(not(windows))]
+pub(crate) fn simplified<'a>(path: &'a Path) -> Cow<'a, Path> {
+    Cow::Borrowed(path)
+}
+
+/// Remove the verbatim prefix of the path if it can be represented without it.
+#[cfg(windows)]
+pub(crate) fn simplified<'a>(path: &'a Path) -> Cow<'a, Path> {
+    match path.to_str().and_then(simplify_verbatim) {
+        Some(s) => Cow::Owned(PathBuf::from(s)),
+        None => Cow::Borrowed(path),
+    }
+}
+
+/// The maximum length of a legacy (non-verbatim) windows path.
+#[cfg(windows)]
+const MAX_PATH: usize = 260;
+
+/// Return the legacy form of a verbatim path, or `None` if it has no legacy form.
+#[cfg(windows)]
+fn simplify_verbatim(path: &str) -> Option<String> {
+    let rest = if path.starts_with(r"\\?\") {
+        &path[4..]
+    } else {
+        return None;
+    };
+
+    let is_unc = rest
+        .get(..4)
+        .map_or(false, |p| p.eq_ignore_ascii_case(r"UNC\"));
+    let (legacy, components) = if is_unc {
+        // \\?\UNC\server\share\rest -> \\server\share\rest
+        let unc = &rest[4..];
+        if unc.is_empty() {
+            return None;
+        }
+        (format!(r"\\{}", unc), unc)
+    } else {
+        let bytes = rest.as_bytes();
+        let is_drive = bytes.len() >= 3
+            && bytes[0].is_ascii_alphabetic()
+            && bytes[1] == b':'
+            && bytes[2] == b'\\';
+        if !is_drive {
+            return None;
+        }
+        (rest.to_string(), &rest[3..])
+    };
+
+    if legacy.len() >= MAX_PATH {
+        return None;
+    }
+    if components.split('\\').all(is_legacy_component) {
+        Some(legacy)
+    } else {
+        None
+    }
+}
+
+/// Whether the component has the same meaning in a legacy path as in a verbatim path.
+#[cfg(windows)]
+fn is_legacy_component(comp: &str) -> bool {
+    use sanitize::RESERVED_NAMES;
+    if comp.is_empty() {
+        // trailing separator is fine
+        return true;
+    }
+    if comp == "." || comp == ".." || comp.ends_with('.') || comp.ends_with(' ') {
+        return false;
+    }
+    if comp.chars().any(|c| c < ' ' || "<>:\"/|?*".contains(c)) {
+        return false;
+    }
+    let stem = comp.split('.').next().unwrap_or(comp).trim_end();
+    !RESERVED_NAMES.iter().any(|r| stem.eq_ignore_ascii_case(r))
+}
@@ -0,0 +1,274 @@
+/* Copyright (c) 2018 Garrett Berg, vitiral@gmail.com
+ *
+ * Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+ * http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+ * http://opensource.org/licenses/MIT>, at your option. This file may not be
+ * copied, modified, or distributed except according to those terms.
+ */
+//! Templates for output paths.
+
+use chrono::format::{Item, StrftimeItems};
+use chrono::{DateTime, Local};
+use error::{Error, Result};
+use path_abs::{PathArc, PathFile};
+use std::fs;
+use std::io;
+use std::time::SystemTime;
+use std_prelude::*;
+
+/// The first number used for `{seq}`.
+const FIRST_SEQ: u64 = 1;
+
+/// A template for output paths, i.e. `logs/%Y-%m-%d/run-{seq}.log`, which is rendered to a new
+/// file.
+///
+/// The template can contain:
+///
+/// - `strftime` specifiers like `%Y`, `%m` or `%H`, which are replaced with the local time, see
+///   [`chrono::format::strftime`] for the full list.
+/// - `{name}`, which is replaced with the variable `name` passed to [`render`].
+/// - `{seq}`, which is replaced with the next unused number, starting at 1. `{seq:3}` pads it with
+///   zeros to 3 digits. It may only appear once and only in the file name.
+/// - `{{` and `}}` for literal braces.
+///
+/// [`render`] creates the parent directories and the file itself. When the template has a
+/// `{seq}` the file is created with `create_new` (`O_EXCL`), so two threads or processes
+/// rendering the same template at the same time always get different files.
+///
+/// [`chrono::format::strftime`]: https://docs.rs/chrono/0.4/chrono/format/strftime/index.html
+/// [`render`]: #method.render
+///
+/// # Examples
+/// ```rust
+/// # extern crate ergo_fs;
+/// use ergo_fs::*;
+///
+/// # fn try_main() -> ::std::io::Result<()> {
+/// let tmp = PathTmp::create("template")?;
+/// let template = PathTemplate::new(format!(
+///     "{}/logs/%Y/{{job}}-{{seq:3}}.log",
+///     tmp.display(),
+/// ));
+///
+/// let first = template.render(&[("job", "build")])?;
+/// let second = template.render(&[("job", "build")])?;
+/// assert!(first.ends_with("build-001.log"));
+/// assert!(second.ends_with("build-002.log"));
+/// assert_eq!(first.parent(), second.parent());
+/// # Ok(()) } fn main() { try_main().unwrap() }
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PathTemplate {
+    template: String,
+}
+
+/// A parsed part of a template.
+enum Part<'a> {
+    /// Literal text, which may contain strftime specifiers.
+    Text(String),
+    Var(&'a str),
+    Seq(usize),
+}
+
+impl PathTemplate {
+    /// Create a template. It is only checked when it is rendered.
+    pub fn new<S: Into<String>>(template: S) -> PathTemplate {
+        PathTemplate {
+            template: template.into(),
+        }
+    }
+
+    /// The template string.
+    pub fn as_str(&self) -> &str {
+        &self.template
+    }
+
+    /// Render the template with the current local time and `vars`, creating the file and its
+    /// parent directories.
+    ///
+    /// If the template has no `{seq}` the file is opened if it already exists (without
+    /// truncating it).
+    ///
+    /// Variable values are inserted as they are, so a value containing `/` adds directories.
+    /// Using a variable which is not in `vars` is an error.
+    pub fn render(&self, vars: &[(&str, &str)]) -> Result<PathFile> {
+        self.render_at(SystemTime::now(), vars)
+    }
+
+    /// Render the template at `time` instead of now, i.e. for the time an event happened.
+    pub fn render_at(&self, time: SystemTime, vars: &[(&str, &str)]) -> Result<PathFile> {
+        let time: DateTime<Local> = time.into();
+        let parts = self.parse()?;
+
+        let mut before = String::new();
+        let mut after = String::new();
+        let mut seq = None;
+        for part in parts {
+            let out = if seq.is_some() {
+                &mut after
+            } else {
+                &mut before
+            };
+            match part {
+                Part::Text(text) => out.push_str(&self.strftime(&text, &time)?),
+                Part::Var(name) => match vars.iter().find(|&&(k, _)| k == name) {
+                    Some(&(_, value)) => out.push_str(value),
+                    None => {
+                        return Err(self.invalid(&format!("no value for variable {{{}}}", name)))
+                    }
+                },
+                Part::Seq(width) => {
+                    if seq.is_some() {
+                        return Err(self.invalid("{seq} is used more than once"));
+                    }
+                    seq = Some(width);
+                }
+            }
+        }
+
+        match seq {
+            None => {
+                let path = PathBuf::from(before);
+                create_parent(&path)?;
+                open_file(&path, false)?;
+                Ok(PathFile::new(path)?)
+            }
+            Some(width) => {
+                if after.contains(is_separator) {
+                    return Err(self.invalid("{seq} must be in the file name"));
+                }
+                let split = before.rfind(is_separator).map(|i| i + 1).unwrap_or(0);
+                let prefix = before.split_off(split);
+                let dir = if before.is_empty() {
+                    PathBuf::from(".")
+                } else {
+                    PathBuf::from(before)
+                };
+                fs::create_dir_all(&dir)
+                    .map_err(|err| Error::new(err, "creating dir", PathArc::new(&dir)))?;
+                create_next(&dir, &prefix, &after, width)
+            }
+        }
+    }
+
+    fn parse(&self) -> Result<Vec<Part<'_>>> {
+        let mut parts = Vec::new();
+        let mut text = String::new();
+        let mut rest = self.template.as_str();
+        while let Some(i) = rest.find(['{', '}']) {
+            text.push_str(&rest[..i]);
+            let c = rest.as_bytes()[i];
+            rest = &rest[i + 1..];
+            if rest.as_bytes().first() == Some(&c) {
+                // `{{` or `}}`
+                text.push(c as char);
+                rest = &rest[1..];
+                continue;
+            }
+            if c == b'}' {
+                return Err(self.invalid("unmatched `}`"));
+            }
+            let end = rest
+                .find('}')
+                .ok_or_else(|| self.invalid("unmatched `{`"))?;
+            let name = &rest[..end];
+            rest = &rest[end + 1..];
+            if !text.is_empty() {
+                parts.push(Part::Text(::std::mem::take(&mut text)));
+            }
+            parts.push(match name.split_once(':') {
+                Some(("seq", width)) => match width.parse() {
+                    Ok(width) => Part::Seq(width),
+                    Err(_) => return Err(self.invalid(&format!("invalid width in {{{}}}", name))),
+                },
+                None if name == "seq" => Part::Seq(0),
+                None if !name.is_empty() => Part::Var(name),
+                _ => return Err(self.invalid(&format!("invalid variable {{{}}}", name))),
+            });
+        }
+        text.push_str(rest);
+        if !text.is_empty() {
+            parts.push(Part::Text(text));
+        }
+        Ok(parts)
+    }
+
+    fn strftime(&self, text: &str, time: &DateTime<Local>) -> Result<String> {
+        let items: Vec<Item> = StrftimeItems::new(text).collect();
+        if items.contains(&Item::Error) {
+            return Err(self.invalid("invalid strftime specifier"));
+        }
+        Ok(time.format_with_items(items.into_iter()).to_string())
+    }
+
+    fn invalid(&self, msg: &str) -> Error {
+        Error::new(
+            io::Error::new(io::ErrorKind::InvalidInput, msg),
+            "rendering path template",
+            PathArc::new(&self.template),
+        )
+    }
+}
+
+/// Create the file `{prefix}{seq}{suffix}` in `dir`, with `seq` one more than the largest
+/// which exists.
+fn create_next(dir: &Path, prefix: &str, suffix: &str, width: usize) -> Result<PathFile> {
+    let read_dir =
+        fs::read_dir(dir).map_err(|err| Error::new(err, "reading dir", PathArc::new(dir)))?;
+    let mut seq = FIRST_SEQ;
+    for entry in read_dir {
+        let entry = entry.map_err(|err| Error::new(err, "reading dir", PathArc::new(dir)))?;
+        let name = entry.file_name();
+        let existing = name
+            .to_str()
+            .and_then(|n| n.strip_prefix(prefix))
+            .and_then(|n| n.strip_suffix(suffix))
+            .filter(|n| !n.is_empty() && n.bytes().all(|b| b.is_ascii_digit()))
+            .and_then(|n| n.parse::<u64>().ok());
+        if let Some(existing) = existing {
+            seq = seq.max(existing + 1);
+        }
+    }
+
+    // another thread or process may take the number first, in which case try the next one
+    loop {
+        let path = dir.join(format!(
+            "{}{:0width$}{}",
+            prefix,
+            seq,
+            suffix,
+            width = width
+        ));
+        match open_file(&path, true) {
+            Ok(()) => return Ok(PathFile::new(path)?),
+            Err(ref err) if err.kind() == io::ErrorKind::AlreadyExists => seq += 1,
+            Err(err) => return Err(err),
+        }
+    }
+}
+
+fn create_parent(path: &Path) -> Result<()> {
+    match path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => fs::create_dir_all(parent)
+            .map_err(|err| Error::new(err, "creating dir", PathArc::new(parent))),
+        _ => Ok(()),
+    }
+}
+
+fn open_file(path: &Path, new: bool) -> Result<()> {
+    let mut options = fs::OpenOptions::new();
+    options.write(true);
+    if new {
+        options.create_new(true);
+    } else {
+        options.create(true);
+    }
+    options
+        .open(path)
+        .map(|_| ())
+        .map_err(|err| Error::new(err, "creating", PathArc::new(path)))
+}
+
+fn is_separator(c: char) -> bool {
+    ::std::path::is_separator(c)
+}
@@ -0,0 +1,56 @@
+/* Copyright (c) 2018 Garrett Berg, vitiral@gmail.com
+ *
+ * Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+ * http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+ * http://opensource.org/licenses/MIT>, at your option. This file may not be
+ * copied, modified, or distributed except according to those terms.
+ */
+//! Listing directories in a deterministic order.
+
+use error::{Error, Result};
+use path_abs::{PathArc, PathType};
+use sort::natural_cmp_names;
+use std::ffi::OsString;
+use std::fs;
+use std::vec;
+use std_prelude::*;
+
+/// The entries of a directory in natural order, returned by
+/// [`PathDirExt::read_sorted`](trait.PathDirExt.html#method.read_sorted).
+///
+/// The names are read and sorted up front (which is cheap), each entry is only resolved to a
+/// `PathType` when it is yielded.
+#[derive(Debug)]
+pub struct ReadSorted {
+    dir: PathBuf,
+    names: vec::IntoIter<OsString>,
+}
+
+impl ReadSorted {
+    pub(crate) fn new(dir: &Path) -> Result<ReadSorted> {
+        let err = |err| Error::new(err, "reading dir", PathArc::new(dir));
+        let mut names = Vec::new();
+        for entry in fs::read_dir(dir).map_err(err)? {
+            names.push(entry.map_err(err)?.file_name());
+        }
+        names.sort_by(|a, b| natural_cmp_names(a, b));
+        Ok(ReadSorted {
+            dir: dir.to_path_buf(),
+            names: names.into_iter(),
+        })
+    }
+}
+
+impl Iterator for ReadSorted {
+    type Item = Result<PathType>;
+
+    fn next(&mut self) -> Option<Result<PathType>> {
+        self.names
+            .next()
+            .map(|name| PathType::new(self.dir.join(name)).map_err(Error::from))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.names.size_hint()
+    }
+}
@@ -0,0 +1,81 @@
+/* Copyright (c) 2018 Garrett Berg, vitiral@gmail.com
+ *
+ * Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+ * http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+ * http://opensource.org/licenses/MIT>, at your option. This file may not be
+ * copied, modified, or distributed except according to those terms.
+ */
+//! Readahead hints for reading many files.
+
+use std::io;
+use std_prelude::*;
+
+/// The number of bytes at the start of each file to prefetch. Files smaller than this are read
+/// entirely from the page cache.
+pub const READAHEAD_LEN: u64 = 256 * 1024;
+
+/// Hint to the OS that the start of the file at `path` will be read soon.
+///
+/// Bulk operations over trees with millions of small files (i.e. `ergo::deep_copy`) are bound
+/// by the latency of each read: a thread copies one file, waits for the disk, copies the next.
+/// Calling this ahead of time (i.e. from the thread that walks the tree) lets the kernel fetch
+/// the first [`READAHEAD_LEN`] bytes of many files concurrently, so the reads which follow hit
+/// the page cache.
+///
+/// This is only implemented on Linux with the `readahead` feature enabled, where it uses
+/// `posix_fadvise(POSIX_FADV_WILLNEED)`. Otherwise it does nothing. It is only a hint, so it is
+/// always safe to call.
+///
+/// [`READAHEAD_LEN`]: constant.READAHEAD_LEN.html
+///
+/// # Examples
+/// ```rust
+/// # extern crate ergo_fs;
+/// use ergo_fs::*;
+///
+/// # fn try_main() -> ::std::io::Result<()> {
+/// for file in glob("src/*.rs").unwrap().files() {
+///     readahead(file?)?;
+/// }
+/// # Ok(()) } fn main() { try_main().unwrap() }
+/// ```
+pub fn readahead<P: AsRef<Path>>(path: P) -> io::Result<()> {
+    imp::readahead(path.as_ref())
+}
+
+#[cfg(all(target_os = "linux", feature = "readahead"))]
+mod imp {
+    use super::READAHEAD_LEN;
+    use libc;
+    use std::fs::File;
+    use std::io;
+    use std::os::unix::io::AsRawFd;
+    use std_prelude::*;
+
+    pub(super) fn readahead(path: &Path) -> io::Result<()> {
+        let file = File::open(path)?;
+        let res = unsafe {
+            libc::posix_fadvise(
+                file.as_raw_fd(),
+                0,
+                READAHEAD_LEN as libc::off_t,
+                libc::POSIX_FADV_WILLNEED,
+            )
+        };
+        // posix_fadvise returns the error rather than setting errno
+        match res {
+            0 => Ok(()),
+            code => Err(io::Error::from_raw_os_error(code)),
+        }
+    }
+}
+
+#[cfg(not(all(target_os = "linux", feature = "readahead")))]
+mod imp {
+    use std::io;
+    use std_prelude::*;
+
+    pub(super) fn readahead(_path: &Path) -> io::Result<()> {
+        Ok(())
+    }
+}
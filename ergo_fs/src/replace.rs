@@ -0,0 +1,218 @@
+/* Copyright (c) 2018 Garrett Berg, vitiral@gmail.com
+ *
+ * Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+ * http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+ * http://opensource.org/licenses/MIT>, at your option. This file may not be
+ * copied, modified, or distributed except according to those terms.
+ */
+//! Editing files in place atomically.
+
+use copy::sync_dir;
+use error::{Error, Result};
+use faults::{self, FsOp};
+use path_abs::{FileWrite, PathArc};
+use std::fs::{self, File};
+use std::io::{self, BufRead, BufReader, BufWriter};
+use std_prelude::*;
+use unique::create_unique;
+
+/// What to do with a line, returned by the closure of
+/// [`PathFileExt::edit_lines`](trait.PathFileExt.html#method.edit_lines).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LineEdit {
+    /// Keep the line as it is.
+    Keep,
+    /// Replace the line. The line ending of the original line is kept.
+    Replace(String),
+    /// Remove the line (and its line ending).
+    Delete,
+}
+
+/// Edit each line of `path` with `edit`, returning the number of lines which changed.
+pub(crate) fn edit_lines<F>(path: &Path, mut edit: F) -> Result<usize>
+where
+    F: FnMut(&str) -> LineEdit,
+{
+    let read_err = |err| Error::new(err, "reading", PathArc::new(path));
+    let mut reader = BufReader::new(File::open(path).map_err(read_err)?);
+    let mut changed = 0;
+    replace_with(path, |out| {
+        let mut buf = Vec::new();
+        let mut number = 0;
+        loop {
+            buf.clear();
+            if reader.read_until(b'\n', &mut buf).map_err(read_err)? == 0 {
+                break;
+            }
+            number += 1;
+            let (line, ending) = split_ending(&buf);
+            let line = ::std::str::from_utf8(line).map_err(|err| {
+                Error::new(
+                    io::Error::new(io::ErrorKind::InvalidData, err),
+                    &format!("decoding line {} of", number),
+                    PathArc::new(path),
+                )
+            })?;
+            let write_err = |err| Error::new(err, "writing the edit of", PathArc::new(path));
+            match edit(line) {
+                LineEdit::Keep => out.write_all(&buf).map_err(write_err)?,
+                LineEdit::Replace(new) => {
+                    if new != line {
+                        changed += 1;
+                    }
+                    out.write_all(new.as_bytes()).map_err(write_err)?;
+                    out.write_all(ending).map_err(write_err)?;
+                }
+                LineEdit::Delete => changed += 1,
+            }
+        }
+        Ok(changed > 0)
+    })?;
+    Ok(changed)
+}
+
+/// Insert or replace the block of `content` between the `marker` lines in `path`, returning
+/// whether the file changed.
+pub(crate) fn ensure_block(path: &Path, marker: &str, content: &str) -> Result<bool> {
+    let old =
+        fs::read_to_string(path).map_err(|err| Error::new(err, "reading", PathArc::new(path)))?;
+    let begin = format!("{} BEGIN", marker);
+    let end = format!("{} END", marker);
+    let mut block = format!("{}\n{}", begin, content);
+    if !content.is_empty() && !content.ends_with('\n') {
+        block.push('\n');
+    }
+    block.push_str(&end);
+    block.push('\n');
+
+    let mut new = String::with_capacity(old.len() + block.len() + 1);
+    match find_block(&old, &begin, &end) {
+        Block::At(start, stop) => {
+            new.push_str(&old[..start]);
+            new.push_str(&block);
+            new.push_str(&old[stop..]);
+        }
+        Block::Unclosed(line) => {
+            let err = io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("`{}` on line {} has no matching `{}`", begin, line, end),
+            );
+            return Err(Error::new(err, "finding block in", PathArc::new(path)));
+        }
+        Block::Missing => {
+            new.push_str(&old);
+            if !old.is_empty() && !old.ends_with('\n') {
+                new.push('\n');
+            }
+            new.push_str(&block);
+        }
+    }
+    if new == old {
+        return Ok(false);
+    }
+    replace_with(path, |out| {
+        out.write_all(new.as_bytes())
+            .map_err(|err| Error::new(err, "writing the edit of", PathArc::new(path)))?;
+        Ok(true)
+    })
+}
+
+enum Block {
+    /// The byte range from the start of the `begin` line to the end of the `end` line.
+    At(usize, usize),
+    /// The line number of a `begin` line without an `end` line.
+    Unclosed(usize),
+    Missing,
+}
+
+fn find_block(text: &str, begin: &str, end: &str) -> Block {
+    let mut offset = 0;
+    let mut start = None;
+    for (number, line) in text.split_inclusive('\n').enumerate() {
+        let trimmed = line.trim_end();
+        match start {
+            None if trimmed == begin => start = Some((offset, number + 1)),
+            Some((start, _)) if trimmed == end => return Block::At(start, offset + line.len()),
+            _ => {}
+        }
+        offset += line.len();
+    }
+    match start {
+        Some((_, number)) => Block::Unclosed(number),
+        None => Block::Missing,
+    }
+}
+
+/// Write the new contents of `path` to a temporary file next to it with `write` and rename it
+/// over `path`, so that readers see either the old or the new contents and never a partial
+/// file.
+///
+/// If `path` is a symlink its target is replaced, so the link survives. The new file gets the
+/// permissions of the old one and is synced before the rename, and the directory is synced after
+/// it. If `write` fails or returns `false` (nothing changed) the temporary file is removed and
+/// `path` is left untouched.
+pub(crate) fn replace_with<F>(path: &Path, write: F) -> Result<bool>
+where
+    F: FnOnce(&mut BufWriter<FileWrite>) -> Result<bool>,
+{
+    let path = &fs::canonicalize(path)
+        .map_err(|err| Error::new(err, "canonicalizing", PathArc::new(path)))?;
+    let name = path
+        .file_name()
+        .expect("files have a name")
+        .to_string_lossy()
+        .into_owned();
+    let dir = path.parent().expect("files have a parent");
+    let perms = fs::metadata(path)
+        .map_err(|err| Error::new(err, "reading metadata", PathArc::new(path)))?
+        .permissions();
+    let (tmp, file) = create_unique(dir, &format!(".{}.", name), ".tmp")?;
+
+    let mut out = BufWriter::new(file);
+    let written = faults::check(FsOp::Write, &tmp)
+        .map_err(|err| Error::new(err, "writing", PathArc::new(&tmp)))
+        .and_then(|()| write(&mut out))
+        .and_then(|changed| {
+            if !changed {
+                return Ok(false);
+            }
+            let file = out
+                .into_inner()
+                .map_err(|err| Error::new(err.into_error(), "writing", PathArc::new(&tmp)))?;
+            file.sync_all()?;
+            fs::set_permissions(&tmp, perms)
+                .map_err(|err| Error::new(err, "setting permissions of", PathArc::new(&tmp)))?;
+            Ok(true)
+        });
+    match written {
+        Ok(true) => {}
+        Ok(false) => {
+            let _ = fs::remove_file(&tmp);
+            return Ok(false);
+        }
+        Err(err) => {
+            let _ = fs::remove_file(&tmp);
+            return Err(err);
+        }
+    }
+    faults::check(FsOp::Rename, path)
+        .and_then(|()| fs::rename(&tmp, path))
+        .map_err(|err| {
+            let _ = fs::remove_file(&tmp);
+            Error::with_paths(err, "renaming", PathArc::new(&tmp), PathArc::new(path))
+        })?;
+    sync_dir(dir)?;
+    Ok(true)
+}
+
+/// Split a line into its contents and its line ending (`\n`, `\r\n` or nothing).
+fn split_ending(line: &[u8]) -> (&[u8], &[u8]) {
+    let len = if line.ends_with(b"\r\n") {
+        2
+    } else if line.ends_with(b"\n") {
+        1
+    } else {
+        0
+    };
+    line.split_at(line.len() - len)
+}
@@ -0,0 +1,233 @@
+/* Copyright (c) 2018 Garrett Berg, vitiral@gmail.com
+ *
+ * Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+ * http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+ * http://opensource.org/licenses/MIT>, at your option. This file may not be
+ * copied, modified, or distributed except according to those terms.
+ */
+//! Retrying of operations which fail with transient errors.
+
+use error::Error;
+use path_abs;
+use std::cmp;
+use std::fmt;
+use std::io;
+use std::thread;
+use std_prelude::*;
+
+/// An error which may be _transient_, meaning that retrying the operation may succeed.
+///
+/// Transient errors are:
+///
+/// - `Interrupted` (`EINTR`), `WouldBlock` (`EAGAIN`) and `TimedOut`.
+/// - `ConnectionReset` and `ConnectionAborted`, i.e. a network blip on a network mount.
+/// - On unix: `EBUSY` and `ESTALE` (a stale NFS file handle).
+/// - On windows: sharing and lock violations and lost network connections.
+pub trait Transient {
+    /// Return whether the error is transient.
+    fn is_transient(&self) -> bool;
+}
+
+impl Transient for io::Error {
+    fn is_transient(&self) -> bool {
+        match self.kind() {
+            io::ErrorKind::Interrupted
+            | io::ErrorKind::WouldBlock
+            | io::ErrorKind::TimedOut
+            | io::ErrorKind::ConnectionReset
+            | io::ErrorKind::ConnectionAborted => true,
+            _ => match self.raw_os_error() {
+                Some(code) => TRANSIENT_OS_ERRORS.contains(&code),
+                None => false,
+            },
+        }
+    }
+}
+
+impl Transient for path_abs::Error {
+    fn is_transient(&self) -> bool {
+        self.io_error().is_transient()
+    }
+}
+
+impl Transient for Error {
+    fn is_transient(&self) -> bool {
+        self.io_error().is_transient()
+    }
+}
+
+/// `EBUSY`, `ESTALE`
+#[cfg(target_os = "linux")]
+const TRANSIENT_OS_ERRORS: &[i32] = &[16, 116];
+
+/// `EBUSY`, `ESTALE`
+#[cfg(all(unix, not(target_os = "linux")))]
+const TRANSIENT_OS_ERRORS: &[i32] = &[16, 70];
+
+/// `ERROR_SHARING_VIOLATION`, `ERROR_LOCK_VIOLATION`, `ERROR_UNEXP_NET_ERR`,
+/// `ERROR_NETNAME_DELETED`, `ERROR_SEM_TIMEOUT`
+#[cfg(windows)]
+const TRANSIENT_OS_ERRORS: &[i32] = &[32, 33, 59, 64, 121];
+
+#[cfg(not(any(unix, windows)))]
+const TRANSIENT_OS_ERRORS: &[i32] = &[];
+
+/// A policy for retrying operations which fail with [`Transient`] errors, using exponential
+/// backoff.
+///
+/// Copies to network mounts (SMB/NFS) fail spuriously. Rather than aborting a whole job, wrap
+/// the operation with a `RetryPolicy`. Errors which are not transient are returned immediately.
+///
+/// The default policy tries an operation 5 times, sleeping 10ms before the first retry and
+/// doubling the sleep for each retry after up to a maximum of 1s.
+///
+/// [`Transient`]: trait.Transient.html
+///
+/// # Examples
+/// ```rust
+/// # extern crate ergo_fs;
+/// use ergo_fs::*;
+///
+/// # fn try_main() -> ::std::io::Result<()> {
+/// let tmp = PathTmp::create("retry")?;
+/// let file = PathFile::create(tmp.join("example.txt"))?;
+///
+/// let retry = RetryPolicy::new().attempts(3);
+/// let copied = retry.run(|| file.copy(tmp.join("copied.txt")))?;
+/// assert!(copied.exists());
+/// # Ok(()) } fn main() { try_main().unwrap() }
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RetryPolicy {
+    attempts: u32,
+    initial: Duration,
+    max: Duration,
+    sleep: Sleep,
+}
+
+/// The function a `RetryPolicy` sleeps with, `thread::sleep` if `None`.
+#[derive(Clone, Default)]
+struct Sleep(Option<Arc<dyn Fn(Duration) + Send + Sync>>);
+
+impl RetryPolicy {
+    /// Create the default retry policy.
+    pub fn new() -> RetryPolicy {
+        RetryPolicy {
+            attempts: 5,
+            initial: Duration::from_millis(10),
+            max: Duration::from_secs(1),
+            sleep: Sleep::default(),
+        }
+    }
+
+    /// A policy which never retries, the operation is run exactly once.
+    pub fn never() -> RetryPolicy {
+        RetryPolicy::new().attempts(1)
+    }
+
+    /// Set the total number of times the operation is attempted (including the first).
+    ///
+    /// Values less than 1 are treated as 1.
+    pub fn attempts(mut self, attempts: u32) -> RetryPolicy {
+        self.attempts = cmp::max(attempts, 1);
+        self
+    }
+
+    /// Set the sleep before the first retry and the maximum sleep between retries.
+    pub fn backoff(mut self, initial: Duration, max: Duration) -> RetryPolicy {
+        self.initial = initial;
+        self.max = max;
+        self
+    }
+
+    /// Sleep between attempts with `sleep` instead of `thread::sleep`.
+    ///
+    /// Use this to test code which retries without waiting for the backoff, i.e. by recording
+    /// the sleeps or sleeping on an `ergo_sync::ManualClock` with
+    /// `move |dur| clock.sleep(dur)`.
+    ///
+    /// # Examples
+    /// ```rust
+    /// # extern crate ergo_fs;
+    /// use std::io;
+    /// use std::sync::{Arc, Mutex};
+    /// use ergo_fs::*;
+    ///
+    /// # fn main() {
+    /// let slept = Arc::new(Mutex::new(Vec::new()));
+    /// let retry = {
+    ///     let slept = slept.clone();
+    ///     RetryPolicy::new().sleep_with(move |dur| slept.lock().unwrap().push(dur))
+    /// };
+    ///
+    /// let result: Result<(), io::Error> = retry.run(|| Err(io::ErrorKind::TimedOut.into()));
+    /// assert!(result.is_err());
+    /// let ms: Vec<_> = slept.lock().unwrap().iter().map(|d| d.subsec_millis()).collect();
+    /// assert_eq!(vec![10, 20, 40, 80], ms);
+    /// # }
+    /// ```
+    pub fn sleep_with<F>(mut self, sleep: F) -> RetryPolicy
+    where
+        F: Fn(Duration) + Send + Sync + 'static,
+    {
+        self.sleep = Sleep(Some(Arc::new(sleep)));
+        self
+    }
+
+    /// Run the operation, retrying it according to this policy when it fails with a transient
+    /// error.
+    ///
+    /// The last error is returned if all attempts fail.
+    pub fn run<T, E, F>(&self, mut op: F) -> Result<T, E>
+    where
+        E: Transient,
+        F: FnMut() -> Result<T, E>,
+    {
+        let mut sleep = self.initial;
+        let mut attempt = 1;
+        loop {
+            match op() {
+                Ok(v) => return Ok(v),
+                Err(err) => {
+                    if attempt >= self.attempts || !err.is_transient() {
+                        return Err(err);
+                    }
+                }
+            }
+            match self.sleep.0 {
+                Some(ref f) => f(sleep),
+                None => thread::sleep(sleep),
+            }
+            sleep = cmp::min(sleep * 2, self.max);
+            attempt += 1;
+        }
+    }
+}
+
+impl Default for RetryPolicy {
+    fn default() -> RetryPolicy {
+        RetryPolicy::new()
+    }
+}
+
+impl fmt::Debug for Sleep {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.0 {
+            Some(_) => f.write_str("Custom"),
+            None => f.write_str("Thread"),
+        }
+    }
+}
+
+/// Custom sleeps are only equal to themselves.
+impl PartialEq for Sleep {
+    fn eq(&self, other: &Sleep) -> bool {
+        match (&self.0, &other.0) {
+            (Some(a), Some(b)) => Arc::ptr_eq(a, b),
+            (None, None) => true,
+            _ => false,
+        }
+    }
+}
+
+impl Eq for Sleep {}
@@ -0,0 +1,309 @@
+/* Copyright (c) 2018 Garrett Berg, vitiral@gmail.com
+ *
+ * Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+ * http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+ * http://opensource.org/licenses/MIT>, at your option. This file may not be
+ * copied, modified, or distributed except according to those terms.
+ */
+//! Files which are rotated, i.e. for logs.
+
+use chrono::{DateTime, Local, NaiveDate};
+use error::{Error, Result};
+use faults::{self, FsOp};
+use path_abs::{FileWrite, PathArc, PathFile};
+use std::fs;
+use std::io::{self, Write};
+use std_prelude::*;
+
+/// When a [`RotatingFile`](struct.RotatingFile.html) is rotated.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RotatePolicy {
+    /// Rotate before a write would make the file larger than this many bytes.
+    ///
+    /// A single write larger than this is still written whole, to an empty file.
+    Size(u64),
+    /// Rotate on the first write of each (local) day.
+    Daily,
+}
+
+/// A file which is appended to and rotated when it becomes too large or a new day starts.
+///
+/// Rotating renames the file to `{name}.{date}` (for [`Daily`]) or `{name}.{date}-{time}` (for
+/// [`Size`]) in the same directory and then starts a new, empty file at the original path. The
+/// rename is atomic so readers (i.e. `tail -F`) never see a partial file. If the rotated name
+/// already exists `.1`, `.2`, etc is appended.
+///
+/// With [`max_keep`] only that many rotated files are kept, the oldest are removed. With the
+/// `gzip` feature, rotated files can also be compressed to `{name}.{date}.gz`.
+///
+/// Only one `RotatingFile` should write to a path at a time. Wrap it in a `Mutex` to share it
+/// between threads.
+///
+/// [`Daily`]: enum.RotatePolicy.html#variant.Daily
+/// [`Size`]: enum.RotatePolicy.html#variant.Size
+/// [`max_keep`]: #method.max_keep
+///
+/// # Examples
+/// ```rust
+/// # extern crate ergo_fs;
+/// use std::io::Write;
+/// use ergo_fs::*;
+///
+/// # fn try_main() -> ::std::io::Result<()> {
+/// let tmp = PathTmp::create("logs")?;
+/// let mut log = RotatingFile::new(tmp.join("app.log"), RotatePolicy::Size(10))?
+///     .max_keep(2);
+///
+/// for i in 0..4 {
+///     writeln!(log, "line {}", i)?; // 7 bytes, so each line rotates the file
+/// }
+/// log.flush()?;
+///
+/// assert_eq!("line 3\n", log.path().read_string()?);
+/// assert_eq!(3, std::fs::read_dir(&tmp)?.count()); // app.log and the 2 newest rotated files
+/// # Ok(()) } fn main() { try_main().unwrap() }
+/// ```
+#[derive(Debug)]
+pub struct RotatingFile {
+    file: FileWrite,
+    path: PathFile,
+    policy: RotatePolicy,
+    max_keep: Option<usize>,
+    gzip: bool,
+    /// The bytes in the current file.
+    size: u64,
+    /// The day the current file was started.
+    day: NaiveDate,
+}
+
+impl RotatingFile {
+    /// Open the file at `path` for appending, creating it if it doesn't exist.
+    ///
+    /// An existing file which was last written on an earlier day is rotated on the first write
+    /// with the `Daily` policy.
+    pub fn new<P: AsRef<Path>>(path: P, policy: RotatePolicy) -> Result<RotatingFile> {
+        let file = FileWrite::append(path)?;
+        let meta = file.metadata()?;
+        let day = match meta.modified() {
+            Ok(m) if meta.len() > 0 => DateTime::<Local>::from(m).date_naive(),
+            _ => today(),
+        };
+        Ok(RotatingFile {
+            path: file.path().clone(),
+            file,
+            policy,
+            max_keep: None,
+            gzip: false,
+            size: meta.len(),
+            day,
+        })
+    }
+
+    /// Keep at most `count` rotated files, removing the oldest after each rotation.
+    /// Default: keep all of them.
+    pub fn max_keep(mut self, count: usize) -> Self {
+        self.max_keep = Some(count);
+        self
+    }
+
+    /// Compress rotated files with gzip, adding `.gz` to their name. Requires the `gzip`
+    /// feature.
+    #[cfg(feature = "gzip")]
+    pub fn gzip(mut self, gzip: bool) -> Self {
+        self.gzip = gzip;
+        self
+    }
+
+    /// The path of the file being written to.
+    pub fn path(&self) -> &PathFile {
+        &self.path
+    }
+
+    /// The policy for rotating the file.
+    pub fn policy(&self) -> RotatePolicy {
+        self.policy
+    }
+
+    /// Rotate the file now, returning the path of the rotated file or `None` if the file was
+    /// empty (and so was not rotated).
+    ///
+    /// This is called by `write` according to the policy, call it directly to rotate at other
+    /// times (i.e. on `SIGHUP`).
+    pub fn rotate(&mut self) -> Result<Option<PathFile>> {
+        if self.size == 0 {
+            self.day = today();
+            return Ok(None);
+        }
+        self.file.flush()?;
+        self.file.sync_all()?;
+
+        let name = match self.policy {
+            RotatePolicy::Daily => self.day.format("%Y-%m-%d").to_string(),
+            RotatePolicy::Size(_) => Local::now().format("%Y-%m-%d-%H%M%S").to_string(),
+        };
+        let rotated = self.free_name(&name)?;
+        faults::check(FsOp::Rename, &rotated)
+            .and_then(|()| fs::rename(&self.path, &rotated))
+            .map_err(|err| {
+                Error::with_paths(
+                    err,
+                    "rotating",
+                    self.path.clone().into(),
+                    PathArc::new(&rotated),
+                )
+            })?;
+        self.file = FileWrite::append(&self.path)?;
+        self.size = 0;
+        self.day = today();
+
+        let rotated = if self.gzip {
+            compress(&rotated)?
+        } else {
+            rotated
+        };
+        let rotated = PathFile::new(rotated)?;
+        if let Some(keep) = self.max_keep {
+            self.remove_old(keep)?;
+        }
+        Ok(Some(rotated))
+    }
+
+    fn should_rotate(&self, len: usize) -> bool {
+        match self.policy {
+            RotatePolicy::Size(max) => self.size > 0 && self.size + len as u64 > max,
+            RotatePolicy::Daily => self.day != today(),
+        }
+    }
+
+    /// `{name}.{suffix}`, with a number appended (one more than the largest one) if it already
+    /// exists.
+    fn free_name(&self, suffix: &str) -> Result<PathBuf> {
+        let base = format!("{}.{}", self.file_name(), suffix);
+        let dir = self.dir();
+        let read_dir =
+            fs::read_dir(&dir).map_err(|err| Error::new(err, "reading dir", PathArc::new(&dir)))?;
+        let mut next = None;
+        for entry in read_dir {
+            let entry = entry.map_err(|err| Error::new(err, "reading dir", PathArc::new(&dir)))?;
+            let name = entry.file_name().to_string_lossy().into_owned();
+            let name = name.trim_end_matches(".gz");
+            if name == base {
+                next = next.max(Some(1));
+            } else if let Some(n) = name
+                .strip_prefix(&base)
+                .and_then(|n| n.strip_prefix('.'))
+                .and_then(|n| n.parse::<u64>().ok())
+            {
+                next = next.max(Some(n + 1));
+            }
+        }
+        Ok(match next {
+            None => dir.join(base),
+            Some(n) => dir.join(format!("{}.{}", base, n)),
+        })
+    }
+
+    /// Remove all but the newest `keep` rotated files.
+    fn remove_old(&self, keep: usize) -> Result<()> {
+        let dir = self.dir();
+        let prefix = format!("{}.", self.file_name());
+        let read_dir =
+            fs::read_dir(&dir).map_err(|err| Error::new(err, "reading dir", PathArc::new(&dir)))?;
+        let mut rotated = Vec::new();
+        for entry in read_dir {
+            let entry = entry.map_err(|err| Error::new(err, "reading dir", PathArc::new(&dir)))?;
+            let name = entry.file_name().to_string_lossy().into_owned();
+            let is_rotated = name.starts_with(&prefix)
+                && !name.ends_with(".tmp")
+                && name[prefix.len()..].starts_with(|c: char| c.is_ascii_digit());
+            if !is_rotated {
+                continue;
+            }
+            let modified = entry.metadata().and_then(|m| m.modified()).ok();
+            rotated.push((modified, name, entry.path()));
+        }
+        // newest first
+        rotated.sort_by(|a, b| b.cmp(a));
+        for (_, _, path) in rotated.into_iter().skip(keep) {
+            match fs::remove_file(&path) {
+                Ok(()) => {}
+                Err(ref err) if err.kind() == io::ErrorKind::NotFound => {}
+                Err(err) => return Err(Error::new(err, "removing", PathArc::new(&path))),
+            }
+        }
+        Ok(())
+    }
+
+    fn file_name(&self) -> String {
+        self.path
+            .file_name()
+            .expect("file has a name")
+            .to_string_lossy()
+            .into_owned()
+    }
+
+    fn dir(&self) -> PathBuf {
+        self.path.parent().expect("file has a parent").to_path_buf()
+    }
+}
+
+impl Write for RotatingFile {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if self.should_rotate(buf.len()) {
+            self.rotate()?;
+        }
+        faults::check(FsOp::Write, &self.path)?;
+        let n = self.file.write(buf)?;
+        self.size += n as u64;
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Write::flush(&mut self.file)
+    }
+}
+
+fn today() -> NaiveDate {
+    Local::now().date_naive()
+}
+
+/// Compress `path` to `{path}.gz` and remove it, returning the compressed path.
+#[cfg(feature = "gzip")]
+fn compress(path: &Path) -> Result<PathBuf> {
+    use flate2::write::GzEncoder;
+    use flate2::Compression;
+
+    let mut gz_name = path.as_os_str().to_owned();
+    gz_name.push(".gz");
+    let gz = PathBuf::from(gz_name);
+    let mut tmp = gz.clone().into_os_string();
+    tmp.push(".tmp");
+    let tmp = PathBuf::from(tmp);
+
+    let written = fs::File::open(path)
+        .map_err(|err| Error::new(err, "opening", PathArc::new(path)))
+        .and_then(|mut src| {
+            let out = fs::File::create(&tmp)
+                .map_err(|err| Error::new(err, "creating", PathArc::new(&tmp)))?;
+            let mut enc = GzEncoder::new(out, Compression::default());
+            io::copy(&mut src, &mut enc)
+                .and_then(|_| enc.finish())
+                .and_then(|out| out.sync_all())
+                .map_err(|err| {
+                    Error::with_paths(err, "compressing", PathArc::new(path), PathArc::new(&tmp))
+                })
+        });
+    if let Err(err) = written {
+        let _ = fs::remove_file(&tmp);
+        return Err(err);
+    }
+    fs::rename(&tmp, &gz)
+        .map_err(|err| Error::with_paths(err, "renaming", PathArc::new(&tmp), PathArc::new(&gz)))?;
+    fs::remove_file(path).map_err(|err| Error::new(err, "removing", PathArc::new(path)))?;
+    Ok(gz)
+}
+
+#[cfg(not(feature = "gzip"))]
+fn compress(_path: &Path) -> Result<PathBuf> {
+    unreachable!("gzip is only set with the gzip feature")
+}
@@ -0,0 +1,92 @@
+/* Copyright (c) 2018 Garrett Berg, vitiral@gmail.com
+ *
+ * Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+ * http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+ * http://opensource.org/licenses/MIT>, at your option. This file may not be
+ * copied, modified, or distributed except according to those terms.
+ */
+//! Making file names valid on every platform.
+
+/// Device names which windows reserves in every directory, with or without an extension.
+pub(crate) const RESERVED_NAMES: &[&str] = &[
+    "CON", "PRN", "AUX", "NUL", "COM1", "COM2", "COM3", "COM4", "COM5", "COM6", "COM7", "COM8",
+    "COM9", "LPT1", "LPT2", "LPT3", "LPT4", "LPT5", "LPT6", "LPT7", "LPT8", "LPT9",
+];
+
+/// The characters which windows does not allow in file names, besides control characters.
+const WINDOWS_INVALID: &str = "<>:\"/\\|?*";
+
+/// The maximum length of a file name in bytes, the limit of most filesystems.
+const MAX_LEN: usize = 255;
+
+/// The platforms which a file name must be valid on, see
+/// [`sanitize_filename`](fn.sanitize_filename.html).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Platform {
+    /// Valid on every platform, for names which may be copied between systems.
+    All,
+    /// Valid on unix: no `/` or NUL.
+    Unix,
+    /// Valid on windows: none of `<>:"/\|?*` or control characters, no reserved device names
+    /// (`CON`, `NUL`, ...) and no trailing dots or spaces.
+    Windows,
+}
+
+/// Make `name` a valid file name on `platform`, i.e. a title or URL from user input which will
+/// be joined onto a directory.
+///
+/// Invalid characters are replaced with `_`, reserved windows names get a `_` appended to
+/// their stem (`CON.txt` becomes `CON_.txt`) and trailing dots and spaces are removed for
+/// windows. Names longer than 255 bytes are truncated. The result is never empty, `.` or `..`
+/// and never contains a path separator, so it is always a single component.
+///
+/// # Examples
+/// ```rust
+/// # extern crate ergo_fs;
+/// use ergo_fs::*;
+///
+/// assert_eq!("Q_A_ what_.txt", sanitize_filename("Q/A: what?.txt", Platform::All));
+/// assert_eq!("Q_A: what?.txt", sanitize_filename("Q/A: what?.txt", Platform::Unix));
+/// assert_eq!("nul_.tar.gz", sanitize_filename("nul.tar.gz", Platform::All));
+/// assert_eq!("notes", sanitize_filename("notes. . ", Platform::Windows));
+/// assert_eq!("_", sanitize_filename("..", Platform::All));
+/// ```
+pub fn sanitize_filename(name: &str, platform: Platform) -> String {
+    let windows = platform != Platform::Unix;
+    let mut out: String = name
+        .chars()
+        .map(|c| match c {
+            '/' | '\0' => '_',
+            c if windows && (c < ' ' || WINDOWS_INVALID.contains(c)) => '_',
+            c => c,
+        })
+        .collect();
+    if windows {
+        trim_end(&mut out);
+        let stem_len = out.find('.').unwrap_or(out.len());
+        let stem = out[..stem_len].trim_end();
+        if RESERVED_NAMES.iter().any(|r| stem.eq_ignore_ascii_case(r)) {
+            out.insert(stem_len, '_');
+        }
+    }
+    if out.len() > MAX_LEN {
+        let mut end = MAX_LEN;
+        while !out.is_char_boundary(end) {
+            end -= 1;
+        }
+        out.truncate(end);
+        if windows {
+            trim_end(&mut out);
+        }
+    }
+    match out.as_str() {
+        "" | "." | ".." => "_".to_string(),
+        _ => out,
+    }
+}
+
+/// Remove the trailing dots and spaces, which windows drops from file names.
+fn trim_end(name: &mut String) {
+    let len = name.trim_end_matches(['.', ' ']).len();
+    name.truncate(len);
+}
@@ -0,0 +1,212 @@
+/* Copyright (c) 2018 Garrett Berg, vitiral@gmail.com
+ *
+ * Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+ * http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+ * http://opensource.org/licenses/MIT>, at your option. This file may not be
+ * copied, modified, or distributed except according to those terms.
+ */
+//! Snapshot assertions for directory trees, for testing.
+//!
+//! Integration tests of features which copy, sync or extract directories end up asserting on
+//! every file they produce. Instead, render the whole tree with [`render_tree`] and compare it
+//! against a snapshot file stored with the tests using [`assert_tree_matches!`].
+//!
+//! - If the snapshot file does not exist it is created (and the assertion passes). Review it
+//!   and commit it with your tests.
+//! - If it exists and does not match, the assertion panics with a line diff of the snapshot
+//!   (`-`) against the actual tree (`+`).
+//! - Set the environment variable `ERGO_UPDATE_SNAPSHOTS=1` to overwrite snapshots which do not
+//!   match, i.e. after an intentional change.
+//!
+//! [`render_tree`]: fn.render_tree.html
+//! [`assert_tree_matches!`]: ../macro.assert_tree_matches.html
+//!
+//! # Examples
+//! ```rust
+//! #[macro_use] extern crate ergo_fs;
+//! use ergo_fs::*;
+//!
+//! # fn try_main() -> ::std::io::Result<()> {
+//! let tmp = PathTmp::create("snapshot")?;
+//! let dir = PathDir::create(tmp.join("dir"))?;
+//! PathFile::create(dir.join("a.txt"))?.write_str("hello")?;
+//! PathDir::create(dir.join("sub"))?;
+//! PathFile::create(dir.join("sub").join("b.txt"))?;
+//!
+//! assert_eq!(
+//!     "a.txt\nsub/\nsub/b.txt\n",
+//!     snapshot::render_tree(&dir, false)?
+//! );
+//!
+//! // The first run creates the snapshot, later runs compare against it.
+//! let snap = tmp.join("dir.snap");
+//! assert_tree_matches!(&dir, &snap, contents);
+//! assert_tree_matches!(&dir, &snap, contents);
+//! # Ok(()) } fn main() { try_main().unwrap() }
+//! ```
+
+use sort;
+use std::env;
+use std::fmt::Write as FmtWrite;
+use std::fs;
+use std::io;
+use std_prelude::*;
+use walkdir::WalkDir;
+
+/// The environment variable which causes snapshots to be overwritten on mismatch.
+pub const UPDATE_ENV: &str = "ERGO_UPDATE_SNAPSHOTS";
+
+/// Assert that a directory tree matches a stored snapshot file.
+///
+/// - `assert_tree_matches!(dir, snapshot)` compares only the structure: the paths of files,
+///   directories and symlinks.
+/// - `assert_tree_matches!(dir, snapshot, contents)` also compares the length and a hash of
+///   the contents of every file.
+///
+/// See the [`snapshot`](snapshot/index.html) module for details.
+#[macro_export]
+macro_rules! assert_tree_matches {
+    ($dir:expr, $snapshot:expr) => {
+        $crate::snapshot::assert_tree_matches($dir, $snapshot, false)
+    };
+    ($dir:expr, $snapshot:expr, contents) => {
+        $crate::snapshot::assert_tree_matches($dir, $snapshot, true)
+    };
+}
+
+/// Render the tree of `dir` as text, one line per entry sorted by path in
+/// [natural order](../sort/index.html).
+///
+/// Paths are relative to `dir` and use `/` as the separator on every platform. Directories end
+/// with `/` and symlinks (which are not followed) are rendered as `link -> target`. When
+/// `contents` is true each file also has its length and the FNV-1a hash of its contents.
+pub fn render_tree<P: AsRef<Path>>(dir: P, contents: bool) -> io::Result<String> {
+    let dir = dir.as_ref();
+    let mut out = String::new();
+    let walk = WalkDir::new(dir)
+        .min_depth(1)
+        .sort_by(|a, b| sort::natural_cmp_names(a.file_name(), b.file_name()));
+    for entry in walk {
+        let entry = entry?;
+        let rel = entry.path().strip_prefix(dir).map_err(io::Error::other)?;
+        let name = portable(rel);
+        let ty = entry.file_type();
+        if ty.is_dir() {
+            writeln!(out, "{}/", name).expect("write to string");
+        } else if ty.is_symlink() {
+            let target = fs::read_link(entry.path())?;
+            writeln!(out, "{} -> {}", name, portable(&target)).expect("write to string");
+        } else if contents {
+            let data = fs::read(entry.path())?;
+            writeln!(out, "{}  {} bytes  {:016x}", name, data.len(), fnv1a(&data))
+                .expect("write to string");
+        } else {
+            writeln!(out, "{}", name).expect("write to string");
+        }
+    }
+    Ok(out)
+}
+
+/// Assert that the tree of `dir` matches the `snapshot` file. Use the
+/// [`assert_tree_matches!`](../macro.assert_tree_matches.html) macro instead of calling this
+/// directly.
+///
+/// # Panics
+/// Panics with a diff if the tree does not match the snapshot, or if the tree or snapshot
+/// cannot be read.
+pub fn assert_tree_matches<P: AsRef<Path>, S: AsRef<Path>>(dir: P, snapshot: S, contents: bool) {
+    let (dir, snapshot) = (dir.as_ref(), snapshot.as_ref());
+    let actual = render_tree(dir, contents)
+        .unwrap_or_else(|e| panic!("failed to render tree {}: {}", dir.display(), e));
+    let expected = match fs::read_to_string(snapshot) {
+        Ok(s) => s,
+        Err(ref e) if e.kind() == io::ErrorKind::NotFound => {
+            write_snapshot(snapshot, &actual);
+            return;
+        }
+        Err(e) => panic!("failed to read snapshot {}: {}", snapshot.display(), e),
+    };
+    if expected == actual {
+        return;
+    }
+    if env::var_os(UPDATE_ENV).is_some_and(|v| v == "1") {
+        write_snapshot(snapshot, &actual);
+        return;
+    }
+    panic!(
+        "tree {} does not match snapshot {} (set {}=1 to update it):\n{}",
+        dir.display(),
+        snapshot.display(),
+        UPDATE_ENV,
+        diff_lines(&expected, &actual)
+    );
+}
+
+/// Render a line diff of `expected` against `actual`. Lines only in `expected` are prefixed
+/// with `-`, lines only in `actual` with `+` and common lines with a space.
+pub fn diff_lines(expected: &str, actual: &str) -> String {
+    let a: Vec<&str> = expected.lines().collect();
+    let b: Vec<&str> = actual.lines().collect();
+
+    // lcs[i][j] is the length of the longest common subsequence of a[i..] and b[j..]
+    let mut lcs = vec![vec![0_usize; b.len() + 1]; a.len() + 1];
+    for i in (0..a.len()).rev() {
+        for j in (0..b.len()).rev() {
+            lcs[i][j] = if a[i] == b[j] {
+                lcs[i + 1][j + 1] + 1
+            } else {
+                lcs[i + 1][j].max(lcs[i][j + 1])
+            };
+        }
+    }
+
+    let mut out = String::new();
+    let (mut i, mut j) = (0, 0);
+    while i < a.len() || j < b.len() {
+        if i < a.len() && j < b.len() && a[i] == b[j] {
+            writeln!(out, "  {}", a[i]).expect("write to string");
+            i += 1;
+            j += 1;
+        } else if j < b.len() && (i == a.len() || lcs[i][j + 1] >= lcs[i + 1][j]) {
+            writeln!(out, "+ {}", b[j]).expect("write to string");
+            j += 1;
+        } else {
+            writeln!(out, "- {}", a[i]).expect("write to string");
+            i += 1;
+        }
+    }
+    out
+}
+
+pub(crate) fn write_snapshot<C: AsRef<[u8]>>(snapshot: &Path, actual: C) {
+    if let Some(parent) = snapshot.parent() {
+        if !parent.as_os_str().is_empty() {
+            fs::create_dir_all(parent)
+                .unwrap_or_else(|e| panic!("failed to create dir {}: {}", parent.display(), e));
+        }
+    }
+    fs::write(snapshot, actual)
+        .unwrap_or_else(|e| panic!("failed to write snapshot {}: {}", snapshot.display(), e));
+}
+
+/// Render a relative path with `/` separators.
+fn portable(path: &Path) -> String {
+    if path.is_absolute() {
+        return path.display().to_string();
+    }
+    let parts: Vec<_> = path
+        .components()
+        .map(|c| c.as_os_str().to_string_lossy())
+        .collect();
+    parts.join("/")
+}
+
+/// The 64 bit FNV-1a hash, which is stable across platforms and rust versions.
+fn fnv1a(data: &[u8]) -> u64 {
+    let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+    for &byte in data {
+        hash ^= u64::from(byte);
+        hash = hash.wrapping_mul(0x0100_0000_01b3);
+    }
+    hash
+}
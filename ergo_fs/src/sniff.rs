@@ -0,0 +1,222 @@
+/* Copyright (c) 2018 Garrett Berg, vitiral@gmail.com
+ *
+ * Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+ * http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+ * http://opensource.org/licenses/MIT>, at your option. This file may not be
+ * copied, modified, or distributed except according to those terms.
+ */
+//! Guessing the type of a file from its extension and first bytes.
+
+use error::{Error, Result};
+use path_abs::PathArc;
+use std::fmt;
+use std::fs::File;
+use std_prelude::*;
+
+/// The number of bytes read from the start of a file to sniff it.
+pub const SNIFF_LEN: usize = 8192;
+
+/// What [`PathFileExt::sniff`] found out about a file.
+///
+/// [`PathFileExt::sniff`]: trait.PathFileExt.html#method.sniff
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Sniff {
+    /// Whether the file is binary, i.e. it has a known binary signature or a NUL byte in its
+    /// first [`SNIFF_LEN`](constant.SNIFF_LEN.html) bytes. Empty files are text.
+    pub is_binary: bool,
+    /// The guessed MIME type. Falls back to `text/plain` or `application/octet-stream`.
+    pub mime_guess: &'static str,
+    /// The encoding given by the byte order mark at the start of the file, if it has one.
+    pub bom: Option<TextEncoding>,
+}
+
+/// A text encoding. The unicode encodings can be detected from their byte order mark (BOM).
+///
+/// See [`FileReadExt::read_string_with`](trait.FileReadExt.html#method.read_string_with).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum TextEncoding {
+    /// UTF-8, the BOM is `EF BB BF`.
+    Utf8,
+    /// UTF-16 little endian, the BOM is `FF FE`. What windows calls "Unicode".
+    Utf16Le,
+    /// UTF-16 big endian, the BOM is `FE FF`.
+    Utf16Be,
+    /// ISO-8859-1, where every byte is the code point of the same value. It has no BOM.
+    Latin1,
+}
+
+/// Signatures of binary formats, checked against the start of the file.
+const MAGIC: &[(&[u8], &str)] = &[
+    (b"\x89PNG\r\n\x1a\n", "image/png"),
+    (b"\xff\xd8\xff", "image/jpeg"),
+    (b"GIF87a", "image/gif"),
+    (b"GIF89a", "image/gif"),
+    (b"%PDF-", "application/pdf"),
+    (b"PK\x03\x04", "application/zip"),
+    (b"\x1f\x8b", "application/gzip"),
+    (b"\x28\xb5\x2f\xfd", "application/zstd"),
+    (b"\xfd7zXZ\x00", "application/x-xz"),
+    (b"BZh", "application/x-bzip2"),
+    (b"\x7fELF", "application/x-executable"),
+    (b"\x00asm", "application/wasm"),
+];
+
+/// MIME types by (lowercase) extension.
+const EXTENSIONS: &[(&str, &str)] = &[
+    ("bz2", "application/x-bzip2"),
+    ("css", "text/css"),
+    ("csv", "text/csv"),
+    ("gif", "image/gif"),
+    ("gz", "application/gzip"),
+    ("htm", "text/html"),
+    ("html", "text/html"),
+    ("ico", "image/x-icon"),
+    ("jpeg", "image/jpeg"),
+    ("jpg", "image/jpeg"),
+    ("js", "text/javascript"),
+    ("json", "application/json"),
+    ("md", "text/markdown"),
+    ("mp3", "audio/mpeg"),
+    ("mp4", "video/mp4"),
+    ("pdf", "application/pdf"),
+    ("png", "image/png"),
+    ("py", "text/x-python"),
+    ("rs", "text/x-rust"),
+    ("sh", "application/x-sh"),
+    ("svg", "image/svg+xml"),
+    ("tar", "application/x-tar"),
+    ("toml", "application/toml"),
+    ("txt", "text/plain"),
+    ("wasm", "application/wasm"),
+    ("webp", "image/webp"),
+    ("xml", "application/xml"),
+    ("xz", "application/x-xz"),
+    ("yaml", "application/yaml"),
+    ("yml", "application/yaml"),
+    ("zip", "application/zip"),
+    ("zst", "application/zstd"),
+];
+
+impl Sniff {
+    /// Sniff a file named `path` which starts with the bytes `head`, without reading it.
+    ///
+    /// Only the first [`SNIFF_LEN`](constant.SNIFF_LEN.html) bytes of `head` are looked at.
+    ///
+    /// # Examples
+    /// ```rust
+    /// # extern crate ergo_fs;
+    /// use ergo_fs::*;
+    ///
+    /// let sniff = Sniff::from_head(Path::new("logo"), b"\x89PNG\r\n\x1a\n\0\0\0\rIHDR");
+    /// assert!(sniff.is_binary);
+    /// assert_eq!("image/png", sniff.mime_guess);
+    ///
+    /// let sniff = Sniff::from_head(Path::new("notes.TXT"), b"\xff\xfeh\0i\0");
+    /// assert!(!sniff.is_binary);
+    /// assert_eq!("text/plain", sniff.mime_guess);
+    /// assert_eq!(Some(TextEncoding::Utf16Le), sniff.bom);
+    /// ```
+    pub fn from_head(path: &Path, head: &[u8]) -> Sniff {
+        let head = &head[..head.len().min(SNIFF_LEN)];
+        let bom = TextEncoding::from_bom(head);
+        let magic = MAGIC
+            .iter()
+            .find(|&&(magic, _)| head.starts_with(magic))
+            .map(|&(_, mime)| mime)
+            .or_else(|| sniff_offset(head));
+        // UTF-16 text is full of NUL bytes
+        let is_binary = magic.is_some() || (!is_utf16(bom) && head.contains(&0));
+        let mime_guess = magic
+            .or_else(|| extension_mime(path))
+            .unwrap_or(if is_binary {
+                "application/octet-stream"
+            } else {
+                "text/plain"
+            });
+        Sniff {
+            is_binary,
+            mime_guess,
+            bom,
+        }
+    }
+}
+
+impl TextEncoding {
+    /// Detect the encoding from the byte order mark at the start of `bytes`.
+    ///
+    /// # Examples
+    /// ```rust
+    /// # extern crate ergo_fs;
+    /// use ergo_fs::*;
+    ///
+    /// assert_eq!(Some(TextEncoding::Utf8), TextEncoding::from_bom(b"\xef\xbb\xbfhi"));
+    /// assert_eq!(Some(TextEncoding::Utf16Be), TextEncoding::from_bom(b"\xfe\xff\0h\0i"));
+    /// assert_eq!(None, TextEncoding::from_bom(b"hi"));
+    /// ```
+    pub fn from_bom(bytes: &[u8]) -> Option<TextEncoding> {
+        [
+            TextEncoding::Utf8,
+            TextEncoding::Utf16Le,
+            TextEncoding::Utf16Be,
+        ]
+        .iter()
+        .cloned()
+        .find(|enc| bytes.starts_with(enc.bom()))
+    }
+
+    /// The byte order mark of the encoding, empty for `Latin1`.
+    pub fn bom(&self) -> &'static [u8] {
+        match *self {
+            TextEncoding::Utf8 => b"\xef\xbb\xbf",
+            TextEncoding::Utf16Le => b"\xff\xfe",
+            TextEncoding::Utf16Be => b"\xfe\xff",
+            TextEncoding::Latin1 => b"",
+        }
+    }
+}
+
+impl fmt::Display for TextEncoding {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match *self {
+            TextEncoding::Utf8 => "UTF-8",
+            TextEncoding::Utf16Le => "UTF-16LE",
+            TextEncoding::Utf16Be => "UTF-16BE",
+            TextEncoding::Latin1 => "Latin-1",
+        })
+    }
+}
+
+pub(crate) fn sniff(path: &Path) -> Result<Sniff> {
+    let err = |e| Error::new(e, "sniffing", PathArc::new(path));
+    let mut head = Vec::with_capacity(SNIFF_LEN);
+    File::open(path)
+        .and_then(|f| f.take(SNIFF_LEN as u64).read_to_end(&mut head))
+        .map_err(err)?;
+    Ok(Sniff::from_head(path, &head))
+}
+
+/// Formats whose signature is not at the start of the file.
+fn sniff_offset(head: &[u8]) -> Option<&'static str> {
+    if head.len() >= 12 && &head[..4] == b"RIFF" && &head[8..12] == b"WEBP" {
+        Some("image/webp")
+    } else if head.get(257..262) == Some(&b"ustar"[..]) {
+        Some("application/x-tar")
+    } else {
+        None
+    }
+}
+
+fn is_utf16(bom: Option<TextEncoding>) -> bool {
+    matches!(
+        bom,
+        Some(TextEncoding::Utf16Le) | Some(TextEncoding::Utf16Be)
+    )
+}
+
+fn extension_mime(path: &Path) -> Option<&'static str> {
+    let ext = path.extension()?.to_str()?.to_ascii_lowercase();
+    EXTENSIONS
+        .iter()
+        .find(|&&(e, _)| e == ext)
+        .map(|&(_, mime)| mime)
+}
@@ -0,0 +1,133 @@
+/* Copyright (c) 2018 Garrett Berg, vitiral@gmail.com
+ *
+ * Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+ * http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+ * http://opensource.org/licenses/MIT>, at your option. This file may not be
+ * copied, modified, or distributed except according to those terms.
+ */
+//! Sorting paths in natural order, so `file2` sorts before `file10`.
+//!
+//! Runs of digits are compared by their numeric value and letters are compared ignoring case
+//! (by their unicode lowercase, this is not a locale's collation). Names which are equal this
+//! way (i.e. `a` and `A`, or `01` and `1`) are then compared by their bytes, so the order is
+//! total and the same on every machine.
+//!
+//! This is the order of [`PathDirExt::read_sorted`], [`WalkOptions::sorted`], the
+//! [`snapshot`] module and [`Deterministic`] archives. Use [`natural`] or the comparators to
+//! sort the results of other APIs, i.e. of [`glob`].
+//!
+//! [`PathDirExt::read_sorted`]: ../trait.PathDirExt.html#method.read_sorted
+//! [`WalkOptions::sorted`]: ../struct.WalkOptions.html#method.sorted
+//! [`snapshot`]: ../snapshot/index.html
+//! [`Deterministic`]: ../struct.Deterministic.html
+//! [`natural`]: fn.natural.html
+//! [`glob`]: ../fn.glob.html
+//!
+//! # Examples
+//! ```rust
+//! # extern crate ergo_fs;
+//! use ergo_fs::*;
+//!
+//! # fn try_main() -> ::std::io::Result<()> {
+//! let tmp = PathTmp::create("sort")?;
+//! for name in &["img10.png", "img2.png", "IMG1.png"] {
+//!     PathFile::create(tmp.join(name))?;
+//! }
+//! let pattern = format!("{}/*.png", tmp.display());
+//! let mut found = glob(&pattern).unwrap().collect::<Result<Vec<_>, _>>()?;
+//! sort::natural(&mut found);
+//!
+//! let names: Vec<_> = found.iter().map(|p| p.file_name().unwrap().to_owned()).collect();
+//! assert_eq!(vec!["IMG1.png", "img2.png", "img10.png"], names);
+//! # Ok(()) } fn main() { try_main().unwrap() }
+//! ```
+
+use std::cmp::Ordering;
+use std::ffi::OsStr;
+use std::iter::Peekable;
+use std::str::Chars;
+use std_prelude::*;
+
+/// Sort `paths` in natural order, see the [module docs](index.html).
+///
+/// Works with any paths, i.e. `Vec<PathType>`, `Vec<PathFile>` or `Vec<PathBuf>`.
+pub fn natural<P: AsRef<Path>>(paths: &mut [P]) {
+    paths.sort_by(|a, b| natural_cmp(a, b));
+}
+
+/// Compare two paths in natural order, component by component.
+///
+/// Use it with `sort_by`, i.e. `files.sort_by(|a, b| natural_cmp(&a.path, &b.path))`.
+pub fn natural_cmp<A: AsRef<Path>, B: AsRef<Path>>(a: A, b: B) -> Ordering {
+    let mut a = a.as_ref().components();
+    let mut b = b.as_ref().components();
+    loop {
+        match (a.next(), b.next()) {
+            (None, None) => return Ordering::Equal,
+            (None, Some(_)) => return Ordering::Less,
+            (Some(_), None) => return Ordering::Greater,
+            (Some(x), Some(y)) => {
+                let ord = natural_cmp_names(x.as_os_str(), y.as_os_str());
+                if ord != Ordering::Equal {
+                    return ord;
+                }
+            }
+        }
+    }
+}
+
+/// Compare two file names in natural order.
+///
+/// Use it with `walkdir`, i.e. `WalkDir::new(dir).sort_by(|a, b|
+/// natural_cmp_names(a.file_name(), b.file_name()))`.
+pub fn natural_cmp_names(a: &OsStr, b: &OsStr) -> Ordering {
+    natural_cmp_str(&a.to_string_lossy(), &b.to_string_lossy())
+        .then_with(|| a.as_encoded_bytes().cmp(b.as_encoded_bytes()))
+}
+
+/// Compare two strings in natural order, without comparing their bytes when they are equal
+/// ignoring case and leading zeros.
+pub fn natural_cmp_str(a: &str, b: &str) -> Ordering {
+    let mut a = a.chars().peekable();
+    let mut b = b.chars().peekable();
+    loop {
+        match (a.peek().cloned(), b.peek().cloned()) {
+            (None, None) => return Ordering::Equal,
+            (None, Some(_)) => return Ordering::Less,
+            (Some(_), None) => return Ordering::Greater,
+            (Some(x), Some(y)) if x.is_ascii_digit() && y.is_ascii_digit() => {
+                let ord = cmp_numbers(&take_digits(&mut a), &take_digits(&mut b));
+                if ord != Ordering::Equal {
+                    return ord;
+                }
+            }
+            (Some(x), Some(y)) => {
+                let ord = x.to_lowercase().cmp(y.to_lowercase());
+                if ord != Ordering::Equal {
+                    return ord;
+                }
+                a.next();
+                b.next();
+            }
+        }
+    }
+}
+
+fn take_digits(chars: &mut Peekable<Chars>) -> String {
+    let mut out = String::new();
+    while let Some(&c) = chars.peek() {
+        if !c.is_ascii_digit() {
+            break;
+        }
+        out.push(c);
+        chars.next();
+    }
+    out
+}
+
+/// Compare runs of ascii digits by value without parsing them, so they can be any length.
+fn cmp_numbers(a: &str, b: &str) -> Ordering {
+    let a = a.trim_start_matches('0');
+    let b = b.trim_start_matches('0');
+    a.len().cmp(&b.len()).then_with(|| a.cmp(b))
+}
@@ -0,0 +1,191 @@
+/* Copyright (c) 2018 Garrett Berg, vitiral@gmail.com
+ *
+ * Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+ * http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+ * http://opensource.org/licenses/MIT>, at your option. This file may not be
+ * copied, modified, or distributed except according to those terms.
+ */
+//! Reading the end of files and following them as they grow, like `tail -F`.
+
+use crossbeam_channel::{self as cb, Receiver, Sender};
+use error::{Error, Result};
+use glob_crate::Pattern;
+use path_abs::{FileRead, PathArc};
+use std::fs;
+use std::io::{self, Seek, SeekFrom};
+use std::thread;
+use std_prelude::*;
+use watch::{GlobWatcher, WatchEvent};
+
+/// The size of the blocks read backwards from the end of a file by `tail`.
+const BLOCK_LEN: u64 = 8 * 1024;
+
+/// How often a followed file is polled for changes.
+const FOLLOW_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Read the last `n` lines of the file, reading backwards from its end.
+pub(crate) fn tail(path: &Path, n: usize) -> Result<Vec<String>> {
+    let mut file = FileRead::read(path)?;
+    let err = |err| Error::new(err, "reading the end of", PathArc::new(path));
+    let len = file.seek(SeekFrom::End(0)).map_err(err)?;
+
+    // Read blocks until there are more than `n` newlines before the final one (so the first of
+    // the `n` lines is complete) or the start of the file is reached.
+    let mut start = len;
+    let mut buf = Vec::new();
+    let mut newlines = 0;
+    while start > 0 && newlines <= n {
+        let block = BLOCK_LEN.min(start);
+        start -= block;
+        let mut chunk = vec![0; block as usize];
+        file.seek(SeekFrom::Start(start)).map_err(err)?;
+        file.read_exact(&mut chunk).map_err(err)?;
+        newlines += chunk.iter().filter(|&&b| b == b'\n').count();
+        chunk.extend_from_slice(&buf);
+        buf = chunk;
+    }
+
+    if len == 0 {
+        return Ok(Vec::new());
+    }
+    if buf.last() == Some(&b'\n') {
+        buf.pop();
+    }
+    let mut lines: Vec<String> = buf
+        .rsplit(|&b| b == b'\n')
+        .take(n)
+        .map(decode_line)
+        .collect();
+    lines.reverse();
+    Ok(lines)
+}
+
+/// Follow the lines appended to the file in a background thread.
+pub(crate) fn follow(path: &Path) -> Result<Receiver<String>> {
+    let mut file = FileRead::read(path)?;
+    let pos = file
+        .seek(SeekFrom::End(0))
+        .map_err(|err| Error::new(err, "seeking to the end of", PathArc::new(path)))?;
+    let id = file.metadata().ok().and_then(|m| file_id(&m));
+    let pattern = match path.to_str() {
+        Some(p) => Pattern::escape(p),
+        None => {
+            let err = io::Error::new(io::ErrorKind::InvalidInput, "path is not valid unicode");
+            return Err(Error::new(err, "following", PathArc::new(path)));
+        }
+    };
+    let watcher = GlobWatcher::new(&[&pattern])
+        .expect("escaped pattern is valid")
+        .interval(FOLLOW_INTERVAL);
+
+    let (send, recv) = cb::unbounded();
+    let mut follower = Follower {
+        path: path.to_path_buf(),
+        file,
+        pos,
+        id,
+        partial: Vec::new(),
+        send,
+    };
+    thread::spawn(move || follower.run(watcher));
+    Ok(recv)
+}
+
+struct Follower {
+    path: PathBuf,
+    file: FileRead,
+    pos: u64,
+    id: Option<(u64, u64)>,
+    /// The bytes of the last line, which doesn't have its newline yet.
+    partial: Vec<u8>,
+    send: Sender<String>,
+}
+
+impl Follower {
+    fn run(&mut self, mut watcher: GlobWatcher) {
+        while !self.send.is_disconnected() {
+            thread::sleep(FOLLOW_INTERVAL);
+            let events = watcher.poll();
+            if events.is_empty() {
+                continue;
+            }
+            let created = events.iter().any(|e| matches!(*e, WatchEvent::Created(_)));
+            // Errors are ignored (there is nobody to report them to), the read is retried when
+            // the file changes again.
+            let _ = self.update(created);
+        }
+    }
+
+    /// Send the lines written since the last update, switching to the new file if the old one
+    /// was rotated.
+    fn update(&mut self, created: bool) -> io::Result<()> {
+        // Whatever was written to the old file before it was rotated is read first.
+        self.read_lines()?;
+
+        let meta = match fs::metadata(&self.path) {
+            Ok(m) => m,
+            // Removed, wait for a new file to be created.
+            Err(_) => return Ok(()),
+        };
+        let id = file_id(&meta);
+        if created || id != self.id {
+            self.flush_partial();
+            self.file = FileRead::read(&self.path)?;
+            self.pos = 0;
+            self.id = id;
+        } else if meta.len() < self.pos {
+            // Truncated, start over from the new start.
+            self.partial.clear();
+            self.file.seek(SeekFrom::Start(0))?;
+            self.pos = 0;
+        }
+        self.read_lines()
+    }
+
+    /// Read to the end of the current file and send each complete line.
+    fn read_lines(&mut self) -> io::Result<()> {
+        let mut buf = Vec::new();
+        let read = self.file.read_to_end(&mut buf)?;
+        self.pos += read as u64;
+
+        let mut rest = &buf[..];
+        while let Some(end) = rest.iter().position(|&b| b == b'\n') {
+            self.partial.extend_from_slice(&rest[..end]);
+            rest = &rest[end + 1..];
+            let line = decode_line(&self.partial);
+            self.partial.clear();
+            let _ = self.send.send(line);
+        }
+        self.partial.extend_from_slice(rest);
+        Ok(())
+    }
+
+    /// The last line of a rotated file will never get its newline, send it as it is.
+    fn flush_partial(&mut self) {
+        if !self.partial.is_empty() {
+            let _ = self.send.send(decode_line(&self.partial));
+            self.partial.clear();
+        }
+    }
+}
+
+fn decode_line(line: &[u8]) -> String {
+    let line = match line.last() {
+        Some(&b'\r') => &line[..line.len() - 1],
+        _ => line,
+    };
+    String::from_utf8_lossy(line).into_owned()
+}
+
+/// The identity of a file, which changes when a new file is created at the same path.
+#[cfg(unix)]
+fn file_id(meta: &fs::Metadata) -> Option<(u64, u64)> {
+    use std::os::unix::fs::MetadataExt;
+    Some((meta.dev(), meta.ino()))
+}
+
+/// Files have no cheap identity, a rotation is detected by the file being created or shrinking.
+#[cfg(not(unix))]
+fn file_id(_meta: &fs::Metadata) -> Option<(u64, u64)> {
+    None
+}
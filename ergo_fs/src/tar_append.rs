@@ -0,0 +1,147 @@
+/* Copyright (c) 2018 Garrett Berg, vitiral@gmail.com
+ *
+ * Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+ * http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+ * http://opensource.org/licenses/MIT>, at your option. This file may not be
+ * copied, modified, or distributed except according to those terms.
+ */
+//! Appending to existing tar archives.
+
+use error::{Error, Result};
+use path_abs::{FileEdit, PathArc, PathType};
+use std::io::{self, Read, Seek, SeekFrom};
+use std_prelude::*;
+use tar;
+
+const BLOCK: u64 = 512;
+
+/// Append `entries` to the existing tar archive `file`.
+///
+/// The end of the archive is found by reading only the headers, so nothing else is rewritten:
+/// the terminating zero blocks are truncated, the entries are appended and a new trailer is
+/// written. An empty file is treated as an empty archive, while a file which is not a tar
+/// archive (a header with the wrong checksum or an entry extending past the end of the file) is
+/// an error with kind `InvalidData` and is left untouched.
+///
+/// Each entry is added under its file name, like `tar -rf` run from its parent directory: a file
+/// becomes `name` and a directory is added recursively as `name/...`.
+///
+/// # Examples
+/// ```rust
+/// # extern crate ergo_fs;
+/// use ergo_fs::*;
+///
+/// # fn try_main() -> ::std::io::Result<()> {
+/// let tmp = PathTmp::create("tar_append")?;
+/// let a = PathFile::create(tmp.join("a.txt"))?;
+/// let b = PathFile::create(tmp.join("b.txt"))?;
+/// let archive = tmp.join("bundle.tar");
+///
+/// tar_append(FileEdit::create(&archive)?, vec![PathType::File(a)])?;
+/// tar_append(FileEdit::edit(&archive)?, vec![PathType::File(b)])?;
+///
+/// let list = TarList::open(FileRead::read(&archive)?)?;
+/// let names: Vec<_> = list.iter().map(|e| e.path().to_path_buf()).collect();
+/// assert_eq!(vec![PathBuf::from("a.txt"), PathBuf::from("b.txt")], names);
+///
+/// let notes = PathFile::create(tmp.join("notes.txt"))?;
+/// notes.write_str(&"not a tar archive\n".repeat(100))?;
+/// let err = tar_append(FileEdit::edit(&notes)?, vec![]).unwrap_err();
+/// assert_eq!(::std::io::ErrorKind::InvalidData, err.kind());
+/// # Ok(()) } fn main() { try_main().unwrap() }
+/// ```
+pub fn tar_append<I>(mut file: FileEdit, entries: I) -> Result<()>
+where
+    I: IntoIterator<Item = PathType>,
+{
+    let path = PathArc::new(file.path());
+    let err = |e| Error::new(e, "appending to tar", path.clone());
+
+    let end = find_end(&mut file).map_err(&err)?;
+    file.set_len(end)?;
+    file.seek(SeekFrom::Start(end)).map_err(&err)?;
+
+    let mut builder = tar::Builder::new(&mut file);
+    for entry in entries {
+        let name = match entry.file_name() {
+            Some(name) => PathBuf::from(name),
+            None => {
+                return Err(err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    format!("{} has no file name", entry.display()),
+                )))
+            }
+        };
+        match entry {
+            PathType::File(f) => builder.append_path_with_name(&f, &name),
+            PathType::Dir(d) => builder.append_dir_all(&name, &d),
+        }
+        .map_err(&err)?;
+    }
+    builder.finish().map_err(&err)?;
+    drop(builder);
+    file.flush()?;
+    Ok(())
+}
+
+/// Find the offset of the end of the archive's entries, i.e. where the terminating zero blocks
+/// start.
+fn find_end<R: Read + Seek>(file: &mut R) -> io::Result<u64> {
+    let len = file.seek(SeekFrom::End(0))?;
+    let mut pos = 0;
+    let mut block = [0_u8; BLOCK as usize];
+    loop {
+        file.seek(SeekFrom::Start(pos))?;
+        if !read_block(file, &mut block)? || block.iter().all(|&b| b == 0) {
+            return Ok(pos);
+        }
+        let header = tar::Header::from_byte_slice(&block);
+        if header.cksum().ok() != Some(checksum(&block)) {
+            return Err(invalid(pos, "has the wrong checksum"));
+        }
+        // the whole header was read, so `pos + BLOCK <= len`
+        let size = header
+            .entry_size()
+            .map_err(|_| invalid(pos, "has an invalid size"))?;
+        let remaining = len - pos - BLOCK;
+        if size > remaining || size.div_ceil(BLOCK) * BLOCK > remaining {
+            return Err(invalid(pos, "extends past the end of the file"));
+        }
+        pos += BLOCK + size.div_ceil(BLOCK) * BLOCK;
+    }
+}
+
+/// The checksum of a header: the sum of its bytes with the checksum field itself as spaces.
+fn checksum(block: &[u8]) -> u32 {
+    let sum = block[..148]
+        .iter()
+        .chain(&block[156..])
+        .map(|&b| u32::from(b))
+        .sum::<u32>();
+    sum + 8 * u32::from(b' ')
+}
+
+fn invalid(pos: u64, problem: &str) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::InvalidData,
+        format!("tar header at byte {} {}", pos, problem),
+    )
+}
+
+/// Read a whole block, returning false at the end of the file.
+fn read_block<R: Read>(file: &mut R, block: &mut [u8]) -> io::Result<bool> {
+    let mut read = 0;
+    while read < block.len() {
+        match file.read(&mut block[read..])? {
+            0 if read == 0 => return Ok(false),
+            0 => {
+                return Err(io::Error::new(
+                    io::ErrorKind::UnexpectedEof,
+                    "tar archive ends in the middle of a header",
+                ))
+            }
+            n => read += n,
+        }
+    }
+    Ok(true)
+}
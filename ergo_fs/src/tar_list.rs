@@ -0,0 +1,252 @@
+/* Copyright (c) 2018 Garrett Berg, vitiral@gmail.com
+ *
+ * Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+ * http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+ * http://opensource.org/licenses/MIT>, at your option. This file may not be
+ * copied, modified, or distributed except according to those terms.
+ */
+//! Listing and extracting entries of tar archives.
+
+use error::{Error, Result};
+use jail::PathJail;
+use path_abs::{FileRead, PathArc, PathDir, PathFile};
+use std::fs;
+use std::io::{self, Seek, SeekFrom};
+use std::path::Component;
+use std::slice;
+use std_prelude::*;
+use tar;
+
+/// The entries of a tar archive, without extracting it.
+///
+/// Every entry path is normalized: leading `/` and `./` are removed and `..` can never escape the
+/// root of the archive. This makes paths safe to compare and join onto a destination.
+///
+/// # Examples
+/// ```rust
+/// # extern crate ergo_fs;
+/// use ergo_fs::*;
+///
+/// # fn try_main() -> ::std::io::Result<()> {
+/// let tmp = PathTmp::create("tar_list")?;
+/// let src = PathDir::create(tmp.join("src"))?;
+/// PathFile::create(src.join("a.txt"))?.write_str("hello")?;
+///
+/// let mut tar = tar::Builder::new(FileWrite::create(tmp.join("src.tar"))?);
+/// tar.append_dir_all("./src", &src)?;
+/// tar.into_inner()?;
+///
+/// let mut list = TarList::open(FileRead::read(tmp.join("src.tar"))?)?;
+/// let files: Vec<_> = list
+///     .iter()
+///     .filter(|e| e.entry_type() == TarEntryType::File)
+///     .map(|e| (e.path().to_path_buf(), e.size()))
+///     .collect();
+/// assert_eq!(vec![(PathBuf::from("src/a.txt"), 5)], files);
+///
+/// let dest = PathDir::create(tmp.join("dest"))?;
+/// let extracted = list.extract_entry("src/a.txt", &dest)?;
+/// assert_eq!("hello", PathFile::new(extracted)?.read_string()?);
+///
+/// // the last of duplicate entries wins
+/// let v2 = PathDir::create_all(tmp.join("v2").join("src"))?;
+/// PathFile::create(v2.join("a.txt"))?.write_str("bye")?;
+/// tar_append(FileEdit::edit(tmp.join("src.tar"))?, vec![PathType::Dir(v2)])?;
+/// let mut list = TarList::open(FileRead::read(tmp.join("src.tar"))?)?;
+/// let extracted = list.extract_entry("src/a.txt", &dest)?;
+/// assert_eq!("bye", PathFile::new(extracted)?.read_string()?);
+/// # Ok(()) } fn main() { try_main().unwrap() }
+/// ```
+#[derive(Debug)]
+pub struct TarList {
+    file: FileRead,
+    entries: Vec<TarEntry>,
+}
+
+/// An entry of a [`TarList`](struct.TarList.html).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TarEntry {
+    path: PathBuf,
+    size: u64,
+    mode: u32,
+    entry_type: TarEntryType,
+    link: Option<PathBuf>,
+}
+
+/// The type of a [`TarEntry`](struct.TarEntry.html).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum TarEntryType {
+    /// A regular file.
+    File,
+    /// A directory.
+    Dir,
+    /// A symbolic link.
+    Symlink,
+    /// A hard link to another entry.
+    HardLink,
+    /// Anything else, i.e. a device or fifo.
+    Other,
+}
+
+impl TarList {
+    /// Read the headers of every entry in the archive.
+    pub fn open(file: FileRead) -> Result<TarList> {
+        let path = PathArc::new(file.path());
+        let err = |e| Error::new(e, "reading tar", path.clone());
+        let mut archive = tar::Archive::new(file);
+        let mut entries = Vec::new();
+        for entry in archive.entries().map_err(&err)? {
+            let entry = entry.map_err(&err)?;
+            let header = entry.header();
+            let link = match entry.link_name() {
+                Ok(link) => link.map(|l| l.into_owned()),
+                Err(e) => return Err(err(e)),
+            };
+            entries.push(TarEntry {
+                path: normalize(&entry.path().map_err(&err)?),
+                size: header.size().map_err(&err)?,
+                mode: header.mode().map_err(&err)?,
+                entry_type: TarEntryType::from_header(header.entry_type()),
+                link,
+            });
+        }
+        Ok(TarList {
+            file: archive.into_inner(),
+            entries,
+        })
+    }
+
+    /// The path of the archive.
+    pub fn path(&self) -> &PathFile {
+        self.file.path()
+    }
+
+    /// The entries, in the order they are in the archive.
+    pub fn entries(&self) -> &[TarEntry] {
+        &self.entries
+    }
+
+    /// Iterate over the entries, in the order they are in the archive.
+    pub fn iter<'a>(&'a self) -> slice::Iter<'a, TarEntry> {
+        self.entries.iter()
+    }
+
+    /// Extract the entry with the (normalized) path `name` into the directory `dest`, returning
+    /// the path it was extracted to.
+    ///
+    /// If the path is in the archive more than once the last entry is extracted, since that is
+    /// the one appended last (see [`tar_append`](fn.tar_append.html)).
+    ///
+    /// Parent directories are created as needed. Fails with `NotFound` if there is no such
+    /// entry, and with a [`JailEscape`](struct.JailEscape.html) if a symlink already in `dest`
+    /// would lead the entry outside of it.
+    pub fn extract_entry<N, D>(&mut self, name: N, dest: D) -> Result<PathArc>
+    where
+        N: AsRef<Path>,
+        D: AsRef<Path>,
+    {
+        let name = normalize(name.as_ref());
+        let dest = dest.as_ref();
+        let archive_path = PathArc::new(self.file.path());
+        let err = |e| Error::with_paths(e, "extracting", archive_path.clone(), PathArc::new(dest));
+
+        let index = match self.entries.iter().rposition(|e| e.path == name) {
+            Some(i) => i,
+            None => {
+                return Err(err(io::Error::new(
+                    io::ErrorKind::NotFound,
+                    format!("no entry {} in archive", name.display()),
+                )))
+            }
+        };
+        fs::create_dir_all(dest).map_err(&err)?;
+        let jail = PathJail::new(PathDir::new(dest)?)?;
+        self.file.seek(SeekFrom::Start(0)).map_err(&err)?;
+        let mut archive = tar::Archive::new(&mut self.file);
+        let mut entry = match archive.entries().map_err(&err)?.nth(index) {
+            Some(entry) => entry.map_err(&err)?,
+            None => {
+                return Err(err(io::Error::new(
+                    io::ErrorKind::UnexpectedEof,
+                    "the archive changed since it was listed",
+                )))
+            }
+        };
+        // symlinks already in `dest` must not lead the entry outside of it
+        let target = jail.resolve(&name).map_err(&err)?;
+        if !entry.unpack_in(jail.root()).map_err(&err)? {
+            return Err(err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "entry path escapes the destination",
+            )));
+        }
+        Ok(PathArc::new(target))
+    }
+}
+
+impl<'a> IntoIterator for &'a TarList {
+    type Item = &'a TarEntry;
+    type IntoIter = slice::Iter<'a, TarEntry>;
+
+    fn into_iter(self) -> slice::Iter<'a, TarEntry> {
+        self.iter()
+    }
+}
+
+impl TarEntry {
+    /// The normalized path of the entry.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// The size of the entry's data in bytes.
+    pub fn size(&self) -> u64 {
+        self.size
+    }
+
+    /// The unix permission bits of the entry.
+    pub fn mode(&self) -> u32 {
+        self.mode
+    }
+
+    /// The type of the entry.
+    pub fn entry_type(&self) -> TarEntryType {
+        self.entry_type
+    }
+
+    /// The target of a symbolic or hard link.
+    pub fn link_target(&self) -> Option<&Path> {
+        self.link.as_deref()
+    }
+}
+
+impl TarEntryType {
+    fn from_header(ty: tar::EntryType) -> TarEntryType {
+        if ty.is_file() {
+            TarEntryType::File
+        } else if ty.is_dir() {
+            TarEntryType::Dir
+        } else if ty.is_symlink() {
+            TarEntryType::Symlink
+        } else if ty.is_hard_link() {
+            TarEntryType::HardLink
+        } else {
+            TarEntryType::Other
+        }
+    }
+}
+
+/// Normalize a path inside an archive so that it is relative and cannot escape the root.
+fn normalize(path: &Path) -> PathBuf {
+    let mut out = PathBuf::new();
+    for comp in path.components() {
+        match comp {
+            Component::Normal(c) => out.push(c),
+            Component::ParentDir => {
+                out.pop();
+            }
+            Component::CurDir | Component::RootDir | Component::Prefix(_) => {}
+        }
+    }
+    out
+}
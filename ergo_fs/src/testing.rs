@@ -0,0 +1,114 @@
+/* Copyright (c) 2018 Garrett Berg, vitiral@gmail.com
+ *
+ * Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+ * http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+ * http://opensource.org/licenses/MIT>, at your option. This file may not be
+ * copied, modified, or distributed except according to those terms.
+ */
+//! Golden file assertions, for testing programs which generate files.
+//!
+//! Compare a generated file against an expected ("golden") file stored with the tests using
+//! [`assert_matches_golden`]. It behaves like the [`snapshot`](../snapshot/index.html)
+//! assertions of directory trees:
+//!
+//! - If the golden file does not exist it is created (and the assertion passes). Review it and
+//!   commit it with your tests.
+//! - If it exists and does not match, the assertion panics with a line diff of the golden file
+//!   (`-`) against the actual file (`+`). With the `diff` feature the diff only shows the
+//!   changed lines and the lines around them, like `diff -u`.
+//! - Set the environment variable `ERGO_UPDATE_SNAPSHOTS=1` to overwrite golden files which
+//!   do not match, i.e. after an intentional change.
+//!
+//! [`assert_matches_golden`]: fn.assert_matches_golden.html
+//!
+//! # Examples
+//! ```rust
+//! # extern crate ergo_fs;
+//! use ergo_fs::*;
+//! use ergo_fs::testing::assert_matches_golden;
+//!
+//! # fn try_main() -> ::std::io::Result<()> {
+//! let tmp = PathTmp::create("golden")?;
+//! let out = PathFile::create(tmp.join("Cargo.toml"))?;
+//! out.write_str("[package]\nname = \"generated\"\n")?;
+//!
+//! // The first run creates the golden file, later runs compare against it.
+//! let golden = tmp.join("Cargo.toml.golden");
+//! assert_matches_golden(&out, &golden);
+//! assert_matches_golden(&out, &golden);
+//! # Ok(()) } fn main() { try_main().unwrap() }
+//! ```
+
+use snapshot::{self, UPDATE_ENV};
+use std::env;
+use std::fs;
+use std::io;
+use std_prelude::*;
+
+/// Assert that the file `actual` has the same contents as the `golden` file.
+///
+/// Files are compared byte for byte. When a file is not UTF-8 the panic only reports the sizes
+/// of the files instead of a diff.
+///
+/// # Panics
+/// Panics with a diff if the contents differ, or if either file cannot be read.
+pub fn assert_matches_golden<A: AsRef<Path>, G: AsRef<Path>>(actual: A, golden: G) {
+    let (actual, golden) = (actual.as_ref(), golden.as_ref());
+    let data =
+        fs::read(actual).unwrap_or_else(|e| panic!("failed to read {}: {}", actual.display(), e));
+    let expected = match fs::read(golden) {
+        Ok(d) => d,
+        Err(ref e) if e.kind() == io::ErrorKind::NotFound => {
+            snapshot::write_snapshot(golden, &data);
+            return;
+        }
+        Err(e) => panic!("failed to read golden file {}: {}", golden.display(), e),
+    };
+    if expected == data {
+        return;
+    }
+    if env::var_os(UPDATE_ENV).is_some_and(|v| v == "1") {
+        snapshot::write_snapshot(golden, &data);
+        return;
+    }
+    let diff = match (
+        ::std::str::from_utf8(&expected),
+        ::std::str::from_utf8(&data),
+    ) {
+        (Ok(expected), Ok(data)) => diff(expected, data),
+        _ => format!(
+            "binary files differ: {} bytes expected, {} bytes actual\n",
+            expected.len(),
+            data.len()
+        ),
+    };
+    panic!(
+        "{} does not match golden file {} (set {}=1 to update it):\n{}",
+        actual.display(),
+        golden.display(),
+        UPDATE_ENV,
+        diff
+    );
+}
+
+#[cfg(feature = "diff")]
+fn diff(expected: &str, actual: &str) -> String {
+    let hunks = ::diff::diff_str(expected, actual);
+    if hunks.is_empty() {
+        return line_endings_differ();
+    }
+    hunks.iter().map(|h| h.to_string()).collect()
+}
+
+#[cfg(not(feature = "diff"))]
+fn diff(expected: &str, actual: &str) -> String {
+    if expected.lines().eq(actual.lines()) {
+        return line_endings_differ();
+    }
+    snapshot::diff_lines(expected, actual)
+}
+
+/// The diffs compare lines without their endings, so they would show no change.
+fn line_endings_differ() -> String {
+    "only the line endings differ\n".to_string()
+}
@@ -11,12 +11,19 @@
 // option. This file may not be copied, modified, or distributed
 // except according to those terms.
 
+use std::collections::BTreeMap;
 use std::env;
 use std::fmt;
+use std::fs;
+use std::io;
+use std::process;
+use std::sync::{MutexGuard, Once};
 
+use ctrlc;
+use ergo_sys::rand;
 use std_prelude::*;
-use tempdir;
-use path_abs::{Error, PathAbs, PathArc, PathDir, Result};
+use path_abs::{PathAbs, PathArc, PathDir};
+use error::{Error, Result};
 
 /// A `PathDir` that is automatically deleted when it goes out of scope.
 ///
@@ -44,8 +51,10 @@ use path_abs::{Error, PathAbs, PathArc, PathDir, Result};
 ///
 /// Note that if the program exits before the `PathTmp` destructor is run, such as via
 /// [`std::process::exit`], by segfaulting, or by receiving a signal like `SIGINT`, then the
-/// temporary directory will not be deleted.
+/// temporary directory will not be deleted. Use [`PathTmp::create_registered`] to also delete
+/// it on Ctrl-C.
 ///
+/// [`PathTmp::create_registered`]: struct.PathTmp.html#method.create_registered
 /// [`File`]: http://doc.rust-lang.org/std/fs/struct.File.html
 /// [`Path`]: http://doc.rust-lang.org/std/path/struct.Path.html
 /// [`ReadDir`]: http://doc.rust-lang.org/std/fs/struct.ReadDir.html
@@ -60,8 +69,46 @@ use path_abs::{Error, PathAbs, PathArc, PathDir, Result};
 pub struct PathTmp {
     /// The reference to the absolute path
     dir: PathDir,
-    /// The reference to the temporary file
-    tmp: tempdir::TempDir,
+    /// Whether the directory has been persisted or removed already.
+    done: bool,
+    /// Removes the directory from the cleanup registry when dropped.
+    _registration: Option<Registration>,
+}
+
+/// The registered temporary directories which still exist, by id.
+static REGISTRY: Mutex<BTreeMap<usize, PathBuf>> = Mutex::new(BTreeMap::new());
+static NEXT_ID: AtomicUsize = AtomicUsize::new(0);
+static HANDLER: Once = Once::new();
+
+/// A directory in the `REGISTRY`.
+struct Registration(usize);
+
+impl Registration {
+    fn new(path: PathBuf) -> Registration {
+        let id = NEXT_ID.fetch_add(1, AtomicOrdering::SeqCst);
+        registry().insert(id, path);
+        HANDLER.call_once(|| {
+            // Best effort: this fails if the application installed its own handler, which
+            // should call `PathTmp::cleanup_registered` itself.
+            let _ = ctrlc::set_handler(|| {
+                PathTmp::cleanup_registered();
+                process::exit(130);
+            });
+        });
+        Registration(id)
+    }
+}
+
+impl Drop for Registration {
+    fn drop(&mut self) {
+        registry().remove(&self.0);
+    }
+}
+
+fn registry() -> MutexGuard<'static, BTreeMap<usize, PathBuf>> {
+    REGISTRY
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
 }
 
 impl PathTmp {
@@ -117,13 +164,88 @@ impl PathTmp {
     /// assert!(!file.exists());
     /// ```
     pub fn create_in<P: AsRef<Path>>(base: P, prefix: &str) -> Result<PathTmp> {
-        let tmp = tempdir::TempDir::new_in(&base, prefix)
-            .map_err(|err| Error::new(err, "creating tmpdir", PathArc::new(&base)))?;
+        let prefix = if prefix.is_empty() {
+            String::new()
+        } else {
+            format!("{}.", prefix)
+        };
+        PathTmp::builder().prefix(prefix).create_in(base)
+    }
 
-        Ok(PathTmp {
-            dir: PathDir::new(tmp.path())?,
-            tmp: tmp,
-        })
+    /// Configure the name of a temporary directory: its prefix, suffix and the number of random
+    /// characters between them.
+    ///
+    /// # Examples
+    /// ```
+    /// use ergo_fs::PathTmp;
+    ///
+    /// let tmp_dir = PathTmp::builder()
+    ///     .prefix("build-")
+    ///     .suffix(".work")
+    ///     .rand_len(16)
+    ///     .create()
+    ///     .unwrap();
+    /// let name = tmp_dir.file_name().unwrap().to_str().unwrap();
+    /// assert!(name.starts_with("build-"));
+    /// assert!(name.ends_with(".work"));
+    /// assert_eq!("build-".len() + 16 + ".work".len(), name.len());
+    /// ```
+    pub fn builder() -> PathTmpBuilder {
+        PathTmpBuilder {
+            prefix: String::new(),
+            suffix: String::new(),
+            rand_len: NUM_RAND_CHARS,
+            registered: false,
+        }
+    }
+
+    /// Create a temporary directory like [`create`](#method.create) which is also deleted when
+    /// the process is interrupted with Ctrl-C.
+    ///
+    /// The directory is added to a process-global registry until the `PathTmp` is dropped,
+    /// closed or persisted. The first call installs a Ctrl-C handler (with the `ctrlc` crate)
+    /// which removes every registered directory and exits with code 130.
+    ///
+    /// Only one Ctrl-C handler can be installed per process. If the application has its own
+    /// handler, call [`PathTmp::cleanup_registered`] from it instead.
+    ///
+    /// [`PathTmp::cleanup_registered`]: #method.cleanup_registered
+    ///
+    /// # Examples
+    /// ```
+    /// use ergo_fs::{PathFile, PathTmp};
+    ///
+    /// let tmp_dir = PathTmp::create_registered("registered").unwrap();
+    /// let file = PathFile::create(tmp_dir.join("work.txt")).unwrap();
+    ///
+    /// // This is what happens on Ctrl-C
+    /// assert_eq!(1, PathTmp::cleanup_registered());
+    /// assert!(!tmp_dir.exists());
+    /// assert!(!file.exists());
+    /// ```
+    pub fn create_registered(prefix: &str) -> Result<PathTmp> {
+        PathTmp::builder()
+            .prefix(format!("{}.", prefix))
+            .registered(true)
+            .create()
+    }
+
+    /// Remove every registered temporary directory which still exists, ignoring errors.
+    /// Returns the number of directories removed.
+    ///
+    /// This is called by the Ctrl-C handler installed by
+    /// [`create_registered`](#method.create_registered).
+    pub fn cleanup_registered() -> usize {
+        let paths: Vec<PathBuf> = {
+            let mut registry = registry();
+            let paths = registry.values().cloned().collect();
+            registry.clear();
+            paths
+        };
+        paths
+            .iter()
+            .filter(|path| fs::remove_dir_all(path).is_ok())
+            .count()
     }
 
     /// Persist the temporary directory on the file system.
@@ -145,9 +267,18 @@ impl PathTmp {
     /// // It can still be manually removed though.
     /// dir.remove().unwrap();
     /// ```
-    pub fn persist(self) -> PathDir {
-        self.tmp.into_path();
-        self.dir
+    pub fn persist(mut self) -> PathDir {
+        self.done = true;
+        self.dir.clone()
+    }
+
+    /// Keep the temporary directory instead of deleting it. This is the same as
+    /// [`persist`](#method.persist).
+    ///
+    /// This is intended for debugging: when a test using a `PathTmp` fails, temporarily add
+    /// `.keep()` to inspect what was left in the directory.
+    pub fn keep(self) -> PathDir {
+        self.persist()
     }
 
     /// Closes and removes the temporary directory, returing a `Result`.
@@ -164,11 +295,10 @@ impl PathTmp {
     /// may be platform specific.
     ///
     /// [`std::io::Error`]: http://doc.rust-lang.org/std/io/struct.Error.html
-    pub fn close(self) -> Result<()> {
-        let dir = self.dir;
-        self.tmp
-            .close()
-            .map_err(|err| Error::new(err, "removing", dir.into()))
+    pub fn close(mut self) -> Result<()> {
+        self.done = true;
+        fs::remove_dir_all(&self.dir)
+            .map_err(|err| Error::new(err, "removing", self.dir.clone().into()))
     }
 
     /// Return a reference to a basic `std::path::Path`
@@ -177,6 +307,105 @@ impl PathTmp {
     }
 }
 
+impl Drop for PathTmp {
+    fn drop(&mut self) {
+        if !self.done {
+            let _ = fs::remove_dir_all(&self.dir);
+        }
+    }
+}
+
+/// The default number of random characters in the name of a [`PathTmp`](struct.PathTmp.html).
+const NUM_RAND_CHARS: usize = 12;
+/// The number of names to try before giving up.
+const NUM_RETRIES: usize = 1 << 16;
+
+/// A builder for the name of a [`PathTmp`](struct.PathTmp.html), created with
+/// [`PathTmp::builder`](struct.PathTmp.html#method.builder).
+///
+/// The name is `{prefix}{random}{suffix}`, where the random characters come from
+/// `ergo_sys::rand::try_token`.
+#[derive(Debug, Clone)]
+pub struct PathTmpBuilder {
+    prefix: String,
+    suffix: String,
+    rand_len: usize,
+    registered: bool,
+}
+
+impl PathTmpBuilder {
+    /// The start of the name (default empty).
+    pub fn prefix<S: Into<String>>(mut self, prefix: S) -> PathTmpBuilder {
+        self.prefix = prefix.into();
+        self
+    }
+
+    /// The end of the name (default empty).
+    pub fn suffix<S: Into<String>>(mut self, suffix: S) -> PathTmpBuilder {
+        self.suffix = suffix.into();
+        self
+    }
+
+    /// The number of random characters in the name (default 12).
+    ///
+    /// # Panics
+    /// Panics if `len` is 0.
+    pub fn rand_len(mut self, len: usize) -> PathTmpBuilder {
+        assert!(len > 0, "rand_len must be at least 1");
+        self.rand_len = len;
+        self
+    }
+
+    /// Also delete the directory on Ctrl-C, see
+    /// [`PathTmp::create_registered`](struct.PathTmp.html#method.create_registered).
+    pub fn registered(mut self, registered: bool) -> PathTmpBuilder {
+        self.registered = registered;
+        self
+    }
+
+    /// Create the directory inside of `env::temp_dir()`.
+    pub fn create(&self) -> Result<PathTmp> {
+        self.create_in(env::temp_dir())
+    }
+
+    /// Create the directory inside of `base`.
+    pub fn create_in<P: AsRef<Path>>(&self, base: P) -> Result<PathTmp> {
+        let base = base.as_ref();
+        let err = |err| Error::new(err, "creating tmpdir", PathArc::new(base));
+        for _ in 0..NUM_RETRIES {
+            let token = rand::try_token(self.rand_len).map_err(err)?;
+            let name = format!("{}{}{}", self.prefix, token, self.suffix);
+            let path = base.join(name);
+            match fs::create_dir(&path) {
+                Ok(()) => {}
+                Err(ref e) if e.kind() == io::ErrorKind::AlreadyExists => continue,
+                Err(e) => return Err(err(e)),
+            }
+            let dir = match PathDir::new(&path) {
+                Ok(dir) => dir,
+                Err(e) => {
+                    let _ = fs::remove_dir(&path);
+                    return Err(e.into());
+                }
+            };
+            let registration = if self.registered {
+                Some(Registration::new(path))
+            } else {
+                None
+            };
+            return Ok(PathTmp {
+                dir,
+                done: false,
+                _registration: registration,
+            });
+        }
+        Err(err(io::Error::new(
+            io::ErrorKind::AlreadyExists,
+            "too many temporary directories already exist",
+        )))
+    }
+}
+
 impl fmt::Debug for PathTmp {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         self.dir.fmt(f)
@@ -224,20 +453,26 @@ impl Deref for PathTmp {
 impl Into<PathAbs> for PathTmp {
     /// Downgrades the `PathTmp` into a `PathAbs`
     fn into(self) -> PathAbs {
-        self.dir.into()
+        let dir = self.dir.clone();
+        drop(self);
+        dir.into()
     }
 }
 
 impl Into<PathArc> for PathTmp {
     /// Downgrades the `PathTmp` into a `PathArc`
     fn into(self) -> PathArc {
-        self.dir.into()
+        let dir = self.dir.clone();
+        drop(self);
+        dir.into()
     }
 }
 
 impl Into<PathBuf> for PathTmp {
     /// Downgrades the `PathTmp` into a `PathBuf`. Avoids a clone if this is the only reference.
     fn into(self) -> PathBuf {
-        self.dir.into()
+        let dir = self.dir.clone();
+        drop(self);
+        dir.into()
     }
 }
@@ -0,0 +1,238 @@
+/* Copyright (c) 2018 Garrett Berg, vitiral@gmail.com
+ *
+ * Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+ * http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+ * http://opensource.org/licenses/MIT>, at your option. This file may not be
+ * copied, modified, or distributed except according to those terms.
+ */
+//! Building directory trees declaratively, for setting up tests.
+
+use error::{Error, Result};
+use path_abs::{PathArc, PathDir, PathFile, PathType};
+use std::collections::BTreeMap;
+use std::fs;
+use std::io;
+use std_prelude::*;
+
+/// Create a tree of directories, files and symlinks in a directory, returning a [`Tree`] of the
+/// created paths.
+///
+/// - `"name": { ... }` creates a directory containing the entries inside the braces.
+/// - `"name": contents` creates a file with the `contents`, which can be anything that is
+///   `AsRef<[u8]>` (i.e. `&str`, `String` or `b"bytes"`).
+/// - `"name" -> target` creates a symlink to `target`, which is relative to the directory of
+///   the link (like `ln -s`). Symlinks are created after everything else, so they can point
+///   anywhere in the tree.
+///
+/// Names may contain `/` to create parent directories. This is the same as
+/// [`PathDirExt::build`] with a list of [`TreeEntry`]s.
+///
+/// [`Tree`]: struct.Tree.html
+/// [`TreeEntry`]: enum.TreeEntry.html
+/// [`PathDirExt::build`]: trait.PathDirExt.html#method.build
+///
+/// # Examples
+/// ```rust
+/// #[macro_use] extern crate ergo_fs;
+/// use ergo_fs::*;
+///
+/// # fn try_main() -> ::std::io::Result<()> {
+/// let tmp = PathTmp::create("tree")?;
+/// let tree = tree!(&tmp, {
+///     "Cargo.toml": "[package]\nname = \"example\"\n",
+///     "src": {
+///         "lib.rs": "pub fn hello() {}\n",
+///         "bin/main.rs": "fn main() {}\n",
+///     },
+///     "data.bin": b"\x00\x01",
+///     "lib.rs" -> "src/lib.rs",
+/// })?;
+///
+/// assert_eq!("pub fn hello() {}\n", tree.file("src/lib.rs").read_string()?);
+/// assert!(tree.dir("src/bin").join("main.rs").exists());
+/// assert_eq!("pub fn hello() {}\n", tree.file("lib.rs").read_string()?);
+/// # Ok(()) } fn main() { try_main().unwrap() }
+/// ```
+#[macro_export]
+macro_rules! tree {
+    ($dir:expr, { $($spec:tt)* }) => {
+        $crate::PathDir::new($dir)
+            .map_err($crate::Error::from)
+            .and_then(|dir| $crate::PathDirExt::build(&dir, &$crate::tree!(@entries [] $($spec)*)))
+    };
+
+    (@entries [$($out:expr,)*]) => {
+        vec![$($out,)*]
+    };
+    (@entries [$($out:expr,)*] $name:tt : { $($inner:tt)* } $(, $($rest:tt)*)?) => {
+        $crate::tree!(@entries [
+            $($out,)*
+            ($name.into(), $crate::TreeEntry::Dir($crate::tree!(@entries [] $($inner)*))),
+        ] $($($rest)*)?)
+    };
+    (@entries [$($out:expr,)*] $name:tt -> $target:expr $(, $($rest:tt)*)?) => {
+        $crate::tree!(@entries [
+            $($out,)*
+            ($name.into(), $crate::TreeEntry::symlink($target)),
+        ] $($($rest)*)?)
+    };
+    (@entries [$($out:expr,)*] $name:tt : $contents:expr $(, $($rest:tt)*)?) => {
+        $crate::tree!(@entries [
+            $($out,)*
+            ($name.into(), $crate::TreeEntry::file($contents)),
+        ] $($($rest)*)?)
+    };
+}
+
+/// An entry of a directory tree to create with [`PathDirExt::build`] or the
+/// [`tree!`](macro.tree.html) macro.
+///
+/// [`PathDirExt::build`]: trait.PathDirExt.html#method.build
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TreeEntry {
+    /// A file with the contents.
+    File(Vec<u8>),
+    /// A directory with the entries.
+    Dir(Vec<(PathBuf, TreeEntry)>),
+    /// A symlink to the target, relative to the directory of the link.
+    Symlink(PathBuf),
+}
+
+impl TreeEntry {
+    /// A file with the `contents`.
+    pub fn file<C: AsRef<[u8]>>(contents: C) -> TreeEntry {
+        TreeEntry::File(contents.as_ref().to_vec())
+    }
+
+    /// A symlink to `target`.
+    pub fn symlink<P: AsRef<Path>>(target: P) -> TreeEntry {
+        TreeEntry::Symlink(target.as_ref().to_path_buf())
+    }
+}
+
+/// The paths created by [`PathDirExt::build`] or the [`tree!`](macro.tree.html) macro, by their
+/// path relative to the root of the tree.
+///
+/// [`PathDirExt::build`]: trait.PathDirExt.html#method.build
+#[derive(Debug, Clone)]
+pub struct Tree {
+    root: PathDir,
+    paths: BTreeMap<PathBuf, PathType>,
+}
+
+impl Tree {
+    /// The directory the tree was created in.
+    pub fn root(&self) -> &PathDir {
+        &self.root
+    }
+
+    /// The created path at `rel`, including the directories created for names with `/`.
+    pub fn get<P: AsRef<Path>>(&self, rel: P) -> Option<&PathType> {
+        self.paths.get(rel.as_ref())
+    }
+
+    /// The file at `rel`.
+    ///
+    /// # Panics
+    /// If no file was created at `rel`.
+    pub fn file<P: AsRef<Path>>(&self, rel: P) -> &PathFile {
+        match self.get(&rel) {
+            Some(PathType::File(file)) => file,
+            _ => panic!("no file {} in the tree", rel.as_ref().display()),
+        }
+    }
+
+    /// The directory at `rel`.
+    ///
+    /// # Panics
+    /// If no directory was created at `rel`.
+    pub fn dir<P: AsRef<Path>>(&self, rel: P) -> &PathDir {
+        match self.get(&rel) {
+            Some(PathType::Dir(dir)) => dir,
+            _ => panic!("no dir {} in the tree", rel.as_ref().display()),
+        }
+    }
+
+    /// Iterate over the created paths, sorted by their relative path.
+    pub fn iter(&self) -> ::std::collections::btree_map::Iter<'_, PathBuf, PathType> {
+        self.paths.iter()
+    }
+}
+
+/// Create the `entries` in `root`.
+pub(crate) fn build(root: &PathDir, entries: &[(PathBuf, TreeEntry)]) -> Result<Tree> {
+    let mut tree = Tree {
+        root: root.clone(),
+        paths: BTreeMap::new(),
+    };
+    let mut links = Vec::new();
+    create(&mut tree, Path::new(""), entries, &mut links)?;
+    for (rel, target) in links {
+        let path = root.as_path().join(&rel);
+        symlink(&target, &path)
+            .map_err(|err| Error::new(err, "creating symlink", PathArc::new(&path)))?;
+        // a broken link has no type
+        if let Ok(ty) = PathType::new(&path) {
+            tree.paths.insert(rel, ty);
+        }
+    }
+    Ok(tree)
+}
+
+fn create(
+    tree: &mut Tree,
+    parent: &Path,
+    entries: &[(PathBuf, TreeEntry)],
+    links: &mut Vec<(PathBuf, PathBuf)>,
+) -> Result<()> {
+    for (name, entry) in entries {
+        let rel = parent.join(name);
+        let path = tree.root.as_path().join(&rel);
+        if let Some(dir) = rel.parent() {
+            create_dirs(tree, dir)?;
+        }
+        match entry {
+            TreeEntry::File(contents) => {
+                fs::write(&path, contents)
+                    .map_err(|err| Error::new(err, "creating file", PathArc::new(&path)))?;
+                tree.paths
+                    .insert(rel, PathType::File(PathFile::new(&path)?));
+            }
+            TreeEntry::Dir(inner) => {
+                create_dirs(tree, &rel)?;
+                create(tree, &rel, inner, links)?;
+            }
+            TreeEntry::Symlink(target) => links.push((rel, target.clone())),
+        }
+    }
+    Ok(())
+}
+
+/// Create the directory `rel` and its parents, recording each of them.
+fn create_dirs(tree: &mut Tree, rel: &Path) -> Result<()> {
+    let mut dir = PathBuf::new();
+    for comp in rel.components() {
+        dir.push(comp);
+        if !tree.paths.contains_key(&dir) {
+            let created = PathDir::create_all(tree.root.as_path().join(&dir))?;
+            tree.paths.insert(dir.clone(), PathType::Dir(created));
+        }
+    }
+    Ok(())
+}
+
+#[cfg(unix)]
+fn symlink(target: &Path, link: &Path) -> io::Result<()> {
+    ::std::os::unix::fs::symlink(target, link)
+}
+
+#[cfg(windows)]
+fn symlink(target: &Path, link: &Path) -> io::Result<()> {
+    use std::os::windows::fs::{symlink_dir, symlink_file};
+    let resolved = link.parent().expect("links have a parent").join(target);
+    if fs::metadata(resolved).map(|m| m.is_dir()).unwrap_or(false) {
+        symlink_dir(target, link)
+    } else {
+        symlink_file(target, link)
+    }
+}
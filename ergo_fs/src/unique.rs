@@ -0,0 +1,78 @@
+/* Copyright (c) 2018 Garrett Berg, vitiral@gmail.com
+ *
+ * Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+ * http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+ * http://opensource.org/licenses/MIT>, at your option. This file may not be
+ * copied, modified, or distributed except according to those terms.
+ */
+//! Creating files with unique names.
+
+use error::{Error, Result};
+use faults::{self, FsOp};
+use path_abs::{FileWrite, PathArc, PathFile};
+use std::collections::hash_map::RandomState;
+use std::fs;
+use std::hash::BuildHasher;
+use std::io;
+use std::process;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::{SystemTime, UNIX_EPOCH};
+use std_prelude::*;
+
+/// The number of random characters in a unique name.
+const RAND_CHARS: usize = 6;
+/// The number of names to try before giving up.
+const NUM_RETRIES: usize = 1 << 16;
+
+const CHARS: &[u8] = b"abcdefghijklmnopqrstuvwxyzABCDEFGHIJKLMNOPQRSTUVWXYZ0123456789";
+
+static COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+/// Create a new file named `{prefix}{random}{suffix}` in `dir`, retrying with a different name
+/// whenever the file already exists.
+///
+/// The file is opened with `create_new` (`O_EXCL`) so it is never one which already existed,
+/// even if another process is creating files in `dir` at the same time.
+pub(crate) fn create_unique(
+    dir: &Path,
+    prefix: &str,
+    suffix: &str,
+) -> Result<(PathFile, FileWrite)> {
+    for _ in 0..NUM_RETRIES {
+        let path = dir.join(format!("{}{}{}", prefix, random_chars(), suffix));
+        faults::check(FsOp::Create, &path)
+            .map_err(|err| Error::new(err, "creating", PathArc::new(&path)))?;
+        let mut options = fs::OpenOptions::new();
+        options.create_new(true);
+        match FileWrite::open(&path, options) {
+            Ok(file) => return Ok((file.path().clone(), file)),
+            Err(ref err) if err.io_error().kind() == io::ErrorKind::AlreadyExists => continue,
+            Err(err) => return Err(err.into()),
+        }
+    }
+    Err(Error::new(
+        io::Error::new(
+            io::ErrorKind::AlreadyExists,
+            "too many unique names already exist",
+        ),
+        "creating unique file in",
+        PathArc::new(dir),
+    ))
+}
+
+/// Random alphanumeric characters, using the randomly seeded hasher from std.
+fn random_chars() -> String {
+    let mut hasher = RandomState::new().build_hasher();
+    COUNTER.fetch_add(1, Ordering::Relaxed).hash(&mut hasher);
+    process::id().hash(&mut hasher);
+    if let Ok(now) = SystemTime::now().duration_since(UNIX_EPOCH) {
+        now.subsec_nanos().hash(&mut hasher);
+    }
+    let mut bits = hasher.finish();
+    let mut out = String::with_capacity(RAND_CHARS);
+    for _ in 0..RAND_CHARS {
+        out.push(CHARS[(bits % CHARS.len() as u64) as usize] as char);
+        bits /= CHARS.len() as u64;
+    }
+    out
+}
@@ -0,0 +1,530 @@
+/* Copyright (c) 2018 Garrett Berg, vitiral@gmail.com
+ *
+ * Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+ * http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+ * http://opensource.org/licenses/MIT>, at your option. This file may not be
+ * copied, modified, or distributed except according to those terms.
+ */
+//! A filesystem interface with an in-memory implementation, for unit tests.
+//!
+//! Code which takes a `&dyn Vfs` instead of using paths directly can run against the real
+//! filesystem with [`RealFs`] and against a [`MemFs`] in unit tests, which never touches the
+//! disk and starts empty for every test.
+//!
+//! The [`Vfs`] trait covers reading and writing whole files, creating and removing files and
+//! directories, renaming, listing and walking. Listings and walks are sorted in
+//! [natural order](../sort/index.html) on both implementations, so tests see the same order as
+//! production. Symlinks and permissions are not modeled.
+//!
+//! Errors are this crate's [`Error`] with the same `kind()` as the real filesystem would return,
+//! i.e. `NotFound` for a missing file and `AlreadyExists` when creating a directory which
+//! exists.
+//!
+//! [`Vfs`]: trait.Vfs.html
+//! [`RealFs`]: struct.RealFs.html
+//! [`MemFs`]: struct.MemFs.html
+//! [`Error`]: ../struct.Error.html
+//!
+//! # Examples
+//! ```rust
+//! # extern crate ergo_fs;
+//! use ergo_fs::*;
+//! use ergo_fs::vfs::{MemFs, Vfs};
+//!
+//! /// Write a default config into `dir` unless there is one, returning its contents.
+//! fn init_config(fs: &dyn Vfs, dir: &Path) -> Result<String, Error> {
+//!     let path = dir.join("config.toml");
+//!     if !fs.exists(&path) {
+//!         fs.create_dir_all(dir)?;
+//!         fs.write(&path, b"verbose = false\n")?;
+//!     }
+//!     fs.read_string(&path)
+//! }
+//!
+//! # fn try_main() -> Result<(), Error> {
+//! let fs = MemFs::new();
+//! let dir = Path::new("/home/user/.config/app");
+//! assert_eq!("verbose = false\n", init_config(&fs, dir)?);
+//!
+//! fs.write(&dir.join("config.toml"), b"verbose = true\n")?;
+//! assert_eq!("verbose = true\n", init_config(&fs, dir)?);
+//! assert_eq!(
+//!     vec![
+//!         PathBuf::from("/home/user"),
+//!         PathBuf::from("/home/user/.config"),
+//!         PathBuf::from("/home/user/.config/app"),
+//!         PathBuf::from("/home/user/.config/app/config.toml"),
+//!     ],
+//!     fs.walk(Path::new("/home"))?
+//! );
+//! # Ok(()) } fn main() { try_main().unwrap() }
+//! ```
+
+use error::{Error, Result};
+use path_abs::PathArc;
+use sort;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::Component;
+use std::sync::MutexGuard;
+use std_prelude::*;
+
+/// The operations of a filesystem, implemented by the real filesystem ([`RealFs`]) and an
+/// in-memory one ([`MemFs`]). See the [module docs](index.html).
+///
+/// [`RealFs`]: struct.RealFs.html
+/// [`MemFs`]: struct.MemFs.html
+pub trait Vfs: Send + Sync {
+    /// Read the contents of the file at `path`.
+    fn read(&self, path: &Path) -> Result<Vec<u8>>;
+
+    /// Create or truncate the file at `path` and write `contents` to it. The parent directory
+    /// must exist.
+    fn write(&self, path: &Path, contents: &[u8]) -> Result<()>;
+
+    /// Append `contents` to the file at `path`, creating it if it does not exist.
+    fn append(&self, path: &Path, contents: &[u8]) -> Result<()>;
+
+    /// The metadata of the file or directory at `path`.
+    fn metadata(&self, path: &Path) -> Result<VfsMetadata>;
+
+    /// Create the directory at `path`. Its parent must exist.
+    fn create_dir(&self, path: &Path) -> Result<()>;
+
+    /// Create the directory at `path` and all of its parents, succeeding if it already exists.
+    fn create_dir_all(&self, path: &Path) -> Result<()>;
+
+    /// The entries of the directory at `path`, sorted in natural order.
+    fn list(&self, path: &Path) -> Result<Vec<PathBuf>>;
+
+    /// Remove the file at `path`.
+    fn remove_file(&self, path: &Path) -> Result<()>;
+
+    /// Remove the directory at `path` and everything in it.
+    fn remove_dir_all(&self, path: &Path) -> Result<()>;
+
+    /// Rename the file or directory `from` to `to`, replacing the file `to` if it exists.
+    fn rename(&self, from: &Path, to: &Path) -> Result<()>;
+
+    /// Read the file at `path` as UTF-8.
+    fn read_string(&self, path: &Path) -> Result<String> {
+        String::from_utf8(self.read(path)?).map_err(|err| {
+            Error::new(
+                io::Error::new(io::ErrorKind::InvalidData, err),
+                "decoding",
+                PathArc::new(path),
+            )
+        })
+    }
+
+    /// Whether a file or directory exists at `path`.
+    fn exists(&self, path: &Path) -> bool {
+        self.metadata(path).is_ok()
+    }
+
+    /// Every path below the directory `root` (not including it), depth first with the entries
+    /// of each directory in natural order.
+    fn walk(&self, root: &Path) -> Result<Vec<PathBuf>> {
+        let mut out = Vec::new();
+        let mut stack = self.list(root)?;
+        stack.reverse();
+        while let Some(path) = stack.pop() {
+            if self.metadata(&path)?.is_dir() {
+                stack.extend(self.list(&path)?.into_iter().rev());
+            }
+            out.push(path);
+        }
+        Ok(out)
+    }
+}
+
+/// The metadata of a path in a [`Vfs`](trait.Vfs.html).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct VfsMetadata {
+    is_dir: bool,
+    len: u64,
+}
+
+impl VfsMetadata {
+    /// Whether the path is a directory.
+    pub fn is_dir(&self) -> bool {
+        self.is_dir
+    }
+
+    /// Whether the path is a file.
+    pub fn is_file(&self) -> bool {
+        !self.is_dir
+    }
+
+    /// The length of the file in bytes, `0` for directories.
+    pub fn len(&self) -> u64 {
+        self.len
+    }
+
+    /// Whether the file is empty (or the path is a directory).
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+}
+
+/// The real filesystem, using `std::fs`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct RealFs;
+
+impl Vfs for RealFs {
+    fn read(&self, path: &Path) -> Result<Vec<u8>> {
+        fs::read(path).map_err(|err| Error::new(err, "reading", PathArc::new(path)))
+    }
+
+    fn write(&self, path: &Path, contents: &[u8]) -> Result<()> {
+        fs::write(path, contents).map_err(|err| Error::new(err, "writing", PathArc::new(path)))
+    }
+
+    fn append(&self, path: &Path, contents: &[u8]) -> Result<()> {
+        fs::OpenOptions::new()
+            .append(true)
+            .create(true)
+            .open(path)
+            .and_then(|mut f| f.write_all(contents))
+            .map_err(|err| Error::new(err, "appending to", PathArc::new(path)))
+    }
+
+    fn metadata(&self, path: &Path) -> Result<VfsMetadata> {
+        let meta = fs::metadata(path)
+            .map_err(|err| Error::new(err, "reading metadata", PathArc::new(path)))?;
+        Ok(VfsMetadata {
+            is_dir: meta.is_dir(),
+            len: if meta.is_dir() { 0 } else { meta.len() },
+        })
+    }
+
+    fn create_dir(&self, path: &Path) -> Result<()> {
+        fs::create_dir(path).map_err(|err| Error::new(err, "creating dir", PathArc::new(path)))
+    }
+
+    fn create_dir_all(&self, path: &Path) -> Result<()> {
+        fs::create_dir_all(path).map_err(|err| Error::new(err, "creating dir", PathArc::new(path)))
+    }
+
+    fn list(&self, path: &Path) -> Result<Vec<PathBuf>> {
+        let err = |err| Error::new(err, "reading dir", PathArc::new(path));
+        let mut out = Vec::new();
+        for entry in fs::read_dir(path).map_err(err)? {
+            out.push(entry.map_err(err)?.path());
+        }
+        sort::natural(&mut out);
+        Ok(out)
+    }
+
+    fn remove_file(&self, path: &Path) -> Result<()> {
+        fs::remove_file(path).map_err(|err| Error::new(err, "removing", PathArc::new(path)))
+    }
+
+    fn remove_dir_all(&self, path: &Path) -> Result<()> {
+        fs::remove_dir_all(path).map_err(|err| Error::new(err, "removing dir", PathArc::new(path)))
+    }
+
+    fn rename(&self, from: &Path, to: &Path) -> Result<()> {
+        fs::rename(from, to)
+            .map_err(|err| Error::with_paths(err, "renaming", PathArc::new(from), PathArc::new(to)))
+    }
+}
+
+/// A filesystem in memory, for unit tests. See the [module docs](index.html).
+///
+/// Clones share the same files. Paths are normalized lexically (`.` is removed and `..`
+/// removes the previous component), relative paths are relative to an empty root which always
+/// exists, and the root `/` always exists.
+#[derive(Clone, Default)]
+pub struct MemFs {
+    nodes: Arc<Mutex<BTreeMap<PathBuf, Node>>>,
+}
+
+#[derive(Clone)]
+enum Node {
+    Dir,
+    File(Vec<u8>),
+}
+
+impl MemFs {
+    /// Create an empty filesystem.
+    pub fn new() -> MemFs {
+        MemFs::default()
+    }
+
+    fn lock(&self) -> MutexGuard<'_, BTreeMap<PathBuf, Node>> {
+        self.nodes.lock().expect("MemFs poisoned")
+    }
+}
+
+impl Vfs for MemFs {
+    fn read(&self, path: &Path) -> Result<Vec<u8>> {
+        let key = normalize(path);
+        match self.lock().get(&key) {
+            Some(Node::File(data)) => Ok(data.clone()),
+            Some(Node::Dir) => Err(error(
+                io::ErrorKind::Other,
+                "is a directory",
+                "reading",
+                path,
+            )),
+            None => Err(not_found("reading", path)),
+        }
+    }
+
+    fn write(&self, path: &Path, contents: &[u8]) -> Result<()> {
+        let mut nodes = self.lock();
+        let key = file_key(&nodes, path, "writing")?;
+        nodes.insert(key, Node::File(contents.to_vec()));
+        Ok(())
+    }
+
+    fn append(&self, path: &Path, contents: &[u8]) -> Result<()> {
+        let mut nodes = self.lock();
+        let key = file_key(&nodes, path, "appending to")?;
+        match nodes.entry(key).or_insert_with(|| Node::File(Vec::new())) {
+            Node::File(data) => data.extend_from_slice(contents),
+            Node::Dir => unreachable!("file_key checks for directories"),
+        }
+        Ok(())
+    }
+
+    fn metadata(&self, path: &Path) -> Result<VfsMetadata> {
+        let key = normalize(path);
+        if is_root(&key) {
+            return Ok(VfsMetadata {
+                is_dir: true,
+                len: 0,
+            });
+        }
+        match self.lock().get(&key) {
+            Some(Node::File(data)) => Ok(VfsMetadata {
+                is_dir: false,
+                len: data.len() as u64,
+            }),
+            Some(Node::Dir) => Ok(VfsMetadata {
+                is_dir: true,
+                len: 0,
+            }),
+            None => Err(not_found("reading metadata", path)),
+        }
+    }
+
+    fn create_dir(&self, path: &Path) -> Result<()> {
+        let mut nodes = self.lock();
+        let key = normalize(path);
+        if is_root(&key) || nodes.contains_key(&key) {
+            return Err(error(
+                io::ErrorKind::AlreadyExists,
+                "already exists",
+                "creating dir",
+                path,
+            ));
+        }
+        check_parent(&nodes, &key, "creating dir", path)?;
+        nodes.insert(key, Node::Dir);
+        Ok(())
+    }
+
+    fn create_dir_all(&self, path: &Path) -> Result<()> {
+        let mut nodes = self.lock();
+        let key = normalize(path);
+        let mut dir = PathBuf::new();
+        for comp in key.components() {
+            dir.push(comp);
+            if is_root(&dir) {
+                continue;
+            }
+            match nodes.get(&dir) {
+                Some(Node::Dir) => {}
+                Some(Node::File(_)) => {
+                    return Err(error(
+                        io::ErrorKind::AlreadyExists,
+                        "a parent is a file",
+                        "creating dir",
+                        path,
+                    ))
+                }
+                None => {
+                    nodes.insert(dir.clone(), Node::Dir);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn list(&self, path: &Path) -> Result<Vec<PathBuf>> {
+        let nodes = self.lock();
+        let key = normalize(path);
+        check_dir(&nodes, &key, "reading dir", path)?;
+        let mut out: Vec<_> = nodes
+            .keys()
+            .filter(|p| p.parent() == Some(&key))
+            .cloned()
+            .collect();
+        sort::natural(&mut out);
+        Ok(out)
+    }
+
+    fn remove_file(&self, path: &Path) -> Result<()> {
+        let mut nodes = self.lock();
+        let key = normalize(path);
+        match nodes.get(&key) {
+            Some(Node::File(_)) => {
+                nodes.remove(&key);
+                Ok(())
+            }
+            Some(Node::Dir) => Err(error(
+                io::ErrorKind::Other,
+                "is a directory",
+                "removing",
+                path,
+            )),
+            None => Err(not_found("removing", path)),
+        }
+    }
+
+    fn remove_dir_all(&self, path: &Path) -> Result<()> {
+        let mut nodes = self.lock();
+        let key = normalize(path);
+        if is_root(&key) {
+            nodes.clear();
+            return Ok(());
+        }
+        check_dir(&nodes, &key, "removing dir", path)?;
+        nodes.retain(|p, _| !p.starts_with(&key));
+        Ok(())
+    }
+
+    fn rename(&self, from: &Path, to: &Path) -> Result<()> {
+        let mut nodes = self.lock();
+        let err = |kind, msg| {
+            let io_err = io::Error::new(kind, msg);
+            Error::with_paths(io_err, "renaming", PathArc::new(from), PathArc::new(to))
+        };
+        let (from_key, to_key) = (normalize(from), normalize(to));
+        let is_dir = match nodes.get(&from_key) {
+            Some(Node::Dir) => true,
+            Some(Node::File(_)) => false,
+            None => return Err(err(io::ErrorKind::NotFound, "not found")),
+        };
+        if from_key == to_key {
+            return Ok(());
+        }
+        if to_key.starts_with(&from_key) {
+            return Err(err(
+                io::ErrorKind::InvalidInput,
+                "cannot move a directory into itself",
+            ));
+        }
+        match nodes.get(&to_key) {
+            Some(Node::Dir) => return Err(err(io::ErrorKind::AlreadyExists, "is a directory")),
+            Some(Node::File(_)) if is_dir => {
+                return Err(err(io::ErrorKind::AlreadyExists, "is a file"))
+            }
+            _ => {}
+        }
+        check_parent(&nodes, &to_key, "renaming to", to)?;
+        let moved: Vec<PathBuf> = nodes
+            .keys()
+            .filter(|p| p.starts_with(&from_key))
+            .cloned()
+            .collect();
+        for old in moved {
+            let node = nodes.remove(&old).expect("key was listed");
+            let rel = old.strip_prefix(&from_key).expect("key starts with from");
+            let new = if rel.as_os_str().is_empty() {
+                to_key.clone()
+            } else {
+                to_key.join(rel)
+            };
+            nodes.insert(new, node);
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Debug for MemFs {
+    /// The paths of the files and directories, directories ending with `/`.
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let nodes = self.lock();
+        let mut paths: Vec<_> = nodes
+            .iter()
+            .map(|(path, node)| match *node {
+                Node::Dir => format!("{}/", path.display()),
+                Node::File(_) => path.display().to_string(),
+            })
+            .collect();
+        paths.sort_by(|a, b| sort::natural_cmp(a, b));
+        f.debug_set().entries(paths).finish()
+    }
+}
+
+/// Normalize `path` lexically.
+fn normalize(path: &Path) -> PathBuf {
+    let mut out = PathBuf::new();
+    for comp in path.components() {
+        match comp {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                if !is_root(&out) {
+                    out.pop();
+                }
+            }
+            comp => out.push(comp),
+        }
+    }
+    out
+}
+
+/// Whether `key` is the root of relative or absolute paths, which always exists.
+fn is_root(key: &Path) -> bool {
+    key.parent().is_none()
+}
+
+/// The key of the file at `path` to write, checking that it is not a directory and that its
+/// parent exists.
+fn file_key(nodes: &BTreeMap<PathBuf, Node>, path: &Path, action: &str) -> Result<PathBuf> {
+    let key = normalize(path);
+    if is_root(&key) {
+        return Err(error(io::ErrorKind::Other, "is a directory", action, path));
+    }
+    if let Some(Node::Dir) = nodes.get(&key) {
+        return Err(error(io::ErrorKind::Other, "is a directory", action, path));
+    }
+    check_parent(nodes, &key, action, path)?;
+    Ok(key)
+}
+
+fn check_parent(
+    nodes: &BTreeMap<PathBuf, Node>,
+    key: &Path,
+    action: &str,
+    path: &Path,
+) -> Result<()> {
+    match key.parent() {
+        Some(parent) => check_dir(nodes, parent, action, path),
+        None => Ok(()),
+    }
+}
+
+fn check_dir(nodes: &BTreeMap<PathBuf, Node>, key: &Path, action: &str, path: &Path) -> Result<()> {
+    if is_root(key) {
+        return Ok(());
+    }
+    match nodes.get(key) {
+        Some(Node::Dir) => Ok(()),
+        Some(Node::File(_)) => Err(error(io::ErrorKind::Other, "not a directory", action, path)),
+        None => Err(not_found(action, path)),
+    }
+}
+
+fn not_found(action: &str, path: &Path) -> Error {
+    error(io::ErrorKind::NotFound, "not found", action, path)
+}
+
+fn error(kind: io::ErrorKind, msg: &str, action: &str, path: &Path) -> Error {
+    Error::new(io::Error::new(kind, msg), action, PathArc::new(path))
+}
@@ -0,0 +1,480 @@
+/* Copyright (c) 2018 Garrett Berg, vitiral@gmail.com
+ *
+ * Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+ * http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+ * http://opensource.org/licenses/MIT>, at your option. This file may not be
+ * copied, modified, or distributed except according to those terms.
+ */
+//! Entries of directory walks.
+
+use error::{Error, Result};
+use glob_wrapper::GlobPattern;
+use observer::{FsEvent, FsObserver};
+use path_abs::{PathAbs, PathArc, PathDir, PathFile, PathType};
+#[cfg(feature = "parallel")]
+use rayon::iter::plumbing::UnindexedConsumer;
+#[cfg(feature = "parallel")]
+use rayon::iter::{ParallelBridge, ParallelIterator};
+use sort;
+use std::fs;
+use std::io;
+use std::ops::Deref;
+use std_prelude::*;
+use walkdir::{self, WalkDir};
+
+/// A `PathType` from a walk which keeps the depth and metadata the walk already read, so that
+/// they don't need to be queried again for every path.
+///
+/// Dereferences to the [`PathType`](enum.PathType.html). Symlinks are resolved: the metadata and
+/// type are those of the link's target, use [`path_is_symlink`] to check whether the entry was a
+/// link. A broken symlink is a `PathType::File` with the metadata of the link itself.
+///
+/// [`path_is_symlink`]: #method.path_is_symlink
+///
+/// # Examples
+/// ```rust
+/// # extern crate ergo_fs;
+/// use ergo_fs::*;
+///
+/// # fn try_main() -> ::std::io::Result<()> {
+/// let tmp = PathTmp::create("walk_entry")?;
+/// PathFile::create(tmp.join("a.txt"))?.write_str("hello")?;
+///
+/// let dir = PathDir::new(&tmp)?;
+/// for entry in dir.walk().min_depth(1) {
+///     let entry = WalkEntry::from_entry(entry?)?;
+///     assert_eq!(1, entry.depth());
+///     assert_eq!(5, entry.metadata().len());
+///     let file: &PathFile = entry.as_file().unwrap();
+///     assert_eq!("hello", file.read_string()?);
+/// }
+/// # Ok(()) } fn main() { try_main().unwrap() }
+/// ```
+#[derive(Debug, Clone)]
+pub struct WalkEntry {
+    ty: PathType,
+    depth: usize,
+    metadata: fs::Metadata,
+    symlink: bool,
+}
+
+impl WalkEntry {
+    /// Create a `WalkEntry` from a `walkdir::DirEntry`.
+    ///
+    /// This reads the metadata of the entry once (twice for symlinks) instead of once for the
+    /// `PathType` and again for every query of the caller.
+    ///
+    /// Prefer iterating over [`WalkEntries`](struct.WalkEntries.html), which does this for you.
+    pub fn from_entry(entry: walkdir::DirEntry) -> Result<WalkEntry> {
+        let mut metadata = entry.metadata()?;
+        if metadata.file_type().is_symlink() {
+            match fs::metadata(entry.path()) {
+                Ok(target) => metadata = target,
+                Err(ref e) if e.kind() == io::ErrorKind::NotFound => {}
+                Err(e) => {
+                    let path = PathArc::new(entry.path());
+                    return Err(Error::new(e, "reading metadata", path));
+                }
+            }
+        }
+        let abs = PathAbs::new(entry.path())?;
+        let ty = if metadata.is_dir() {
+            PathType::Dir(PathDir::from_abs_unchecked(abs))
+        } else {
+            PathType::File(PathFile::from_abs_unchecked(abs))
+        };
+        Ok(WalkEntry {
+            ty,
+            depth: entry.depth(),
+            metadata,
+            symlink: entry.path_is_symlink(),
+        })
+    }
+
+    /// The depth of the entry relative to the root of the walk, which has depth `0`.
+    pub fn depth(&self) -> usize {
+        self.depth
+    }
+
+    /// The metadata of the entry (or of its target if it is a symlink which is not broken).
+    pub fn metadata(&self) -> &fs::Metadata {
+        &self.metadata
+    }
+
+    /// The file type of the entry (or of its target if it is a symlink which is not broken).
+    pub fn file_type(&self) -> fs::FileType {
+        self.metadata.file_type()
+    }
+
+    /// Whether the entry was found through a symlink.
+    pub fn path_is_symlink(&self) -> bool {
+        self.symlink
+    }
+
+    /// The entry as a `PathType`.
+    pub fn path_type(&self) -> &PathType {
+        &self.ty
+    }
+
+    /// Consume the entry, returning its `PathType`.
+    pub fn into_path_type(self) -> PathType {
+        self.ty
+    }
+
+    /// The entry as a file, if it is one.
+    pub fn as_file(&self) -> Option<&PathFile> {
+        match self.ty {
+            PathType::File(ref file) => Some(file),
+            PathType::Dir(_) => None,
+        }
+    }
+
+    /// The entry as a directory, if it is one.
+    pub fn as_dir(&self) -> Option<&PathDir> {
+        match self.ty {
+            PathType::Dir(ref dir) => Some(dir),
+            PathType::File(_) => None,
+        }
+    }
+}
+
+impl Deref for WalkEntry {
+    type Target = PathType;
+
+    fn deref(&self) -> &PathType {
+        &self.ty
+    }
+}
+
+impl AsRef<PathType> for WalkEntry {
+    fn as_ref(&self) -> &PathType {
+        &self.ty
+    }
+}
+
+impl AsRef<Path> for WalkEntry {
+    fn as_ref(&self) -> &Path {
+        self.ty.as_ref()
+    }
+}
+
+impl From<WalkEntry> for PathType {
+    fn from(entry: WalkEntry) -> PathType {
+        entry.ty
+    }
+}
+
+/// Options for walking a directory which can be shared between everything that walks, i.e.
+/// [`PathDirExt::walk_with`] and `ergo::DeepCopy::walk_options`.
+///
+/// Include and exclude patterns are matched against the path of each entry relative to the root
+/// of the walk, so `target` excludes only the top level `target` directory and `**/*.rs` includes
+/// every rust file. An excluded (or hidden) directory is not descended into.
+///
+/// [`PathDirExt::walk_with`]: trait.PathDirExt.html#method.walk_with
+///
+/// # Examples
+/// ```rust
+/// # extern crate ergo_fs;
+/// use ergo_fs::*;
+///
+/// # fn try_main() -> ::std::io::Result<()> {
+/// let tmp = PathTmp::create("walk_options")?;
+/// let dir = PathDir::new(&tmp)?;
+/// PathFile::create(dir.join("lib.rs"))?;
+/// PathFile::create(dir.join("README.md"))?;
+/// PathDir::create(dir.join(".git"))?;
+/// PathFile::create(dir.join(".git").join("HEAD.rs"))?;
+/// PathDir::create(dir.join("target"))?;
+/// PathFile::create(dir.join("target").join("build.rs"))?;
+///
+/// let options = WalkOptions::new()
+///     .min_depth(1)
+///     .skip_hidden(true)
+///     .include(GlobPattern::new("**/*.rs").unwrap())
+///     .exclude(GlobPattern::new("target").unwrap());
+/// let found = dir
+///     .walk_with(&options)
+///     .map(|entry| entry.map(|e| e.file_name().unwrap().to_os_string()))
+///     .collect::<Result<Vec<_>, _>>()?;
+/// assert_eq!(vec!["lib.rs"], found);
+/// # Ok(()) } fn main() { try_main().unwrap() }
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WalkOptions {
+    min_depth: usize,
+    max_depth: Option<usize>,
+    follow_symlinks: bool,
+    same_file_system: bool,
+    contents_first: bool,
+    skip_hidden: bool,
+    sorted: bool,
+    include: Vec<GlobPattern>,
+    exclude: Vec<GlobPattern>,
+}
+
+impl WalkOptions {
+    /// Create the default walk options, which yield everything including the root.
+    pub fn new() -> WalkOptions {
+        WalkOptions {
+            min_depth: 0,
+            max_depth: None,
+            follow_symlinks: false,
+            same_file_system: false,
+            contents_first: false,
+            skip_hidden: false,
+            sorted: false,
+            include: Vec::new(),
+            exclude: Vec::new(),
+        }
+    }
+
+    /// Only yield entries at least `depth` below the root (default `0`, which yields the root).
+    pub fn min_depth(mut self, depth: usize) -> WalkOptions {
+        self.min_depth = depth;
+        self
+    }
+
+    /// Don't yield or descend into entries more than `depth` below the root (default unlimited).
+    pub fn max_depth(mut self, depth: usize) -> WalkOptions {
+        self.max_depth = Some(depth);
+        self
+    }
+
+    /// Follow symlinks (default `false`). Symlinks which point to one of their own ancestors
+    /// yield a [`SymlinkLoop`](struct.SymlinkLoop.html) error instead.
+    pub fn follow_symlinks(mut self, follow: bool) -> WalkOptions {
+        self.follow_symlinks = follow;
+        self
+    }
+
+    /// Don't descend into directories on a different filesystem (mount point) than the root
+    /// (default `false`). See [`ErgoMetadata::device_id`] to implement other policies.
+    ///
+    /// [`ErgoMetadata::device_id`]: trait.ErgoMetadata.html#tymethod.device_id
+    pub fn same_file_system(mut self, same: bool) -> WalkOptions {
+        self.same_file_system = same;
+        self
+    }
+
+    /// Yield the contents of every directory before the directory itself (default `false`).
+    pub fn contents_first(mut self, contents_first: bool) -> WalkOptions {
+        self.contents_first = contents_first;
+        self
+    }
+
+    /// Skip hidden files and directories, i.e. those whose name starts with `.` (default
+    /// `false`). The root is never skipped.
+    pub fn skip_hidden(mut self, skip: bool) -> WalkOptions {
+        self.skip_hidden = skip;
+        self
+    }
+
+    /// Yield the entries of each directory sorted by name in
+    /// [natural order](sort/index.html) (default `false`, the order of the filesystem).
+    pub fn sorted(mut self, sorted: bool) -> WalkOptions {
+        self.sorted = sorted;
+        self
+    }
+
+    /// Only yield files (and other non-directories) which match at least one of the include
+    /// patterns. Directories are always descended into, unless they are excluded.
+    pub fn include(mut self, pattern: GlobPattern) -> WalkOptions {
+        self.include.push(pattern);
+        self
+    }
+
+    /// Skip entries matching the pattern. Excluded directories are not descended into.
+    pub fn exclude(mut self, pattern: GlobPattern) -> WalkOptions {
+        self.exclude.push(pattern);
+        self
+    }
+
+    /// Whether symlinks are followed.
+    pub fn follows_symlinks(&self) -> bool {
+        self.follow_symlinks
+    }
+
+    /// Walk `root` with these options.
+    pub fn walk<P: AsRef<Path>>(&self, root: P) -> WalkEntries {
+        let root = root.as_ref();
+        let mut walk = WalkDir::new(root)
+            .min_depth(self.min_depth)
+            .follow_links(self.follow_symlinks)
+            .same_file_system(self.same_file_system)
+            .contents_first(self.contents_first);
+        if let Some(depth) = self.max_depth {
+            walk = walk.max_depth(depth);
+        }
+        if self.sorted {
+            walk = walk.sort_by(|a, b| sort::natural_cmp_names(a.file_name(), b.file_name()));
+        }
+        WalkEntries {
+            it: walk.into_iter(),
+            filter: Some(Filter {
+                root: root.to_path_buf(),
+                options: self.clone(),
+            }),
+            observer: None,
+        }
+    }
+}
+
+impl Default for WalkOptions {
+    fn default() -> WalkOptions {
+        WalkOptions::new()
+    }
+}
+
+/// The filtering part of `WalkOptions`, applied to the raw entries before they are converted.
+struct Filter {
+    root: PathBuf,
+    options: WalkOptions,
+}
+
+impl Filter {
+    fn is_match(&self, entry: &walkdir::DirEntry) -> bool {
+        let rel = match entry.path().strip_prefix(&self.root) {
+            Ok(rel) if entry.depth() > 0 => rel,
+            _ => return true,
+        };
+        // A contents-first walk yields children before their parent is checked, so every
+        // ancestor must be checked with them.
+        if self.options.contents_first {
+            let mut ancestor = PathBuf::new();
+            for comp in rel.components() {
+                ancestor.push(comp);
+                if ancestor.as_path() != rel && self.is_skipped(&ancestor) {
+                    return false;
+                }
+            }
+        }
+        if self.is_skipped(rel) {
+            return false;
+        }
+        let opts = &self.options;
+        entry.file_type().is_dir()
+            || opts.include.is_empty()
+            || opts.include.iter().any(|p| p.matches(rel))
+    }
+
+    /// Whether the path (and everything below it) is hidden or excluded.
+    fn is_skipped(&self, rel: &Path) -> bool {
+        let hidden = self.options.skip_hidden
+            && rel
+                .file_name()
+                .and_then(|n| n.to_str())
+                .is_some_and(|n| n.starts_with('.'));
+        hidden || self.options.exclude.iter().any(|p| p.matches(rel))
+    }
+}
+
+/// An iterator over the [`WalkEntry`](struct.WalkEntry.html)s of a walk.
+///
+/// Create it from a configured `WalkDir` with `WalkEntries::from`, or use
+/// [`PathDirExt::walk_post_order`](trait.PathDirExt.html#method.walk_post_order) or
+/// [`WalkOptions::walk`](struct.WalkOptions.html#method.walk).
+pub struct WalkEntries {
+    it: walkdir::IntoIter,
+    filter: Option<Filter>,
+    observer: Option<Arc<dyn FsObserver>>,
+}
+
+impl WalkEntries {
+    /// Skip the descendants of the directory which was just yielded. Does nothing if it was not
+    /// a directory or the walk is contents-first.
+    pub fn skip_current_dir(&mut self) {
+        self.it.skip_current_dir()
+    }
+
+    /// Call `observer` with an event for every directory, every entry which the
+    /// [`WalkOptions`](struct.WalkOptions.html) skip and every error, see
+    /// [`FsObserver`](trait.FsObserver.html).
+    pub fn observe(mut self, observer: Arc<dyn FsObserver>) -> WalkEntries {
+        self.observer = Some(observer);
+        self
+    }
+
+    fn notify(&self, event: &FsEvent) {
+        if let Some(ref observer) = self.observer {
+            observer.on_event(event);
+        }
+    }
+}
+
+impl From<WalkDir> for WalkEntries {
+    fn from(walk: WalkDir) -> WalkEntries {
+        WalkEntries {
+            it: walk.into_iter(),
+            filter: None,
+            observer: None,
+        }
+    }
+}
+
+impl Iterator for WalkEntries {
+    type Item = Result<WalkEntry>;
+
+    fn next(&mut self) -> Option<Result<WalkEntry>> {
+        loop {
+            let entry = match self.it.next()? {
+                Ok(entry) => entry,
+                Err(err) => {
+                    let err = Error::from(err);
+                    self.notify(&FsEvent::Error(&err));
+                    return Some(Err(err));
+                }
+            };
+            if let Some(ref filter) = self.filter {
+                if !filter.is_match(&entry) {
+                    self.notify(&FsEvent::Skipped(entry.path()));
+                    if entry.file_type().is_dir() {
+                        self.it.skip_current_dir();
+                    }
+                    continue;
+                }
+            }
+            let entry = WalkEntry::from_entry(entry);
+            match entry {
+                Ok(ref entry) if entry.as_dir().is_some() => {
+                    self.notify(&FsEvent::EnteredDir(entry.as_path()))
+                }
+                Ok(_) => {}
+                Err(ref err) => self.notify(&FsEvent::Error(err)),
+            }
+            return Some(entry);
+        }
+    }
+}
+
+/// A parallel iterator over the entries of a walk, on rayon's global thread pool.
+///
+/// The directories are read lazily as the threads take entries, so a slow `for_each` holds the
+/// walk back instead of buffering the whole tree. Entries are not yielded in walk order.
+///
+/// Create it with [`PathDirExt::par_walk`](trait.PathDirExt.html#method.par_walk), or from
+/// configured [`WalkEntries`](struct.WalkEntries.html) with `ParWalk::from`. Needs the
+/// `parallel` feature.
+#[cfg(feature = "parallel")]
+pub struct ParWalk {
+    entries: WalkEntries,
+}
+
+#[cfg(feature = "parallel")]
+impl From<WalkEntries> for ParWalk {
+    fn from(entries: WalkEntries) -> ParWalk {
+        ParWalk { entries }
+    }
+}
+
+#[cfg(feature = "parallel")]
+impl ParallelIterator for ParWalk {
+    type Item = Result<WalkEntry>;
+
+    fn drive_unindexed<C>(self, consumer: C) -> C::Result
+    where
+        C: UnindexedConsumer<Self::Item>,
+    {
+        self.entries.par_bridge().drive_unindexed(consumer)
+    }
+}
@@ -0,0 +1,265 @@
+/* Copyright (c) 2018 Garrett Berg, vitiral@gmail.com
+ *
+ * Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+ * http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+ * http://opensource.org/licenses/MIT>, at your option. This file may not be
+ * copied, modified, or distributed except according to those terms.
+ */
+//! Watching glob patterns for changes.
+
+use glob_crate::{MatchOptions, Pattern};
+use glob_wrapper::GlobPatternError;
+use std::collections::{BTreeMap, VecDeque};
+use std::path::Component;
+use std::thread;
+use std::time::SystemTime;
+use std_prelude::*;
+use walkdir;
+
+/// Watch the files matching the glob `pattern` for changes.
+///
+/// This is the same as `GlobWatcher::new(&[pattern])`, see [`GlobWatcher`] for details.
+///
+/// [`GlobWatcher`]: struct.GlobWatcher.html
+pub fn watch_glob(pattern: &str) -> Result<GlobWatcher, GlobPatternError> {
+    GlobWatcher::new(&[pattern])
+}
+
+/// A change to a file which matched a watched pattern.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum WatchEvent {
+    /// The file was created.
+    Created(PathBuf),
+    /// The file's contents (modification time or length) changed.
+    Modified(PathBuf),
+    /// The file was removed.
+    Removed(PathBuf),
+}
+
+impl WatchEvent {
+    /// The path of the file which changed.
+    pub fn path(&self) -> &Path {
+        match *self {
+            WatchEvent::Created(ref p)
+            | WatchEvent::Modified(ref p)
+            | WatchEvent::Removed(ref p) => p,
+        }
+    }
+}
+
+/// Watches a set of glob patterns, emitting only the changes to files which match them.
+///
+/// Only the minimal set of parent directories are scanned: the literal prefix of each pattern,
+/// down to the depth the pattern can match (unlimited if it contains `**`). So
+/// `src/**/*.rs` scans `src/` recursively and `config/*.toml` scans only `config/` itself.
+///
+/// Changes are detected by polling (every 500ms by default, see [`interval`]). The watcher takes
+/// a snapshot when it is created, so only changes made after that are reported. Directories are
+/// never reported, only files. Events are emitted with the paths as the patterns were written,
+/// i.e. a relative pattern emits relative paths.
+///
+/// Use [`poll`] to check for changes without blocking, or iterate over the watcher to block
+/// until each change happens.
+///
+/// [`interval`]: struct.GlobWatcher.html#method.interval
+/// [`poll`]: struct.GlobWatcher.html#method.poll
+///
+/// # Examples
+/// ```rust
+/// # extern crate ergo_fs;
+/// use ergo_fs::*;
+///
+/// # fn try_main() -> ::std::io::Result<()> {
+/// let tmp = PathTmp::create("watch")?;
+/// let mut watcher = watch_glob(&format!("{}/**/*.txt", tmp.display())).unwrap();
+/// assert!(watcher.poll().is_empty());
+///
+/// let file = PathFile::create(tmp.join("example.txt"))?;
+/// PathFile::create(tmp.join("ignored.md"))?;
+/// assert_eq!(
+///     vec![WatchEvent::Created(tmp.join("example.txt").to_path_buf())],
+///     watcher.poll()
+/// );
+///
+/// file.write_str("changed")?;
+/// assert_eq!(
+///     vec![WatchEvent::Modified(tmp.join("example.txt").to_path_buf())],
+///     watcher.poll()
+/// );
+/// # Ok(()) } fn main() { try_main().unwrap() }
+/// ```
+#[derive(Debug)]
+pub struct GlobWatcher {
+    roots: Vec<Root>,
+    patterns: Vec<Pattern>,
+    interval: Duration,
+    files: BTreeMap<PathBuf, Stamp>,
+    pending: VecDeque<WatchEvent>,
+}
+
+/// A directory which must be scanned to find matches of a pattern.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Root {
+    /// The literal prefix of the pattern, empty for the current directory.
+    dir: PathBuf,
+    /// `None` if the pattern can match at any depth.
+    max_depth: Option<usize>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Stamp {
+    modified: Option<SystemTime>,
+    len: u64,
+}
+
+const OPTIONS: MatchOptions = MatchOptions {
+    case_sensitive: true,
+    require_literal_separator: true,
+    require_literal_leading_dot: false,
+};
+
+impl GlobWatcher {
+    /// Watch the files matching any of the glob `patterns` for changes.
+    pub fn new(patterns: &[&str]) -> Result<GlobWatcher, GlobPatternError> {
+        let mut roots = Vec::new();
+        let mut compiled = Vec::new();
+        for pattern in patterns {
+            compiled.push(Pattern::new(pattern)?);
+            let root = Root::from_pattern(pattern);
+            if !roots.contains(&root) {
+                roots.push(root);
+            }
+        }
+        let mut watcher = GlobWatcher {
+            roots,
+            patterns: compiled,
+            interval: Duration::from_millis(500),
+            files: BTreeMap::new(),
+            pending: VecDeque::new(),
+        };
+        watcher.files = watcher.snapshot();
+        Ok(watcher)
+    }
+
+    /// Set the interval at which the filesystem is polled when iterating.
+    pub fn interval(mut self, interval: Duration) -> GlobWatcher {
+        self.interval = interval;
+        self
+    }
+
+    /// Check for changes since the last poll, returning immediately.
+    pub fn poll(&mut self) -> Vec<WatchEvent> {
+        let files = self.snapshot();
+        let mut events = Vec::new();
+        for (path, stamp) in &files {
+            match self.files.get(path) {
+                None => events.push(WatchEvent::Created(path.clone())),
+                Some(old) if old != stamp => events.push(WatchEvent::Modified(path.clone())),
+                Some(_) => {}
+            }
+        }
+        for path in self.files.keys() {
+            if !files.contains_key(path) {
+                events.push(WatchEvent::Removed(path.clone()));
+            }
+        }
+        self.files = files;
+        events
+    }
+
+    fn snapshot(&self) -> BTreeMap<PathBuf, Stamp> {
+        let mut files = BTreeMap::new();
+        for root in &self.roots {
+            let dir = if root.dir.as_os_str().is_empty() {
+                Path::new(".")
+            } else {
+                root.dir.as_path()
+            };
+            let mut walk = walkdir::WalkDir::new(dir).min_depth(1);
+            if let Some(depth) = root.max_depth {
+                walk = walk.max_depth(depth);
+            }
+            // Errors are ignored: files routinely disappear between listing and reading them.
+            for entry in walk.into_iter().filter_map(|e| e.ok()) {
+                let meta = match entry.metadata() {
+                    Ok(m) => m,
+                    Err(_) => continue,
+                };
+                if meta.is_dir() {
+                    continue;
+                }
+                let path = if root.dir.as_os_str().is_empty() {
+                    entry.path().strip_prefix(".").unwrap_or(entry.path())
+                } else {
+                    entry.path()
+                };
+                if self
+                    .patterns
+                    .iter()
+                    .any(|p| p.matches_path_with(path, &OPTIONS))
+                {
+                    let stamp = Stamp {
+                        modified: meta.modified().ok(),
+                        len: meta.len(),
+                    };
+                    files.insert(path.to_path_buf(), stamp);
+                }
+            }
+        }
+        files
+    }
+}
+
+impl Iterator for GlobWatcher {
+    type Item = WatchEvent;
+
+    /// Block until the next change happens. The iterator never ends.
+    fn next(&mut self) -> Option<WatchEvent> {
+        loop {
+            if let Some(event) = self.pending.pop_front() {
+                return Some(event);
+            }
+            thread::sleep(self.interval);
+            let events = self.poll();
+            self.pending.extend(events);
+        }
+    }
+}
+
+impl Root {
+    fn from_pattern(pattern: &str) -> Root {
+        let mut dir = PathBuf::new();
+        let mut rest = Vec::new();
+        let mut components = Path::new(pattern).components();
+        while let Some(comp) = components.next() {
+            if is_literal(&comp) {
+                dir.push(comp.as_os_str());
+            } else {
+                rest.push(comp);
+                rest.extend(components);
+                break;
+            }
+        }
+        if rest.is_empty() {
+            // A literal path, watch its parent.
+            dir.pop();
+            return Root {
+                dir,
+                max_depth: Some(1),
+            };
+        }
+        let max_depth = if rest.iter().any(|c| c.as_os_str() == "**") {
+            None
+        } else {
+            Some(rest.len())
+        };
+        Root { dir, max_depth }
+    }
+}
+
+fn is_literal(comp: &Component) -> bool {
+    match comp.as_os_str().to_str() {
+        Some(s) => !s.contains(['*', '?', '[']),
+        None => true,
+    }
+}
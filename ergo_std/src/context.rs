@@ -0,0 +1,124 @@
+//! Adding context to errors.
+
+use std::error;
+use std::fmt;
+use std::io;
+
+/// An error wrapped with a message describing what was being done when it happened.
+///
+/// It displays as `{context}: {error}`, so wrapping an error several times builds up a chain
+/// like `building package foo: reading manifest: No such file or directory when opening
+/// foo/Cargo.toml`. The wrapped error is also available through `Error::source`.
+///
+/// Create it with [`ResultExt::context`] or [`ResultExt::with_context`].
+///
+/// [`ResultExt::context`]: trait.ResultExt.html#tymethod.context
+/// [`ResultExt::with_context`]: trait.ResultExt.html#tymethod.with_context
+#[derive(Debug)]
+pub struct Context<E> {
+    context: String,
+    error: E,
+}
+
+impl<E> Context<E> {
+    /// Wrap `error` with the `context` message.
+    pub fn new<C: fmt::Display>(context: C, error: E) -> Context<E> {
+        Context {
+            context: context.to_string(),
+            error,
+        }
+    }
+
+    /// The context message.
+    pub fn context(&self) -> &str {
+        &self.context
+    }
+
+    /// A reference to the wrapped error.
+    pub fn get_ref(&self) -> &E {
+        &self.error
+    }
+
+    /// Unwrap the error, discarding the context.
+    pub fn into_inner(self) -> E {
+        self.error
+    }
+
+    /// Convert the wrapped error, keeping the context.
+    pub fn map<F, U>(self, op: F) -> Context<U>
+    where
+        F: FnOnce(E) -> U,
+    {
+        Context {
+            context: self.context,
+            error: op(self.error),
+        }
+    }
+}
+
+impl<E: fmt::Display> fmt::Display for Context<E> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}: {}", self.context, self.error)
+    }
+}
+
+impl<E: error::Error + 'static> error::Error for Context<E> {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        Some(&self.error)
+    }
+}
+
+impl<E> From<Context<E>> for io::Error
+where
+    E: error::Error + Send + Sync + 'static,
+{
+    fn from(err: Context<E>) -> io::Error {
+        io::Error::other(err)
+    }
+}
+
+/// Extension methods for adding context to the error of a `Result`.
+///
+/// # Examples
+/// ```rust
+/// # extern crate ergo_std;
+/// use ergo_std::*;
+/// use std::fs;
+///
+/// # fn main() {
+/// let name = "foo";
+/// let err = fs::read_to_string("/this/does/not/exist")
+///     .context("reading manifest")
+///     .with_context(|| format!("building package {}", name))
+///     .unwrap_err();
+///
+/// let msg = err.to_string();
+/// assert!(msg.starts_with("building package foo: reading manifest: "), "{}", msg);
+/// assert_eq!("building package foo", err.context());
+/// assert_eq!("reading manifest", err.get_ref().context());
+/// # }
+/// ```
+pub trait ResultExt<T, E> {
+    /// Wrap the error with a `context` message.
+    fn context<C: fmt::Display>(self, context: C) -> Result<T, Context<E>>;
+
+    /// Wrap the error with a context message which is only built if there is an error.
+    fn with_context<C, F>(self, context: F) -> Result<T, Context<E>>
+    where
+        C: fmt::Display,
+        F: FnOnce() -> C;
+}
+
+impl<T, E> ResultExt<T, E> for Result<T, E> {
+    fn context<C: fmt::Display>(self, context: C) -> Result<T, Context<E>> {
+        self.map_err(|err| Context::new(context, err))
+    }
+
+    fn with_context<C, F>(self, context: F) -> Result<T, Context<E>>
+    where
+        C: fmt::Display,
+        F: FnOnce() -> C,
+    {
+        self.map_err(|err| Context::new(context(), err))
+    }
+}
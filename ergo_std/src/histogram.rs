@@ -0,0 +1,264 @@
+//! A low overhead histogram for tracking the distribution of durations and sizes.
+
+use std::cmp;
+use std::fmt;
+use std::time::Duration;
+
+/// The number of bits of precision kept for each value. Values are bucketed with a relative
+/// error of at most `1 / 2^SUB_BITS` (~3%).
+const SUB_BITS: u32 = 5;
+const SUB_BUCKETS: usize = 1 << SUB_BITS;
+/// Enough buckets for every `u64`.
+const BUCKETS: usize = (64 - SUB_BITS as usize) * SUB_BUCKETS + SUB_BUCKETS;
+
+/// A histogram of `u64` values (i.e. durations or sizes) supporting percentile queries.
+///
+/// Recording a value is a couple of arithmetic operations and an increment, and the memory used
+/// is fixed (~15KiB) no matter how many values are recorded. This is achieved by storing values
+/// in log-linear buckets (like [HdrHistogram]), so percentiles are accurate to within ~3% of the
+/// true value. `min`, `max`, `count` and `mean` are exact.
+///
+/// Durations are recorded as nanoseconds, use the `*_duration` methods to convert back.
+///
+/// Averages hide tail latency, percentiles don't: use this to track performance regressions
+/// in pipelines where a few slow operations matter.
+///
+/// [HdrHistogram]: http://hdrhistogram.org/
+///
+/// # Examples
+/// ```rust
+/// # extern crate ergo_std;
+/// use ergo_std::*;
+///
+/// # fn main() {
+/// let mut hist = Histogram::new();
+/// for ms in 1..101 {
+///     hist.record_duration(Duration::from_millis(ms));
+/// }
+/// assert_eq!(100, hist.count());
+/// assert_eq!(Duration::from_millis(100), hist.max_duration());
+///
+/// let p50 = hist.p50_duration();
+/// assert!(p50 >= Duration::from_millis(49) && p50 <= Duration::from_millis(52), "{:?}", p50);
+/// let p99 = hist.p99_duration();
+/// assert!(p99 >= Duration::from_millis(98) && p99 <= Duration::from_millis(100), "{:?}", p99);
+/// # }
+/// ```
+#[derive(Clone, PartialEq, Eq)]
+pub struct Histogram {
+    /// Allocated lazily, so an empty histogram is cheap.
+    counts: Vec<u64>,
+    count: u64,
+    sum: u128,
+    min: u64,
+    max: u64,
+}
+
+impl Histogram {
+    /// Create an empty histogram.
+    pub fn new() -> Histogram {
+        Histogram {
+            counts: Vec::new(),
+            count: 0,
+            sum: 0,
+            min: u64::MAX,
+            max: 0,
+        }
+    }
+
+    /// Record a value.
+    pub fn record(&mut self, value: u64) {
+        self.record_n(value, 1);
+    }
+
+    /// Record a value `n` times.
+    pub fn record_n(&mut self, value: u64, n: u64) {
+        if n == 0 {
+            return;
+        }
+        if self.counts.is_empty() {
+            self.counts = vec![0; BUCKETS];
+        }
+        self.counts[bucket(value)] += n;
+        self.count += n;
+        self.sum += u128::from(value) * u128::from(n);
+        self.min = cmp::min(self.min, value);
+        self.max = cmp::max(self.max, value);
+    }
+
+    /// Record a duration as nanoseconds, saturating at `u64::MAX` (~584 years).
+    pub fn record_duration(&mut self, duration: Duration) {
+        self.record(duration_nanos(duration));
+    }
+
+    /// Add all the values recorded in `other` to this histogram.
+    pub fn merge(&mut self, other: &Histogram) {
+        if other.count == 0 {
+            return;
+        }
+        if self.counts.is_empty() {
+            self.counts = vec![0; BUCKETS];
+        }
+        for (mine, theirs) in self.counts.iter_mut().zip(other.counts.iter()) {
+            *mine += *theirs;
+        }
+        self.count += other.count;
+        self.sum += other.sum;
+        self.min = cmp::min(self.min, other.min);
+        self.max = cmp::max(self.max, other.max);
+    }
+
+    /// Remove all recorded values.
+    pub fn clear(&mut self) {
+        *self = Histogram::new();
+    }
+
+    /// The number of values recorded.
+    pub fn count(&self) -> u64 {
+        self.count
+    }
+
+    /// Whether no values have been recorded.
+    pub fn is_empty(&self) -> bool {
+        self.count == 0
+    }
+
+    /// The smallest value recorded, or 0 if the histogram is empty.
+    pub fn min(&self) -> u64 {
+        if self.count == 0 {
+            0
+        } else {
+            self.min
+        }
+    }
+
+    /// The largest value recorded, or 0 if the histogram is empty.
+    pub fn max(&self) -> u64 {
+        self.max
+    }
+
+    /// The mean of the values recorded, or 0 if the histogram is empty.
+    pub fn mean(&self) -> u64 {
+        if self.count == 0 {
+            0
+        } else {
+            (self.sum / u128::from(self.count)) as u64
+        }
+    }
+
+    /// The value at the percentile `p` (between `0.0` and `100.0`), or 0 if the histogram is
+    /// empty.
+    ///
+    /// This is the value which `p` percent of recorded values are less than or equal to.
+    pub fn percentile(&self, p: f64) -> u64 {
+        if self.count == 0 {
+            return 0;
+        }
+        let p = p.clamp(0.0, 100.0);
+        let rank = cmp::max(1, ((p / 100.0) * self.count as f64).ceil() as u64);
+        let mut seen = 0;
+        for (i, &c) in self.counts.iter().enumerate() {
+            seen += c;
+            if seen >= rank {
+                return cmp::max(cmp::min(bucket_high(i), self.max), self.min);
+            }
+        }
+        self.max
+    }
+
+    /// The median (50th percentile).
+    pub fn p50(&self) -> u64 {
+        self.percentile(50.0)
+    }
+
+    /// The 95th percentile.
+    pub fn p95(&self) -> u64 {
+        self.percentile(95.0)
+    }
+
+    /// The 99th percentile.
+    pub fn p99(&self) -> u64 {
+        self.percentile(99.0)
+    }
+
+    /// `min` as a duration.
+    pub fn min_duration(&self) -> Duration {
+        Duration::from_nanos(self.min())
+    }
+
+    /// `max` as a duration.
+    pub fn max_duration(&self) -> Duration {
+        Duration::from_nanos(self.max())
+    }
+
+    /// `mean` as a duration.
+    pub fn mean_duration(&self) -> Duration {
+        Duration::from_nanos(self.mean())
+    }
+
+    /// `percentile` as a duration.
+    pub fn percentile_duration(&self, p: f64) -> Duration {
+        Duration::from_nanos(self.percentile(p))
+    }
+
+    /// `p50` as a duration.
+    pub fn p50_duration(&self) -> Duration {
+        Duration::from_nanos(self.p50())
+    }
+
+    /// `p95` as a duration.
+    pub fn p95_duration(&self) -> Duration {
+        Duration::from_nanos(self.p95())
+    }
+
+    /// `p99` as a duration.
+    pub fn p99_duration(&self) -> Duration {
+        Duration::from_nanos(self.p99())
+    }
+}
+
+impl Default for Histogram {
+    fn default() -> Histogram {
+        Histogram::new()
+    }
+}
+
+impl fmt::Debug for Histogram {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Histogram")
+            .field("count", &self.count())
+            .field("min", &self.min())
+            .field("p50", &self.p50())
+            .field("p95", &self.p95())
+            .field("p99", &self.p99())
+            .field("max", &self.max())
+            .finish()
+    }
+}
+
+fn duration_nanos(duration: Duration) -> u64 {
+    cmp::min(duration.as_nanos(), u128::from(u64::MAX)) as u64
+}
+
+/// The index of the bucket for `value`.
+///
+/// Values below `2 * SUB_BUCKETS` get their own bucket. Above that each power of two is split
+/// into `SUB_BUCKETS` equal buckets.
+fn bucket(value: u64) -> usize {
+    if value < SUB_BUCKETS as u64 {
+        return value as usize;
+    }
+    let exp = 63 - value.leading_zeros();
+    let shift = exp - SUB_BITS;
+    shift as usize * SUB_BUCKETS + (value >> shift) as usize
+}
+
+/// The largest value which is stored in the bucket `index`.
+fn bucket_high(index: usize) -> u64 {
+    if index < SUB_BUCKETS {
+        return index as u64;
+    }
+    let shift = (index / SUB_BUCKETS - 1) as u32;
+    let mantissa = (index - shift as usize * SUB_BUCKETS) as u64;
+    (mantissa << shift) + ((1 << shift) - 1)
+}
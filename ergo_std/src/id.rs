@@ -0,0 +1,279 @@
+//! Unique ids: sortable ULIDs and (with the `uuid` feature) random UUIDs.
+//!
+//! - [`ulid`](fn.ulid.html): a 128 bit id made of a millisecond timestamp and 80 random bits,
+//!   displayed as 26 characters which sort in the order the ids were created, i.e.
+//!   `01ARZ3NDEKTSV4RRFFQ69G5FAV`. Use it for job ids, tmp names and manifest entries.
+//! - [`uuid_v4`](fn.uuid_v4.html): a random (version 4) UUID, i.e.
+//!   `0b5d5c4e-3f3a-4d0c-9c2e-8c1f2a6b7d90`, for when a format which other tools expect is
+//!   needed. Requires the `uuid` feature.
+//!
+//! The random bits come from the operating system's secure random source, see
+//! [`ergo_sys::rand::bytes`](../../ergo_sys/rand/fn.bytes.html). Both types are serialized as
+//! their string.
+//!
+//! # Examples
+//! ```rust
+//! # extern crate ergo_std;
+//! use ergo_std::id::{ulid, Ulid};
+//!
+//! # fn main() {
+//! let first = ulid();
+//! let second = ulid();
+//! assert!(first < second);
+//! assert!(first.to_string() < second.to_string());
+//! assert_eq!(26, first.to_string().len());
+//!
+//! let parsed: Ulid = first.to_string().parse().unwrap();
+//! assert_eq!(first, parsed);
+//! # }
+//! ```
+
+use std::error;
+use std::fmt;
+use std::str::FromStr;
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use ergo_sys::rand;
+use serde::de::{self, Deserialize, Deserializer};
+use serde::ser::{Serialize, Serializer};
+
+/// Crockford's base32 alphabet, which ULIDs are displayed in.
+const CROCKFORD: &[u8; 32] = b"0123456789ABCDEFGHJKMNPQRSTVWXYZ";
+const ULID_LEN: usize = 26;
+const RANDOM_BITS: u32 = 80;
+const RANDOM_MASK: u128 = (1 << RANDOM_BITS) - 1;
+
+lazy_static! {
+    /// The last ULID created in this process, to keep them increasing within a millisecond.
+    static ref LAST_ULID: Mutex<u128> = Mutex::new(0);
+}
+
+/// Create a new ULID.
+///
+/// ULIDs created by this process are always increasing: within the same millisecond the random
+/// part of the previous id is incremented.
+pub fn ulid() -> Ulid {
+    Ulid::new()
+}
+
+/// A Universally Unique Lexicographically Sortable Identifier, see the [module docs](index.html).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Ulid(u128);
+
+impl Ulid {
+    /// Create a new ULID, the same as [`ulid`](fn.ulid.html).
+    pub fn new() -> Ulid {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_millis() as u64)
+            .unwrap_or(0);
+        let mut random = [0_u8; 16];
+        random[6..].copy_from_slice(&rand::bytes(10));
+        let candidate = Ulid::from_parts(now, u128::from_be_bytes(random));
+
+        let mut last = LAST_ULID.lock().unwrap_or_else(|e| e.into_inner());
+        let id = if Ulid(*last).timestamp_ms() >= now {
+            // same millisecond (or the clock went backwards): stay sortable
+            Ulid(*last + 1)
+        } else {
+            candidate
+        };
+        *last = id.0;
+        id
+    }
+
+    /// Create a ULID from a millisecond unix timestamp and random bits. Only the lower 48 bits of
+    /// `timestamp_ms` and 80 bits of `random` are used.
+    pub fn from_parts(timestamp_ms: u64, random: u128) -> Ulid {
+        Ulid((u128::from(timestamp_ms) << RANDOM_BITS) | (random & RANDOM_MASK))
+    }
+
+    /// The millisecond unix timestamp of when the ULID was created.
+    pub fn timestamp_ms(&self) -> u64 {
+        (self.0 >> RANDOM_BITS) as u64
+    }
+
+    /// The time the ULID was created.
+    pub fn system_time(&self) -> SystemTime {
+        UNIX_EPOCH + ::std::time::Duration::from_millis(self.timestamp_ms())
+    }
+
+    /// The 128 bit value.
+    pub fn to_u128(&self) -> u128 {
+        self.0
+    }
+}
+
+impl Default for Ulid {
+    fn default() -> Ulid {
+        Ulid::new()
+    }
+}
+
+impl From<u128> for Ulid {
+    fn from(value: u128) -> Ulid {
+        Ulid(value)
+    }
+}
+
+impl fmt::Display for Ulid {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut out = [0_u8; ULID_LEN];
+        for (i, c) in out.iter_mut().enumerate() {
+            let shift = 5 * (ULID_LEN - 1 - i);
+            *c = CROCKFORD[((self.0 >> shift) & 0x1f) as usize];
+        }
+        f.write_str(::std::str::from_utf8(&out).expect("ascii"))
+    }
+}
+
+impl FromStr for Ulid {
+    type Err = ParseIdError;
+
+    /// Parse a ULID, ignoring case.
+    fn from_str(s: &str) -> Result<Ulid, ParseIdError> {
+        let err = || ParseIdError::new("ULID", s);
+        // the first character only holds 3 bits
+        if s.len() != ULID_LEN || s.as_bytes()[0] > b'7' {
+            return Err(err());
+        }
+        let mut value: u128 = 0;
+        for b in s.bytes() {
+            let b = b.to_ascii_uppercase();
+            let digit = CROCKFORD.iter().position(|&c| c == b).ok_or_else(err)?;
+            value = (value << 5) | digit as u128;
+        }
+        Ok(Ulid(value))
+    }
+}
+
+impl Serialize for Ulid {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for Ulid {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Ulid, D::Error> {
+        let s = String::deserialize(deserializer)?;
+        s.parse().map_err(de::Error::custom)
+    }
+}
+
+/// Create a random (version 4) UUID. Requires the `uuid` feature.
+///
+/// # Examples
+/// ```rust
+/// # extern crate ergo_std;
+/// use ergo_std::id::{uuid_v4, Uuid};
+///
+/// # fn main() {
+/// let id = uuid_v4();
+/// let s = id.to_string();
+/// assert_eq!(36, s.len());
+/// assert_eq!(Some('4'), s.chars().nth(14));
+/// assert_eq!(id, s.parse::<Uuid>().unwrap());
+/// # }
+/// ```
+#[cfg(feature = "uuid")]
+pub fn uuid_v4() -> Uuid {
+    let mut bytes = [0_u8; 16];
+    bytes.copy_from_slice(&rand::bytes(16));
+    bytes[6] = (bytes[6] & 0x0f) | 0x40; // version 4
+    bytes[8] = (bytes[8] & 0x3f) | 0x80; // RFC 4122 variant
+    Uuid(bytes)
+}
+
+/// A UUID, displayed in the hyphenated lowercase form. Requires the `uuid` feature.
+#[cfg(feature = "uuid")]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Uuid([u8; 16]);
+
+#[cfg(feature = "uuid")]
+impl Uuid {
+    /// Create a UUID from its bytes.
+    pub fn from_bytes(bytes: [u8; 16]) -> Uuid {
+        Uuid(bytes)
+    }
+
+    /// The bytes of the UUID.
+    pub fn as_bytes(&self) -> &[u8; 16] {
+        &self.0
+    }
+}
+
+#[cfg(feature = "uuid")]
+impl fmt::Display for Uuid {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (i, b) in self.0.iter().enumerate() {
+            if i == 4 || i == 6 || i == 8 || i == 10 {
+                f.write_str("-")?;
+            }
+            write!(f, "{:02x}", b)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(feature = "uuid")]
+impl FromStr for Uuid {
+    type Err = ParseIdError;
+
+    /// Parse a hyphenated UUID, ignoring case.
+    fn from_str(s: &str) -> Result<Uuid, ParseIdError> {
+        let err = || ParseIdError::new("UUID", s);
+        let hyphens = s.len() == 36
+            && s.is_ascii()
+            && s.char_indices()
+                .all(|(i, c)| (c == '-') == (i == 8 || i == 13 || i == 18 || i == 23));
+        if !hyphens {
+            return Err(err());
+        }
+        let hex: String = s.chars().filter(|&c| c != '-').collect();
+        let mut out = [0_u8; 16];
+        for (i, byte) in out.iter_mut().enumerate() {
+            *byte = u8::from_str_radix(&hex[i * 2..i * 2 + 2], 16).map_err(|_| err())?;
+        }
+        Ok(Uuid(out))
+    }
+}
+
+#[cfg(feature = "uuid")]
+impl Serialize for Uuid {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+#[cfg(feature = "uuid")]
+impl<'de> Deserialize<'de> for Uuid {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Uuid, D::Error> {
+        let s = String::deserialize(deserializer)?;
+        s.parse().map_err(de::Error::custom)
+    }
+}
+
+/// The error for a string which is not a valid id.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseIdError {
+    kind: &'static str,
+    input: String,
+}
+
+impl ParseIdError {
+    fn new(kind: &'static str, input: &str) -> ParseIdError {
+        ParseIdError {
+            kind,
+            input: input.to_string(),
+        }
+    }
+}
+
+impl fmt::Display for ParseIdError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "invalid {} {:?}", self.kind, self.input)
+    }
+}
+
+impl error::Error for ParseIdError {}
@@ -44,7 +44,35 @@
 //!   compliment rust's existing `vec!` macro. These
 //! - **[`Regex`]**: the regular expression type from the `regex` crate.
 //!
+//! In addition, the following types are defined here:
+//!
+//! - **[`term`]**: terminal detection (`is_tty`, `width`) and a tiny color API
+//!   (`style("error").red().bold()`) which is disabled when not a terminal or `NO_COLOR` is set.
+//! - **[`strings`]**: `dedent`, `indent`, `wrap` and `truncate_chars` for formatting help text,
+//!   error messages and generated code.
+//! - **[`template`]**: `render(template, &vars)` replaces `{{var}}` placeholders with values
+//!   from a map, reporting missing variables with their line and column.
+//! - **[`time`]**: re-exports the common types of [`chrono`] and adds `now_utc()`, an RFC 3339
+//!   [`Timestamp`] for serialized manifests, a [`Stopwatch`] which displays the elapsed time for
+//!   humans and `deadline(dur)`.
+//! - **[`id`]**: unique ids, `ulid()` for ids which sort by creation time and `uuid_v4()`
+//!   (with the `uuid` feature), which are serialized as strings.
+//! - **[`ResultExt`]**: `context` and `with_context` methods which wrap an error with a message
+//!   describing what was being done, i.e. "while building package X".
+//! - **[`Histogram`]**: a low overhead histogram of durations or sizes which can be queried for
+//!   percentiles (p50/p95/p99).
+//!
 //! [`ergo`]: https://github.com/rust-crates/ergo
+//! [`Histogram`]: struct.Histogram.html
+//! [`ResultExt`]: trait.ResultExt.html
+//! [`term`]: term/index.html
+//! [`strings`]: strings/index.html
+//! [`template`]: template/index.html
+//! [`time`]: time/index.html
+//! [`id`]: id/index.html
+//! [`chrono`]: ../chrono/index.html
+//! [`Timestamp`]: time/struct.Timestamp.html
+//! [`Stopwatch`]: time/struct.Stopwatch.html
 //! [`std_prelude`]: ../std_prelude/index.html
 //! [`itertools`]: ../itertools/index.html
 //! [`indexmap`]: ../indexmap/index.html
@@ -67,6 +95,7 @@
 //!   fast iteration (previously named `ordermap`)
 //! - [**maplit**](https://github.com/bluss/maplit): Rust container / collection literal macros for
 //!   HashMap, HashSet, BTreeMap, BTreeSet.
+//! - [**chrono**](https://github.com/chronotope/chrono): Date and time library for Rust.
 //! - [**regex**](https://github.com/rust-lang/regex): An implementation of regular expressions for
 //!   Rust. This implementation uses finite automata and guarantees linear time matching on all
 //!   inputs.
@@ -74,6 +103,8 @@
 //! Consider supporting their development individually and starring them on github.
 #![allow(unused_imports)]
 
+pub extern crate chrono;
+extern crate ergo_sys;
 #[macro_use]
 pub extern crate itertools;
 #[macro_use]
@@ -87,6 +118,8 @@ pub extern crate regex;
 pub extern crate serde;
 #[macro_use]
 pub extern crate serde_derive;
+#[cfg(unix)]
+extern crate libc;
 
 pub use std_prelude::*;
 pub use lazy_static::*;
@@ -97,3 +130,13 @@ pub use regex::Regex;
 pub use serde::*;
 pub use serde_derive::*;
 
+mod context;
+mod histogram;
+pub mod id;
+pub mod strings;
+pub mod template;
+pub mod term;
+pub mod time;
+
+pub use context::{Context, ResultExt};
+pub use histogram::Histogram;
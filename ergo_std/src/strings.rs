@@ -0,0 +1,148 @@
+//! Formatting text: dedenting, indenting, wrapping and truncating.
+//!
+//! These are for help text, error messages and generated code. Widths are counted in `char`s,
+//! which is the number of columns for most (but not all) text.
+//!
+//! # Examples
+//! ```rust
+//! # extern crate ergo_std;
+//! use ergo_std::strings::*;
+//!
+//! # fn main() {
+//! let body = dedent("
+//!     fn main() {
+//!         println!(\"hi\");
+//!     }
+//! ");
+//! assert_eq!("fn main() {\n    println!(\"hi\");\n}\n", body);
+//! assert_eq!("  fn main() {\n      println!(\"hi\");\n  }\n", indent(&body, 2));
+//!
+//! assert_eq!("the quick\nbrown fox", wrap("the quick brown fox", 10));
+//! assert_eq!("the qu…", truncate_chars("the quick brown fox", 7));
+//! # }
+//! ```
+
+use std::borrow::Cow;
+
+/// The character appended by [`truncate_chars`](fn.truncate_chars.html).
+pub const ELLIPSIS: char = '…';
+
+/// Remove the whitespace which is common to the start of every line.
+///
+/// Lines which are only whitespace are ignored when finding the common whitespace and become
+/// empty. If `s` starts with a newline it is removed, so that a string literal can start on
+/// the line after the opening quote.
+pub fn dedent(s: &str) -> String {
+    let s = s.strip_prefix('\n').unwrap_or(s);
+    let common = s
+        .lines()
+        .filter(|l| !l.trim().is_empty())
+        .map(|l| l.len() - l.trim_start().len())
+        .min()
+        .unwrap_or(0);
+
+    let mut out = String::with_capacity(s.len());
+    for line in s.split_inclusive('\n') {
+        let (line, newline) = match line.strip_suffix('\n') {
+            Some(l) => (l, "\n"),
+            None => (line, ""),
+        };
+        if !line.trim().is_empty() {
+            // `common` is not at a char boundary if lines mix unicode and ascii whitespace
+            out.push_str(line.get(common..).unwrap_or_else(|| line.trim_start()));
+        }
+        out.push_str(newline);
+    }
+    out
+}
+
+/// Indent every line which is not empty by `n` spaces.
+pub fn indent(s: &str, n: usize) -> String {
+    let prefix = " ".repeat(n);
+    let mut out = String::with_capacity(s.len() + n * s.lines().count());
+    for line in s.split_inclusive('\n') {
+        if line != "\n" && line != "\r\n" {
+            out.push_str(&prefix);
+        }
+        out.push_str(line);
+    }
+    out
+}
+
+/// Wrap each line of `s` so that it is at most `width` characters long, breaking at whitespace.
+///
+/// The leading whitespace of a line is kept on the lines it is wrapped into, so indented
+/// lists stay indented. Other runs of whitespace become a single space. Words longer than
+/// `width` are put on their own line and not broken.
+///
+/// # Examples
+/// ```rust
+/// # extern crate ergo_std;
+/// use ergo_std::strings::wrap;
+///
+/// # fn main() {
+/// let help = "Options:\n  - print every file which is copied";
+/// assert_eq!(
+///     "Options:\n  - print every file\n  which is copied",
+///     wrap(help, 20),
+/// );
+/// # }
+/// ```
+pub fn wrap(s: &str, width: usize) -> String {
+    let mut out = String::with_capacity(s.len());
+    for (i, line) in s.split('\n').enumerate() {
+        if i > 0 {
+            out.push('\n');
+        }
+        let rest = line.trim_start();
+        let lead = &line[..line.len() - rest.len()];
+        let lead_len = lead.chars().count();
+
+        out.push_str(lead);
+        let mut col = lead_len;
+        let mut first = true;
+        for word in rest.split_whitespace() {
+            let len = word.chars().count();
+            if !first && col + 1 + len > width {
+                out.push('\n');
+                out.push_str(lead);
+                col = lead_len;
+                first = true;
+            }
+            if !first {
+                out.push(' ');
+                col += 1;
+            }
+            out.push_str(word);
+            col += len;
+            first = false;
+        }
+    }
+    out
+}
+
+/// Truncate `s` to at most `max` characters, replacing the end with `…` if it was too long.
+///
+/// # Examples
+/// ```rust
+/// # extern crate ergo_std;
+/// use ergo_std::strings::truncate_chars;
+///
+/// # fn main() {
+/// assert_eq!("short", truncate_chars("short", 5));
+/// assert_eq!("héll…", truncate_chars("héllo wörld", 5));
+/// # }
+/// ```
+pub fn truncate_chars(s: &str, max: usize) -> Cow<'_, str> {
+    match s.char_indices().nth(max) {
+        None => Cow::Borrowed(s),
+        Some(_) if max == 0 => Cow::Borrowed(""),
+        Some(_) => {
+            let end = s.char_indices().nth(max - 1).map(|(i, _)| i).unwrap_or(0);
+            let mut out = String::with_capacity(end + ELLIPSIS.len_utf8());
+            out.push_str(&s[..end]);
+            out.push(ELLIPSIS);
+            Cow::Owned(out)
+        }
+    }
+}
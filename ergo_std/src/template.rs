@@ -0,0 +1,171 @@
+//! Rendering text templates with `{{var}}` placeholders.
+//!
+//! Unlike `shellexpand` (which expands `$VAR` from the environment) the values come from a map
+//! and every placeholder must have a value: a missing one is an error with its name and
+//! position, so a typo in a template doesn't silently produce an empty string.
+//!
+//! - `{{name}}` is replaced with the value of `name`. Whitespace inside the braces is ignored,
+//!   so `{{ name }}` is the same.
+//! - `\{{` is a literal `{{`.
+//!
+//! # Examples
+//! ```rust
+//! #[macro_use] extern crate ergo_std;
+//! use ergo_std::template;
+//!
+//! # fn main() {
+//! let vars = hashmap!{"name" => "ergo", "version" => "0.1.0"};
+//! let out = template::render("[package]\nname = \"{{name}}\"\nversion = \"{{ version }}\"\n", &vars);
+//! assert_eq!("[package]\nname = \"ergo\"\nversion = \"0.1.0\"\n", out.unwrap());
+//!
+//! let err = template::render("name = \"{{nmae}}\"", &vars).unwrap_err();
+//! assert_eq!("missing template variable `nmae` at line 1, column 9", err.to_string());
+//!
+//! assert_eq!("literal {{name}}", template::render("literal \\{{name}}", &vars).unwrap());
+//! # }
+//! ```
+
+use std::borrow::Borrow;
+use std::collections::HashMap;
+use std::error;
+use std::fmt;
+use std::hash::{BuildHasher, Hash};
+
+/// Render `template`, replacing each `{{name}}` with `vars[name]`.
+pub fn render<K, V, S>(template: &str, vars: &HashMap<K, V, S>) -> Result<String, TemplateError>
+where
+    K: Borrow<str> + Hash + Eq,
+    V: fmt::Display,
+    S: BuildHasher,
+{
+    render_with(template, |name| vars.get(name))
+}
+
+/// Render `template`, replacing each `{{name}}` with the value returned by `lookup(name)`.
+///
+/// Use this when the values are not in a `HashMap`, i.e. to look them up in a struct or to
+/// compute them lazily.
+///
+/// # Examples
+/// ```rust
+/// # extern crate ergo_std;
+/// use ergo_std::template;
+///
+/// # fn main() {
+/// let out = template::render_with("{{a}} + {{b}}", |name| match name {
+///     "a" => Some(1),
+///     "b" => Some(2),
+///     _ => None,
+/// });
+/// assert_eq!("1 + 2", out.unwrap());
+/// # }
+/// ```
+pub fn render_with<F, V>(template: &str, mut lookup: F) -> Result<String, TemplateError>
+where
+    F: FnMut(&str) -> Option<V>,
+    V: fmt::Display,
+{
+    use std::fmt::Write;
+
+    let mut out = String::with_capacity(template.len());
+    let mut rest = template;
+    while let Some(start) = rest.find("{{") {
+        if rest[..start].ends_with('\\') {
+            out.push_str(&rest[..start - 1]);
+            out.push_str("{{");
+            rest = &rest[start + 2..];
+            continue;
+        }
+        out.push_str(&rest[..start]);
+        let offset = template.len() - rest.len() + start;
+        let inner = &rest[start + 2..];
+        let end = match inner.find("}}") {
+            Some(end) => end,
+            None => return Err(TemplateError::new(ErrorKind::Unclosed, template, offset)),
+        };
+        let name = inner[..end].trim();
+        if name.is_empty() || name.contains(|c: char| c.is_whitespace() || c == '{') {
+            let kind = ErrorKind::InvalidName(inner[..end].to_string());
+            return Err(TemplateError::new(kind, template, offset));
+        }
+        match lookup(name) {
+            Some(value) => write!(out, "{}", value).expect("writing to a String"),
+            None => {
+                let kind = ErrorKind::Missing(name.to_string());
+                return Err(TemplateError::new(kind, template, offset));
+            }
+        }
+        rest = &inner[end + 2..];
+    }
+    out.push_str(rest);
+    Ok(out)
+}
+
+/// The error when rendering a template, with the line and column of the placeholder.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TemplateError {
+    kind: ErrorKind,
+    line: usize,
+    column: usize,
+}
+
+/// The kind of a [`TemplateError`](struct.TemplateError.html).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ErrorKind {
+    /// There is no value for the variable.
+    Missing(String),
+    /// The placeholder is empty or the name contains whitespace.
+    InvalidName(String),
+    /// A `{{` has no matching `}}`.
+    Unclosed,
+}
+
+impl TemplateError {
+    fn new(kind: ErrorKind, template: &str, offset: usize) -> TemplateError {
+        let before = &template[..offset];
+        let line_start = before.rfind('\n').map(|i| i + 1).unwrap_or(0);
+        TemplateError {
+            kind,
+            line: before.matches('\n').count() + 1,
+            column: before[line_start..].chars().count() + 1,
+        }
+    }
+
+    /// The kind of error.
+    pub fn kind(&self) -> &ErrorKind {
+        &self.kind
+    }
+
+    /// The name of the missing variable, if that was the error.
+    pub fn missing(&self) -> Option<&str> {
+        match self.kind {
+            ErrorKind::Missing(ref name) => Some(name),
+            _ => None,
+        }
+    }
+
+    /// The line of the placeholder, starting at 1.
+    pub fn line(&self) -> usize {
+        self.line
+    }
+
+    /// The column (in chars) of the placeholder's `{{`, starting at 1.
+    pub fn column(&self) -> usize {
+        self.column
+    }
+}
+
+impl fmt::Display for TemplateError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.kind {
+            ErrorKind::Missing(ref name) => write!(f, "missing template variable `{}`", name)?,
+            ErrorKind::InvalidName(ref name) => {
+                write!(f, "invalid template variable `{{{{{}}}}}`", name)?
+            }
+            ErrorKind::Unclosed => write!(f, "unclosed `{{{{` in template")?,
+        }
+        write!(f, " at line {}, column {}", self.line, self.column)
+    }
+}
+
+impl error::Error for TemplateError {}
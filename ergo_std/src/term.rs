@@ -0,0 +1,219 @@
+//! Terminal detection and colored output.
+//!
+//! Color is only written when it will be seen: the stream must be a terminal, `NO_COLOR` must
+//! not be set (see [no-color.org](https://no-color.org)) and `TERM` must not be `dumb`.
+//!
+//! # Examples
+//! ```rust
+//! # extern crate ergo_std;
+//! use ergo_std::term::{self, style};
+//!
+//! # fn main() {
+//! eprintln!("{} something went wrong", style("error:").red().bold().stderr());
+//!
+//! // Forcing color on or off is useful for tests and `--color` flags.
+//! assert_eq!("\u{1b}[1;32mok\u{1b}[0m", style("ok").green().bold().force(true).to_string());
+//! assert_eq!("ok", style("ok").green().bold().force(false).to_string());
+//!
+//! let width = term::width().unwrap_or(80);
+//! println!("{}", "-".repeat(width));
+//! # }
+//! ```
+
+use std::env;
+use std::fmt;
+use std::io::{self, IsTerminal};
+
+/// A standard output stream of the process.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Stream {
+    /// Standard output.
+    Stdout,
+    /// Standard error.
+    Stderr,
+}
+
+/// Whether the stream is connected to a terminal.
+pub fn is_tty(stream: Stream) -> bool {
+    match stream {
+        Stream::Stdout => io::stdout().is_terminal(),
+        Stream::Stderr => io::stderr().is_terminal(),
+    }
+}
+
+/// Whether color should be written to the stream.
+///
+/// This is false if the stream is not a terminal, `NO_COLOR` is set to anything or `TERM` is
+/// `dumb`.
+pub fn color_enabled(stream: Stream) -> bool {
+    if env::var_os("NO_COLOR").is_some() {
+        return false;
+    }
+    if env::var_os("TERM").is_some_and(|t| t == "dumb") {
+        return false;
+    }
+    is_tty(stream)
+}
+
+/// The width of the terminal in columns.
+///
+/// This asks the terminal connected to stdout or stderr and falls back to the `COLUMNS`
+/// environment variable. Returns `None` if neither is available, i.e. when output is piped.
+pub fn width() -> Option<usize> {
+    sys::width().or_else(|| {
+        env::var("COLUMNS")
+            .ok()
+            .and_then(|c| c.parse().ok())
+            .filter(|&c| c > 0)
+    })
+}
+
+/// Style `value` for display on a terminal. See [`Styled`](struct.Styled.html).
+pub fn style<D: fmt::Display>(value: D) -> Styled<D> {
+    Styled {
+        value,
+        fg: None,
+        bold: false,
+        dim: false,
+        underline: false,
+        stream: Stream::Stdout,
+        force: None,
+    }
+}
+
+/// A value with a terminal style, created with [`style`](fn.style.html).
+///
+/// When it is displayed the ANSI escape codes are only written if color is enabled for its
+/// stream (stdout unless [`stderr`](#method.stderr) is called), see
+/// [`color_enabled`](fn.color_enabled.html).
+#[derive(Debug, Clone)]
+pub struct Styled<D> {
+    value: D,
+    fg: Option<u8>,
+    bold: bool,
+    dim: bool,
+    underline: bool,
+    stream: Stream,
+    force: Option<bool>,
+}
+
+impl<D: fmt::Display> Styled<D> {
+    fn fg(mut self, code: u8) -> Styled<D> {
+        self.fg = Some(code);
+        self
+    }
+
+    /// Red foreground.
+    pub fn red(self) -> Styled<D> {
+        self.fg(31)
+    }
+
+    /// Green foreground.
+    pub fn green(self) -> Styled<D> {
+        self.fg(32)
+    }
+
+    /// Yellow foreground.
+    pub fn yellow(self) -> Styled<D> {
+        self.fg(33)
+    }
+
+    /// Blue foreground.
+    pub fn blue(self) -> Styled<D> {
+        self.fg(34)
+    }
+
+    /// Magenta foreground.
+    pub fn magenta(self) -> Styled<D> {
+        self.fg(35)
+    }
+
+    /// Cyan foreground.
+    pub fn cyan(self) -> Styled<D> {
+        self.fg(36)
+    }
+
+    /// Bold text.
+    pub fn bold(mut self) -> Styled<D> {
+        self.bold = true;
+        self
+    }
+
+    /// Dim text.
+    pub fn dim(mut self) -> Styled<D> {
+        self.dim = true;
+        self
+    }
+
+    /// Underlined text.
+    pub fn underline(mut self) -> Styled<D> {
+        self.underline = true;
+        self
+    }
+
+    /// Detect whether to use color based on stderr instead of stdout.
+    pub fn stderr(mut self) -> Styled<D> {
+        self.stream = Stream::Stderr;
+        self
+    }
+
+    /// Always (`true`) or never (`false`) write color, regardless of the stream.
+    pub fn force(mut self, color: bool) -> Styled<D> {
+        self.force = Some(color);
+        self
+    }
+
+    fn codes(&self) -> Vec<String> {
+        let mut codes = Vec::new();
+        if self.bold {
+            codes.push("1".to_string());
+        }
+        if self.dim {
+            codes.push("2".to_string());
+        }
+        if self.underline {
+            codes.push("4".to_string());
+        }
+        if let Some(fg) = self.fg {
+            codes.push(fg.to_string());
+        }
+        codes
+    }
+}
+
+impl<D: fmt::Display> fmt::Display for Styled<D> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let color = self.force.unwrap_or_else(|| color_enabled(self.stream));
+        let codes = self.codes();
+        if !color || codes.is_empty() {
+            return self.value.fmt(f);
+        }
+        write!(f, "\x1b[{}m", codes.join(";"))?;
+        self.value.fmt(f)?;
+        f.write_str("\x1b[0m")
+    }
+}
+
+#[cfg(unix)]
+mod sys {
+    use libc;
+
+    pub(super) fn width() -> Option<usize> {
+        for fd in &[libc::STDOUT_FILENO, libc::STDERR_FILENO] {
+            // Safety: TIOCGWINSZ only writes a winsize to the pointer.
+            let mut size: libc::winsize = unsafe { ::std::mem::zeroed() };
+            let ret = unsafe { libc::ioctl(*fd, libc::TIOCGWINSZ, &mut size) };
+            if ret == 0 && size.ws_col > 0 {
+                return Some(usize::from(size.ws_col));
+            }
+        }
+        None
+    }
+}
+
+#[cfg(not(unix))]
+mod sys {
+    pub(super) fn width() -> Option<usize> {
+        None
+    }
+}
@@ -0,0 +1,297 @@
+//! Timestamps, stopwatches and deadlines.
+//!
+//! This re-exports the most used types of [`chrono`](../chrono/index.html) and adds:
+//!
+//! - [`now_utc`](fn.now_utc.html): the current time in UTC.
+//! - [`Timestamp`](struct.Timestamp.html): a UTC time which is displayed, parsed and serialized
+//!   as an RFC 3339 string, i.e. `2018-03-04T12:30:00Z`.
+//! - [`Stopwatch`](struct.Stopwatch.html): measures elapsed time and displays it for humans,
+//!   i.e. `1.52s` or `2m 03s`.
+//! - [`deadline`](fn.deadline.html): a point in time to give up at, with the time remaining.
+//!
+//! # Examples
+//! ```rust
+//! # extern crate ergo_std;
+//! use ergo_std::time::*;
+//!
+//! # fn main() {
+//! let watch = Stopwatch::start();
+//! let started = Timestamp::now();
+//! let stamp: Timestamp = "2018-03-04T12:30:00+01:00".parse().unwrap();
+//! assert_eq!("2018-03-04T11:30:00Z", stamp.to_string());
+//! assert!(started > stamp);
+//! println!("done in {}", watch);
+//! # }
+//! ```
+
+use std::fmt;
+use std::ops::Deref;
+use std::str::FromStr;
+use std::time::{Duration, Instant, SystemTime};
+
+use chrono::{self, SecondsFormat};
+use serde::de::{self, Deserialize, Deserializer};
+use serde::ser::{Serialize, Serializer};
+
+pub use chrono::{DateTime, Datelike, Local, NaiveDate, NaiveDateTime, TimeZone, Timelike, Utc};
+
+/// The current time in UTC.
+pub fn now_utc() -> DateTime<Utc> {
+    Utc::now()
+}
+
+/// A UTC time which is displayed, parsed and serialized as an RFC 3339 string.
+///
+/// Use it in structs which are serialized to i.e. JSON or TOML manifests, so the times in them
+/// are unambiguous and readable. Parsing accepts any offset and converts it to UTC. It
+/// dereferences to a `DateTime<Utc>` for formatting and arithmetic.
+///
+/// # Examples
+/// ```rust
+/// #[macro_use] extern crate ergo_std;
+/// extern crate serde_json;
+/// use ergo_std::*;
+/// use ergo_std::time::{Datelike, Timestamp};
+///
+/// #[derive(Serialize, Deserialize)]
+/// struct Manifest {
+///     built: Timestamp,
+/// }
+///
+/// # fn main() {
+/// let json = r#"{"built":"2018-03-04T12:30:00.250Z"}"#;
+/// let manifest: Manifest = serde_json::from_str(json).unwrap();
+/// assert_eq!(2018, manifest.built.year());
+/// assert_eq!(json, serde_json::to_string(&manifest).unwrap());
+/// # }
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Timestamp(DateTime<Utc>);
+
+impl Timestamp {
+    /// The current time.
+    pub fn now() -> Timestamp {
+        Timestamp(Utc::now())
+    }
+
+    /// The time as a `DateTime`.
+    pub fn datetime(&self) -> DateTime<Utc> {
+        self.0
+    }
+
+    /// The time as a `SystemTime`.
+    pub fn system_time(&self) -> SystemTime {
+        self.0.into()
+    }
+}
+
+impl Deref for Timestamp {
+    type Target = DateTime<Utc>;
+
+    fn deref(&self) -> &DateTime<Utc> {
+        &self.0
+    }
+}
+
+impl From<DateTime<Utc>> for Timestamp {
+    fn from(time: DateTime<Utc>) -> Timestamp {
+        Timestamp(time)
+    }
+}
+
+impl From<SystemTime> for Timestamp {
+    fn from(time: SystemTime) -> Timestamp {
+        Timestamp(time.into())
+    }
+}
+
+impl From<Timestamp> for DateTime<Utc> {
+    fn from(stamp: Timestamp) -> DateTime<Utc> {
+        stamp.0
+    }
+}
+
+impl fmt::Display for Timestamp {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&self.0.to_rfc3339_opts(SecondsFormat::AutoSi, true))
+    }
+}
+
+impl FromStr for Timestamp {
+    type Err = chrono::ParseError;
+
+    fn from_str(s: &str) -> Result<Timestamp, chrono::ParseError> {
+        DateTime::parse_from_rfc3339(s).map(|t| Timestamp(t.with_timezone(&Utc)))
+    }
+}
+
+impl Serialize for Timestamp {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for Timestamp {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Timestamp, D::Error> {
+        let s = String::deserialize(deserializer)?;
+        s.parse().map_err(|err| {
+            de::Error::custom(format!("invalid RFC 3339 timestamp {:?}: {}", s, err))
+        })
+    }
+}
+
+/// Measures the time elapsed since it was started.
+///
+/// It displays the elapsed time for humans, see [`HumanDuration`](struct.HumanDuration.html).
+///
+/// # Examples
+/// ```rust
+/// # extern crate ergo_std;
+/// use ergo_std::time::Stopwatch;
+///
+/// # fn main() {
+/// let mut watch = Stopwatch::start();
+/// // .. do some work
+/// println!("step 1 took {}", watch);
+/// let step1 = watch.restart();
+/// // .. do some more work
+/// println!("step 2 took {}, {:?} in total", watch, step1 + watch.elapsed());
+/// # }
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Stopwatch {
+    started: Instant,
+}
+
+impl Stopwatch {
+    /// Start measuring now.
+    pub fn start() -> Stopwatch {
+        Stopwatch {
+            started: Instant::now(),
+        }
+    }
+
+    /// The time elapsed since the stopwatch was started.
+    pub fn elapsed(&self) -> Duration {
+        self.started.elapsed()
+    }
+
+    /// Start measuring again, returning the time elapsed until now.
+    pub fn restart(&mut self) -> Duration {
+        let now = Instant::now();
+        let elapsed = now - self.started;
+        self.started = now;
+        elapsed
+    }
+
+    /// The instant the stopwatch was started.
+    pub fn started(&self) -> Instant {
+        self.started
+    }
+}
+
+impl fmt::Display for Stopwatch {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        HumanDuration(self.elapsed()).fmt(f)
+    }
+}
+
+/// Displays a duration for humans with about three significant digits, i.e. `850us`, `12.3ms`,
+/// `1.52s`, `2m 03s` or `1h 02m`.
+///
+/// # Examples
+/// ```rust
+/// # extern crate ergo_std;
+/// use std::time::Duration;
+/// use ergo_std::time::HumanDuration;
+///
+/// # fn main() {
+/// assert_eq!("12.3ms", HumanDuration(Duration::from_micros(12_345)).to_string());
+/// assert_eq!("1.52s", HumanDuration(Duration::from_millis(1_520)).to_string());
+/// assert_eq!("2m 03s", HumanDuration(Duration::from_secs(123)).to_string());
+/// assert_eq!("1h 02m", HumanDuration(Duration::from_secs(3_720)).to_string());
+/// # }
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct HumanDuration(pub Duration);
+
+impl fmt::Display for HumanDuration {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let secs = self.0.as_secs();
+        if secs >= 60 * 60 {
+            write!(f, "{}h {:02}m", secs / 3600, secs % 3600 / 60)
+        } else if secs >= 60 {
+            write!(f, "{}m {:02}s", secs / 60, secs % 60)
+        } else if secs >= 1 {
+            fmt_significant(f, self.0.as_secs_f64(), "s")
+        } else if self.0.as_millis() >= 1 {
+            fmt_significant(f, self.0.as_secs_f64() * 1e3, "ms")
+        } else if self.0.as_micros() >= 1 {
+            fmt_significant(f, self.0.as_secs_f64() * 1e6, "us")
+        } else {
+            write!(f, "{}ns", self.0.as_nanos())
+        }
+    }
+}
+
+/// Write a value in `[1, 1000)` with three significant digits.
+fn fmt_significant(f: &mut fmt::Formatter, value: f64, unit: &str) -> fmt::Result {
+    if value >= 100.0 {
+        write!(f, "{:.0}{}", value, unit)
+    } else if value >= 10.0 {
+        write!(f, "{:.1}{}", value, unit)
+    } else {
+        write!(f, "{:.2}{}", value, unit)
+    }
+}
+
+/// A deadline `duration` from now.
+///
+/// # Examples
+/// ```rust
+/// # extern crate ergo_std;
+/// use std::time::Duration;
+/// use ergo_std::time::deadline;
+///
+/// # fn main() {
+/// let deadline = deadline(Duration::from_secs(10));
+/// while !deadline.is_expired() {
+///     // .. try something, i.e. poll a server
+///     # break;
+///     std::thread::sleep(deadline.remaining().min(Duration::from_millis(100)));
+/// }
+/// # }
+/// ```
+pub fn deadline(duration: Duration) -> Deadline {
+    Deadline {
+        at: Instant::now() + duration,
+    }
+}
+
+/// A point in time to give up at, created with [`deadline`](fn.deadline.html).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Deadline {
+    at: Instant,
+}
+
+impl Deadline {
+    /// A deadline at `instant`.
+    pub fn at(instant: Instant) -> Deadline {
+        Deadline { at: instant }
+    }
+
+    /// The instant of the deadline.
+    pub fn instant(&self) -> Instant {
+        self.at
+    }
+
+    /// The time until the deadline, zero if it has passed.
+    pub fn remaining(&self) -> Duration {
+        self.at.saturating_duration_since(Instant::now())
+    }
+
+    /// Whether the deadline has passed.
+    pub fn is_expired(&self) -> bool {
+        Instant::now() >= self.at
+    }
+}
@@ -0,0 +1,117 @@
+//! Receiving messages in batches.
+
+use std::sync::mpsc;
+use std::time::Instant;
+
+use crossbeam_channel as cb;
+use std_prelude::*;
+
+/// Receive messages in batches, for consumers which amortize the cost of each item (i.e.
+/// inserting into a database or writing to a file).
+///
+/// This is implemented for both `crossbeam-channel` (which is exported in [`ch`]) and `std::mpsc`
+/// receivers, the same as the [`ch!`] macro.
+///
+/// [`ch`]: ch/index.html
+/// [`ch!`]: macro.ch.html
+///
+/// # Examples
+///
+/// ```rust
+/// #[macro_use] extern crate ergo_sync;
+/// use ergo_sync::*;
+///
+/// # fn main() {
+/// let (send, recv) = ch::bounded(100);
+/// for i in 0..10 {
+///     ch!(send <- i);
+/// }
+///
+/// let batch = recv.recv_batch(4, Duration::from_millis(10));
+/// assert_eq!(vec![0, 1, 2, 3], batch);
+///
+/// drop(send);
+/// let rest: Vec<_> = recv.batches(4, Duration::from_millis(10)).collect();
+/// assert_eq!(vec![vec![4, 5, 6, 7], vec![8, 9]], rest);
+/// # }
+/// ```
+pub trait RecvBatch {
+    /// The type of the messages.
+    type Item;
+
+    /// Block until a message is received, then keep receiving until `max_items` messages are
+    /// received or `max_wait` has passed since the first one.
+    ///
+    /// Returns an empty `Vec` only if all senders are dropped and there are no more messages.
+    ///
+    /// # Panics
+    /// Panics if `max_items` is 0.
+    fn recv_batch(&self, max_items: usize, max_wait: Duration) -> Vec<Self::Item>;
+
+    /// Iterate over batches from [`recv_batch`](#tymethod.recv_batch) until all senders are
+    /// dropped and there are no more messages.
+    fn batches<'a>(&'a self, max_items: usize, max_wait: Duration) -> Batches<'a, Self>
+    where
+        Self: Sized,
+    {
+        Batches {
+            recv: self,
+            max_items,
+            max_wait,
+        }
+    }
+}
+
+/// An iterator over batches of messages, see
+/// [`RecvBatch::batches`](trait.RecvBatch.html#method.batches).
+pub struct Batches<'a, R: 'a> {
+    recv: &'a R,
+    max_items: usize,
+    max_wait: Duration,
+}
+
+impl<'a, R: RecvBatch> Iterator for Batches<'a, R> {
+    type Item = Vec<R::Item>;
+
+    fn next(&mut self) -> Option<Vec<R::Item>> {
+        let batch = self.recv.recv_batch(self.max_items, self.max_wait);
+        if batch.is_empty() {
+            None
+        } else {
+            Some(batch)
+        }
+    }
+}
+
+macro_rules! impl_recv_batch {
+    ($recv:ty) => {
+        impl<T> RecvBatch for $recv {
+            type Item = T;
+
+            fn recv_batch(&self, max_items: usize, max_wait: Duration) -> Vec<T> {
+                assert!(max_items > 0, "recv_batch max_items must be greater than 0");
+                let mut batch = Vec::new();
+                match self.recv() {
+                    Ok(v) => batch.push(v),
+                    Err(_) => return batch,
+                }
+                let deadline = Instant::now() + max_wait;
+                while batch.len() < max_items {
+                    let now = Instant::now();
+                    if now >= deadline {
+                        break;
+                    }
+                    match self.recv_timeout(deadline - now) {
+                        Ok(v) => batch.push(v),
+                        // timed out or disconnected
+                        Err(_) => break,
+                    }
+                }
+                batch
+            }
+        }
+    };
+}
+
+impl_recv_batch!(cb::Receiver<T>);
+impl_recv_batch!(mpsc::Receiver<T>);
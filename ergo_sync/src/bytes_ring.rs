@@ -0,0 +1,238 @@
+//! A channel of reusable byte buffers, exported in the [`ch`](../ch/index.html) module.
+
+use std::fmt;
+use std::ops::{Deref, DerefMut};
+use std::sync::Condvar;
+
+use crossbeam_channel::{self as cb, Receiver, SendError, Sender, TrySendError};
+use std_prelude::*;
+
+/// Create a channel for sending byte buffers which holds at most `capacity_bytes` bytes.
+///
+/// Buffers are taken from the [`BytesSender`] with [`buffer`], filled and sent. When the
+/// received [`BytesBuf`] is dropped its memory is returned to the channel and reused by the
+/// next call to `buffer`, so a pipeline passing 1MB chunks between threads doesn't allocate a
+/// new `Vec` per chunk. `buffer` blocks while `capacity_bytes` are in use (sent but not yet
+/// dropped), which bounds the memory of the pipeline.
+///
+/// The receiver is a normal `Receiver`, so it works with `ch!`, `select_loop!`, etc.
+///
+/// [`BytesSender`]: struct.BytesSender.html
+/// [`buffer`]: struct.BytesSender.html#method.buffer
+/// [`BytesBuf`]: struct.BytesBuf.html
+///
+/// # Examples
+///
+/// ```rust
+/// #[macro_use] extern crate ergo_sync;
+/// use std::io::Read;
+/// use ergo_sync::*;
+///
+/// # fn main() {
+/// let data: Vec<u8> = (0..100_000).map(|i| i as u8).collect();
+/// let expected: u64 = data.iter().map(|b| *b as u64).sum();
+///
+/// let (send, recv) = ch::bytes_ring(64 * 1024);
+/// spawn(move || {
+///     let mut reader = &data[..];
+///     loop {
+///         let mut buf = send.buffer(16 * 1024);
+///         let n = reader.read(&mut buf).unwrap();
+///         if n == 0 {
+///             break;
+///         }
+///         buf.truncate(n);
+///         ch!(send <- buf);
+///     }
+/// });
+///
+/// let mut total = 0;
+/// for buf in recv.iter() {
+///     total += buf.iter().map(|b| *b as u64).sum::<u64>();
+/// } // each buf is returned to the ring here
+/// assert_eq!(expected, total);
+/// # }
+/// ```
+pub fn bytes_ring(capacity_bytes: usize) -> (BytesSender, Receiver<BytesBuf>) {
+    let (send, recv) = cb::unbounded();
+    let ring = Arc::new(Ring {
+        capacity: capacity_bytes,
+        state: Mutex::new(State {
+            in_use: 0,
+            free: Vec::new(),
+            free_bytes: 0,
+        }),
+        freed: Condvar::new(),
+    });
+    (BytesSender { send, ring }, recv)
+}
+
+/// The sending side of a [`bytes_ring`](fn.bytes_ring.html).
+///
+/// Cloning it is cheap and the clones share the same buffers.
+#[derive(Clone)]
+pub struct BytesSender {
+    send: Sender<BytesBuf>,
+    ring: Arc<Ring>,
+}
+
+/// A buffer from a [`bytes_ring`](fn.bytes_ring.html), which is returned to the ring when it is
+/// dropped.
+///
+/// It dereferences to `[u8]`. The bytes of a reused buffer are left as they were, so only read
+/// what was written.
+pub struct BytesBuf {
+    data: Vec<u8>,
+    reserved: usize,
+    ring: Arc<Ring>,
+}
+
+struct Ring {
+    capacity: usize,
+    state: Mutex<State>,
+    freed: Condvar,
+}
+
+struct State {
+    /// The bytes reserved by the buffers which exist.
+    in_use: usize,
+    free: Vec<Vec<u8>>,
+    /// The capacity of the `free` buffers.
+    free_bytes: usize,
+}
+
+impl BytesSender {
+    /// Get a buffer of `len` bytes, blocking until that many are not in use.
+    ///
+    /// If the receiver is dropped this no longer blocks, so that the following send fails.
+    ///
+    /// # Panics
+    /// If `len` is larger than the capacity of the ring.
+    pub fn buffer(&self, len: usize) -> BytesBuf {
+        assert!(
+            len <= self.ring.capacity,
+            "requested a buffer of {} bytes from a bytes_ring of {} bytes",
+            len,
+            self.ring.capacity
+        );
+        let mut state = self.ring.state();
+        while state.in_use + len > self.ring.capacity && !self.send.is_disconnected() {
+            state = self
+                .ring
+                .freed
+                .wait_timeout(state, Duration::from_millis(100))
+                .expect("bytes_ring poisoned")
+                .0;
+        }
+        state.in_use += len;
+
+        let reuse = state
+            .free
+            .iter()
+            .position(|b| b.capacity() >= len)
+            .or_else(|| state.free.len().checked_sub(1));
+        let mut data = match reuse {
+            Some(i) => {
+                let data = state.free.swap_remove(i);
+                state.free_bytes -= data.capacity();
+                data
+            }
+            None => Vec::new(),
+        };
+        drop(state);
+        data.resize(len, 0);
+        BytesBuf {
+            data,
+            reserved: len,
+            ring: self.ring.clone(),
+        }
+    }
+
+    /// Send a buffer, blocking if necessary. Returns an error containing the buffer if the
+    /// receiver was dropped.
+    pub fn send(&self, buf: BytesBuf) -> Result<(), SendError<BytesBuf>> {
+        self.send.send(buf)
+    }
+
+    /// Send a buffer without blocking, returning it if it could not be sent.
+    pub fn try_send(&self, buf: BytesBuf) -> Result<(), TrySendError<BytesBuf>> {
+        self.send.try_send(buf)
+    }
+
+    /// The capacity of the ring in bytes.
+    pub fn capacity(&self) -> usize {
+        self.ring.capacity
+    }
+
+    /// The number of bytes in buffers which have not been dropped.
+    pub fn in_use(&self) -> usize {
+        self.ring.state().in_use
+    }
+}
+
+impl fmt::Debug for BytesSender {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "BytesSender {{ in_use: {}, capacity: {} }}",
+            self.in_use(),
+            self.capacity()
+        )
+    }
+}
+
+impl BytesBuf {
+    /// Shorten the buffer to `len` bytes, i.e. after reading fewer bytes than requested.
+    ///
+    /// The bytes stay reserved until the buffer is dropped.
+    pub fn truncate(&mut self, len: usize) {
+        self.data.truncate(len);
+    }
+}
+
+impl Deref for BytesBuf {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        &self.data
+    }
+}
+
+impl DerefMut for BytesBuf {
+    fn deref_mut(&mut self) -> &mut [u8] {
+        &mut self.data
+    }
+}
+
+impl AsRef<[u8]> for BytesBuf {
+    fn as_ref(&self) -> &[u8] {
+        &self.data
+    }
+}
+
+impl fmt::Debug for BytesBuf {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "BytesBuf({} bytes)", self.data.len())
+    }
+}
+
+impl Drop for BytesBuf {
+    fn drop(&mut self) {
+        let data = ::std::mem::take(&mut self.data);
+        let mut state = self.ring.state();
+        state.in_use -= self.reserved;
+        // keep at most `capacity` bytes of buffers around for reuse
+        if state.free_bytes + data.capacity() <= self.ring.capacity {
+            state.free_bytes += data.capacity();
+            state.free.push(data);
+        }
+        drop(state);
+        self.ring.freed.notify_all();
+    }
+}
+
+impl Ring {
+    fn state<'a>(&'a self) -> ::std::sync::MutexGuard<'a, State> {
+        self.state.lock().expect("bytes_ring poisoned")
+    }
+}
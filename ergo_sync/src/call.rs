@@ -0,0 +1,183 @@
+//! Request/response channels, exported in the [`ch`](../ch/index.html) module.
+
+use std::error;
+use std::fmt;
+
+use crossbeam_channel::{self as cb, IntoIter, Iter, Receiver, Sender};
+
+/// Create a channel for calling a thread with a request and blocking until it replies.
+///
+/// Both the [`Caller`] and the [`Responder`] can be cloned, so any number of threads can make
+/// calls and any number can serve them. Each call gets the reply to _its_ request, no matter
+/// how many calls are in flight.
+///
+/// This is the "send a reply channel with the message" idiom with types for both sides.
+///
+/// [`Caller`]: struct.Caller.html
+/// [`Responder`]: struct.Responder.html
+///
+/// # Examples
+///
+/// ```rust
+/// #[macro_use] extern crate ergo_sync;
+/// use ergo_sync::*;
+///
+/// # fn main() {
+/// let (caller, responder) = ch::call_channel::<u32, String>();
+/// spawn(move || {
+///     for call in responder {
+///         let resp = format!("got {}", call.request());
+///         call.reply(resp);
+///     }
+/// });
+///
+/// let threads: Vec<_> = (0..4)
+///     .map(|i| {
+///         take!(=caller);
+///         spawn(move || caller.call(i).unwrap())
+///     })
+///     .collect();
+/// for (i, th) in threads.into_iter().enumerate() {
+///     assert_eq!(format!("got {}", i), th.finish());
+/// }
+/// # }
+/// ```
+pub fn call_channel<Req, Resp>() -> (Caller<Req, Resp>, Responder<Req, Resp>) {
+    let (send, recv) = cb::unbounded();
+    (Caller { send }, Responder { recv })
+}
+
+/// The calling side of a [`call_channel`](fn.call_channel.html).
+pub struct Caller<Req, Resp> {
+    send: Sender<Call<Req, Resp>>,
+}
+
+/// The responding side of a [`call_channel`](fn.call_channel.html). Iterate over it to receive
+/// the [`Call`](struct.Call.html)s, the iterator ends once every `Caller` is dropped.
+pub struct Responder<Req, Resp> {
+    recv: Receiver<Call<Req, Resp>>,
+}
+
+/// A request received by a [`Responder`](struct.Responder.html) which must be replied to.
+///
+/// If the call is dropped without a reply the caller gets `CallError::Dropped`.
+pub struct Call<Req, Resp> {
+    request: Req,
+    reply: Sender<Resp>,
+}
+
+/// The error when a [`Caller::call`](struct.Caller.html#method.call) fails.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CallError<Req> {
+    /// Every `Responder` was dropped, so the request could not be sent. Contains the request.
+    Disconnected(Req),
+    /// The request was received but the `Call` was dropped without a reply.
+    Dropped,
+}
+
+impl<Req, Resp> Caller<Req, Resp> {
+    /// Send the request and block until its response is received.
+    pub fn call(&self, request: Req) -> Result<Resp, CallError<Req>> {
+        let (reply, recv_reply) = cb::bounded(1);
+        if let Err(err) = self.send.send(Call { request, reply }) {
+            return Err(CallError::Disconnected(err.into_inner().request));
+        }
+        recv_reply.recv().map_err(|_| CallError::Dropped)
+    }
+}
+
+impl<Req, Resp> Clone for Caller<Req, Resp> {
+    fn clone(&self) -> Caller<Req, Resp> {
+        Caller {
+            send: self.send.clone(),
+        }
+    }
+}
+
+impl<Req, Resp> Responder<Req, Resp> {
+    /// Block until a call is received, returning `None` once every `Caller` is dropped.
+    pub fn recv(&self) -> Option<Call<Req, Resp>> {
+        self.recv.recv().ok()
+    }
+
+    /// Iterate over the calls until every `Caller` is dropped.
+    pub fn iter<'a>(&'a self) -> Iter<'a, Call<Req, Resp>> {
+        self.recv.iter()
+    }
+
+    /// Reply to every call with the result of `f` until every `Caller` is dropped.
+    pub fn serve<F>(&self, mut f: F)
+    where
+        F: FnMut(Req) -> Resp,
+    {
+        for call in self.iter() {
+            let Call { request, reply } = call;
+            // The caller is blocked waiting on the reply, it cannot be gone.
+            let _ = reply.send(f(request));
+        }
+    }
+}
+
+impl<Req, Resp> Clone for Responder<Req, Resp> {
+    fn clone(&self) -> Responder<Req, Resp> {
+        Responder {
+            recv: self.recv.clone(),
+        }
+    }
+}
+
+impl<Req, Resp> IntoIterator for Responder<Req, Resp> {
+    type Item = Call<Req, Resp>;
+    type IntoIter = IntoIter<Call<Req, Resp>>;
+
+    fn into_iter(self) -> IntoIter<Call<Req, Resp>> {
+        self.recv.into_iter()
+    }
+}
+
+impl<'a, Req, Resp> IntoIterator for &'a Responder<Req, Resp> {
+    type Item = Call<Req, Resp>;
+    type IntoIter = Iter<'a, Call<Req, Resp>>;
+
+    fn into_iter(self) -> Iter<'a, Call<Req, Resp>> {
+        self.iter()
+    }
+}
+
+impl<Req, Resp> Call<Req, Resp> {
+    /// The request.
+    pub fn request(&self) -> &Req {
+        &self.request
+    }
+
+    /// Reply to the caller.
+    pub fn reply(self, response: Resp) {
+        // The caller is blocked waiting on the reply, it cannot be gone.
+        let _ = self.reply.send(response);
+    }
+
+    /// Take the request, returning it along with a `Call` to reply with.
+    pub fn into_request(self) -> (Req, Call<(), Resp>) {
+        let Call { request, reply } = self;
+        (request, Call { request: (), reply })
+    }
+}
+
+impl<Req: fmt::Debug, Resp> fmt::Debug for Call<Req, Resp> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Call")
+            .field("request", &self.request)
+            .finish()
+    }
+}
+
+impl<Req> fmt::Display for CallError<Req> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            CallError::Disconnected(_) => write!(f, "call failed: every responder is dropped"),
+            CallError::Dropped => write!(f, "call failed: the call was dropped without a reply"),
+        }
+    }
+}
+
+impl<Req: fmt::Debug> error::Error for CallError<Req> {}
@@ -173,9 +173,16 @@
 //! ```
 //!
 
-pub use crossbeam_channel::{bounded, unbounded, IntoIter, Iter, Receiver, RecvError,
-                            RecvTimeoutError, Select, SelectRecvError, SelectSendError, SendError,
-                            SendTimeoutError, Sender, TryIter, TryRecvError, TrySendError};
+pub use batch::{Batches, RecvBatch};
+pub use bytes_ring::{bytes_ring, BytesBuf, BytesSender};
+pub use call::{call_channel, Call, CallError, Caller, Responder};
+pub use crossbeam_channel::{
+    bounded, unbounded, IntoIter, Iter, Receiver, RecvError, RecvTimeoutError, Select,
+    SelectRecvError, SelectSendError, SendError, SendTimeoutError, Sender, TryIter, TryRecvError,
+    TrySendError,
+};
+#[cfg(feature = "ipc")]
+pub use ipc::{ipc, Ipc, MAX_FRAME_LEN};
 
 /// Use with channels with ergonomic syntax and panic with helpful error messages when
 /// sending/receiving on a channel is invalid.
@@ -195,7 +202,9 @@ pub use crossbeam_channel::{bounded, unbounded, IntoIter, Iter, Receiver, RecvEr
 /// > This syntax works with both `crossbeam-channel` channels (which are exported by this crate) as
 /// > well as `std::mspc` channels.
 ///
-/// > Note that these operations can deadlock if a channel is leaked.
+/// > Note that these operations can deadlock if a channel is leaked. Enable the
+/// > `deadlock_watchdog` feature to print a warning when one blocks for too long, see the
+/// > [`watchdog`](watchdog/index.html) module.
 ///
 /// **Non-Blocking syntax:**
 ///
@@ -296,12 +305,17 @@ macro_rules! ch {
         }
     };
 
-    [$send:ident <- $value:expr] => {
-        match $send.send($value) {
+    [$send:ident <- $value:expr] => {{
+        let value = $value;
+        let _blocked = $crate::watchdog::blocking(
+            concat!("ch!(", stringify!($send), " <- ..)"),
+            concat!(file!(), ":", line!()),
+        );
+        match $send.send(value) {
             Ok(_) => {},
             Err(err) => panic!("{} for `send`.", err),
         }
-    };
+    }};
 
     [<-? $recv:ident] => {
         match $recv.try_recv() {
@@ -312,12 +326,16 @@ macro_rules! ch {
             }
         }
     };
-    [<- $recv:ident] => {
+    [<- $recv:ident] => {{
+        let _blocked = $crate::watchdog::blocking(
+            concat!("ch!(<- ", stringify!($recv), ")"),
+            concat!(file!(), ":", line!()),
+        );
         match $recv.recv() {
             Ok(v) => v,
             Err(err) => panic!("{} for `recv`.", err),
         }
-    };
+    }};
 
 
     [! <-? $recv:ident] => {
@@ -327,12 +345,16 @@ macro_rules! ch {
             Err($crate::ch::TryRecvError::Disconnected) => false, // no more senders
         }
     };
-    [! <- $recv:ident] => {
+    [! <- $recv:ident] => {{
+        let _blocked = $crate::watchdog::blocking(
+            concat!("ch!(! <- ", stringify!($recv), ")"),
+            concat!(file!(), ":", line!()),
+        );
         match $recv.recv() {
             Ok(v) => panic!("Got {:?} when expecting senders to be closed.", v),
             Err(err) => (),
         }
-    };
+    }};
 }
 
 /// Handle an expression that could be `Err` and send it over a channel if it is.
@@ -379,3 +401,80 @@ macro_rules! ch_try {
         }
     };
 }
+
+/// Receive from one or more channels until all of their senders are dropped, running the body of
+/// whichever channel received a message.
+///
+/// This replaces the `for msg in recv { ... }` pattern, with the advantage that several channels
+/// (i.e. a channel of values and a channel of errors) can be handled at the same time. The loop
+/// ends once _every_ channel is disconnected and all of their values have been received.
+///
+/// - `recv_loop!(recv, msg => { ... })`
+/// - `recv_loop!(recv, msg => { ... }, errs, err => { ... })`
+///
+/// Each body is an expression which may `return`, but it may not use an unlabeled `break` or
+/// `continue` (just like [`select_loop!`]).
+///
+/// # Panics
+/// Panics if a channel still has values after all channels reported being disconnected, which
+/// indicates a bug in the channel implementation rather than a lost message.
+///
+/// [`select_loop!`]: macro.select_loop.html
+///
+/// # Examples
+///
+/// ```rust
+/// #[macro_use] extern crate ergo_sync;
+/// use ergo_sync::*;
+///
+/// # fn main() {
+/// let (send, recv) = ch::bounded(4);
+/// let (send_err, recv_err) = ch::bounded(4);
+/// spawn(move || {
+///     for i in 0..10 {
+///         if i % 3 == 0 {
+///             ch!(send_err <- format!("{} is divisible by 3", i));
+///         } else {
+///             ch!(send <- i);
+///         }
+///     }
+/// });
+///
+/// let mut sum = 0;
+/// let mut errs = Vec::new();
+/// recv_loop!(
+///     recv, v => sum += v,
+///     recv_err, err => errs.push(err),
+/// );
+/// assert_eq!(27, sum);
+/// assert_eq!(4, errs.len());
+/// # }
+/// ```
+#[macro_export]
+macro_rules! recv_loop {
+    [$($recv:ident, $msg:pat => $body:expr),+ $(,)*] => {{
+        loop {
+            let open = select_loop! {
+                $(
+                    recv($recv, $msg) => {
+                        $body;
+                        true
+                    }
+                )+
+                disconnected() => false
+            };
+            if !open {
+                break;
+            }
+        }
+        $(
+            if $recv.try_recv().is_ok() {
+                panic!(concat!(
+                    "recv_loop! stopped while `",
+                    stringify!($recv),
+                    "` still had values"
+                ));
+            }
+        )+
+    }};
+}
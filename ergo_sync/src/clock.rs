@@ -0,0 +1,229 @@
+//! Clocks which can be replaced in tests.
+//!
+//! Code which sleeps or waits for timers is slow to test with real time, and flaky when the
+//! test guesses how long to sleep for. Instead, take a [`Clock`] (usually an
+//! `Arc<dyn Clock>`) and use it for the current time and for sleeping:
+//!
+//! - [`SystemClock`] is the real time, for production.
+//! - [`ManualClock`] only moves when a test calls [`advance`], so sleeps and timers finish
+//!   exactly when the test says so.
+//!
+//! The [`Scheduler`] takes a clock with [`Scheduler::with_clock`].
+//!
+//! [`Clock`]: trait.Clock.html
+//! [`SystemClock`]: struct.SystemClock.html
+//! [`ManualClock`]: struct.ManualClock.html
+//! [`advance`]: struct.ManualClock.html#method.advance
+//! [`Scheduler`]: ../timer/struct.Scheduler.html
+//! [`Scheduler::with_clock`]: ../timer/struct.Scheduler.html#method.with_clock
+//!
+//! # Examples
+//! ```rust
+//! #[macro_use] extern crate ergo_sync;
+//! use std::time::Instant;
+//! use ergo_sync::*;
+//!
+//! const DAY: u64 = 24 * 60 * 60;
+//!
+//! /// Run a daily job three times, returning when the last run finished.
+//! fn run_daily(clock: &dyn Clock, runs: &AtomicUsize) -> Instant {
+//!     for _ in 0..3 {
+//!         clock.sleep(Duration::from_secs(DAY));
+//!         runs.fetch_add(1, AtomicOrdering::SeqCst);
+//!     }
+//!     clock.now()
+//! }
+//!
+//! # fn main() {
+//! let clock = ManualClock::new();
+//! let start = clock.now();
+//! let runs = Arc::new(AtomicUsize::new(0));
+//! let th = {
+//!     take!(=clock, =runs);
+//!     spawn(move || run_daily(&clock, &runs))
+//! };
+//!
+//! // three days pass instantly
+//! for day in 0..3 {
+//!     clock.wait_for_sleepers(1);
+//!     assert_eq!(day, runs.load(AtomicOrdering::SeqCst));
+//!     clock.advance(Duration::from_secs(DAY));
+//! }
+//! assert_eq!(start + Duration::from_secs(3 * DAY), th.finish());
+//! # }
+//! ```
+
+use std::fmt;
+use std::mem;
+use std::sync::{Condvar, MutexGuard};
+use std::thread;
+use std::time::Instant;
+
+use std_prelude::*;
+
+/// A source of the current time which can also sleep.
+///
+/// See the [`clock`](index.html) module.
+pub trait Clock: Send + Sync {
+    /// The current time.
+    fn now(&self) -> Instant;
+
+    /// Block the current thread until `dur` has passed on this clock.
+    fn sleep(&self, dur: Duration);
+
+    /// Block the current thread until `millis` milliseconds have passed on this clock, the
+    /// same as [`sleep_ms`](../fn.sleep_ms.html) for the real time.
+    fn sleep_ms(&self, millis: u64) {
+        self.sleep(Duration::from_millis(millis))
+    }
+
+    /// Call `wake` whenever this clock jumps forward, for waiting on a `Condvar` with a timeout
+    /// computed from this clock. `wake` is removed once it returns `false`.
+    ///
+    /// Clocks which follow the real time never jump, so the default does nothing.
+    fn on_advance(&self, wake: Box<dyn FnMut() -> bool + Send>) {
+        let _ = wake;
+    }
+}
+
+/// The real time, using `Instant::now` and `thread::sleep`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> Instant {
+        Instant::now()
+    }
+
+    fn sleep(&self, dur: Duration) {
+        thread::sleep(dur)
+    }
+}
+
+/// A clock which only moves when it is [advanced](#method.advance), for tests.
+///
+/// Clones share the same time. It starts at the real time when it was created, and sleeping
+/// threads wake up as soon as the clock is advanced past the end of their sleep.
+#[derive(Clone)]
+pub struct ManualClock {
+    inner: Arc<Inner>,
+}
+
+struct Inner {
+    state: Mutex<State>,
+    changed: Condvar,
+}
+
+struct State {
+    now: Instant,
+    /// The end of the sleep of every sleeping thread.
+    sleeping: Vec<Instant>,
+    wakers: Vec<Box<dyn FnMut() -> bool + Send>>,
+}
+
+impl ManualClock {
+    /// Create a clock starting at the current real time.
+    pub fn new() -> ManualClock {
+        ManualClock {
+            inner: Arc::new(Inner {
+                state: Mutex::new(State {
+                    now: Instant::now(),
+                    sleeping: Vec::new(),
+                    wakers: Vec::new(),
+                }),
+                changed: Condvar::new(),
+            }),
+        }
+    }
+
+    /// Move the clock forward by `dur`, waking the threads whose sleep has ended.
+    pub fn advance(&self, dur: Duration) {
+        let mut wakers = {
+            let mut state = self.lock();
+            state.now += dur;
+            self.inner.changed.notify_all();
+            mem::take(&mut state.wakers)
+        };
+        // call the wakers without the lock, they may read the clock
+        wakers.retain_mut(|wake| wake());
+        self.lock().wakers.extend(wakers);
+    }
+
+    /// The number of threads sleeping on this clock whose sleep has not ended yet.
+    pub fn sleepers(&self) -> usize {
+        self.lock().sleepers()
+    }
+
+    /// Block until at least `count` threads are sleeping on this clock.
+    ///
+    /// Call this before [`advance`](#method.advance) so that the threads under test have
+    /// started their sleep, and the clock is not advanced before they read it.
+    pub fn wait_for_sleepers(&self, count: usize) {
+        let mut state = self.lock();
+        while state.sleepers() < count {
+            state = self.wait(state);
+        }
+    }
+
+    fn lock(&self) -> MutexGuard<'_, State> {
+        self.inner.state.lock().expect("ManualClock poisoned")
+    }
+
+    fn wait<'a>(&self, state: MutexGuard<'a, State>) -> MutexGuard<'a, State> {
+        self.inner
+            .changed
+            .wait(state)
+            .expect("ManualClock poisoned")
+    }
+}
+
+impl State {
+    fn sleepers(&self) -> usize {
+        self.sleeping
+            .iter()
+            .filter(|&&until| until > self.now)
+            .count()
+    }
+}
+
+impl Clock for ManualClock {
+    fn now(&self) -> Instant {
+        self.lock().now
+    }
+
+    fn sleep(&self, dur: Duration) {
+        let mut state = self.lock();
+        let until = state.now + dur;
+        state.sleeping.push(until);
+        self.inner.changed.notify_all();
+        while state.now < until {
+            state = self.wait(state);
+        }
+        let i = state
+            .sleeping
+            .iter()
+            .position(|&t| t == until)
+            .expect("sleep was recorded");
+        state.sleeping.swap_remove(i);
+    }
+
+    fn on_advance(&self, wake: Box<dyn FnMut() -> bool + Send>) {
+        self.lock().wakers.push(wake);
+    }
+}
+
+impl Default for ManualClock {
+    fn default() -> ManualClock {
+        ManualClock::new()
+    }
+}
+
+impl fmt::Debug for ManualClock {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let state = self.lock();
+        f.debug_struct("ManualClock")
+            .field("now", &state.now)
+            .field("sleepers", &state.sleepers())
+            .finish()
+    }
+}
@@ -0,0 +1,277 @@
+//! A persistent queue stored in a directory.
+
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, BufRead, BufReader, Read, Seek, SeekFrom, Write};
+use std::marker::PhantomData;
+use std::path::{Path, PathBuf};
+
+use path_abs::PathDir;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use serde_json;
+
+/// Compact the log once this many bytes at its start have been popped (and they are at least
+/// half of it).
+const COMPACT_BYTES: u64 = 1024 * 1024;
+
+/// A FIFO queue which is persisted to a directory, so that items pushed by one run of a program
+/// can be popped by the next one. Requires the `disk_queue` feature.
+///
+/// Items are appended as lines of JSON to a log file and the position of the first item is
+/// stored in a separate file which is replaced atomically. Both are synced to disk before
+/// [`push`](#method.push) and [`pop`](#method.pop) return, so a crash (or power loss) never
+/// loses a pushed item or returns a popped one again. An item which was partially written when
+/// the program crashed is discarded when the queue is opened.
+///
+/// The log is compacted when the queue is emptied or when most of it has been popped.
+///
+/// Only one `DiskQueue` may use a directory at a time. Wrap it in a `Mutex` to share it between
+/// threads.
+///
+/// # Examples
+///
+/// ```rust
+/// extern crate ergo_sync;
+/// extern crate path_abs;
+/// use ergo_sync::DiskQueue;
+/// use path_abs::PathDir;
+///
+/// # fn try_main() -> ::std::io::Result<()> {
+/// let dir = std::env::temp_dir().join("ergo_sync_disk_queue_example");
+/// # let _ = std::fs::remove_dir_all(&dir);
+/// let dir = PathDir::create_all(dir)?;
+/// {
+///     let mut queue = DiskQueue::open(&dir)?;
+///     queue.push(&"a.txt".to_string())?;
+///     queue.push(&"b.txt".to_string())?;
+///     queue.push(&"c.txt".to_string())?;
+///     assert_eq!(Some("a.txt".to_string()), queue.pop()?);
+/// } // i.e. the program exits
+///
+/// let mut queue: DiskQueue<String> = DiskQueue::open(&dir)?;
+/// assert_eq!(2, queue.len());
+/// assert_eq!(Some("b.txt".to_string()), queue.pop()?);
+/// assert_eq!(Some("c.txt".to_string()), queue.pop()?);
+/// assert_eq!(None, queue.pop()?);
+/// # Ok(()) } fn main() { try_main().unwrap() }
+/// ```
+pub struct DiskQueue<T> {
+    dir: PathDir,
+    /// The generation of the log, incremented when it is compacted.
+    gen: u64,
+    log: File,
+    /// The offset of the first item.
+    head: u64,
+    /// The offset after the last item.
+    end: u64,
+    len: usize,
+    ty: PhantomData<fn(T) -> T>,
+}
+
+impl<T: Serialize + DeserializeOwned> DiskQueue<T> {
+    /// Open the queue stored in `dir`, creating an empty queue if there is none.
+    pub fn open(dir: &PathDir) -> io::Result<DiskQueue<T>> {
+        let head_path = dir.join("queue.head");
+        let (gen, head) = match fs::read_to_string(&head_path) {
+            Ok(s) => parse_head(&s).ok_or_else(|| {
+                path_error(
+                    io::Error::new(io::ErrorKind::InvalidData, "invalid queue head"),
+                    "reading",
+                    &head_path,
+                )
+            })?,
+            Err(ref err) if err.kind() == io::ErrorKind::NotFound => (0, 0),
+            Err(err) => return Err(path_error(err, "reading", &head_path)),
+        };
+
+        let log_path = log_path(dir, gen);
+        let log = OpenOptions::new()
+            .read(true)
+            .append(true)
+            .create(true)
+            .open(&log_path)
+            .map_err(|err| path_error(err, "opening", &log_path))?;
+
+        // count the complete items and drop a partially written one
+        let file_len = log.metadata()?.len();
+        let head = head.min(file_len);
+        let mut reader = BufReader::new(&log);
+        reader.seek(SeekFrom::Start(head))?;
+        let (mut end, mut len) = (head, 0);
+        let mut line = Vec::new();
+        loop {
+            line.clear();
+            let n = reader.read_until(b'\n', &mut line)?;
+            if n == 0 || line.last() != Some(&b'\n') {
+                break;
+            }
+            end += n as u64;
+            len += 1;
+        }
+        if end < file_len {
+            log.set_len(end)
+                .map_err(|err| path_error(err, "truncating", &log_path))?;
+            log.sync_all()?;
+        }
+
+        let queue = DiskQueue {
+            dir: dir.clone(),
+            gen,
+            log,
+            head,
+            end,
+            len,
+            ty: PhantomData,
+        };
+        queue.remove_old_logs()?;
+        Ok(queue)
+    }
+
+    /// Push an item to the back of the queue.
+    pub fn push(&mut self, item: &T) -> io::Result<()> {
+        let mut line = serde_json::to_vec(item)
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+        line.push(b'\n');
+        let path = log_path(&self.dir, self.gen);
+        self.log
+            .write_all(&line)
+            .and_then(|_| self.log.sync_data())
+            .map_err(|err| path_error(err, "appending to", &path))?;
+        self.end += line.len() as u64;
+        self.len += 1;
+        Ok(())
+    }
+
+    /// Pop the item at the front of the queue, returning `None` if it is empty.
+    ///
+    /// If the item cannot be deserialized it is still removed (so that the queue is not stuck
+    /// on it) and an error of kind `InvalidData` is returned.
+    pub fn pop(&mut self) -> io::Result<Option<T>> {
+        if self.len == 0 {
+            return Ok(None);
+        }
+        let path = log_path(&self.dir, self.gen);
+        let mut line = Vec::new();
+        (&self.log).seek(SeekFrom::Start(self.head))?;
+        BufReader::new(&self.log)
+            .read_until(b'\n', &mut line)
+            .map_err(|err| path_error(err, "reading", &path))?;
+        self.head += line.len() as u64;
+        self.len -= 1;
+
+        if self.len == 0 || (self.head >= COMPACT_BYTES && self.head * 2 >= self.end) {
+            self.compact()?;
+        } else {
+            self.write_head()?;
+        }
+        serde_json::from_slice(&line).map(Some).map_err(|err| {
+            path_error(
+                io::Error::new(io::ErrorKind::InvalidData, err),
+                "parsing an item from",
+                &path,
+            )
+        })
+    }
+
+    /// The number of items in the queue.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Whether the queue is empty.
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// The directory the queue is stored in.
+    pub fn dir(&self) -> &PathDir {
+        &self.dir
+    }
+
+    /// Copy the remaining items to a new log and switch to it.
+    fn compact(&mut self) -> io::Result<()> {
+        let new_path = log_path(&self.dir, self.gen + 1);
+        let mut new_log = OpenOptions::new()
+            .read(true)
+            .append(true)
+            .create(true)
+            .truncate(false)
+            .open(&new_path)
+            .map_err(|err| path_error(err, "creating", &new_path))?;
+        new_log.set_len(0)?;
+        (&self.log).seek(SeekFrom::Start(self.head))?;
+        io::copy(&mut (&self.log).take(self.end - self.head), &mut new_log)
+            .and_then(|_| new_log.sync_all())
+            .map_err(|err| path_error(err, "writing", &new_path))?;
+
+        // the switch happens when the new head is written
+        let old_path = log_path(&self.dir, self.gen);
+        self.end -= self.head;
+        self.head = 0;
+        self.gen += 1;
+        self.log = new_log;
+        self.write_head()?;
+        fs::remove_file(&old_path).map_err(|err| path_error(err, "removing", &old_path))
+    }
+
+    /// Atomically replace the head file.
+    fn write_head(&self) -> io::Result<()> {
+        let tmp = self.dir.join("queue.head.tmp");
+        let path = self.dir.join("queue.head");
+        {
+            let mut f = File::create(&tmp).map_err(|err| path_error(err, "creating", &tmp))?;
+            write!(f, "{} {}", self.gen, self.head)
+                .and_then(|_| f.sync_all())
+                .map_err(|err| path_error(err, "writing", &tmp))?;
+        }
+        fs::rename(&tmp, &path).map_err(|err| path_error(err, "replacing", &path))?;
+        sync_dir(&self.dir)
+    }
+
+    /// Remove logs left by a compaction which was interrupted.
+    fn remove_old_logs(&self) -> io::Result<()> {
+        let current = log_path(&self.dir, self.gen);
+        for entry in fs::read_dir(&self.dir)? {
+            let path = entry?.path();
+            let is_log = path
+                .file_name()
+                .and_then(|n| n.to_str())
+                .map(|n| n.starts_with("queue.") && n.ends_with(".ndjson"))
+                .unwrap_or(false);
+            if is_log && path != current {
+                fs::remove_file(&path).map_err(|err| path_error(err, "removing", &path))?;
+            }
+        }
+        Ok(())
+    }
+}
+
+fn log_path(dir: &PathDir, gen: u64) -> PathBuf {
+    let dir: &Path = dir.as_ref();
+    dir.join(format!("queue.{}.ndjson", gen))
+}
+
+fn parse_head(s: &str) -> Option<(u64, u64)> {
+    let mut parts = s.split_whitespace();
+    let gen = parts.next()?.parse().ok()?;
+    let head = parts.next()?.parse().ok()?;
+    Some((gen, head))
+}
+
+/// Sync the directory so that a rename in it is durable.
+#[cfg(unix)]
+fn sync_dir(dir: &Path) -> io::Result<()> {
+    File::open(dir)?.sync_all()
+}
+
+#[cfg(not(unix))]
+fn sync_dir(_dir: &Path) -> io::Result<()> {
+    Ok(())
+}
+
+fn path_error(err: io::Error, action: &str, path: &Path) -> io::Error {
+    io::Error::new(
+        err.kind(),
+        format!("{} when {} {}", err, action, path.display()),
+    )
+}
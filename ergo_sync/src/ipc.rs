@@ -0,0 +1,214 @@
+//! Channels between processes, exported in the [`ch`](../ch/index.html) module.
+
+use std::io::{self, Read, Write};
+use std::marker::PhantomData;
+use std::path::{Path, PathBuf};
+use std::thread;
+
+use bincode;
+use crossbeam_channel::{self as cb, Receiver, Sender};
+use interprocess::local_socket::{LocalSocketListener, LocalSocketStream};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+
+/// The largest serialized value which can be sent over an [`ipc`](fn.ipc.html) channel.
+///
+/// A listener closes a connection which announces a larger frame rather than allocating it.
+pub const MAX_FRAME_LEN: usize = 64 * 1024 * 1024;
+
+/// Create a channel between processes over a Unix domain socket (or a named pipe on windows) at
+/// `path`. Requires the `ipc` feature.
+///
+/// One process calls [`listen`](struct.Ipc.html#method.listen) to get the `Receiver` and any
+/// number of processes call [`connect`](struct.Ipc.html#method.connect) to get a `Sender`. These
+/// are normal channels, so they work with `ch!`, `select_loop!`, etc.
+///
+/// Values are serialized with `bincode` and framed by their length, which is at most
+/// [`MAX_FRAME_LEN`](constant.MAX_FRAME_LEN.html) bytes. Both sides are
+/// [rendevous channels](index.html#example-rendevous-channel): a send blocks until the value
+/// has been received by the other process.
+///
+/// # Examples
+///
+/// The processes are threads here, it works the same way with separate processes.
+///
+/// ```rust
+/// #[macro_use] extern crate ergo_sync;
+/// use ergo_sync::*;
+///
+/// # fn main() {
+/// let path = std::env::temp_dir().join("ergo_sync_ipc_example.sock");
+/// let recv: ch::Receiver<(u32, String)> = ch::ipc(&path).listen().unwrap();
+///
+/// let worker = spawn(move || {
+///     let send = ch::ipc(&path).connect().unwrap();
+///     ch!(send <- (1, "one".to_string()));
+///     ch!(send <- (2, "two".to_string()));
+/// });
+///
+/// assert_eq!((1, "one".to_string()), ch!(<- recv));
+/// assert_eq!((2, "two".to_string()), ch!(<- recv));
+/// worker.join().unwrap();
+/// # }
+/// ```
+pub fn ipc<T, P: AsRef<Path>>(path: P) -> Ipc<T> {
+    Ipc {
+        path: path.as_ref().to_path_buf(),
+        ty: PhantomData,
+    }
+}
+
+/// The address of a channel between processes, see [`ipc`](fn.ipc.html).
+#[derive(Debug, Clone)]
+pub struct Ipc<T> {
+    path: PathBuf,
+    ty: PhantomData<fn(T) -> T>,
+}
+
+impl<T> Ipc<T> {
+    /// The path of the socket.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+impl<T: DeserializeOwned + Send + 'static> Ipc<T> {
+    /// Listen on the socket, receiving the values sent by every process which connects.
+    ///
+    /// On unix a socket file left behind by a process which exited is replaced. The receiver
+    /// is never disconnected since another process could always connect. If a process sends a
+    /// value which cannot be deserialized its connection is closed.
+    pub fn listen(&self) -> io::Result<Receiver<T>> {
+        let listener = bind(&self.path)?;
+        let (send, recv) = cb::bounded(0);
+        thread::spawn(move || {
+            for stream in listener.incoming() {
+                let stream = match stream {
+                    Ok(s) => s,
+                    Err(_) => continue,
+                };
+                if send.is_disconnected() {
+                    return;
+                }
+                let send = send.clone();
+                thread::spawn(move || {
+                    let _ = forward_frames(stream, &send);
+                });
+            }
+        });
+        Ok(recv)
+    }
+}
+
+impl<T: Serialize + Send + 'static> Ipc<T> {
+    /// Connect to the process which is [`listen`](#method.listen)ing on the socket.
+    ///
+    /// The sender is disconnected when the connection is lost, i.e. when the other process
+    /// exits, or when a value cannot be sent because it serializes to more than
+    /// [`MAX_FRAME_LEN`](constant.MAX_FRAME_LEN.html) bytes.
+    pub fn connect(&self) -> io::Result<Sender<T>> {
+        let mut stream = LocalSocketStream::connect(socket_name(&self.path))
+            .map_err(|err| path_error(err, "connecting to", &self.path))?;
+        let (send, recv) = cb::bounded::<T>(0);
+        thread::spawn(move || {
+            for value in recv.iter() {
+                let frame = match encode_frame(&value) {
+                    Ok(f) => f,
+                    Err(_) => return,
+                };
+                if stream
+                    .write_all(&frame)
+                    .and_then(|_| stream.flush())
+                    .is_err()
+                {
+                    return;
+                }
+            }
+        });
+        Ok(send)
+    }
+}
+
+/// A length prefixed `bincode` frame.
+fn encode_frame<T: Serialize>(value: &T) -> io::Result<Vec<u8>> {
+    let len = bincode::serialized_size(value).map_err(invalid_data)?;
+    if len > MAX_FRAME_LEN as u64 {
+        return Err(frame_too_long(len));
+    }
+    let mut frame = Vec::with_capacity(4 + len as usize);
+    frame.extend_from_slice(&(len as u32).to_le_bytes());
+    bincode::serialize_into(&mut frame, value).map_err(invalid_data)?;
+    Ok(frame)
+}
+
+/// Send every frame received on the stream until it is closed or the receiver is dropped.
+///
+/// Returns an error with kind `InvalidData` if a frame is too long or cannot be deserialized.
+fn forward_frames<T: DeserializeOwned, R: Read>(mut stream: R, send: &Sender<T>) -> io::Result<()> {
+    let mut len = [0; 4];
+    let mut buf = Vec::new();
+    loop {
+        if stream.read_exact(&mut len).is_err() {
+            return Ok(());
+        }
+        let len = u32::from_le_bytes(len);
+        if len as usize > MAX_FRAME_LEN {
+            return Err(frame_too_long(len.into()));
+        }
+        buf.resize(len as usize, 0);
+        stream.read_exact(&mut buf)?;
+        let value = bincode::deserialize(&buf).map_err(invalid_data)?;
+        if send.send(value).is_err() {
+            return Ok(());
+        }
+    }
+}
+
+fn frame_too_long(len: u64) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::InvalidData,
+        format!("ipc frame of {} bytes is too long", len),
+    )
+}
+
+fn invalid_data(err: bincode::Error) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, err)
+}
+
+fn bind(path: &Path) -> io::Result<LocalSocketListener> {
+    match LocalSocketListener::bind(socket_name(path)) {
+        #[cfg(unix)]
+        Err(ref err) if err.kind() == io::ErrorKind::AddrInUse => {
+            // nobody is listening on a socket file left by a process which exited
+            if LocalSocketStream::connect(path).is_err() {
+                let _ = ::std::fs::remove_file(path);
+                return LocalSocketListener::bind(path)
+                    .map_err(|err| path_error(err, "listening on", path));
+            }
+            Err(path_error(
+                io::ErrorKind::AddrInUse.into(),
+                "listening on",
+                path,
+            ))
+        }
+        r => r.map_err(|err| path_error(err, "listening on", path)),
+    }
+}
+
+#[cfg(unix)]
+fn socket_name(path: &Path) -> &Path {
+    path
+}
+
+#[cfg(not(unix))]
+fn socket_name(path: &Path) -> String {
+    // named pipes have their own namespace and their names cannot contain `\`
+    path.to_string_lossy().replace(['\\', '/', ':'], "_")
+}
+
+fn path_error(err: io::Error, action: &str, path: &Path) -> io::Error {
+    io::Error::new(
+        err.kind(),
+        format!("{} when {} {}", err, action, path.display()),
+    )
+}
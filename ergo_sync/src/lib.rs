@@ -48,12 +48,35 @@
 //!
 //! ## Types Functions and Modules
 //!
-//! - **[`ch` module]**: for channel types (also see the [`ch!`] and [`select_loop!`] macros).
+//! - **[`ch` module]**: for channel types (also see the [`ch!`], [`select_loop!`] and
+//!   [`recv_loop!`] macros).
 //! - **[`spawn`]**: the standad `std::thread::spawn` which spawns a regular OS thread. The
 //!   advantage of this (over scoped threads) is that it can outlive the current function. The
 //!   disadvantage is that as far as the compiler knows it _always_ outlives the current function,
 //!   meaning it must own all of its variables (or they have to be `'static`).
 //! - **[`num_cpus`]**: for getting the number of cpus when creating your own thread pools.
+//! - **[`clock`]**: a [`Clock`] trait for the current time and sleeping, with a
+//!   [`ManualClock`] which tests advance by hand instead of sleeping for real.
+//! - **[`DiskQueue`]**: with the `disk_queue` feature, a queue persisted to a directory so that
+//!   work which was queued survives restarts.
+//! - **[`ObjectPool`]**: a pool of expensive objects (i.e. buffers) which are reused by tasks
+//!   instead of being created for each one.
+//! - **[`par_map_ordered`]**: map over the items of a channel in parallel, sending the results in
+//!   the order the items were received.
+//! - **[`RecvBatch`]**: receive messages in batches (up to a number of items or a time limit)
+//!   with `recv.recv_batch(..)` and `recv.batches(..)`.
+//! - **[`Pool`]**: a minimal thread pool which records statistics (tasks queued/running/completed,
+//!   per-worker busy time and average latency) so you can measure how many threads you need.
+//! - **[`scope_channels`]**: scoped threads connected by channels whose senders are owned by the
+//!   threads, so receivers can never be left waiting on a leaked sender.
+//! - **[`stats`]**: thread safe counters, gauges and histograms which can be registered by name
+//!   and snapshotted together to report the progress of a pipeline.
+//! - **[`testing`]**: assertions for receiving from channels within a timeout in tests (also
+//!   see the [`assert_recv_eq!`] and [`assert_closed!`] macros).
+//! - **[`timer`]**: a `Scheduler` for running jobs after a delay, periodically or on a cron
+//!   schedule (with the `cron_schedule` feature), on a `Pool` or over channels.
+//! - **[`watchdog`]**: with the `deadlock_watchdog` feature, warn when a `ch!` operation is
+//!   blocked for too long (i.e. because a channel was leaked).
 //! - **[`std_prelude`]**: Various concurrency related types from `std_prelude` including:
 //!   - `Atomic*`, `Mutex`, `Arc` for concurrency safe types
 //!   - `sleep` and (redefined non-deprecated) `sleep_ms`.
@@ -69,16 +92,33 @@
 //! - **[`ch_try!`]**: to handle an expression that could be `Err` and send it over a channel if it
 //!   is.
 //! - **[`select_loop!`]**: for selecting from multiple channels.
+//! - **[`recv_loop!`]**: for receiving from one or more channels until they are all closed.
 //! - **[`take!`]**: for expressing ownership consisely. You will move or clone
 //!   variables extremely often in threads, this helps you express that better than
 //!   `let value = value`.
 //!
 //! [`ch` module]: ch/index.html
 //! [`spawn`]: fn.spawn.html
+//! [`clock`]: clock/index.html
+//! [`Clock`]: clock/trait.Clock.html
+//! [`ManualClock`]: clock/struct.ManualClock.html
+//! [`Pool`]: pool/struct.Pool.html
+//! [`DiskQueue`]: struct.DiskQueue.html
+//! [`ObjectPool`]: struct.ObjectPool.html
+//! [`par_map_ordered`]: fn.par_map_ordered.html
+//! [`RecvBatch`]: trait.RecvBatch.html
+//! [`scope_channels`]: fn.scope_channels.html
+//! [`stats`]: stats/index.html
+//! [`testing`]: testing/index.html
+//! [`assert_recv_eq!`]: macro.assert_recv_eq.html
+//! [`assert_closed!`]: macro.assert_closed.html
+//! [`timer`]: timer/index.html
+//! [`watchdog`]: watchdog/index.html
 //! [`take!`]: macro.take.html
 //! [`ch!`]: macro.ch.html
 //! [`ch_try!`]: macro.ch_try.html
 //! [`select_loop!`]: macro.select_loop.html
+//! [`recv_loop!`]: macro.recv_loop.html
 //! [`std_prelude`]: ../std_prelude/index.html
 //!
 //! # Examples
@@ -260,6 +300,7 @@
 //!     assert_eq!(0, handle_errs.finish());
 //! }
 //! ```
+extern crate ergo_std;
 #[allow(unused_imports)]
 #[macro_use(take)]
 extern crate taken;
@@ -269,6 +310,21 @@ pub extern crate crossbeam_channel;
 pub extern crate std_prelude;
 pub extern crate num_cpus;
 
+#[cfg(feature = "ipc")]
+extern crate bincode;
+#[cfg(feature = "cron_schedule")]
+extern crate chrono;
+#[cfg(feature = "cron_schedule")]
+extern crate cron;
+#[cfg(feature = "ipc")]
+extern crate interprocess;
+#[cfg(feature = "disk_queue")]
+extern crate path_abs;
+#[cfg(any(feature = "ipc", feature = "disk_queue"))]
+extern crate serde;
+#[cfg(feature = "disk_queue")]
+extern crate serde_json;
+
 // -------- std_prelude exports --------
 // Types
 pub use std_prelude::{Arc, Duration, Mutex};
@@ -288,7 +344,32 @@ pub mod reexports {
 }
 pub use reexports::*;
 
+mod batch;
+mod bytes_ring;
+mod call;
 pub mod ch;
+pub mod clock;
+#[cfg(feature = "disk_queue")]
+mod disk_queue;
+#[cfg(feature = "ipc")]
+mod ipc;
+mod object_pool;
+mod par_map;
+pub mod pool;
+mod scope;
+pub mod stats;
+pub mod testing;
+pub mod timer;
+pub mod watchdog;
+
+pub use batch::RecvBatch;
+pub use clock::{Clock, ManualClock, SystemClock};
+#[cfg(feature = "disk_queue")]
+pub use disk_queue::DiskQueue;
+pub use object_pool::{ObjectPool, PoolGuard};
+pub use par_map::par_map_ordered;
+pub use pool::{Pool, PoolStats, WorkerStats};
+pub use scope::{scope_channels, ChannelScope, Joined, SendHandle};
 
 use std_prelude::*;
 
@@ -330,6 +411,9 @@ impl<T: Send + 'static> FinishHandle<T> for ::std::thread::JoinHandle<T> {
 ///
 /// This function exists in `std::thread` but is deprecated, so it created here instead.
 ///
+/// To sleep in code which is tested with a [`ManualClock`](clock/struct.ManualClock.html), use
+/// [`Clock::sleep_ms`](clock/trait.Clock.html#method.sleep_ms) instead.
+///
 /// # Examples
 /// ```rust
 /// # extern crate ergo_sync;
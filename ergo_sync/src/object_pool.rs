@@ -0,0 +1,173 @@
+//! A pool of reusable objects.
+
+use std::fmt;
+use std::ops::{Deref, DerefMut};
+
+use std_prelude::*;
+
+/// A pool of expensive objects (buffers, compressors, database handles) which are created on
+/// demand and reused instead of being dropped.
+///
+/// Cloning the pool is cheap and the clones share the same objects, so a clone can be moved into
+/// every task. Use [`Pool::execute_with`] to run a [`Pool`] task with an object from the pool.
+///
+/// [`Pool`]: pool/struct.Pool.html
+/// [`Pool::execute_with`]: pool/struct.Pool.html#method.execute_with
+///
+/// # Examples
+///
+/// ```rust
+/// #[macro_use] extern crate ergo_sync;
+/// use ergo_sync::*;
+///
+/// # fn main() {
+/// let buffers = ObjectPool::new(|| Vec::with_capacity(1024));
+/// {
+///     let mut buf = buffers.get();
+///     buf.extend_from_slice(b"hello");
+/// } // buf is returned to the pool
+/// assert_eq!(1, buffers.idle());
+///
+/// let threads = Pool::new(4);
+/// let (send, recv) = ch::unbounded();
+/// for i in 0..100_usize {
+///     take!(=send);
+///     threads.execute_with(&buffers, move |buf: &mut Vec<u8>| {
+///         buf.clear();
+///         buf.resize(i, 0);
+///         ch!(send <- buf.len());
+///     });
+/// }
+/// drop(send);
+/// assert_eq!(4950_usize, recv.iter().sum::<usize>());
+/// // at most one buffer was created per thread
+/// assert!(buffers.idle() <= 4);
+/// # }
+/// ```
+pub struct ObjectPool<T> {
+    inner: Arc<Inner<T>>,
+}
+
+struct Inner<T> {
+    factory: Box<dyn Fn() -> T + Send + Sync>,
+    idle: Mutex<Vec<T>>,
+    max_idle: usize,
+}
+
+/// An object borrowed from an [`ObjectPool`](struct.ObjectPool.html), which is returned to the
+/// pool when it is dropped.
+pub struct PoolGuard<T> {
+    value: Option<T>,
+    pool: ObjectPool<T>,
+}
+
+impl<T> ObjectPool<T> {
+    /// Create an empty pool which creates objects with `factory`.
+    pub fn new<F>(factory: F) -> ObjectPool<T>
+    where
+        F: Fn() -> T + Send + Sync + 'static,
+    {
+        ObjectPool::with_max_idle(usize::MAX, factory)
+    }
+
+    /// Create an empty pool which keeps at most `max_idle` objects which are not in use, any
+    /// more are dropped when they are returned.
+    pub fn with_max_idle<F>(max_idle: usize, factory: F) -> ObjectPool<T>
+    where
+        F: Fn() -> T + Send + Sync + 'static,
+    {
+        ObjectPool {
+            inner: Arc::new(Inner {
+                factory: Box::new(factory),
+                idle: Mutex::new(Vec::new()),
+                max_idle,
+            }),
+        }
+    }
+
+    /// Get an idle object from the pool, or create one if there are none.
+    ///
+    /// Objects are returned as they were left, clear them before (or after) using them if
+    /// necessary.
+    pub fn get(&self) -> PoolGuard<T> {
+        let idle = self.idle_objects().pop();
+        PoolGuard {
+            value: Some(idle.unwrap_or_else(|| (self.inner.factory)())),
+            pool: self.clone(),
+        }
+    }
+
+    /// The number of objects in the pool which are not in use.
+    pub fn idle(&self) -> usize {
+        self.idle_objects().len()
+    }
+
+    fn idle_objects<'a>(&'a self) -> ::std::sync::MutexGuard<'a, Vec<T>> {
+        self.inner.idle.lock().expect("ObjectPool poisoned")
+    }
+
+    fn put(&self, value: T) {
+        let mut idle = self.idle_objects();
+        if idle.len() < self.inner.max_idle {
+            idle.push(value);
+        }
+    }
+}
+
+impl<T> Clone for ObjectPool<T> {
+    fn clone(&self) -> ObjectPool<T> {
+        ObjectPool {
+            inner: self.inner.clone(),
+        }
+    }
+}
+
+impl<T> fmt::Debug for ObjectPool<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("ObjectPool")
+            .field("idle", &self.idle())
+            .field("max_idle", &self.inner.max_idle)
+            .finish()
+    }
+}
+
+impl<T> PoolGuard<T> {
+    /// Take the object out of the pool permanently.
+    pub fn detach(mut self) -> T {
+        self.value
+            .take()
+            .expect("value only taken on detach or drop")
+    }
+}
+
+impl<T> Deref for PoolGuard<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        self.value
+            .as_ref()
+            .expect("value only taken on detach or drop")
+    }
+}
+
+impl<T> DerefMut for PoolGuard<T> {
+    fn deref_mut(&mut self) -> &mut T {
+        self.value
+            .as_mut()
+            .expect("value only taken on detach or drop")
+    }
+}
+
+impl<T: fmt::Debug> fmt::Debug for PoolGuard<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_tuple("PoolGuard").field(&**self).finish()
+    }
+}
+
+impl<T> Drop for PoolGuard<T> {
+    fn drop(&mut self) {
+        if let Some(value) = self.value.take() {
+            self.pool.put(value);
+        }
+    }
+}
@@ -0,0 +1,113 @@
+//! Mapping over a channel in parallel while preserving order.
+
+use std::collections::BTreeMap;
+use std::panic::{self, AssertUnwindSafe};
+
+use crossbeam_channel::{self as cb, Receiver, Sender};
+use std_prelude::*;
+use FinishHandle;
+
+/// Apply `f` to every item received from `recv_in` using `n_workers` threads, sending the results
+/// to `send_out` in the same order as the items were received.
+///
+/// Unlike `rayon` this works on a stream of items of unknown length, which is what a pipeline
+/// stage like "compress chunks in order" needs. At most `2 * n_workers` items are in flight at
+/// once, so a slow item holds up at most that many finished results instead of buffering
+/// without bound.
+///
+/// This blocks until `recv_in` is disconnected and every result has been sent, so it is usually
+/// run in its own thread with [`spawn`](fn.spawn.html). It stops early if `send_out` is
+/// disconnected.
+///
+/// # Panics
+/// Panics if `n_workers` is 0 or if `f` panics.
+///
+/// # Examples
+///
+/// ```rust
+/// #[macro_use] extern crate ergo_sync;
+/// use ergo_sync::*;
+///
+/// # fn main() {
+/// let (send_in, recv_in) = ch::bounded(16);
+/// let (send_out, recv_out) = ch::bounded(16);
+/// spawn(move || par_map_ordered(recv_in, send_out, 4, |i: u64| {
+///     // later items finish first
+///     sleep_ms(10 - i);
+///     i * 2
+/// }));
+///
+/// spawn(move || {
+///     for i in 0..10 {
+///         ch!(send_in <- i);
+///     }
+/// });
+/// let out: Vec<_> = recv_out.iter().collect();
+/// assert_eq!(vec![0, 2, 4, 6, 8, 10, 12, 14, 16, 18], out);
+/// # }
+/// ```
+pub fn par_map_ordered<T, U, F>(recv_in: Receiver<T>, send_out: Sender<U>, n_workers: usize, f: F)
+where
+    T: Send + 'static,
+    U: Send + 'static,
+    F: Fn(T) -> U + Send + Sync + 'static,
+{
+    assert!(
+        n_workers > 0,
+        "par_map_ordered n_workers must be greater than 0"
+    );
+    let window = n_workers * 2;
+
+    // Each item in flight holds a token, which is returned once its result is sent.
+    let (send_token, recv_token) = cb::bounded(window);
+    for _ in 0..window {
+        send_token.send(()).expect("token receiver exists");
+    }
+
+    let (send_work, recv_work) = cb::bounded(n_workers);
+    let dispatcher = spawn(move || {
+        for (index, item) in recv_in.iter().enumerate() {
+            if recv_token.recv().is_err() || send_work.send((index, item)).is_err() {
+                // the collector stopped early
+                return;
+            }
+        }
+    });
+
+    let f = Arc::new(f);
+    let (send_done, recv_done) = cb::unbounded();
+    for _ in 0..n_workers {
+        let recv_work: Receiver<(usize, T)> = recv_work.clone();
+        let send_done = send_done.clone();
+        let f = f.clone();
+        spawn(move || {
+            for (index, item) in recv_work.iter() {
+                let result = panic::catch_unwind(AssertUnwindSafe(|| f(item)));
+                if send_done.send((index, result)).is_err() {
+                    return;
+                }
+            }
+        });
+    }
+    drop((recv_work, send_done));
+
+    // Results which finished before an earlier item.
+    let mut pending = BTreeMap::new();
+    let mut next = 0;
+    for (index, result) in recv_done.iter() {
+        pending.insert(index, result);
+        while let Some(result) = pending.remove(&next) {
+            match result {
+                Ok(out) => {
+                    if send_out.send(out).is_err() {
+                        return;
+                    }
+                }
+                Err(payload) => panic::resume_unwind(payload),
+            }
+            next += 1;
+            let _ = send_token.send(());
+        }
+    }
+    dispatcher.finish();
+}
@@ -0,0 +1,233 @@
+//! A minimal instrumented thread pool.
+//!
+//! `ergo_sync` generally recommends [`spawn`] with channels, and [`Pool`] is exactly that: a
+//! fixed number of threads receiving boxed tasks over a channel. What it adds is
+//! [`Pool::stats`], a snapshot of how the pool is being used, so that capacity planning can be
+//! based on data rather than guessing that `num_cpus::get()` threads is right.
+//!
+//! [`spawn`]: ../fn.spawn.html
+//! [`Pool`]: struct.Pool.html
+//! [`Pool::stats`]: struct.Pool.html#method.stats
+//!
+//! # Examples
+//! ```rust
+//! #[macro_use] extern crate ergo_sync;
+//! use ergo_sync::*;
+//!
+//! # fn main() {
+//! let pool = Pool::new(4);
+//! let (send, recv) = ch::unbounded();
+//! for i in 0..100_u64 {
+//!     take!(=send);
+//!     pool.execute(move || ch!(send <- i * 2));
+//! }
+//! drop(send);
+//! assert_eq!(9900_u64, recv.iter().sum::<u64>());
+//!
+//! let stats = pool.join();
+//! assert_eq!(100, stats.completed);
+//! assert_eq!(4, stats.workers.len());
+//! assert_eq!(100, stats.latency.count());
+//! assert!(stats.latency.p50_duration() <= stats.latency.p99_duration());
+//! # }
+//! ```
+
+use std::fmt;
+use std::panic;
+use std::thread::JoinHandle;
+use std::time::Instant;
+
+use crossbeam_channel::{self as cb, Receiver, Sender};
+use ergo_std::Histogram;
+use std_prelude::*;
+use FinishHandle;
+use ObjectPool;
+
+struct Task {
+    submitted: Instant,
+    run: Box<dyn FnOnce() + Send>,
+}
+
+/// A fixed size pool of threads which execute tasks and record statistics about them.
+///
+/// Dropping the pool waits for all queued tasks to complete.
+pub struct Pool {
+    send: Option<Sender<Task>>,
+    workers: Vec<JoinHandle<()>>,
+    shared: Arc<Shared>,
+}
+
+/// A snapshot of the statistics of a [`Pool`](struct.Pool.html).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PoolStats {
+    /// Tasks which have been submitted but not yet started.
+    pub queued: usize,
+    /// Tasks which are currently running.
+    pub running: usize,
+    /// Tasks which have completed (including those which panicked).
+    pub completed: u64,
+    /// Tasks which panicked.
+    pub panicked: u64,
+    /// The average time from a task being submitted until it completed.
+    pub avg_latency: Duration,
+    /// The distribution of the time from a task being submitted until it completed, query it
+    /// for percentiles (i.e. `latency.p99_duration()`).
+    pub latency: Histogram,
+    /// Statistics for each worker thread.
+    pub workers: Vec<WorkerStats>,
+}
+
+/// Statistics for a single worker thread of a [`Pool`](struct.Pool.html).
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct WorkerStats {
+    /// The total time this worker has spent running tasks.
+    pub busy: Duration,
+    /// The number of tasks this worker has completed.
+    pub completed: u64,
+}
+
+#[derive(Default)]
+struct Shared {
+    running: AtomicUsize,
+    totals: Mutex<Totals>,
+    workers: Mutex<Vec<WorkerStats>>,
+}
+
+#[derive(Default)]
+struct Totals {
+    completed: u64,
+    panicked: u64,
+    latency: Histogram,
+}
+
+impl Pool {
+    /// Create a pool with `threads` worker threads.
+    ///
+    /// # Panics
+    /// Panics if `threads` is 0.
+    pub fn new(threads: usize) -> Pool {
+        assert!(threads > 0, "a Pool must have at least one thread");
+        let (send, recv) = cb::unbounded();
+        let shared = Arc::new(Shared {
+            workers: Mutex::new(vec![WorkerStats::default(); threads]),
+            ..Shared::default()
+        });
+        let workers = (0..threads)
+            .map(|id| {
+                let recv = recv.clone();
+                let shared = shared.clone();
+                spawn(move || work(id, &recv, &shared))
+            })
+            .collect();
+        Pool {
+            send: Some(send),
+            workers,
+            shared,
+        }
+    }
+
+    /// Execute the task on one of the pool's threads.
+    ///
+    /// A task which panics does not bring down its worker, it is counted in
+    /// [`PoolStats::panicked`](struct.PoolStats.html#structfield.panicked).
+    pub fn execute<F>(&self, task: F)
+    where
+        F: FnOnce() + Send + 'static,
+    {
+        let task = Task {
+            submitted: Instant::now(),
+            run: Box::new(task),
+        };
+        self.sender()
+            .send(task)
+            .expect("Pool workers are disconnected");
+    }
+
+    /// Execute the task on one of the pool's threads with an object borrowed from `objects`,
+    /// which is returned to `objects` once the task completes.
+    ///
+    /// See [`ObjectPool`](../struct.ObjectPool.html).
+    pub fn execute_with<T, F>(&self, objects: &ObjectPool<T>, task: F)
+    where
+        T: Send + 'static,
+        F: FnOnce(&mut T) + Send + 'static,
+    {
+        let objects = objects.clone();
+        self.execute(move || task(&mut objects.get()))
+    }
+
+    /// Get a snapshot of the statistics of the pool.
+    pub fn stats(&self) -> PoolStats {
+        let totals = self.shared.totals.lock().expect("Pool stats poisoned");
+        PoolStats {
+            queued: self.send.as_ref().map_or(0, |s| s.len()),
+            running: self.shared.running.load(AtomicOrdering::SeqCst),
+            completed: totals.completed,
+            panicked: totals.panicked,
+            avg_latency: totals.latency.mean_duration(),
+            latency: totals.latency.clone(),
+            workers: self
+                .shared
+                .workers
+                .lock()
+                .expect("Pool stats poisoned")
+                .clone(),
+        }
+    }
+
+    /// Wait for all queued tasks to complete, stop the worker threads and return the final
+    /// statistics.
+    pub fn join(mut self) -> PoolStats {
+        self.finish_workers();
+        self.stats()
+    }
+
+    fn sender(&self) -> &Sender<Task> {
+        self.send.as_ref().expect("sender only taken on join")
+    }
+
+    fn finish_workers(&mut self) {
+        // disconnect the channel so the workers exit once the queue is empty.
+        self.send.take();
+        for worker in self.workers.drain(..) {
+            worker.finish();
+        }
+    }
+}
+
+impl fmt::Debug for Pool {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Pool")
+            .field("threads", &self.workers.len())
+            .finish()
+    }
+}
+
+impl Drop for Pool {
+    fn drop(&mut self) {
+        self.finish_workers();
+    }
+}
+
+fn work(id: usize, recv: &Receiver<Task>, shared: &Shared) {
+    for task in recv.iter() {
+        shared.running.fetch_add(1, AtomicOrdering::SeqCst);
+        let start = Instant::now();
+        let result = panic::catch_unwind(panic::AssertUnwindSafe(task.run));
+        let end = Instant::now();
+        shared.running.fetch_sub(1, AtomicOrdering::SeqCst);
+
+        {
+            let mut workers = shared.workers.lock().expect("Pool stats poisoned");
+            let worker = &mut workers[id];
+            worker.busy += end - start;
+            worker.completed += 1;
+        }
+        let mut totals = shared.totals.lock().expect("Pool stats poisoned");
+        totals.completed += 1;
+        totals.latency.record_duration(end - task.submitted);
+        if result.is_err() {
+            totals.panicked += 1;
+        }
+    }
+}
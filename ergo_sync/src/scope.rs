@@ -0,0 +1,197 @@
+//! Scoped threads which own the senders of their channels.
+
+use std::cell::RefCell;
+use std::fmt;
+use std::rc::Rc;
+use std::thread;
+
+use crossbeam_channel::{self as cb, Receiver, Sender};
+
+/// Spawn scoped threads connected by channels whose senders are owned by the threads.
+///
+/// A channel's receiver only stops blocking once _every_ sender is dropped, so the usual rule is
+/// "always have your final producer take `send` without cloning it". Inside `scope_channels` that
+/// rule is enforced by construction: the channels give you a [`SendHandle`] instead of a
+/// `Sender`, which can only be used to spawn threads with their own clone of the sender through
+/// [`ChannelScope::spawn_with`]. The scope drops its own sender as soon as `f` returns, so each
+/// receiver disconnects once the threads sending to it have finished.
+///
+/// Like `std::thread::scope` the threads can borrow local variables, and every thread is joined
+/// before this returns. Return the [`Joined`] handles of your consumers from `f` and join them
+/// afterwards, joining a consumer _inside_ `f` would block forever since the scope still owns
+/// the senders.
+///
+/// # Panics
+/// Panics if any of the threads panicked, after every thread is joined.
+///
+/// [`SendHandle`]: struct.SendHandle.html
+/// [`ChannelScope::spawn_with`]: struct.ChannelScope.html#method.spawn_with
+/// [`Joined`]: struct.Joined.html
+///
+/// # Examples
+///
+/// ```rust
+/// #[macro_use] extern crate ergo_sync;
+/// use ergo_sync::*;
+///
+/// # fn main() {
+/// let multiplier = 42;
+/// let consumer = scope_channels(|sc| {
+///     let (send, recv) = sc.bounded(0);
+///
+///     let consumer = sc.spawn(move || -> u64 { recv.iter().sum() });
+///
+///     // the producers may borrow `multiplier` and need no special care for `send`
+///     let multiplier = &multiplier;
+///     for i in 0..10_u64 {
+///         sc.spawn_with(&send, move |send| {
+///             ch!(send <- i * multiplier);
+///         });
+///     }
+///     consumer
+/// });
+/// assert_eq!(1890, consumer.join());
+/// # }
+/// ```
+pub fn scope_channels<'env, F, R>(f: F) -> R
+where
+    F: for<'scope> FnOnce(&ChannelScope<'scope, 'env>) -> R,
+{
+    thread::scope(|scope| {
+        let sc = ChannelScope {
+            scope,
+            senders: RefCell::new(Vec::new()),
+        };
+        // `sc` is dropped (sealing the senders) before the threads are joined, even if `f`
+        // panics.
+        f(&sc)
+    })
+}
+
+/// The scope given to [`scope_channels`](fn.scope_channels.html), for creating channels and
+/// spawning threads.
+pub struct ChannelScope<'scope, 'env: 'scope> {
+    scope: &'scope thread::Scope<'scope, 'env>,
+    senders: RefCell<Vec<Rc<dyn Seal + 'scope>>>,
+}
+
+/// The sending side of a channel created by a [`ChannelScope`](struct.ChannelScope.html).
+///
+/// This is not a `Sender` and cannot be sent to other threads, use it with
+/// [`ChannelScope::spawn_with`](struct.ChannelScope.html#method.spawn_with) to spawn a thread
+/// which owns a clone of the sender.
+pub struct SendHandle<T> {
+    send: Rc<RefCell<Option<Sender<T>>>>,
+}
+
+/// The result of a thread spawned in a [`ChannelScope`](struct.ChannelScope.html).
+///
+/// Unlike a `JoinHandle` this can be returned from the scope and joined afterwards.
+pub struct Joined<T> {
+    recv: Receiver<T>,
+}
+
+impl<'scope, 'env> ChannelScope<'scope, 'env> {
+    /// Create a channel with the given capacity, see
+    /// [`ch::bounded`](ch/fn.bounded.html).
+    pub fn bounded<T: Send + 'scope>(&self, cap: usize) -> (SendHandle<T>, Receiver<T>) {
+        let (send, recv) = cb::bounded(cap);
+        (self.handle(send), recv)
+    }
+
+    /// Create a channel of unbounded capacity, see [`ch::unbounded`](ch/fn.unbounded.html).
+    pub fn unbounded<T: Send + 'scope>(&self) -> (SendHandle<T>, Receiver<T>) {
+        let (send, recv) = cb::unbounded();
+        (self.handle(send), recv)
+    }
+
+    /// Spawn a thread in the scope.
+    ///
+    /// A thread which receives from a channel should take ownership of the `Receiver` with a
+    /// `move` closure.
+    pub fn spawn<F, T>(&self, f: F) -> Joined<T>
+    where
+        F: FnOnce() -> T + Send + 'scope,
+        T: Send + 'scope,
+    {
+        let (send_out, recv_out) = cb::bounded(1);
+        self.scope.spawn(move || {
+            // Joined only receives after this thread finishes.
+            let _ = send_out.send(f());
+        });
+        Joined { recv: recv_out }
+    }
+
+    /// Spawn a thread in the scope which owns a clone of the sender of `handle`. The sender is
+    /// dropped when `f` finishes.
+    pub fn spawn_with<F, T, U>(&self, handle: &SendHandle<U>, f: F) -> Joined<T>
+    where
+        F: FnOnce(Sender<U>) -> T + Send + 'scope,
+        T: Send + 'scope,
+        U: Send + 'scope,
+    {
+        let send = handle
+            .send
+            .borrow()
+            .clone()
+            .expect("SendHandle used after its scope returned");
+        self.spawn(move || f(send))
+    }
+
+    fn handle<T: Send + 'scope>(&self, send: Sender<T>) -> SendHandle<T> {
+        let send = Rc::new(RefCell::new(Some(send)));
+        self.senders.borrow_mut().push(send.clone());
+        SendHandle { send }
+    }
+}
+
+impl<'scope, 'env> Drop for ChannelScope<'scope, 'env> {
+    fn drop(&mut self) {
+        for sender in self.senders.borrow_mut().drain(..) {
+            sender.seal();
+        }
+    }
+}
+
+impl<T> Joined<T> {
+    /// Block until the thread finishes, returning its result.
+    ///
+    /// # Panics
+    /// Panics if the thread panicked.
+    pub fn join(self) -> T {
+        self.recv.recv().expect("scoped thread panicked")
+    }
+}
+
+impl<'scope, 'env> fmt::Debug for ChannelScope<'scope, 'env> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("ChannelScope")
+            .field("channels", &self.senders.borrow().len())
+            .finish()
+    }
+}
+
+impl<T> fmt::Debug for SendHandle<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("SendHandle")
+            .field("open", &self.send.borrow().is_some())
+            .finish()
+    }
+}
+
+impl<T> fmt::Debug for Joined<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Joined").finish()
+    }
+}
+
+/// A sender owned by the scope which is dropped when the scope's closure returns.
+trait Seal {
+    fn seal(&self);
+}
+
+impl<T> Seal for RefCell<Option<Sender<T>>> {
+    fn seal(&self) {
+        self.borrow_mut().take();
+    }
+}
@@ -0,0 +1,291 @@
+//! Thread safe counters, gauges and histograms for reporting metrics.
+//!
+//! Every metric is cheap to clone and the clones share the same value, so a clone can be moved
+//! into each thread of a pipeline. Register them by name in [`Stats`] to take a consistent
+//! [`snapshot`] of all of them, i.e. to log or display progress.
+//!
+//! [`Counter`] and [`Gauge`] are lock-free. [`Histogram`] locks a mutex to record a value, which
+//! is uncontended unless many threads record at the same instant.
+//!
+//! [`Stats`]: struct.Stats.html
+//! [`snapshot`]: struct.Stats.html#method.snapshot
+//! [`Counter`]: struct.Counter.html
+//! [`Gauge`]: struct.Gauge.html
+//! [`Histogram`]: struct.Histogram.html
+//!
+//! # Examples
+//!
+//! ```rust
+//! #[macro_use] extern crate ergo_sync;
+//! use ergo_sync::*;
+//! use ergo_sync::stats::Stats;
+//!
+//! # fn main() {
+//! let stats = Stats::new();
+//! let lines = stats.counter("lines");
+//! let in_flight = stats.gauge("in_flight");
+//! let latency = stats.histogram("latency");
+//!
+//! let threads: Vec<_> = (0..4)
+//!     .map(|_| {
+//!         take!(=lines, =in_flight, =latency);
+//!         spawn(move || {
+//!             for _ in 0..25 {
+//!                 in_flight.inc();
+//!                 lines.inc();
+//!                 latency.record_duration(Duration::from_millis(1));
+//!                 in_flight.dec();
+//!             }
+//!         })
+//!     })
+//!     .collect();
+//! for th in threads {
+//!     th.finish();
+//! }
+//!
+//! let snapshot = stats.snapshot();
+//! assert_eq!(Some(100), snapshot.counter("lines"));
+//! assert_eq!(Some(0), snapshot.gauge("in_flight"));
+//! assert_eq!(100, snapshot.histogram("latency").unwrap().count());
+//! println!("{}", snapshot);
+//! # }
+//! ```
+
+use std::collections::BTreeMap;
+use std::fmt;
+use std::sync::atomic::{AtomicI64, AtomicU64};
+
+use ergo_std;
+use std_prelude::*;
+
+/// A value which only goes up, i.e. the number of items processed.
+#[derive(Debug, Clone, Default)]
+pub struct Counter {
+    value: Arc<AtomicU64>,
+}
+
+/// A value which goes up and down, i.e. the number of items in flight.
+#[derive(Debug, Clone, Default)]
+pub struct Gauge {
+    value: Arc<AtomicI64>,
+}
+
+/// A distribution of values, i.e. the latency of an operation.
+///
+/// See [`ergo_std::Histogram`](../../ergo_std/struct.Histogram.html) for details.
+#[derive(Debug, Clone, Default)]
+pub struct Histogram {
+    hist: Arc<Mutex<ergo_std::Histogram>>,
+}
+
+/// A registry of named metrics.
+#[derive(Debug, Clone, Default)]
+pub struct Stats {
+    metrics: Arc<Mutex<Metrics>>,
+}
+
+#[derive(Debug, Default)]
+struct Metrics {
+    counters: BTreeMap<String, Counter>,
+    gauges: BTreeMap<String, Gauge>,
+    histograms: BTreeMap<String, Histogram>,
+}
+
+/// The values of every metric of a [`Stats`](struct.Stats.html) at one point in time.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct StatsSnapshot {
+    /// The values of the counters by name.
+    pub counters: BTreeMap<String, u64>,
+    /// The values of the gauges by name.
+    pub gauges: BTreeMap<String, i64>,
+    /// The histograms by name.
+    pub histograms: BTreeMap<String, ergo_std::Histogram>,
+}
+
+impl Counter {
+    /// Create a counter at `0`.
+    pub fn new() -> Counter {
+        Counter::default()
+    }
+
+    /// Add one.
+    pub fn inc(&self) {
+        self.add(1);
+    }
+
+    /// Add `n`.
+    pub fn add(&self, n: u64) {
+        self.value.fetch_add(n, AtomicOrdering::Relaxed);
+    }
+
+    /// The current value.
+    pub fn get(&self) -> u64 {
+        self.value.load(AtomicOrdering::Relaxed)
+    }
+
+    /// Set the value to `0`, returning the value it had.
+    pub fn reset(&self) -> u64 {
+        self.value.swap(0, AtomicOrdering::Relaxed)
+    }
+}
+
+impl Gauge {
+    /// Create a gauge at `0`.
+    pub fn new() -> Gauge {
+        Gauge::default()
+    }
+
+    /// Set the value.
+    pub fn set(&self, value: i64) {
+        self.value.store(value, AtomicOrdering::Relaxed);
+    }
+
+    /// Add one.
+    pub fn inc(&self) {
+        self.add(1);
+    }
+
+    /// Subtract one.
+    pub fn dec(&self) {
+        self.add(-1);
+    }
+
+    /// Add `n`, which may be negative.
+    pub fn add(&self, n: i64) {
+        self.value.fetch_add(n, AtomicOrdering::Relaxed);
+    }
+
+    /// The current value.
+    pub fn get(&self) -> i64 {
+        self.value.load(AtomicOrdering::Relaxed)
+    }
+}
+
+impl Histogram {
+    /// Create an empty histogram.
+    pub fn new() -> Histogram {
+        Histogram::default()
+    }
+
+    /// Record a value.
+    pub fn record(&self, value: u64) {
+        self.lock().record(value);
+    }
+
+    /// Record a duration as nanoseconds.
+    pub fn record_duration(&self, duration: Duration) {
+        self.lock().record_duration(duration);
+    }
+
+    /// A copy of the values recorded so far.
+    pub fn snapshot(&self) -> ergo_std::Histogram {
+        self.lock().clone()
+    }
+
+    fn lock<'a>(&'a self) -> ::std::sync::MutexGuard<'a, ergo_std::Histogram> {
+        self.hist.lock().expect("Histogram poisoned")
+    }
+}
+
+impl Stats {
+    /// Create an empty registry.
+    pub fn new() -> Stats {
+        Stats::default()
+    }
+
+    /// Get the counter named `name`, creating it if it does not exist.
+    pub fn counter(&self, name: &str) -> Counter {
+        let mut metrics = self.lock();
+        metrics
+            .counters
+            .entry(name.to_string())
+            .or_default()
+            .clone()
+    }
+
+    /// Get the gauge named `name`, creating it if it does not exist.
+    pub fn gauge(&self, name: &str) -> Gauge {
+        let mut metrics = self.lock();
+        metrics.gauges.entry(name.to_string()).or_default().clone()
+    }
+
+    /// Get the histogram named `name`, creating it if it does not exist.
+    pub fn histogram(&self, name: &str) -> Histogram {
+        let mut metrics = self.lock();
+        metrics
+            .histograms
+            .entry(name.to_string())
+            .or_default()
+            .clone()
+    }
+
+    /// Get the current value of every metric.
+    ///
+    /// Metrics which are being updated while the snapshot is taken may be included before or
+    /// after the update, each value is consistent on its own.
+    pub fn snapshot(&self) -> StatsSnapshot {
+        let metrics = self.lock();
+        StatsSnapshot {
+            counters: metrics
+                .counters
+                .iter()
+                .map(|(name, c)| (name.clone(), c.get()))
+                .collect(),
+            gauges: metrics
+                .gauges
+                .iter()
+                .map(|(name, g)| (name.clone(), g.get()))
+                .collect(),
+            histograms: metrics
+                .histograms
+                .iter()
+                .map(|(name, h)| (name.clone(), h.snapshot()))
+                .collect(),
+        }
+    }
+
+    fn lock<'a>(&'a self) -> ::std::sync::MutexGuard<'a, Metrics> {
+        self.metrics.lock().expect("Stats poisoned")
+    }
+}
+
+impl StatsSnapshot {
+    /// The value of the counter named `name`.
+    pub fn counter(&self, name: &str) -> Option<u64> {
+        self.counters.get(name).cloned()
+    }
+
+    /// The value of the gauge named `name`.
+    pub fn gauge(&self, name: &str) -> Option<i64> {
+        self.gauges.get(name).cloned()
+    }
+
+    /// The histogram named `name`.
+    pub fn histogram(&self, name: &str) -> Option<&ergo_std::Histogram> {
+        self.histograms.get(name)
+    }
+}
+
+/// One metric per line, sorted by name.
+impl fmt::Display for StatsSnapshot {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (name, value) in &self.counters {
+            writeln!(f, "{}: {}", name, value)?;
+        }
+        for (name, value) in &self.gauges {
+            writeln!(f, "{}: {}", name, value)?;
+        }
+        for (name, hist) in &self.histograms {
+            writeln!(
+                f,
+                "{}: count={} p50={} p99={} max={}",
+                name,
+                hist.count(),
+                hist.p50(),
+                hist.p99(),
+                hist.max()
+            )?;
+        }
+        Ok(())
+    }
+}
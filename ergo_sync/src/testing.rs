@@ -0,0 +1,149 @@
+//! Assertions on channels, for testing pipelines.
+//!
+//! Tests of threads connected by channels need to wait for a value, but not forever if the
+//! code under test is broken. Instead of hand writing loops with timeouts, use:
+//!
+//! - [`assert_recv_eq!`]: receive a value within a timeout and assert that it is equal to the
+//!   expected one.
+//! - [`assert_closed!`]: assert that every sender was dropped (within a timeout) without
+//!   sending anything else.
+//! - [`drain`]: collect the values which are already in a channel without blocking.
+//!
+//! The timeout is [`DEFAULT_TIMEOUT`] unless given with `within`, i.e.
+//! `assert_recv_eq!(recv, 42, within 100 ms)` or
+//! `assert_recv_eq!(recv, 42, within Duration::from_secs(5))`.
+//!
+//! [`assert_recv_eq!`]: ../macro.assert_recv_eq.html
+//! [`assert_closed!`]: ../macro.assert_closed.html
+//! [`drain`]: fn.drain.html
+//! [`DEFAULT_TIMEOUT`]: constant.DEFAULT_TIMEOUT.html
+//!
+//! # Examples
+//! ```rust
+//! #[macro_use] extern crate ergo_sync;
+//! use ergo_sync::*;
+//! use ergo_sync::testing::drain;
+//!
+//! # fn main() {
+//! let (send, recv) = ch::unbounded();
+//! let th = spawn(move || {
+//!     for i in 0..5 {
+//!         ch!(send <- i * 10);
+//!     }
+//! });
+//!
+//! assert_recv_eq!(recv, 0);
+//! assert_recv_eq!(recv, 10, within 100 ms);
+//! th.finish();
+//! assert_eq!(vec![20, 30, 40], drain(&recv));
+//! assert_closed!(recv, within Duration::from_secs(5));
+//! # }
+//! ```
+
+use std::fmt::Debug;
+
+use crossbeam_channel::{Receiver, RecvTimeoutError};
+use std_prelude::*;
+
+/// How long the assertions wait when no timeout is given.
+pub const DEFAULT_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// Receive a value from a channel within a timeout and assert that it equals the expected value.
+///
+/// - `assert_recv_eq!(recv, expected)` waits for [`DEFAULT_TIMEOUT`].
+/// - `assert_recv_eq!(recv, expected, within 100 ms)` waits for 100 milliseconds.
+/// - `assert_recv_eq!(recv, expected, within timeout)` waits for the `Duration` `timeout`.
+///
+/// See the [`testing`](testing/index.html) module.
+///
+/// [`DEFAULT_TIMEOUT`]: testing/constant.DEFAULT_TIMEOUT.html
+///
+/// # Panics
+/// Panics if the value is not equal, no value is received in time or every sender is dropped.
+#[macro_export]
+macro_rules! assert_recv_eq {
+    ($recv:expr, $expected:expr) => {
+        assert_eq!(
+            $crate::testing::recv_within(&$recv, $crate::testing::DEFAULT_TIMEOUT),
+            $expected
+        )
+    };
+    ($recv:expr, $expected:expr, within $ms:tt ms) => {
+        assert_eq!(
+            $crate::testing::recv_within(&$recv, $crate::Duration::from_millis($ms)),
+            $expected
+        )
+    };
+    ($recv:expr, $expected:expr, within $timeout:expr) => {
+        assert_eq!($crate::testing::recv_within(&$recv, $timeout), $expected)
+    };
+}
+
+/// Assert that every sender of a channel is dropped within a timeout, and that the channel has
+/// no values left.
+///
+/// - `assert_closed!(recv)` waits for [`DEFAULT_TIMEOUT`].
+/// - `assert_closed!(recv, within 100 ms)` waits for 100 milliseconds.
+/// - `assert_closed!(recv, within timeout)` waits for the `Duration` `timeout`.
+///
+/// See the [`testing`](testing/index.html) module.
+///
+/// [`DEFAULT_TIMEOUT`]: testing/constant.DEFAULT_TIMEOUT.html
+///
+/// # Panics
+/// Panics if a value is received or a sender still exists after the timeout.
+#[macro_export]
+macro_rules! assert_closed {
+    ($recv:expr) => {
+        $crate::testing::closed_within(&$recv, $crate::testing::DEFAULT_TIMEOUT)
+    };
+    ($recv:expr, within $ms:tt ms) => {
+        $crate::testing::closed_within(&$recv, $crate::Duration::from_millis($ms))
+    };
+    ($recv:expr, within $timeout:expr) => {
+        $crate::testing::closed_within(&$recv, $timeout)
+    };
+}
+
+/// Receive the values which are already in the channel, without blocking.
+///
+/// To wait for every value until the senders are dropped, use `recv.iter().collect()`.
+pub fn drain<T>(recv: &Receiver<T>) -> Vec<T> {
+    let mut out = Vec::new();
+    while let Ok(v) = recv.try_recv() {
+        out.push(v);
+    }
+    out
+}
+
+/// Receive a value within `timeout`, the implementation of
+/// [`assert_recv_eq!`](../macro.assert_recv_eq.html).
+///
+/// # Panics
+/// Panics if no value is received in time or every sender is dropped.
+#[track_caller]
+pub fn recv_within<T>(recv: &Receiver<T>, timeout: Duration) -> T {
+    match recv.recv_timeout(timeout) {
+        Ok(v) => v,
+        Err(RecvTimeoutError::Timeout) => panic!("no value was received within {:?}", timeout),
+        Err(RecvTimeoutError::Disconnected) => {
+            panic!("the senders were dropped while waiting for a value")
+        }
+    }
+}
+
+/// Wait up to `timeout` for every sender to be dropped, the implementation of
+/// [`assert_closed!`](../macro.assert_closed.html).
+///
+/// # Panics
+/// Panics if a value is received or a sender still exists after the timeout.
+#[track_caller]
+pub fn closed_within<T: Debug>(recv: &Receiver<T>, timeout: Duration) {
+    match recv.recv_timeout(timeout) {
+        Ok(v) => panic!("received {:?} when expecting the channel to be closed", v),
+        Err(RecvTimeoutError::Timeout) => {
+            panic!("the senders were not dropped within {:?}", timeout)
+        }
+        Err(RecvTimeoutError::Disconnected) => {}
+    }
+}
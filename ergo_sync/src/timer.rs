@@ -0,0 +1,507 @@
+//! Running jobs after a delay, periodically or on a cron schedule.
+//!
+//! Long running programs often need periodic maintenance (flushing a cache, rotating a log,
+//! printing [`stats`]). Instead of hand writing a thread with a sleeping loop for each of them,
+//! register them with a single [`Scheduler`]. Jobs are either closures, which run on a [`Pool`]
+//! so a slow job doesn't delay the others, or firings delivered over a channel to be handled
+//! with the [`ch!`] and [`select_loop!`] macros.
+//!
+//! Cron expressions are supported with the `cron_schedule` feature, see
+//! [`Scheduler::cron`](struct.Scheduler.html#method.cron).
+//!
+//! To test code which uses timers without waiting for them, create the scheduler with a
+//! [`ManualClock`](../clock/struct.ManualClock.html), see
+//! [`Scheduler::with_clock`](struct.Scheduler.html#method.with_clock).
+//!
+//! [`stats`]: ../stats/index.html
+//! [`Scheduler`]: struct.Scheduler.html
+//! [`Pool`]: ../pool/struct.Pool.html
+//! [`ch!`]: ../macro.ch.html
+//! [`select_loop!`]: ../macro.select_loop.html
+//!
+//! # Examples
+//!
+//! ```rust
+//! #[macro_use] extern crate ergo_sync;
+//! use ergo_sync::*;
+//! use ergo_sync::timer::Scheduler;
+//!
+//! # fn main() {
+//! let scheduler = Scheduler::new();
+//!
+//! let ticks = Arc::new(AtomicUsize::new(0));
+//! take!(=ticks as t);
+//! scheduler.every(Duration::from_millis(10), move || {
+//!     t.fetch_add(1, AtomicOrdering::SeqCst);
+//! });
+//!
+//! let (send, recv) = ch::bounded(1);
+//! scheduler.after(Duration::from_millis(50), move || ch!(send <- "done"));
+//!
+//! let timeout = scheduler.after_channel(Duration::from_millis(40));
+//! ch!(<- timeout);
+//! assert_eq!("done", ch!(<- recv));
+//!
+//! // stop the timers and wait for any running jobs
+//! scheduler.shutdown();
+//! assert!(ticks.load(AtomicOrdering::SeqCst) >= 3);
+//! # }
+//! ```
+
+use std::cmp::Ordering;
+use std::collections::{BinaryHeap, HashSet};
+use std::fmt;
+use std::sync::{Condvar, MutexGuard};
+use std::thread::JoinHandle;
+use std::time::Instant;
+
+use clock::{Clock, SystemClock};
+use crossbeam_channel::{self as cb, Receiver, Sender, TrySendError};
+use std_prelude::*;
+use FinishHandle;
+use Pool;
+
+#[cfg(feature = "cron_schedule")]
+use chrono::{DateTime, Utc};
+
+/// The error for an invalid cron expression.
+#[cfg(feature = "cron_schedule")]
+pub use cron::error::Error as CronError;
+
+/// Runs jobs after a delay, periodically or on a cron schedule using a single timer thread.
+///
+/// Closures run on a [`Pool`](../pool/struct.Pool.html) (one thread unless created with
+/// [`with_pool`](#method.with_pool)). Dropping the scheduler is the same as calling
+/// [`shutdown`](#method.shutdown).
+pub struct Scheduler {
+    shared: Arc<Shared>,
+    thread: Option<JoinHandle<()>>,
+}
+
+/// Identifies a timer so it can be [cancelled](struct.Scheduler.html#method.cancel).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct TimerId(u64);
+
+struct Shared {
+    state: Mutex<State>,
+    wake: Condvar,
+    pool: Pool,
+    clock: Arc<dyn Clock>,
+}
+
+#[derive(Default)]
+struct State {
+    timers: BinaryHeap<Timer>,
+    cancelled: HashSet<TimerId>,
+    next_id: u64,
+    shutdown: bool,
+}
+
+struct Timer {
+    at: Instant,
+    id: TimerId,
+    job: Job,
+    repeat: Repeat,
+}
+
+enum Job {
+    Once(Box<dyn FnOnce() + Send>),
+    Repeat(Arc<dyn Fn() + Send + Sync>),
+    Channel(Sender<Instant>),
+}
+
+enum Repeat {
+    Once,
+    Every(Duration),
+    #[cfg(feature = "cron_schedule")]
+    Cron(Box<CronRepeat>),
+}
+
+/// A cron schedule with the time of day at an instant of the scheduler's clock, from which the
+/// time of day at any other instant is derived.
+#[cfg(feature = "cron_schedule")]
+struct CronRepeat {
+    schedule: cron::Schedule,
+    start: Instant,
+    start_utc: DateTime<Utc>,
+}
+
+impl Scheduler {
+    /// Create a scheduler which runs closures on a pool with one thread.
+    pub fn new() -> Scheduler {
+        Scheduler::with_pool(Pool::new(1))
+    }
+
+    /// Create a scheduler which runs closures on `pool`.
+    pub fn with_pool(pool: Pool) -> Scheduler {
+        Scheduler::with_pool_and_clock(pool, Arc::new(SystemClock))
+    }
+
+    /// Create a scheduler which reads the time from `clock`, i.e. a
+    /// [`ManualClock`](../clock/struct.ManualClock.html) so that tests decide when timers
+    /// fire. Closures run on a pool with one thread.
+    ///
+    /// Cron schedules start from the real time of day when they are added and then follow
+    /// `clock`.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// #[macro_use] extern crate ergo_sync;
+    /// use ergo_sync::*;
+    /// use ergo_sync::timer::Scheduler;
+    ///
+    /// # fn main() {
+    /// let clock = ManualClock::new();
+    /// let scheduler = Scheduler::with_clock(Arc::new(clock.clone()));
+    /// let hourly = scheduler.every_channel(Duration::from_secs(3600));
+    ///
+    /// clock.advance(Duration::from_secs(1800));
+    /// assert!(hourly.try_recv().is_err());
+    ///
+    /// clock.advance(Duration::from_secs(1800));
+    /// let fired = ch!(<- hourly);
+    /// assert_eq!(clock.now(), fired);
+    /// # }
+    /// ```
+    pub fn with_clock(clock: Arc<dyn Clock>) -> Scheduler {
+        Scheduler::with_pool_and_clock(Pool::new(1), clock)
+    }
+
+    /// Create a scheduler which runs closures on `pool` and reads the time from `clock`.
+    pub fn with_pool_and_clock(pool: Pool, clock: Arc<dyn Clock>) -> Scheduler {
+        let shared = Arc::new(Shared {
+            state: Mutex::new(State::default()),
+            wake: Condvar::new(),
+            pool,
+            clock,
+        });
+        let weak = Arc::downgrade(&shared);
+        shared
+            .clock
+            .on_advance(Box::new(move || match weak.upgrade() {
+                Some(shared) => {
+                    // lock so the timer thread is either waiting or has not read the time yet
+                    let _state = shared.lock();
+                    shared.wake.notify_one();
+                    true
+                }
+                None => false,
+            }));
+        let thread = {
+            let shared = shared.clone();
+            spawn(move || run(&shared))
+        };
+        Scheduler {
+            shared,
+            thread: Some(thread),
+        }
+    }
+
+    /// Run `job` once after `delay`.
+    pub fn after<F>(&self, delay: Duration, job: F) -> TimerId
+    where
+        F: FnOnce() + Send + 'static,
+    {
+        self.add(delay, Job::Once(Box::new(job)), Repeat::Once)
+    }
+
+    /// Run `job` every `period`, starting one `period` from now.
+    ///
+    /// If the jobs fall behind (i.e. because the pool is busy) the missed firings are skipped
+    /// instead of running in a burst.
+    ///
+    /// # Panics
+    /// Panics if `period` is zero.
+    pub fn every<F>(&self, period: Duration, job: F) -> TimerId
+    where
+        F: Fn() + Send + Sync + 'static,
+    {
+        assert_period(period);
+        self.add(period, Job::Repeat(Arc::new(job)), Repeat::Every(period))
+    }
+
+    /// Receive the time the timer fired once after `delay`.
+    pub fn after_channel(&self, delay: Duration) -> Receiver<Instant> {
+        let (send, recv) = cb::bounded(1);
+        self.add(delay, Job::Channel(send), Repeat::Once);
+        recv
+    }
+
+    /// Receive the time the timer fired every `period`, starting one `period` from now.
+    ///
+    /// Firings are dropped while the previous one has not been received yet. The timer is
+    /// cancelled once the receiver is dropped.
+    ///
+    /// # Panics
+    /// Panics if `period` is zero.
+    pub fn every_channel(&self, period: Duration) -> Receiver<Instant> {
+        assert_period(period);
+        let (send, recv) = cb::bounded(1);
+        self.add(period, Job::Channel(send), Repeat::Every(period));
+        recv
+    }
+
+    /// Run `job` on a cron schedule in UTC, i.e. `"0 */5 * * * *"` for every five minutes.
+    ///
+    /// The expression has the fields `sec min hour day_of_month month day_of_week [year]`, see
+    /// the [`cron`](https://docs.rs/cron) crate for the full syntax.
+    ///
+    /// This requires the `cron_schedule` feature.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # extern crate ergo_sync;
+    /// use ergo_sync::timer::Scheduler;
+    ///
+    /// # fn main() {
+    /// let scheduler = Scheduler::new();
+    /// scheduler.cron("0 0 3 * * *", || println!("it is 3am")).unwrap();
+    /// assert!(scheduler.cron("not cron", || {}).is_err());
+    /// # }
+    /// ```
+    #[cfg(feature = "cron_schedule")]
+    pub fn cron<F>(&self, expr: &str, job: F) -> Result<TimerId, CronError>
+    where
+        F: Fn() + Send + Sync + 'static,
+    {
+        let schedule: cron::Schedule = expr.parse()?;
+        Ok(self.add_cron(schedule, Job::Repeat(Arc::new(job))))
+    }
+
+    /// Receive the time the timer fired on a cron schedule in UTC, see
+    /// [`cron`](#method.cron) and [`every_channel`](#method.every_channel).
+    ///
+    /// This requires the `cron_schedule` feature.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// #[macro_use] extern crate ergo_sync;
+    /// use ergo_sync::*;
+    /// use ergo_sync::timer::Scheduler;
+    ///
+    /// # fn main() {
+    /// let clock = ManualClock::new();
+    /// let scheduler = Scheduler::with_clock(Arc::new(clock.clone()));
+    /// let hourly = scheduler.cron_channel("0 0 * * * *").unwrap();
+    ///
+    /// // the next full hour is at most an hour away
+    /// clock.advance(Duration::from_secs(3600));
+    /// ch!(<- hourly);
+    /// # }
+    /// ```
+    #[cfg(feature = "cron_schedule")]
+    pub fn cron_channel(&self, expr: &str) -> Result<Receiver<Instant>, CronError> {
+        let schedule: cron::Schedule = expr.parse()?;
+        let (send, recv) = cb::bounded(1);
+        self.add_cron(schedule, Job::Channel(send));
+        Ok(recv)
+    }
+
+    /// Cancel a timer so it never fires again.
+    ///
+    /// Returns `false` if the timer already finished or was already cancelled. A job which is
+    /// already running is not interrupted.
+    pub fn cancel(&self, id: TimerId) -> bool {
+        let mut state = self.shared.lock();
+        let pending = state.timers.iter().any(|t| t.id == id);
+        pending && state.cancelled.insert(id)
+    }
+
+    /// The number of timers which have not finished or been cancelled.
+    pub fn pending(&self) -> usize {
+        let state = self.shared.lock();
+        state.timers.len() - state.cancelled.len()
+    }
+
+    /// Stop firing timers and wait for any jobs which are already running to complete.
+    pub fn shutdown(self) {
+        // See `Drop`.
+    }
+
+    fn add(&self, delay: Duration, job: Job, repeat: Repeat) -> TimerId {
+        self.add_at(self.shared.clock.now() + delay, job, repeat)
+    }
+
+    #[cfg(feature = "cron_schedule")]
+    fn add_cron(&self, schedule: cron::Schedule, job: Job) -> TimerId {
+        let now = self.shared.clock.now();
+        let repeat = Repeat::Cron(Box::new(CronRepeat {
+            schedule,
+            start: now,
+            start_utc: Utc::now(),
+        }));
+        match repeat.next(now) {
+            Some(at) => self.add_at(at, job, repeat),
+            // the schedule never fires, i.e. a year in the past
+            None => self.shared.lock().new_id(),
+        }
+    }
+
+    fn add_at(&self, at: Instant, job: Job, repeat: Repeat) -> TimerId {
+        let mut state = self.shared.lock();
+        let id = state.new_id();
+        state.timers.push(Timer {
+            at,
+            id,
+            job,
+            repeat,
+        });
+        self.shared.wake.notify_one();
+        id
+    }
+}
+
+impl Default for Scheduler {
+    fn default() -> Scheduler {
+        Scheduler::new()
+    }
+}
+
+impl Drop for Scheduler {
+    fn drop(&mut self) {
+        self.shared.lock().shutdown = true;
+        self.shared.wake.notify_one();
+        if let Some(thread) = self.thread.take() {
+            thread.finish();
+        }
+        // The pool waits for running jobs when the last reference to `shared` is dropped.
+    }
+}
+
+impl fmt::Debug for Scheduler {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Scheduler")
+            .field("pending", &self.pending())
+            .finish()
+    }
+}
+
+impl Shared {
+    fn lock<'a>(&'a self) -> MutexGuard<'a, State> {
+        self.state.lock().expect("Scheduler poisoned")
+    }
+}
+
+impl State {
+    fn new_id(&mut self) -> TimerId {
+        self.next_id += 1;
+        TimerId(self.next_id)
+    }
+}
+
+impl Repeat {
+    /// The next time to fire after firing at `now`.
+    fn next(&self, now: Instant) -> Option<Instant> {
+        match *self {
+            Repeat::Once => None,
+            Repeat::Every(period) => Some(now + period),
+            #[cfg(feature = "cron_schedule")]
+            Repeat::Cron(ref cron) => {
+                let elapsed = now.saturating_duration_since(cron.start);
+                let now_utc = cron.start_utc + chrono::Duration::from_std(elapsed).ok()?;
+                cron.schedule.after(&now_utc).next().map(|at| {
+                    let delay = (at - now_utc).to_std().unwrap_or_default();
+                    now + delay
+                })
+            }
+        }
+    }
+}
+
+impl Timer {
+    /// Fire the timer, returning it if it should fire again.
+    fn fire(self, pool: &Pool, now: Instant) -> Option<Timer> {
+        let Timer {
+            at,
+            id,
+            job,
+            repeat,
+        } = self;
+        match job {
+            Job::Once(f) => {
+                pool.execute(f);
+                return None;
+            }
+            Job::Repeat(ref f) => {
+                let f = f.clone();
+                pool.execute(move || f());
+            }
+            Job::Channel(ref send) => {
+                if let Err(TrySendError::Disconnected(_)) = send.try_send(now) {
+                    return None;
+                }
+            }
+        }
+        let next = match repeat {
+            // keep to the original schedule unless it fell behind
+            Repeat::Every(period) if at + period > now => Some(at + period),
+            _ => repeat.next(now),
+        };
+        next.map(|at| Timer {
+            at,
+            id,
+            job,
+            repeat,
+        })
+    }
+}
+
+// `BinaryHeap` is a max-heap, so the earliest timer is the greatest.
+impl Ord for Timer {
+    fn cmp(&self, other: &Timer) -> Ordering {
+        other.at.cmp(&self.at).then(other.id.cmp(&self.id))
+    }
+}
+
+impl PartialOrd for Timer {
+    fn partial_cmp(&self, other: &Timer) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl PartialEq for Timer {
+    fn eq(&self, other: &Timer) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Timer {}
+
+fn assert_period(period: Duration) {
+    assert!(
+        period > Duration::from_secs(0),
+        "the period of a timer must not be zero"
+    );
+}
+
+fn run(shared: &Shared) {
+    let mut state = shared.lock();
+    loop {
+        if state.shutdown {
+            return;
+        }
+        let now = shared.clock.now();
+        match state.timers.peek().map(|t| t.at) {
+            Some(at) if at <= now => {
+                let timer = state.timers.pop().expect("timer was peeked");
+                if state.cancelled.remove(&timer.id) {
+                    continue;
+                }
+                if let Some(timer) = timer.fire(&shared.pool, now) {
+                    state.timers.push(timer);
+                }
+            }
+            Some(at) => {
+                state = shared
+                    .wake
+                    .wait_timeout(state, at - now)
+                    .expect("Scheduler poisoned")
+                    .0;
+            }
+            None => state = shared.wake.wait(state).expect("Scheduler poisoned"),
+        }
+    }
+}
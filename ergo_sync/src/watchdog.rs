@@ -0,0 +1,183 @@
+//! A watchdog which warns about threads blocked in [`ch!`](../macro.ch.html) for too long.
+//!
+//! The blocking `ch!` operations can deadlock if a channel is leaked, i.e. if a sender is cloned
+//! into a thread which never finishes, then `ch!(<- recv)` will wait forever. When the
+//! `deadlock_watchdog` feature is enabled every blocking `ch!` operation registers with a
+//! watchdog thread, which prints a warning to stderr naming the thread and the operation when it
+//! is blocked for longer than the [`threshold`] (10 seconds by default):
+//!
+//! ```text
+//! ergo_sync watchdog: thread 'worker' blocked for 10.002s on `ch!(<- recv)` at src/main.rs:12
+//! ```
+//!
+//! Another message is printed once the operation completes, so it is clear whether the thread was
+//! deadlocked or only slow.
+//!
+//! Without the feature the operations are not tracked and this module does nothing. Enable it
+//! while debugging with:
+//!
+//! ```toml
+//! [dependencies]
+//! ergo_sync = { version = "0.1", features = ["deadlock_watchdog"] }
+//! ```
+//!
+//! [`threshold`]: fn.set_threshold.html
+//!
+//! # Examples
+//!
+//! ```rust
+//! #[macro_use] extern crate ergo_sync;
+//! use ergo_sync::*;
+//!
+//! # fn main() {
+//! watchdog::set_threshold(Duration::from_millis(20));
+//!
+//! let (send, recv) = ch::bounded::<u32>(1);
+//! let th = spawn(move || {
+//!     sleep_ms(100);
+//!     ch!(send <- 42);
+//! });
+//! // Warns after 20ms if the feature is enabled.
+//! assert_eq!(42, ch!(<- recv));
+//! th.finish();
+//! # }
+//! ```
+
+use std::sync::atomic::AtomicU64;
+
+use std_prelude::*;
+
+static THRESHOLD_MS: AtomicU64 = AtomicU64::new(10_000);
+
+/// Return whether the `deadlock_watchdog` feature is enabled.
+pub fn enabled() -> bool {
+    cfg!(feature = "deadlock_watchdog")
+}
+
+/// Set how long a `ch!` operation can block before the watchdog warns about it.
+pub fn set_threshold(threshold: Duration) {
+    let ms = threshold.as_secs() * 1000 + u64::from(threshold.subsec_millis());
+    THRESHOLD_MS.store(ms.max(1), AtomicOrdering::SeqCst);
+}
+
+/// How long a `ch!` operation can block before the watchdog warns about it.
+pub fn threshold() -> Duration {
+    Duration::from_millis(THRESHOLD_MS.load(AtomicOrdering::SeqCst))
+}
+
+/// Register the current thread as blocked on `op` until the returned value is dropped.
+///
+/// This is called by the `ch!` macro and is not useful otherwise.
+#[doc(hidden)]
+#[inline]
+pub fn blocking(op: &'static str, location: &'static str) -> Blocked {
+    imp::blocking(op, location)
+}
+
+/// The current thread's registration with the watchdog, see [`blocking`](fn.blocking.html).
+#[doc(hidden)]
+#[derive(Debug)]
+pub struct Blocked {
+    #[allow(dead_code)]
+    id: u64,
+}
+
+#[cfg(not(feature = "deadlock_watchdog"))]
+mod imp {
+    use super::Blocked;
+
+    #[inline]
+    pub fn blocking(_op: &'static str, _location: &'static str) -> Blocked {
+        Blocked { id: 0 }
+    }
+}
+
+#[cfg(feature = "deadlock_watchdog")]
+mod imp {
+    use std::cmp;
+    use std::collections::BTreeMap;
+    use std::sync::atomic::AtomicU64;
+    use std::sync::Once;
+    use std::thread;
+    use std::time::Instant;
+
+    use super::{threshold, Blocked};
+    use std_prelude::*;
+
+    struct Entry {
+        thread: String,
+        op: &'static str,
+        location: &'static str,
+        since: Instant,
+        warned: bool,
+    }
+
+    static NEXT_ID: AtomicU64 = AtomicU64::new(1);
+    static BLOCKED: Mutex<BTreeMap<u64, Entry>> = Mutex::new(BTreeMap::new());
+    static START: Once = Once::new();
+
+    pub fn blocking(op: &'static str, location: &'static str) -> Blocked {
+        START.call_once(|| {
+            thread::Builder::new()
+                .name("ergo_sync watchdog".to_string())
+                .spawn(watch)
+                .expect("failed to spawn the watchdog thread");
+        });
+        let current = thread::current();
+        let name = match current.name() {
+            Some(name) => name.to_string(),
+            None => format!("{:?}", current.id()),
+        };
+        let id = NEXT_ID.fetch_add(1, AtomicOrdering::SeqCst);
+        let entry = Entry {
+            thread: name,
+            op,
+            location,
+            since: Instant::now(),
+            warned: false,
+        };
+        lock().insert(id, entry);
+        Blocked { id }
+    }
+
+    fn lock() -> ::std::sync::MutexGuard<'static, BTreeMap<u64, Entry>> {
+        // A panic while printing doesn't corrupt the map.
+        BLOCKED.lock().unwrap_or_else(|err| err.into_inner())
+    }
+
+    fn watch() {
+        loop {
+            let threshold = threshold();
+            for entry in lock().values_mut() {
+                let elapsed = entry.since.elapsed();
+                if !entry.warned && elapsed >= threshold {
+                    entry.warned = true;
+                    eprintln!(
+                        "ergo_sync watchdog: thread '{}' blocked for {:?} on `{}` at {}",
+                        entry.thread, elapsed, entry.op, entry.location
+                    );
+                }
+            }
+            let interval = cmp::min(threshold / 4, Duration::from_secs(1));
+            thread::sleep(cmp::max(interval, Duration::from_millis(1)));
+        }
+    }
+
+    impl Drop for Blocked {
+        fn drop(&mut self) {
+            let entry = match lock().remove(&self.id) {
+                Some(e) => e,
+                None => return,
+            };
+            if entry.warned {
+                eprintln!(
+                    "ergo_sync watchdog: thread '{}' unblocked after {:?} on `{}` at {}",
+                    entry.thread,
+                    entry.since.elapsed(),
+                    entry.op,
+                    entry.location
+                );
+            }
+        }
+    }
+}
@@ -0,0 +1,368 @@
+//! Ergonomic, typed access to environment variables.
+//!
+//! - [`env_get`]: get and parse a variable, with an error naming the variable and expected type.
+//! - [`env_get_or`]: the same but with a default when the variable is not set.
+//! - [`env_override`]: temporarily override variables while running a closure (i.e. in tests).
+//! - [`load_dotenv`]: load a dotenv-style file into the process environment. Loaded variables
+//!   are then visible to shell expansion (i.e. `ergo_fs::expand`).
+//!
+//! [`env_get`]: fn.env_get.html
+//! [`env_get_or`]: fn.env_get_or.html
+//! [`env_override`]: fn.env_override.html
+//! [`load_dotenv`]: fn.load_dotenv.html
+//!
+//! # Examples
+//! ```rust
+//! # extern crate ergo_sys;
+//! use ergo_sys::env::*;
+//!
+//! # fn try_main() -> Result<(), EnvError> {
+//! env_override(&[("PORT", "8000")], || -> Result<(), EnvError> {
+//!     let port: u16 = env_get("PORT")?;
+//!     assert_eq!(8000, port);
+//!     assert_eq!(10, env_get_or("WORKERS_NOT_SET", 10)?);
+//!     Ok(())
+//! })?;
+//!
+//! env_override(&[("PORT", "eighty")], || {
+//!     let err = env_get::<u16>("PORT").unwrap_err();
+//!     assert_eq!(
+//!         "environment variable PORT=\"eighty\" is not a valid u16: invalid digit found in string",
+//!         err.to_string(),
+//!     );
+//! });
+//! # Ok(()) } fn main() { try_main().unwrap() }
+//! ```
+use std::any;
+use std::env;
+use std::error;
+use std::ffi::{OsStr, OsString};
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::str::FromStr;
+use std::sync::Mutex;
+
+lazy_static! {
+    /// The environment is global to the process, only one override may happen at a time.
+    static ref OVERRIDE_LOCK: Mutex<()> = Mutex::new(());
+}
+
+/// An error from getting or parsing an environment variable.
+///
+/// The `Display` implementation always names the variable, and for parse errors also the value
+/// and the expected type.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum EnvError {
+    /// The variable is not set.
+    NotPresent {
+        /// The name of the variable.
+        var: String,
+    },
+    /// The variable is set but is not valid unicode.
+    NotUnicode {
+        /// The name of the variable.
+        var: String,
+        /// The raw value of the variable.
+        value: OsString,
+    },
+    /// The variable is set but could not be parsed into the expected type.
+    Parse {
+        /// The name of the variable.
+        var: String,
+        /// The value of the variable.
+        value: String,
+        /// The name of the type that was expected.
+        expected: &'static str,
+        /// The message of the parse error.
+        cause: String,
+    },
+}
+
+impl EnvError {
+    /// The name of the variable associated with this error.
+    pub fn var(&self) -> &str {
+        match *self {
+            EnvError::NotPresent { ref var }
+            | EnvError::NotUnicode { ref var, .. }
+            | EnvError::Parse { ref var, .. } => var,
+        }
+    }
+}
+
+impl fmt::Display for EnvError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            EnvError::NotPresent { ref var } => {
+                write!(f, "environment variable {} is not set", var)
+            }
+            EnvError::NotUnicode { ref var, ref value } => write!(
+                f,
+                "environment variable {}={:?} is not valid unicode",
+                var, value
+            ),
+            EnvError::Parse {
+                ref var,
+                ref value,
+                expected,
+                ref cause,
+            } => write!(
+                f,
+                "environment variable {}={:?} is not a valid {}: {}",
+                var, value, expected, cause
+            ),
+        }
+    }
+}
+
+impl error::Error for EnvError {}
+
+impl From<EnvError> for io::Error {
+    fn from(err: EnvError) -> io::Error {
+        let kind = match err {
+            EnvError::NotPresent { .. } => io::ErrorKind::NotFound,
+            _ => io::ErrorKind::InvalidData,
+        };
+        io::Error::new(kind, err)
+    }
+}
+
+/// Get the environment variable `var` and parse it into `T`.
+///
+/// # Examples
+/// ```rust
+/// # extern crate ergo_sys;
+/// use ergo_sys::env::*;
+///
+/// # fn main() {
+/// env_override(&[("RETRIES", "3")], || {
+///     assert_eq!(Ok(3_u8), env_get("RETRIES"));
+/// });
+/// assert_eq!(
+///     Err(EnvError::NotPresent { var: "RETRIES".into() }),
+///     env_get::<u8>("RETRIES"),
+/// );
+/// # }
+/// ```
+pub fn env_get<T>(var: &str) -> Result<T, EnvError>
+where
+    T: FromStr,
+    T::Err: fmt::Display,
+{
+    match env_get_maybe(var)? {
+        Some(v) => Ok(v),
+        None => Err(EnvError::NotPresent { var: var.into() }),
+    }
+}
+
+/// Get the environment variable `var` and parse it into `T`, returning `default` if it is not
+/// set.
+///
+/// An error is still returned if the variable _is_ set but is invalid, since silently ignoring
+/// a misconfiguration is rarely what you want.
+pub fn env_get_or<T>(var: &str, default: T) -> Result<T, EnvError>
+where
+    T: FromStr,
+    T::Err: fmt::Display,
+{
+    Ok(env_get_maybe(var)?.unwrap_or(default))
+}
+
+fn env_get_maybe<T>(var: &str) -> Result<Option<T>, EnvError>
+where
+    T: FromStr,
+    T::Err: fmt::Display,
+{
+    let value = match env::var(var) {
+        Ok(v) => v,
+        Err(env::VarError::NotPresent) => return Ok(None),
+        Err(env::VarError::NotUnicode(value)) => {
+            return Err(EnvError::NotUnicode {
+                var: var.into(),
+                value,
+            })
+        }
+    };
+    match value.parse() {
+        Ok(v) => Ok(Some(v)),
+        Err(err) => Err(EnvError::Parse {
+            var: var.into(),
+            cause: err.to_string(),
+            value,
+            expected: any::type_name::<T>(),
+        }),
+    }
+}
+
+/// Run `f` with the environment variables in `vars` set, restoring their previous values
+/// afterwards (even if `f` panics).
+///
+/// Overrides are serialized with a global lock so that tests running in parallel do not see each
+/// other's variables. Do not call `env_override` from within `f`, it will deadlock.
+pub fn env_override<K, V, F, T>(vars: &[(K, V)], f: F) -> T
+where
+    K: AsRef<OsStr>,
+    V: AsRef<OsStr>,
+    F: FnOnce() -> T,
+{
+    let _lock = match OVERRIDE_LOCK.lock() {
+        Ok(l) => l,
+        // a panic in a previous override was already restored by its guard.
+        Err(poisoned) => poisoned.into_inner(),
+    };
+
+    let _restore = Restore {
+        previous: vars
+            .iter()
+            .map(|(k, _)| (k.as_ref().to_os_string(), env::var_os(k)))
+            .collect(),
+    };
+    for (k, v) in vars {
+        env::set_var(k, v);
+    }
+
+    f()
+}
+
+/// Restores the environment on drop.
+struct Restore {
+    previous: Vec<(OsString, Option<OsString>)>,
+}
+
+impl Drop for Restore {
+    fn drop(&mut self) {
+        // restore in reverse order in case the same variable was given twice.
+        for (k, v) in self.previous.iter().rev() {
+            match *v {
+                Some(ref v) => env::set_var(k, v),
+                None => env::remove_var(k),
+            }
+        }
+    }
+}
+
+/// Parse the contents of a dotenv-style file, returning the variables in order.
+///
+/// The supported syntax is:
+///
+/// - `KEY=value`, with optional leading `export `.
+/// - Values may be wrapped in single quotes (taken literally) or double quotes (supporting
+///   `\n`, `\t`, `\"` and `\\` escapes).
+/// - Empty lines and lines beginning with `#` are ignored.
+///
+/// # Examples
+/// ```rust
+/// # extern crate ergo_sys;
+/// use ergo_sys::env::parse_dotenv;
+///
+/// # fn main() {
+/// let vars = parse_dotenv("# comment\nexport A=1\nB = \"two\\nlines\"\nC='$literal'\n").unwrap();
+/// assert_eq!(
+///     vec![
+///         ("A".to_string(), "1".to_string()),
+///         ("B".to_string(), "two\nlines".to_string()),
+///         ("C".to_string(), "$literal".to_string()),
+///     ],
+///     vars
+/// );
+/// # }
+/// ```
+pub fn parse_dotenv(text: &str) -> io::Result<Vec<(String, String)>> {
+    let mut out = Vec::new();
+    for (i, line) in text.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let line = match line.strip_prefix("export ") {
+            Some(rest) => rest.trim_start(),
+            None => line,
+        };
+        let invalid = |msg: &str| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("{} on line {}", msg, i + 1),
+            )
+        };
+        let eq = match line.find('=') {
+            Some(eq) => eq,
+            None => return Err(invalid("expected KEY=VALUE")),
+        };
+        let key = line[..eq].trim();
+        if key.is_empty() || key.contains(char::is_whitespace) {
+            return Err(invalid("invalid variable name"));
+        }
+        let value = parse_dotenv_value(line[eq + 1..].trim()).map_err(invalid)?;
+        out.push((key.to_string(), value));
+    }
+    Ok(out)
+}
+
+fn parse_dotenv_value(raw: &str) -> Result<String, &'static str> {
+    if raw.len() >= 2 && raw.starts_with('\'') && raw.ends_with('\'') {
+        return Ok(raw[1..raw.len() - 1].to_string());
+    }
+    if !raw.starts_with('"') {
+        // unquoted, strip trailing comments
+        let value = match raw.find(" #") {
+            Some(c) => &raw[..c],
+            None => raw,
+        };
+        return Ok(value.trim_end().to_string());
+    }
+    if raw.len() < 2 || !raw.ends_with('"') {
+        return Err("unterminated double quote");
+    }
+    let mut out = String::with_capacity(raw.len());
+    let mut chars = raw[1..raw.len() - 1].chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            out.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('n') => out.push('\n'),
+            Some('t') => out.push('\t'),
+            Some(c @ '"') | Some(c @ '\\') => out.push(c),
+            Some(c) => {
+                out.push('\\');
+                out.push(c);
+            }
+            None => return Err("trailing backslash"),
+        }
+    }
+    Ok(out)
+}
+
+/// Load the dotenv-style file at `path` into the process environment, returning the variables
+/// that were set.
+///
+/// Variables which are already set in the environment are _not_ overriden, so the real
+/// environment always takes precedence over the file. Since the variables become part of the
+/// process environment, they are also used by shell expansion (i.e. `ergo_fs::expand`).
+///
+/// See [`parse_dotenv`](fn.parse_dotenv.html) for the supported syntax.
+pub fn load_dotenv<P: AsRef<Path>>(path: P) -> io::Result<Vec<(String, String)>> {
+    let path = path.as_ref();
+    let text = fs::read_to_string(path).map_err(|err| {
+        io::Error::new(
+            err.kind(),
+            format!("{} when reading {}", err, path.display()),
+        )
+    })?;
+    let vars = parse_dotenv(&text).map_err(|err| {
+        io::Error::new(
+            err.kind(),
+            format!("{} when loading {}", err, path.display()),
+        )
+    })?;
+    let vars: Vec<_> = vars
+        .into_iter()
+        .filter(|(k, _)| env::var_os(k).is_none())
+        .collect();
+    for (k, v) in &vars {
+        env::set_var(k, v);
+    }
+    Ok(vars)
+}
@@ -0,0 +1,87 @@
+//! Metadata about the current invocation of the program.
+
+use std::env;
+use std::path::PathBuf;
+use std::process;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use rand_crate::{self, Rng};
+
+lazy_static! {
+    static ref INVOCATION: Invocation = Invocation::current();
+}
+
+/// Get the metadata of the current invocation (run) of the program.
+///
+/// This is computed once, the first time it is called, and every later call returns the same
+/// value. Call it early in `main` so that `start` is accurate.
+///
+/// Embed the [`run_id`] in logs, journals and metrics so that all of the outputs of a single run
+/// of a tool can be correlated.
+///
+/// [`run_id`]: struct.Invocation.html#structfield.run_id
+///
+/// # Examples
+/// ```rust
+/// # extern crate ergo_sys;
+/// use ergo_sys::*;
+///
+/// # fn main() {
+/// let inv = invocation();
+/// println!("run {} started by {:?} in {:?}", inv.run_id, inv.user, inv.cwd);
+///
+/// // the same invocation is returned every time
+/// assert_eq!(inv.run_id, invocation().run_id);
+/// assert_eq!(::std::process::id(), inv.pid);
+/// # }
+/// ```
+pub fn invocation() -> &'static Invocation {
+    &INVOCATION
+}
+
+/// Metadata about a run of the program, see [`invocation`](fn.invocation.html).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Invocation {
+    /// A unique identifier for this run.
+    ///
+    /// This is the start time in milliseconds since the unix epoch followed by random bits, both
+    /// in fixed width hex (i.e. `0162a1b2c3d4-9f3e0a17`). Ids of later runs sort after those of
+    /// earlier runs.
+    pub run_id: String,
+    /// The time the invocation was first requested.
+    pub start: SystemTime,
+    /// The process id.
+    pub pid: u32,
+    /// The command line arguments, including the program name. Arguments which are not valid
+    /// unicode are converted lossily.
+    pub args: Vec<String>,
+    /// The current working directory at the start, if it could be determined.
+    pub cwd: Option<PathBuf>,
+    /// The name of the user running the program, from the `USER`, `LOGNAME` or `USERNAME`
+    /// environment variables.
+    pub user: Option<String>,
+}
+
+impl Invocation {
+    fn current() -> Invocation {
+        let start = SystemTime::now();
+        let millis = start
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs() * 1000 + u64::from(d.subsec_millis()))
+            .unwrap_or(0);
+        let random: u32 = rand_crate::thread_rng().gen();
+        Invocation {
+            run_id: format!("{:012x}-{:08x}", millis, random),
+            start,
+            pid: process::id(),
+            args: env::args_os()
+                .map(|a| a.to_string_lossy().into_owned())
+                .collect(),
+            cwd: env::current_dir().ok(),
+            user: ["USER", "LOGNAME", "USERNAME"]
+                .iter()
+                .filter_map(|v| env::var(v).ok())
+                .find(|u| !u.is_empty()),
+        }
+    }
+}
@@ -9,6 +9,21 @@
 //!
 //! [`ergo`]: https://github.com/rust-crates/ergo
 //!
+//! # Modules
+//!
+//! - [`env`](env/index.html): typed access to environment variables and dotenv loading.
+//! - [`rand`](rand/index.html): rexport of the `rand` crate with helpers for random tokens,
+//!   bytes and deterministic RNGs.
+//! - [`net`](net/index.html): downloading files over HTTP(S) with resuming, checksums and
+//!   progress, and line protocols over TCP or Unix sockets. Requires the `net` feature.
+//! - [`unix`](unix/index.html): the effective user, checking for root and dropping privileges.
+//!   Only on Unix.
+//!
+//! # Functions
+//!
+//! - [`invocation`](fn.invocation.html): a unique run id and other metadata about the current
+//!   run of the program (start time, args, cwd and user).
+//!
 //! ### Special thanks
 //!
 //! The crates that are exported are:
@@ -19,5 +34,27 @@
 //!
 //! Consider supporting their development individually and starring them on github.
 
-pub extern crate rand;
 pub extern crate ctrlc;
+extern crate rand as rand_crate;
+#[macro_use]
+extern crate lazy_static;
+#[cfg(feature = "net")]
+extern crate crossbeam_channel;
+#[cfg(unix)]
+extern crate libc;
+#[cfg(feature = "net")]
+extern crate path_abs;
+#[cfg(feature = "net")]
+extern crate sha2;
+#[cfg(feature = "net")]
+extern crate ureq;
+
+pub mod env;
+mod invocation;
+#[cfg(feature = "net")]
+pub mod net;
+pub mod rand;
+#[cfg(unix)]
+pub mod unix;
+
+pub use invocation::{invocation, Invocation};
@@ -0,0 +1,681 @@
+//! Simple networking, requires the `net` feature.
+//!
+//! - [`download`](fn.download.html): download a file over HTTP(S), the network half of the
+//!   canonical "download then extract a tarball" script. See [`Download`](struct.Download.html)
+//!   for resuming, checksums and progress.
+//! - [`listen_lines`](fn.listen_lines.html) and [`connect_lines`](fn.connect_lines.html): bridge
+//!   a line based protocol over TCP or Unix sockets to channels, i.e. for the control plane of a
+//!   daemon.
+
+use std::fmt;
+use std::fs;
+use std::io::{self, BufRead, BufReader, Read, Seek, SeekFrom, Write};
+use std::net::{TcpListener, TcpStream};
+use std::ops::Deref;
+#[cfg(unix)]
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::path::Path;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+use crossbeam_channel::{self as chan, Receiver, Sender};
+use path_abs::{PathDir, PathFile};
+use sha2::{Digest, Sha256};
+use ureq;
+
+const BUF_SIZE: usize = 64 * 1024;
+
+/// The longest line (including its line ending) which [`listen_lines`](fn.listen_lines.html)
+/// accepts. Connections which send a longer one are closed.
+pub const MAX_LINE_LEN: usize = 64 * 1024;
+
+/// The longest pause after failing to accept a connection, i.e. when out of file descriptors.
+const MAX_ACCEPT_BACKOFF: Duration = Duration::from_secs(1);
+
+/// Download `url` into the directory `dest`, naming the file after the last segment of the url.
+///
+/// See [`Download`](struct.Download.html) for resuming, checksums and progress.
+///
+/// # Examples
+/// ```rust,no_run
+/// # extern crate ergo_sys;
+/// # extern crate path_abs;
+/// use path_abs::PathDir;
+/// use ergo_sys::net::download;
+///
+/// # fn try_main() -> ::std::io::Result<()> {
+/// let dest = PathDir::create("downloads")?;
+/// let file = download("https://example.com/archive.tar.gz", &dest)?;
+/// assert_eq!("archive.tar.gz", file.file_name().unwrap());
+/// # Ok(()) } fn main() { try_main().unwrap() }
+/// ```
+pub fn download(url: &str, dest: &PathDir) -> io::Result<PathFile> {
+    Download::new(url).to(dest)
+}
+
+/// The progress of a [`Download`](struct.Download.html).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Progress {
+    /// The number of bytes of the file which have been downloaded, including any bytes from a
+    /// previous attempt which were resumed.
+    pub downloaded: u64,
+    /// The size of the file, if the server reported it.
+    pub total: Option<u64>,
+}
+
+/// Options for downloading a file.
+///
+/// The file is downloaded to `{name}.part` in the destination directory and renamed once it is
+/// complete (and verified), so a file with the final name is never partial. If the `.part`
+/// file already exists the download resumes from where it stopped when the server supports
+/// range requests, and starts over otherwise or when the server's answer doesn't match the size
+/// of the `.part` file.
+///
+/// # Examples
+/// ```rust,no_run
+/// # extern crate ergo_sys;
+/// # extern crate crossbeam_channel;
+/// # extern crate path_abs;
+/// use std::thread;
+/// use path_abs::PathDir;
+/// use ergo_sys::net::Download;
+///
+/// # fn try_main() -> ::std::io::Result<()> {
+/// let (send, recv) = crossbeam_channel::unbounded();
+/// thread::spawn(move || {
+///     for progress in recv.iter() {
+///         println!("{:?}", progress);
+///     }
+/// });
+///
+/// let dest = PathDir::create("downloads")?;
+/// let file = Download::new("https://example.com/archive.tar.gz?version=2")
+///     .file_name("archive-2.tar.gz")
+///     .sha256("9f86d081884c7d659a2feaa0c55ad015a3bf4f1b2b0b822cd15d6c15b0f00a08")
+///     .progress(send)
+///     .to(&dest)?;
+/// # Ok(()) } fn main() { try_main().unwrap() }
+/// ```
+#[derive(Debug, Clone)]
+pub struct Download {
+    url: String,
+    file_name: Option<String>,
+    sha256: Option<String>,
+    progress: Option<Sender<Progress>>,
+}
+
+impl Download {
+    /// Create the options for downloading `url`.
+    pub fn new<S: Into<String>>(url: S) -> Download {
+        Download {
+            url: url.into(),
+            file_name: None,
+            sha256: None,
+            progress: None,
+        }
+    }
+
+    /// The name of the downloaded file. Default: the last segment of the url's path, which is an
+    /// error if it is `.`, `..` or contains a `\` so that a url can't escape the directory.
+    pub fn file_name<S: Into<String>>(mut self, name: S) -> Self {
+        self.file_name = Some(name.into());
+        self
+    }
+
+    /// Verify the SHA-256 checksum of the file, given in hex.
+    ///
+    /// If the file does not match it is deleted and an error of kind `InvalidData` is returned.
+    /// If the file already exists and matches it is not downloaded again.
+    pub fn sha256<S: Into<String>>(mut self, hex: S) -> Self {
+        self.sha256 = Some(hex.into().to_lowercase());
+        self
+    }
+
+    /// Send the progress of the download over a channel after every chunk which is received.
+    ///
+    /// Nothing happens if the receiver is dropped.
+    pub fn progress(mut self, send: Sender<Progress>) -> Self {
+        self.progress = Some(send);
+        self
+    }
+
+    /// Download the file into the directory `dest`.
+    pub fn to(&self, dest: &PathDir) -> io::Result<PathFile> {
+        let name = match self.file_name {
+            Some(ref name) => name.clone(),
+            None => url_file_name(&self.url)?,
+        };
+        let path = dest.join(&name);
+        if let Some(ref expected) = self.sha256 {
+            if path.is_file() && sha256_file(&path)? == *expected {
+                return Ok(PathFile::new(&path)?);
+            }
+        }
+
+        let part = dest.join(format!("{}.part", name));
+        self.fetch(&part)?;
+        if let Some(ref expected) = self.sha256 {
+            let actual = sha256_file(&part)?;
+            if actual != *expected {
+                let _ = fs::remove_file(&part);
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!(
+                        "sha256 of {} is {} but expected {}",
+                        self.url, actual, expected
+                    ),
+                ));
+            }
+        }
+        fs::rename(&part, &path).map_err(|err| {
+            io::Error::new(
+                err.kind(),
+                format!(
+                    "{} when renaming {} to {}",
+                    err,
+                    part.display(),
+                    path.display()
+                ),
+            )
+        })?;
+        Ok(PathFile::new(&path)?)
+    }
+
+    /// Download (or resume downloading) the url to `part`.
+    fn fetch(&self, part: &Path) -> io::Result<()> {
+        let resume_from = fs::metadata(part).map(|m| m.len()).unwrap_or(0);
+        let mut request = ureq::get(&self.url);
+        if resume_from > 0 {
+            request = request.set("Range", &format!("bytes={}-", resume_from));
+        }
+        let response = match request.call() {
+            Ok(r) => r,
+            // the part file is already complete if it has the size of the whole file
+            Err(ureq::Error::Status(416, response)) if resume_from > 0 => {
+                let (_, total) = content_range(response.header("Content-Range"));
+                if total == Some(resume_from) {
+                    return Ok(());
+                }
+                return self.restart(part, resume_from);
+            }
+            Err(err) => return Err(self.error(err)),
+        };
+
+        let (start, range_total) = content_range(response.header("Content-Range"));
+        if response.status() == 206 && start != Some(resume_from) {
+            // appending a different range would corrupt the file
+            return self.restart(part, resume_from);
+        }
+
+        let mut file = fs::OpenOptions::new()
+            .create(true)
+            .write(true)
+            .truncate(false)
+            .open(part)?;
+        let (mut downloaded, total) = if response.status() == 206 {
+            file.seek(SeekFrom::End(0))?;
+            (resume_from, range_total)
+        } else {
+            // the server sent the whole file
+            file.set_len(0)?;
+            let total = response
+                .header("Content-Length")
+                .and_then(|l| l.parse().ok());
+            (0, total)
+        };
+
+        let mut reader = response.into_reader();
+        let mut buf = vec![0; BUF_SIZE];
+        loop {
+            let n = match reader.read(&mut buf) {
+                Ok(0) => break,
+                Ok(n) => n,
+                Err(ref err) if err.kind() == io::ErrorKind::Interrupted => continue,
+                Err(err) => {
+                    return Err(io::Error::new(
+                        err.kind(),
+                        format!("{} when downloading {}", err, self.url),
+                    ))
+                }
+            };
+            file.write_all(&buf[..n])?;
+            downloaded += n as u64;
+            if let Some(ref send) = self.progress {
+                let _ = send.send(Progress { downloaded, total });
+            }
+        }
+        file.sync_all()?;
+        if let Some(total) = total {
+            if downloaded != total {
+                return Err(io::Error::new(
+                    io::ErrorKind::UnexpectedEof,
+                    format!(
+                        "downloaded {} of {} bytes of {}",
+                        downloaded, total, self.url
+                    ),
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    /// Truncate `part` and download the whole file, since the server's answer to resuming from
+    /// `resume_from` doesn't match it.
+    fn restart(&self, part: &Path, resume_from: u64) -> io::Result<()> {
+        if resume_from == 0 {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("{} sent a range which was not requested", self.url),
+            ));
+        }
+        fs::File::create(part)?;
+        self.fetch(part)
+    }
+
+    fn error(&self, err: ureq::Error) -> io::Error {
+        let kind = match err {
+            ureq::Error::Status(404, _) => io::ErrorKind::NotFound,
+            ureq::Error::Status(401, _) | ureq::Error::Status(403, _) => {
+                io::ErrorKind::PermissionDenied
+            }
+            _ => io::ErrorKind::Other,
+        };
+        // ureq's errors already include the url
+        io::Error::new(kind, err.to_string())
+    }
+}
+
+/// The last segment of the path of a url, if it is a usable file name.
+fn url_file_name(url: &str) -> io::Result<String> {
+    let without_query = url.split(['?', '#']).next().unwrap_or("");
+    let after_scheme = match without_query.find("://") {
+        Some(i) => &without_query[i + 3..],
+        None => without_query,
+    };
+    match after_scheme
+        .split_once('/')
+        .and_then(|(_, p)| p.rsplit('/').next())
+    {
+        Some(name) if !(name.is_empty() || name == "." || name == ".." || name.contains('\\')) => {
+            Ok(name.to_string())
+        }
+        _ => Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("cannot determine a file name from {}, set one", url),
+        )),
+    }
+}
+
+/// The start and the total size of a `Content-Range: bytes <start>-<end>/<total>` header. Both
+/// are `None` when they are missing or `*`.
+fn content_range(header: Option<&str>) -> (Option<u64>, Option<u64>) {
+    let range = match header {
+        Some(h) => h.trim().trim_start_matches("bytes").trim(),
+        None => return (None, None),
+    };
+    let mut parts = range.splitn(2, '/');
+    let start = parts
+        .next()
+        .and_then(|r| r.split('-').next())
+        .and_then(|s| s.trim().parse().ok());
+    let total = parts.next().and_then(|t| t.trim().parse().ok());
+    (start, total)
+}
+
+fn sha256_file(path: &Path) -> io::Result<String> {
+    let mut file = fs::File::open(path)?;
+    let mut hasher = Sha256::new();
+    let mut buf = vec![0; BUF_SIZE];
+    loop {
+        match file.read(&mut buf)? {
+            0 => break,
+            n => hasher.update(&buf[..n]),
+        }
+    }
+    Ok(hasher
+        .finalize()
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect())
+}
+
+// ##################################################
+// # Line protocols
+
+/// A connection accepted by [`listen_lines`](fn.listen_lines.html), used to reply to it.
+///
+/// Cloning a `Peer` is cheap and the clones refer to the same connection.
+#[derive(Clone)]
+pub struct Peer {
+    id: usize,
+    addr: Arc<str>,
+    writer: Arc<Mutex<Stream>>,
+}
+
+impl Peer {
+    /// A number which is unique for every connection accepted by the process.
+    pub fn id(&self) -> usize {
+        self.id
+    }
+
+    /// The address of the peer, or the path of the socket for Unix sockets.
+    pub fn addr(&self) -> &str {
+        &self.addr
+    }
+
+    /// Send a line to the peer. A newline is appended to `line`.
+    pub fn send(&self, line: &str) -> io::Result<()> {
+        let mut writer = self.writer.lock().expect("peer writer poisoned");
+        write_line(&mut *writer, line)
+    }
+}
+
+impl fmt::Debug for Peer {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Peer({}, {})", self.id, self.addr)
+    }
+}
+
+impl PartialEq for Peer {
+    fn eq(&self, other: &Peer) -> bool {
+        self.id == other.id
+    }
+}
+
+impl Eq for Peer {}
+
+/// Listen on `addr`, receiving every line sent by every connection along with the
+/// [`Peer`](struct.Peer.html) which sent it.
+///
+/// `addr` is a TCP address like `"127.0.0.1:9000"` or, on unix, the path of a Unix socket
+/// prefixed with `unix:`, like `"unix:/run/myd.sock"`. With port 0 the OS picks a free port,
+/// see [`LineListener::local_addr`](struct.LineListener.html#method.local_addr).
+///
+/// Every connection gets its own thread, which reads lines (without the trailing newline) and
+/// sends them over the channel. Connections which send invalid UTF-8 or a line longer than
+/// [`MAX_LINE_LEN`](constant.MAX_LINE_LEN.html) are closed. Once the receiver is dropped every
+/// connection is closed after its next line, and the listener after the next connection.
+///
+/// # Examples
+/// ```rust
+/// # extern crate ergo_sys;
+/// use std::thread;
+/// use ergo_sys::net::{connect_lines, listen_lines};
+///
+/// # fn try_main() -> ::std::io::Result<()> {
+/// // port 0 lets the OS pick a free port
+/// let requests = listen_lines("127.0.0.1:0")?;
+/// let addr = requests.local_addr().to_string();
+/// thread::spawn(move || {
+///     for (peer, line) in requests.iter() {
+///         peer.send(&line.to_uppercase()).unwrap();
+///     }
+/// });
+///
+/// let (send, recv) = connect_lines(&addr)?;
+/// send.send("ping".to_string()).unwrap();
+/// assert_eq!("PING", recv.recv().unwrap());
+/// # Ok(()) } fn main() { try_main().unwrap() }
+/// ```
+pub fn listen_lines(addr: &str) -> io::Result<LineListener> {
+    let listener = Listener::bind(addr)?;
+    let local_addr = listener.local_addr()?;
+    let (send, recv) = chan::unbounded();
+    thread::spawn(move || {
+        static NEXT_ID: AtomicUsize = AtomicUsize::new(0);
+        let mut backoff = Duration::from_millis(0);
+        loop {
+            let (stream, peer_addr) = match listener.accept() {
+                Ok(s) => {
+                    backoff = Duration::from_millis(0);
+                    s
+                }
+                Err(_) => {
+                    // errors like running out of file descriptors persist for a while, don't
+                    // spin on them
+                    backoff = (backoff * 2).clamp(Duration::from_millis(5), MAX_ACCEPT_BACKOFF);
+                    thread::sleep(backoff);
+                    continue;
+                }
+            };
+            if send.is_disconnected() {
+                return;
+            }
+            let reader = match stream.try_clone() {
+                Ok(r) => r,
+                Err(_) => continue,
+            };
+            let peer = Peer {
+                id: NEXT_ID.fetch_add(1, Ordering::SeqCst),
+                addr: peer_addr.into(),
+                writer: Arc::new(Mutex::new(stream)),
+            };
+            let send = send.clone();
+            thread::spawn(move || {
+                let mut reader = BufReader::new(reader);
+                while let Some(line) = read_line(&mut reader) {
+                    if send.send((peer.clone(), line)).is_err() {
+                        return;
+                    }
+                }
+            });
+        }
+    });
+    Ok(LineListener { local_addr, recv })
+}
+
+/// The lines received by [`listen_lines`](fn.listen_lines.html), which dereferences to the
+/// `Receiver` of them.
+#[derive(Debug)]
+pub struct LineListener {
+    local_addr: String,
+    recv: Receiver<(Peer, String)>,
+}
+
+impl LineListener {
+    /// The address which is listened on, in the format accepted by
+    /// [`connect_lines`](fn.connect_lines.html). For TCP this contains the port picked by the
+    /// OS if the port was 0.
+    pub fn local_addr(&self) -> &str {
+        &self.local_addr
+    }
+}
+
+impl Deref for LineListener {
+    type Target = Receiver<(Peer, String)>;
+
+    fn deref(&self) -> &Receiver<(Peer, String)> {
+        &self.recv
+    }
+}
+
+/// Read a line of at most `MAX_LINE_LEN` bytes without its line ending, returning `None` at
+/// the end of the stream or if the line is too long or not UTF-8.
+fn read_line<R: BufRead>(reader: &mut R) -> Option<String> {
+    let mut buf = Vec::new();
+    match reader.take(MAX_LINE_LEN as u64).read_until(b'\n', &mut buf) {
+        Ok(0) | Err(_) => return None,
+        Ok(_) => {}
+    }
+    if buf.last() == Some(&b'\n') {
+        buf.pop();
+        if buf.last() == Some(&b'\r') {
+            buf.pop();
+        }
+    } else if buf.len() == MAX_LINE_LEN {
+        return None;
+    }
+    String::from_utf8(buf).ok()
+}
+
+/// Connect to a server listening on `addr`, returning a channel for sending lines to it and one
+/// for receiving the lines it sends back.
+///
+/// `addr` has the same format as for [`listen_lines`](fn.listen_lines.html). Newlines are
+/// appended to the sent lines and stripped from the received ones. The connection is closed once
+/// the sender is dropped and the receiver is disconnected when the server closes it.
+pub fn connect_lines(addr: &str) -> io::Result<(Sender<String>, Receiver<String>)> {
+    let mut writer = Stream::connect(addr)?;
+    let reader = writer.try_clone()?;
+    let (send_out, recv_out) = chan::unbounded::<String>();
+    let (send_in, recv_in) = chan::unbounded();
+    thread::spawn(move || {
+        for line in recv_out.iter() {
+            if write_line(&mut writer, &line).is_err() {
+                return;
+            }
+        }
+        writer.shutdown();
+    });
+    thread::spawn(move || {
+        for line in BufReader::new(reader).lines() {
+            match line {
+                Ok(l) => {
+                    if send_in.send(l).is_err() {
+                        return;
+                    }
+                }
+                Err(_) => return,
+            }
+        }
+    });
+    Ok((send_out, recv_in))
+}
+
+fn write_line<W: Write>(w: &mut W, line: &str) -> io::Result<()> {
+    // write the line in a single call so that lines are not interleaved
+    let mut buf = String::with_capacity(line.len() + 1);
+    buf.push_str(line);
+    buf.push('\n');
+    w.write_all(buf.as_bytes())?;
+    w.flush()
+}
+
+/// Split an address into a unix socket path or a TCP address.
+fn unix_path(addr: &str) -> io::Result<Option<&str>> {
+    if !addr.starts_with("unix:") {
+        return Ok(None);
+    }
+    if cfg!(unix) {
+        Ok(Some(&addr["unix:".len()..]))
+    } else {
+        Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("unix sockets are not supported on this platform: {}", addr),
+        ))
+    }
+}
+
+fn addr_error(err: io::Error, action: &str, addr: &str) -> io::Error {
+    io::Error::new(err.kind(), format!("{} when {} {}", err, action, addr))
+}
+
+enum Listener {
+    Tcp(TcpListener),
+    #[cfg(unix)]
+    Unix(UnixListener, String),
+}
+
+impl Listener {
+    fn bind(addr: &str) -> io::Result<Listener> {
+        match unix_path(addr)? {
+            #[cfg(unix)]
+            Some(path) => UnixListener::bind(path)
+                .map(|l| Listener::Unix(l, path.to_string()))
+                .map_err(|err| addr_error(err, "binding", addr)),
+            #[cfg(not(unix))]
+            Some(_) => unreachable!(),
+            None => TcpListener::bind(addr)
+                .map(Listener::Tcp)
+                .map_err(|err| addr_error(err, "binding", addr)),
+        }
+    }
+
+    fn local_addr(&self) -> io::Result<String> {
+        match *self {
+            Listener::Tcp(ref l) => Ok(l.local_addr()?.to_string()),
+            #[cfg(unix)]
+            Listener::Unix(_, ref path) => Ok(format!("unix:{}", path)),
+        }
+    }
+
+    fn accept(&self) -> io::Result<(Stream, String)> {
+        match *self {
+            Listener::Tcp(ref l) => l
+                .accept()
+                .map(|(s, addr)| (Stream::Tcp(s), addr.to_string())),
+            #[cfg(unix)]
+            Listener::Unix(ref l, ref path) => {
+                l.accept().map(|(s, _)| (Stream::Unix(s), path.clone()))
+            }
+        }
+    }
+}
+
+enum Stream {
+    Tcp(TcpStream),
+    #[cfg(unix)]
+    Unix(UnixStream),
+}
+
+impl Stream {
+    fn connect(addr: &str) -> io::Result<Stream> {
+        match unix_path(addr)? {
+            #[cfg(unix)]
+            Some(path) => UnixStream::connect(path)
+                .map(Stream::Unix)
+                .map_err(|err| addr_error(err, "connecting to", addr)),
+            #[cfg(not(unix))]
+            Some(_) => unreachable!(),
+            None => TcpStream::connect(addr)
+                .map(Stream::Tcp)
+                .map_err(|err| addr_error(err, "connecting to", addr)),
+        }
+    }
+
+    fn try_clone(&self) -> io::Result<Stream> {
+        match *self {
+            Stream::Tcp(ref s) => s.try_clone().map(Stream::Tcp),
+            #[cfg(unix)]
+            Stream::Unix(ref s) => s.try_clone().map(Stream::Unix),
+        }
+    }
+
+    fn shutdown(&self) {
+        let _ = match *self {
+            Stream::Tcp(ref s) => s.shutdown(::std::net::Shutdown::Both),
+            #[cfg(unix)]
+            Stream::Unix(ref s) => s.shutdown(::std::net::Shutdown::Both),
+        };
+    }
+}
+
+impl Read for Stream {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        match *self {
+            Stream::Tcp(ref mut s) => s.read(buf),
+            #[cfg(unix)]
+            Stream::Unix(ref mut s) => s.read(buf),
+        }
+    }
+}
+
+impl Write for Stream {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match *self {
+            Stream::Tcp(ref mut s) => s.write(buf),
+            #[cfg(unix)]
+            Stream::Unix(ref mut s) => s.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match *self {
+            Stream::Tcp(ref mut s) => s.flush(),
+            #[cfg(unix)]
+            Stream::Unix(ref mut s) => s.flush(),
+        }
+    }
+}
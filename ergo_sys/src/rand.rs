@@ -0,0 +1,154 @@
+//! Rexport of the [`rand`] crate with ergonomic helpers.
+//!
+//! Everything from [`rand`] is available here, i.e. `ergo_sys::rand::Rng`. In addition it
+//! provides:
+//!
+//! - [`bytes`]: `n` random bytes from the operating system's secure random source.
+//! - [`token`]: a URL-safe random string, i.e. for temporary names and IDs. [`try_token`]
+//!   returns an error instead of panicking.
+//! - [`pick`]: choose a random element from a slice.
+//! - [`SeededRng`]: a deterministic RNG with the same helpers as methods, for reproducible tests.
+//!
+//! [`rand`]: ../../rand/index.html
+//! [`bytes`]: fn.bytes.html
+//! [`token`]: fn.token.html
+//! [`try_token`]: fn.try_token.html
+//! [`pick`]: fn.pick.html
+//! [`SeededRng`]: struct.SeededRng.html
+//!
+//! # Examples
+//! ```rust
+//! # extern crate ergo_sys;
+//! use ergo_sys::rand;
+//!
+//! # fn main() {
+//! let t = rand::token(16);
+//! assert_eq!(16, t.len());
+//! assert!(t.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_'));
+//!
+//! assert_eq!(32, rand::bytes(32).len());
+//!
+//! let colors = ["red", "green", "blue"];
+//! assert!(colors.contains(rand::pick(&colors).unwrap()));
+//! assert_eq!(None, rand::pick::<u8>(&[]));
+//! # }
+//! ```
+pub use rand_crate::*;
+use std::io;
+
+/// The alphabet used by [`token`](fn.token.html): the URL-safe base64 alphabet.
+pub const TOKEN_CHARS: &[u8; 64] =
+    b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789-_";
+
+/// Get `n` random bytes from the operating system's secure random source.
+///
+/// # Panics
+/// Panics if the operating system's random source cannot be accessed.
+pub fn bytes(n: usize) -> Vec<u8> {
+    bytes_with(&mut os_rng(), n)
+}
+
+/// Get a URL-safe random string of `len` characters from the operating system's secure random
+/// source.
+///
+/// Each character holds 6 bits of entropy, so a `token(22)` is comparable to a 128 bit UUID.
+///
+/// # Panics
+/// Panics if the operating system's random source cannot be accessed.
+pub fn token(len: usize) -> String {
+    token_with(&mut os_rng(), len)
+}
+
+/// Like [`token`](fn.token.html), but returns an error if the operating system's random source
+/// cannot be accessed.
+pub fn try_token(len: usize) -> io::Result<String> {
+    Ok(token_with(&mut OsRng::new()?, len))
+}
+
+/// Pick a random element from `slice`, returning `None` if it is empty.
+///
+/// This uses `thread_rng()`, which is fast but not intended for secrets.
+pub fn pick<T>(slice: &[T]) -> Option<&T> {
+    thread_rng().choose(slice)
+}
+
+/// A deterministic random number generator for tests and reproducible runs.
+///
+/// The same seed always produces the same values on every platform. **Do not use this for
+/// secrets.**
+///
+/// # Examples
+/// ```rust
+/// # extern crate ergo_sys;
+/// use ergo_sys::rand::{Rng, SeededRng};
+///
+/// # fn main() {
+/// let mut a = SeededRng::new(42);
+/// let mut b = SeededRng::new(42);
+/// assert_eq!(a.token(10), b.token(10));
+/// assert_eq!(a.bytes(4), b.bytes(4));
+/// assert_eq!(a.gen::<u64>(), b.gen::<u64>());
+/// assert_eq!(a.pick(&[1, 2, 3]), b.pick(&[1, 2, 3]));
+/// # }
+/// ```
+#[derive(Debug, Clone)]
+pub struct SeededRng {
+    rng: ChaChaRng,
+}
+
+impl SeededRng {
+    /// Create a new deterministic RNG from a seed.
+    pub fn new(seed: u64) -> SeededRng {
+        let seed = [seed as u32, (seed >> 32) as u32];
+        SeededRng {
+            rng: ChaChaRng::from_seed(&seed[..]),
+        }
+    }
+
+    /// Get `n` deterministic random bytes.
+    pub fn bytes(&mut self, n: usize) -> Vec<u8> {
+        bytes_with(&mut self.rng, n)
+    }
+
+    /// Get a deterministic URL-safe string of `len` characters.
+    pub fn token(&mut self, len: usize) -> String {
+        token_with(&mut self.rng, len)
+    }
+
+    /// Pick a deterministic element from `slice`, returning `None` if it is empty.
+    pub fn pick<'a, T>(&mut self, slice: &'a [T]) -> Option<&'a T> {
+        self.rng.choose(slice)
+    }
+}
+
+impl Rng for SeededRng {
+    fn next_u32(&mut self) -> u32 {
+        self.rng.next_u32()
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        self.rng.next_u64()
+    }
+
+    fn fill_bytes(&mut self, dest: &mut [u8]) {
+        self.rng.fill_bytes(dest)
+    }
+}
+
+fn os_rng() -> OsRng {
+    OsRng::new().expect("failed to access the operating system's random source")
+}
+
+fn bytes_with<R: Rng>(rng: &mut R, n: usize) -> Vec<u8> {
+    let mut out = vec![0_u8; n];
+    rng.fill_bytes(&mut out);
+    out
+}
+
+fn token_with<R: Rng>(rng: &mut R, len: usize) -> String {
+    // The alphabet has exactly 64 characters so masking a byte is unbiased.
+    bytes_with(rng, len)
+        .into_iter()
+        .map(|b| TOKEN_CHARS[(b & 0x3f) as usize] as char)
+        .collect()
+}
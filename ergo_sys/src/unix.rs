@@ -0,0 +1,176 @@
+//! Users and privileges on Unix, for daemons and installers.
+//!
+//! - [`effective_user`]: the user the process is running as.
+//! - [`is_root`]: whether the process is running as root.
+//! - [`drop_privileges`]: switch a process started as root to an unprivileged user, i.e. after
+//!   binding to a port below 1024.
+//!
+//! To create a directory owned by a [`User`] use `PathDir::create_owned` from `ergo_fs`.
+//!
+//! [`effective_user`]: fn.effective_user.html
+//! [`is_root`]: fn.is_root.html
+//! [`drop_privileges`]: fn.drop_privileges.html
+//! [`User`]: struct.User.html
+//!
+//! # Examples
+//! ```rust
+//! # extern crate ergo_sys;
+//! use ergo_sys::unix::*;
+//!
+//! # fn try_main() -> ::std::io::Result<()> {
+//! let user = effective_user()?;
+//! println!("running as {} ({}:{})", user.name(), user.uid(), user.gid());
+//! assert_eq!(is_root(), user.uid() == 0);
+//! assert_eq!(user, User::from_name(user.name())?);
+//! # Ok(()) } fn main() { try_main().unwrap() }
+//! ```
+
+use libc;
+use std::ffi::{CStr, CString, OsStr};
+use std::io;
+use std::mem;
+use std::os::unix::ffi::OsStrExt;
+use std::path::{Path, PathBuf};
+use std::ptr;
+
+/// A user from the user database (`/etc/passwd` or whatever NSS is configured with).
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct User {
+    name: String,
+    uid: u32,
+    gid: u32,
+    home: PathBuf,
+}
+
+impl User {
+    /// Look up the user named `name`, failing with `NotFound` if there is none.
+    pub fn from_name(name: &str) -> io::Result<User> {
+        let cname =
+            CString::new(name).map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+        lookup(|pwd, buf, len, result| unsafe {
+            libc::getpwnam_r(cname.as_ptr(), pwd, buf, len, result)
+        })?
+        .ok_or_else(|| not_found(format!("no user named {:?}", name)))
+    }
+
+    /// Look up the user with the id `uid`, failing with `NotFound` if there is none.
+    pub fn from_uid(uid: u32) -> io::Result<User> {
+        lookup(|pwd, buf, len, result| unsafe {
+            libc::getpwuid_r(uid as libc::uid_t, pwd, buf, len, result)
+        })?
+        .ok_or_else(|| not_found(format!("no user with uid {}", uid)))
+    }
+
+    /// The login name.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// The user id.
+    pub fn uid(&self) -> u32 {
+        self.uid
+    }
+
+    /// The id of the user's primary group.
+    pub fn gid(&self) -> u32 {
+        self.gid
+    }
+
+    /// The home directory.
+    pub fn home(&self) -> &Path {
+        &self.home
+    }
+}
+
+/// The user which the process is running as (its effective user id).
+pub fn effective_user() -> io::Result<User> {
+    User::from_uid(unsafe { libc::geteuid() } as u32)
+}
+
+/// Whether the process is running as root (its effective user id is `0`).
+pub fn is_root() -> bool {
+    unsafe { libc::geteuid() == 0 }
+}
+
+/// Permanently switch the process to `user`: its supplementary groups, its primary group and
+/// then its user id.
+///
+/// This needs root. Afterwards the process can not get the privileges back, which is checked
+/// before returning. Do everything which needs root first, i.e. binding to privileged ports or
+/// opening log files.
+///
+/// # Examples
+/// ```rust,no_run
+/// # extern crate ergo_sys;
+/// use std::net::TcpListener;
+/// use ergo_sys::unix::*;
+///
+/// # fn try_main() -> ::std::io::Result<()> {
+/// let listener = TcpListener::bind("0.0.0.0:80")?;
+/// if is_root() {
+///     drop_privileges(&User::from_name("www-data")?)?;
+/// }
+/// # Ok(()) } fn main() { try_main().unwrap() }
+/// ```
+pub fn drop_privileges(user: &User) -> io::Result<()> {
+    let name = CString::new(user.name.as_str())
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+    // initgroups takes an int on macos and a gid_t elsewhere
+    check(unsafe { libc::initgroups(name.as_ptr(), user.gid as _) })?;
+    check(unsafe { libc::setgid(user.gid as libc::gid_t) })?;
+    check(unsafe { libc::setuid(user.uid as libc::uid_t) })?;
+    if user.uid != 0 && unsafe { libc::setuid(0) } == 0 {
+        return Err(io::Error::other(
+            "privileges could be regained after dropping them",
+        ));
+    }
+    Ok(())
+}
+
+/// Call a `get*_r` function with a growing buffer until it fits, returning `None` if there is no
+/// such entry.
+fn lookup<F>(get: F) -> io::Result<Option<User>>
+where
+    F: Fn(
+        *mut libc::passwd,
+        *mut libc::c_char,
+        libc::size_t,
+        *mut *mut libc::passwd,
+    ) -> libc::c_int,
+{
+    let mut buf: Vec<libc::c_char> = vec![0; 1024];
+    loop {
+        let mut pwd: libc::passwd = unsafe { mem::zeroed() };
+        let mut result = ptr::null_mut();
+        match get(&mut pwd, buf.as_mut_ptr(), buf.len(), &mut result) {
+            0 if result.is_null() => return Ok(None),
+            // the strings of `pwd` point into `buf`, copy them out while it is alive
+            0 => return Ok(Some(unsafe { user_from(&pwd) })),
+            libc::ERANGE => {
+                let len = buf.len() * 2;
+                buf.resize(len, 0);
+            }
+            code => return Err(io::Error::from_raw_os_error(code)),
+        }
+    }
+}
+
+unsafe fn user_from(pwd: &libc::passwd) -> User {
+    User {
+        name: CStr::from_ptr(pwd.pw_name).to_string_lossy().into_owned(),
+        uid: pwd.pw_uid,
+        gid: pwd.pw_gid,
+        home: PathBuf::from(OsStr::from_bytes(CStr::from_ptr(pwd.pw_dir).to_bytes())),
+    }
+}
+
+fn check(res: libc::c_int) -> io::Result<()> {
+    match res {
+        0 => Ok(()),
+        _ => Err(io::Error::last_os_error()),
+    }
+}
+
+fn not_found(msg: String) -> io::Error {
+    io::Error::new(io::ErrorKind::NotFound, msg)
+}